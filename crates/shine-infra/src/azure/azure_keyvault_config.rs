@@ -1,12 +1,11 @@
+use crate::secrets::{SecretSource, SecretSourceError};
 use async_trait::async_trait;
 use azure_core::credentials::TokenCredential;
 use azure_security_keyvault_secrets::SecretClient;
-use config::{
-    AsyncSource as ConfigAsyncSource, ConfigError, Map as ConfigMap, Value as ConfigValue, ValueKind as ConfigValueKind,
-};
+use config::ConfigError;
 use core::fmt;
 use futures::TryStreamExt;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
@@ -46,46 +45,37 @@ impl AzureKeyvaultConfigSource {
 }
 
 #[async_trait]
-impl ConfigAsyncSource for AzureKeyvaultConfigSource {
-    async fn collect(&self) -> Result<ConfigMap<String, ConfigValue>, ConfigError> {
-        let mut config = ConfigMap::new();
+impl SecretSource for AzureKeyvaultConfigSource {
+    fn origin(&self) -> &str {
+        &self.keyvault_url
+    }
+
+    async fn load(&self) -> Result<HashMap<String, String>, SecretSourceError> {
+        let provider_error = |err| SecretSourceError::Provider(Box::new(AzureKeyvaultConfigError(err)));
 
-        log::info!("Loading secrets from {} ...", self.keyvault_url);
-        let origin = self.keyvault_url.to_string();
-        let mut pager = self
-            .client
-            .list_secret_properties(None)
-            .map_err(AzureKeyvaultConfigError)?;
-        while let Some(secret) = pager.try_next().await.map_err(AzureKeyvaultConfigError)? {
+        let mut secrets = HashMap::new();
+        let mut pager = self.client.list_secret_properties(None).map_err(provider_error)?;
+        while let Some(secret) = pager.try_next().await.map_err(provider_error)? {
             if let Some(id) = secret.id {
                 let key = id.split('/').next_back();
                 if let Some(key) = key {
                     let path = key.replace('-', ".");
-                    log::debug!("Reading secret {key:?}");
                     let secret = self
                         .client
                         .get_secret(key, None)
                         .await
-                        .map_err(AzureKeyvaultConfigError)?
+                        .map_err(provider_error)?
                         .into_model()
-                        .map_err(AzureKeyvaultConfigError)?;
+                        .map_err(provider_error)?;
                     if let (Some(attributes), Some(value)) = (secret.attributes, secret.value) {
                         if attributes.enabled.unwrap_or(false) {
-                            // try to parse value, as conversion from string to a concrete type is not automatic.
-                            let value = if let Ok(parsed) = value.parse::<i64>() {
-                                ConfigValueKind::I64(parsed)
-                            } else {
-                                ConfigValueKind::String(value)
-                            };
-
-                            config.insert(path, ConfigValue::new(Some(&origin), value));
+                            secrets.insert(path, value);
                         }
                     }
                 }
             }
         }
 
-        //log::info!("keyvault config: {:#?}", config);
-        Ok(config)
+        Ok(secrets)
     }
 }
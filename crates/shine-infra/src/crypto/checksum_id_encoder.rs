@@ -0,0 +1,108 @@
+use super::{IdEncoder, IdEncoderError};
+
+/// Append a Luhn mod N check character to the ids of the wrapped encoder. It detects all single character
+/// errors and most of the transpositions of adjacent characters, thus mistyped ids can be rejected without
+/// a database lookup. The output of the inner encoder must use only the characters of the alphabet.
+pub struct ChecksumIdEncoder<E: IdEncoder> {
+    alphabet: Vec<char>,
+    encoder: E,
+}
+
+impl<E: IdEncoder> ChecksumIdEncoder<E> {
+    pub fn new(alphabet: &str, encoder: E) -> Result<Self, IdEncoderError> {
+        let alphabet: Vec<char> = alphabet.chars().collect();
+        if alphabet.len() < 2 {
+            return Err(IdEncoderError::InvalidConfig(
+                "Alphabet must have at least 2 characters".to_string(),
+            ));
+        }
+        if (1..alphabet.len()).any(|i| alphabet[..i].contains(&alphabet[i])) {
+            return Err(IdEncoderError::InvalidConfig(
+                "Alphabet must contain unique characters".to_string(),
+            ));
+        }
+        Ok(Self { alphabet, encoder })
+    }
+
+    fn check_character(&self, id: &str) -> Result<char, IdEncoderError> {
+        let n = self.alphabet.len();
+        let mut sum = 0;
+        for (i, c) in id.chars().rev().enumerate() {
+            let code = self
+                .alphabet
+                .iter()
+                .position(|x| *x == c)
+                .ok_or_else(|| IdEncoderError::InvalidObfuscatedId(format!("Invalid character: {c}")))?;
+            let addend = if i % 2 == 0 { code * 2 } else { code };
+            sum += addend / n + addend % n;
+        }
+        Ok(self.alphabet[(n - sum % n) % n])
+    }
+}
+
+impl<E: IdEncoder> IdEncoder for ChecksumIdEncoder<E> {
+    fn obfuscate(&self, id: u64) -> Result<String, IdEncoderError> {
+        let id = self.encoder.obfuscate(id)?;
+        let check = self
+            .check_character(&id)
+            .map_err(|err| IdEncoderError::InvalidConfig(format!("Encoder and checksum alphabet mismatch: {err}")))?;
+        Ok(format!("{id}{check}"))
+    }
+
+    fn deobfuscate(&self, id: &str) -> Result<u64, IdEncoderError> {
+        let mut chars = id.chars();
+        let check = chars
+            .next_back()
+            .ok_or_else(|| IdEncoderError::InvalidObfuscatedId("Missing checksum".to_string()))?;
+        let id = chars.as_str();
+        if self.check_character(id)? != check {
+            return Err(IdEncoderError::InvalidObfuscatedId("Checksum mismatch".to_string()));
+        }
+        self.encoder.deobfuscate(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{HarshIdEncoder, OptimusIdEncoder};
+    use shine_test::test;
+
+    #[test]
+    fn encode_decode() {
+        let encoder =
+            ChecksumIdEncoder::new(OptimusIdEncoder::ALPHABET, OptimusIdEncoder::new(309779747, 57733611)).unwrap();
+        for i in 0..10_000 {
+            let id = encoder.obfuscate(i).unwrap();
+            assert_eq!(encoder.deobfuscate(&id).unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn detect_typo() {
+        let encoder = ChecksumIdEncoder::new(HarshIdEncoder::ALPHABET, HarshIdEncoder::new("salt").unwrap()).unwrap();
+
+        for i in 0..100 {
+            let id: Vec<char> = encoder.obfuscate(i).unwrap().chars().collect();
+
+            // single character errors
+            for pos in 0..id.len() {
+                for c in HarshIdEncoder::ALPHABET.chars().filter(|c| *c != id[pos]) {
+                    let mut typo = id.clone();
+                    typo[pos] = c;
+                    let typo: String = typo.into_iter().collect();
+                    assert!(encoder.deobfuscate(&typo).is_err(), "{typo}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let encoder =
+            ChecksumIdEncoder::new(OptimusIdEncoder::ALPHABET, OptimusIdEncoder::new(309779747, 57733611)).unwrap();
+        assert!(encoder.deobfuscate("").is_err());
+        assert!(encoder.deobfuscate("12a").is_err());
+        assert!(ChecksumIdEncoder::new("00", OptimusIdEncoder::new(309779747, 57733611)).is_err());
+    }
+}
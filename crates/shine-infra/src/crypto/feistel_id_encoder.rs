@@ -0,0 +1,186 @@
+use ring::hmac;
+
+use super::{IdEncoder, IdEncoderError};
+
+const ROUNDS: u8 = 10;
+
+/// Format preserving encoder using a keyed Feistel network. Ids are mapped to fixed length strings over
+/// the given alphabet, the mapping is a bijection on the `alphabet.len() ^ length` domain.
+pub struct FeistelIdEncoder {
+    alphabet: Vec<char>,
+    length: usize,
+    /// Modulus of the high and low halves
+    modulus: (u64, u64),
+    key: hmac::Key,
+}
+
+impl FeistelIdEncoder {
+    pub const DEFAULT_ALPHABET: &'static str = "abcdefghijklmnopqrstuvwxyz1234567890";
+
+    pub fn new(alphabet: &str, length: usize, key: &str) -> Result<Self, IdEncoderError> {
+        let alphabet: Vec<char> = alphabet.chars().collect();
+        if alphabet.len() < 2 {
+            return Err(IdEncoderError::InvalidConfig(
+                "Alphabet must have at least 2 characters".to_string(),
+            ));
+        }
+        if (1..alphabet.len()).any(|i| alphabet[..i].contains(&alphabet[i])) {
+            return Err(IdEncoderError::InvalidConfig(
+                "Alphabet must contain unique characters".to_string(),
+            ));
+        }
+        if length < 2 {
+            return Err(IdEncoderError::InvalidConfig("Length must be at least 2".to_string()));
+        }
+        if key.is_empty() {
+            return Err(IdEncoderError::InvalidConfig("Missing key".to_string()));
+        }
+
+        let base = alphabet.len() as u64;
+        let high = length / 2;
+        let low = length - high;
+        let modulus = (base.checked_pow(high as u32), base.checked_pow(low as u32));
+        let modulus = match modulus {
+            (Some(high), Some(low)) if high.checked_mul(low).is_some() => (high, low),
+            _ => {
+                return Err(IdEncoderError::InvalidConfig(
+                    "Domain does not fit into 64 bits, reduce the length".to_string(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            alphabet,
+            length,
+            modulus,
+            key: hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes()),
+        })
+    }
+
+    /// The number of ids that can be encoded.
+    pub fn domain(&self) -> u64 {
+        self.modulus.0 * self.modulus.1
+    }
+
+    fn round(&self, round: u8, value: u64, modulus: u64) -> u64 {
+        let mut data = [0u8; 9];
+        data[0] = round;
+        data[1..].copy_from_slice(&value.to_be_bytes());
+        let tag = hmac::sign(&self.key, &data);
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&tag.as_ref()[..8]);
+        u64::from_be_bytes(bytes) % modulus
+    }
+
+    fn permute(&self, id: u64) -> u64 {
+        let (mut ma, mut mb) = self.modulus;
+        let (mut a, mut b) = (id / mb, id % mb);
+        for round in 0..ROUNDS {
+            let c = ((a as u128 + self.round(round, b, ma) as u128) % ma as u128) as u64;
+            (a, b) = (b, c);
+            (ma, mb) = (mb, ma);
+        }
+        a * mb + b
+    }
+
+    fn inverse(&self, id: u64) -> u64 {
+        let (mut ma, mut mb) = self.modulus;
+        let (mut a, mut b) = (id / mb, id % mb);
+        for round in (0..ROUNDS).rev() {
+            (ma, mb) = (mb, ma);
+            let c = ((b as u128 + ma as u128 - self.round(round, a, ma) as u128) % ma as u128) as u64;
+            (a, b) = (c, a);
+        }
+        a * mb + b
+    }
+}
+
+impl IdEncoder for FeistelIdEncoder {
+    fn obfuscate(&self, id: u64) -> Result<String, IdEncoderError> {
+        if id >= self.domain() {
+            return Err(IdEncoderError::InvalidConfig(format!(
+                "Id is out of the domain ({})",
+                self.domain()
+            )));
+        }
+
+        let base = self.alphabet.len() as u64;
+        let mut n = self.permute(id);
+        let mut digits = vec![self.alphabet[0]; self.length];
+        for digit in digits.iter_mut().rev() {
+            *digit = self.alphabet[(n % base) as usize];
+            n /= base;
+        }
+        Ok(digits.into_iter().collect())
+    }
+
+    fn deobfuscate(&self, id: &str) -> Result<u64, IdEncoderError> {
+        let base = self.alphabet.len() as u64;
+        let mut n = 0u64;
+        let mut length = 0;
+        for c in id.chars() {
+            if length == self.length {
+                return Err(IdEncoderError::InvalidObfuscatedId("Invalid length".to_string()));
+            }
+            let digit = self
+                .alphabet
+                .iter()
+                .position(|x| *x == c)
+                .ok_or_else(|| IdEncoderError::InvalidObfuscatedId(format!("Invalid character: {c}")))?;
+            n = n * base + digit as u64;
+            length += 1;
+        }
+        if length != self.length {
+            return Err(IdEncoderError::InvalidObfuscatedId("Invalid length".to_string()));
+        }
+        Ok(self.inverse(n))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    fn encode_decode() {
+        for (alphabet, length) in [("0123456789", 7), (FeistelIdEncoder::DEFAULT_ALPHABET, 6), ("ab", 9)] {
+            let encoder = FeistelIdEncoder::new(alphabet, length, "secret").unwrap();
+            let domain = encoder.domain();
+            for i in (0..10_000.min(domain)).chain(domain - 100.min(domain)..domain) {
+                let id = encoder.obfuscate(i).unwrap();
+                assert_eq!(id.chars().count(), length);
+                assert!(id.chars().all(|c| alphabet.contains(c)));
+                assert_eq!(encoder.deobfuscate(&id).unwrap(), i);
+            }
+        }
+    }
+
+    #[test]
+    fn is_bijection() {
+        let encoder = FeistelIdEncoder::new("abcde", 5, "secret").unwrap();
+        let mut ids: Vec<_> = (0..encoder.domain()).map(|i| encoder.obfuscate(i).unwrap()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len() as u64, encoder.domain());
+    }
+
+    #[test]
+    fn key_dependent() {
+        let a = FeistelIdEncoder::new(FeistelIdEncoder::DEFAULT_ALPHABET, 6, "key-a").unwrap();
+        let b = FeistelIdEncoder::new(FeistelIdEncoder::DEFAULT_ALPHABET, 6, "key-b").unwrap();
+        assert_ne!(a.obfuscate(1).unwrap(), b.obfuscate(1).unwrap());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let encoder = FeistelIdEncoder::new(FeistelIdEncoder::DEFAULT_ALPHABET, 6, "secret").unwrap();
+        assert!(encoder.obfuscate(encoder.domain()).is_err());
+        assert!(encoder.deobfuscate("abc").is_err());
+        assert!(encoder.deobfuscate("abcdefg").is_err());
+        assert!(encoder.deobfuscate("ABCDEF").is_err());
+
+        assert!(FeistelIdEncoder::new("aa", 6, "secret").is_err());
+        assert!(FeistelIdEncoder::new("ab", 65, "secret").is_err());
+    }
+}
@@ -5,14 +5,15 @@ use super::{IdEncoder, IdEncoderError};
 pub struct HarshIdEncoder(Harsh);
 
 impl HarshIdEncoder {
+    pub const ALPHABET: &'static str = "abcdefghijklmnopqrstuvwxyz1234567890";
+
     pub fn new(salt: &str) -> Result<Self, IdEncoderError> {
-        const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz1234567890";
         const SEPARATORS: &[u8] = b"cfhistu";
 
         let harsh = Harsh::builder()
             .salt(salt.as_bytes())
             .length(6)
-            .alphabet(ALPHABET)
+            .alphabet(Self::ALPHABET.as_bytes())
             .separators(SEPARATORS)
             .build()
             .map_err(|err| IdEncoderError::InvalidConfig(format!("{err}")))?;
@@ -31,8 +32,32 @@ impl IdEncoder for HarshIdEncoder {
             .decode(id)
             .map_err(|err| IdEncoderError::InvalidObfuscatedId(format!("{err}")))?;
         match n.len() {
-            1 => Ok(n[1]),
+            1 => Ok(n[0]),
             _ => Err(IdEncoderError::InvalidObfuscatedId("Id is too big".to_string())),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    fn encode_decode() {
+        let encoder = HarshIdEncoder::new("salt").unwrap();
+        for i in (0..10_000).chain(u64::MAX - 100..u64::MAX) {
+            let id = encoder.obfuscate(i).unwrap();
+            assert!(id.len() >= 6);
+            assert_eq!(encoder.deobfuscate(&id).unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let encoder = HarshIdEncoder::new("salt").unwrap();
+        let pair = encoder.0.encode(&[1, 2]);
+        assert!(encoder.deobfuscate(&pair).is_err());
+        assert!(encoder.deobfuscate("ABC-!").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
+use ring::{
+    rand::SystemRandom,
+    signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair, UnparsedPublicKey},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error as ThisError;
+use utoipa::ToSchema;
+
+#[derive(Debug, ThisError)]
+pub enum JwtKeyError {
+    #[error("Invalid key ({0})")]
+    InvalidKey(String),
+    #[error("Failed to generate key")]
+    KeyGeneration,
+    #[error("Failed to sign data")]
+    SigningFailed,
+    #[error("Signature verification failed")]
+    InvalidSignature,
+}
+
+/// The supported signature algorithms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema, JsonSchema)]
+pub enum JwtAlgorithm {
+    /// Ed25519 signature
+    EdDSA,
+    /// ECDSA using P-256 and SHA-256
+    ES256,
+}
+
+impl JwtAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JwtAlgorithm::EdDSA => "EdDSA",
+            JwtAlgorithm::ES256 => "ES256",
+        }
+    }
+}
+
+impl fmt::Display for JwtAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+enum KeyPairKind {
+    Ed25519(Ed25519KeyPair),
+    Es256(EcdsaKeyPair),
+}
+
+/// Private key used to sign tokens.
+pub struct JwtSigningKey {
+    kid: String,
+    key_pair: KeyPairKind,
+}
+
+impl fmt::Debug for JwtSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtSigningKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm())
+            .finish()
+    }
+}
+
+impl JwtSigningKey {
+    /// Create a signing key from a PKCS#8 (DER) encoded private key.
+    pub fn from_pkcs8<S: ToString>(kid: S, algorithm: JwtAlgorithm, pkcs8: &[u8]) -> Result<Self, JwtKeyError> {
+        let key_pair = match algorithm {
+            JwtAlgorithm::EdDSA => KeyPairKind::Ed25519(
+                Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+                    .map_err(|err| JwtKeyError::InvalidKey(format!("{err}")))?,
+            ),
+            JwtAlgorithm::ES256 => KeyPairKind::Es256(
+                EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8, &SystemRandom::new())
+                    .map_err(|err| JwtKeyError::InvalidKey(format!("{err}")))?,
+            ),
+        };
+
+        Ok(Self { kid: kid.to_string(), key_pair })
+    }
+
+    /// Generate a new PKCS#8 (DER) encoded private key for the given algorithm.
+    pub fn generate_pkcs8(algorithm: JwtAlgorithm) -> Result<Vec<u8>, JwtKeyError> {
+        let random = SystemRandom::new();
+        let document = match algorithm {
+            JwtAlgorithm::EdDSA => Ed25519KeyPair::generate_pkcs8(&random).map_err(|_| JwtKeyError::KeyGeneration)?,
+            JwtAlgorithm::ES256 => EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &random)
+                .map_err(|_| JwtKeyError::KeyGeneration)?,
+        };
+        Ok(document.as_ref().to_vec())
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn algorithm(&self) -> JwtAlgorithm {
+        match &self.key_pair {
+            KeyPairKind::Ed25519(_) => JwtAlgorithm::EdDSA,
+            KeyPairKind::Es256(_) => JwtAlgorithm::ES256,
+        }
+    }
+
+    /// Return the public part of the key.
+    pub fn verifying_key(&self) -> JwtVerifyingKey {
+        let public_key = match &self.key_pair {
+            KeyPairKind::Ed25519(key_pair) => key_pair.public_key().as_ref().to_vec(),
+            KeyPairKind::Es256(key_pair) => key_pair.public_key().as_ref().to_vec(),
+        };
+        JwtVerifyingKey {
+            kid: self.kid.clone(),
+            algorithm: self.algorithm(),
+            public_key,
+        }
+    }
+
+    pub fn sign(&self, random: &SystemRandom, message: &[u8]) -> Result<Vec<u8>, JwtKeyError> {
+        match &self.key_pair {
+            KeyPairKind::Ed25519(key_pair) => Ok(key_pair.sign(message).as_ref().to_vec()),
+            KeyPairKind::Es256(key_pair) => key_pair
+                .sign(random, message)
+                .map(|sig| sig.as_ref().to_vec())
+                .map_err(|_| JwtKeyError::SigningFailed),
+        }
+    }
+}
+
+/// Public key used to verify token signatures.
+#[derive(Clone, Debug)]
+pub struct JwtVerifyingKey {
+    kid: String,
+    algorithm: JwtAlgorithm,
+    public_key: Vec<u8>,
+}
+
+impl JwtVerifyingKey {
+    /// Create a verifying key from the raw public key bytes. For Ed25519 it is the 32 bytes key,
+    /// for ES256 it is the uncompressed (65 bytes) point.
+    pub fn new<S: ToString>(kid: S, algorithm: JwtAlgorithm, public_key: Vec<u8>) -> Result<Self, JwtKeyError> {
+        let is_valid = match algorithm {
+            JwtAlgorithm::EdDSA => public_key.len() == 32,
+            JwtAlgorithm::ES256 => public_key.len() == 65 && public_key[0] == 0x04,
+        };
+        if !is_valid {
+            return Err(JwtKeyError::InvalidKey(format!(
+                "malformed {algorithm} public key of {} bytes",
+                public_key.len()
+            )));
+        }
+
+        Ok(Self {
+            kid: kid.to_string(),
+            algorithm,
+            public_key,
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn algorithm(&self) -> JwtAlgorithm {
+        self.algorithm
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JwtKeyError> {
+        let algorithm: &dyn signature::VerificationAlgorithm = match self.algorithm {
+            JwtAlgorithm::EdDSA => &signature::ED25519,
+            JwtAlgorithm::ES256 => &signature::ECDSA_P256_SHA256_FIXED,
+        };
+        UnparsedPublicKey::new(algorithm, &self.public_key)
+            .verify(message, signature)
+            .map_err(|_| JwtKeyError::InvalidSignature)
+    }
+
+    /// Convert the key into the JSON Web Key representation.
+    pub fn to_jwk(&self) -> Jwk {
+        match self.algorithm {
+            JwtAlgorithm::EdDSA => Jwk {
+                kty: "OKP".into(),
+                crv: "Ed25519".into(),
+                x: B64.encode(&self.public_key),
+                y: None,
+                kid: self.kid.clone(),
+                alg: self.algorithm,
+                usage: "sig".into(),
+            },
+            JwtAlgorithm::ES256 => {
+                // skip the leading 0x04 (uncompressed point) tag, the length is checked on creation
+                let (x, y) = self.public_key[1..].split_at(32);
+                Jwk {
+                    kty: "EC".into(),
+                    crv: "P-256".into(),
+                    x: B64.encode(x),
+                    y: Some(B64.encode(y)),
+                    kid: self.kid.clone(),
+                    alg: self.algorithm,
+                    usage: "sig".into(),
+                }
+            }
+        }
+    }
+}
+
+/// JSON Web Key as of [RFC-7517](https://datatracker.ietf.org/doc/html/rfc7517).
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    pub kid: String,
+    pub alg: JwtAlgorithm,
+    #[serde(rename = "use")]
+    pub usage: String,
+}
+
+/// JSON Web Key Set
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
@@ -0,0 +1,470 @@
+use crate::{
+    crypto::{JwkSet, JwtAlgorithm, JwtKeyError, JwtSigningKey, JwtVerifyingKey},
+    web::{responses::Problem, TokenConfig},
+};
+use axum::{Extension, Json};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
+use chrono::{DateTime, Duration, Utc};
+use ring::rand::SystemRandom;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use thiserror::Error as ThisError;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+const DEFAULT_LEEWAY: u64 = 30;
+
+#[derive(Debug, ThisError)]
+pub enum JwtError {
+    #[error("Invalid token configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Token issuing is not enabled")]
+    MissingSigningKey,
+    #[error(transparent)]
+    KeyError(#[from] JwtKeyError),
+    #[error("Failed to encode claims")]
+    Encode(#[source] serde_json::Error),
+
+    #[error("Malformed token")]
+    Malformed,
+    #[error("Unknown key: {0}")]
+    UnknownKey(String),
+    #[error("Algorithm mismatch")]
+    AlgorithmMismatch,
+    #[error("Token expired")]
+    Expired,
+    #[error("Token is not valid yet")]
+    NotYetValid,
+    #[error("Invalid issuer")]
+    InvalidIssuer,
+    #[error("Invalid audience")]
+    InvalidAudience,
+}
+
+impl From<JwtError> for Problem {
+    fn from(value: JwtError) -> Self {
+        match value {
+            JwtError::InvalidConfig(_) | JwtError::MissingSigningKey | JwtError::Encode(_) => Problem::internal_error()
+                .with_detail(value.to_string())
+                .with_sensitive_dbg(value),
+            JwtError::KeyError(JwtKeyError::InvalidSignature) => Problem::unauthorized_ty("invalid-token")
+                .with_detail(value.to_string())
+                .with_sensitive("invalidSignature"),
+            JwtError::KeyError(_) => Problem::internal_error()
+                .with_detail(value.to_string())
+                .with_sensitive_dbg(value),
+            _ => Problem::unauthorized_ty("invalid-token")
+                .with_detail(value.to_string())
+                .with_sensitive_dbg(value),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JwtHeader {
+    alg: JwtAlgorithm,
+    typ: String,
+    kid: String,
+}
+
+/// The audience claim, it is either a single string or a list of strings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JwtAudience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl JwtAudience {
+    pub fn contains(&self, audience: &str) -> bool {
+        match self {
+            JwtAudience::One(aud) => aud == audience,
+            JwtAudience::Many(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+/// The registered claims with the service specific (private) claims.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JwtClaims<T> {
+    pub iss: String,
+    pub sub: String,
+    pub aud: JwtAudience,
+    pub iat: i64,
+    pub nbf: i64,
+    pub exp: i64,
+    #[serde(flatten)]
+    pub custom: T,
+}
+
+/// A signed token with its expiration.
+#[derive(Clone, Debug)]
+pub struct IssuedJwt {
+    pub token: String,
+    pub expire_at: DateTime<Utc>,
+}
+
+/// Issue and validate signed, expiring tokens (JWT compact serialization).
+pub struct JwtService {
+    issuer: String,
+    audience: String,
+    ttl: Duration,
+    leeway: i64,
+    signing_key: Option<JwtSigningKey>,
+    verifying_keys: HashMap<String, JwtVerifyingKey>,
+    random: SystemRandom,
+}
+
+impl JwtService {
+    pub fn new<S1: ToString, S2: ToString>(issuer: S1, audience: S2, ttl: Duration) -> Self {
+        Self {
+            issuer: issuer.to_string(),
+            audience: audience.to_string(),
+            ttl,
+            leeway: DEFAULT_LEEWAY as i64,
+            signing_key: None,
+            verifying_keys: HashMap::new(),
+            random: SystemRandom::new(),
+        }
+    }
+
+    pub fn from_config(config: &TokenConfig) -> Result<Self, JwtError> {
+        let ttl = i64::try_from(config.ttl).map_err(|err| JwtError::InvalidConfig(format!("ttl: {err}")))?;
+        let leeway = i64::try_from(config.leeway.unwrap_or(DEFAULT_LEEWAY))
+            .map_err(|err| JwtError::InvalidConfig(format!("leeway: {err}")))?;
+
+        let mut service = Self::new(&config.issuer, &config.audience, Duration::seconds(ttl)).with_leeway(leeway);
+        for key in &config.keys {
+            let decode = |value: &str| {
+                B64.decode(value)
+                    .map_err(|err| JwtError::InvalidConfig(format!("key {}: {err}", key.kid)))
+            };
+
+            if let Some(private_key) = &key.private_key {
+                let signing_key = JwtSigningKey::from_pkcs8(&key.kid, key.algorithm, &decode(private_key)?)?;
+                if config.signing_key.as_deref() == Some(key.kid.as_str()) {
+                    service = service.with_signing_key(signing_key);
+                } else {
+                    service = service.with_verifying_key(signing_key.verifying_key());
+                }
+            } else if let Some(public_key) = &key.public_key {
+                service =
+                    service.with_verifying_key(JwtVerifyingKey::new(&key.kid, key.algorithm, decode(public_key)?)?);
+            } else {
+                return Err(JwtError::InvalidConfig(format!(
+                    "key {}: missing key material",
+                    key.kid
+                )));
+            }
+        }
+
+        if let Some(kid) = &config.signing_key {
+            if service.signing_key.is_none() {
+                return Err(JwtError::InvalidConfig(format!("missing private key for {kid}")));
+            }
+        }
+
+        Ok(service)
+    }
+
+    #[must_use]
+    pub fn with_leeway(self, leeway: i64) -> Self {
+        Self { leeway, ..self }
+    }
+
+    /// Set the key used for signing. The public part is also registered for verification.
+    #[must_use]
+    pub fn with_signing_key(mut self, key: JwtSigningKey) -> Self {
+        let verifying_key = key.verifying_key();
+        self.verifying_keys
+            .insert(verifying_key.kid().to_string(), verifying_key);
+        self.signing_key = Some(key);
+        self
+    }
+
+    #[must_use]
+    pub fn with_verifying_key(mut self, key: JwtVerifyingKey) -> Self {
+        self.verifying_keys.insert(key.kid().to_string(), key);
+        self
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn audience(&self) -> &str {
+        &self.audience
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Issue a token for the default audience with the default time to live.
+    pub fn issue<T: Serialize>(&self, subject: &str, custom: T) -> Result<IssuedJwt, JwtError> {
+        self.issue_with(subject, JwtAudience::One(self.audience.clone()), self.ttl, custom)
+    }
+
+    pub fn issue_with<T: Serialize>(
+        &self,
+        subject: &str,
+        audience: JwtAudience,
+        ttl: Duration,
+        custom: T,
+    ) -> Result<IssuedJwt, JwtError> {
+        let signing_key = self.signing_key.as_ref().ok_or(JwtError::MissingSigningKey)?;
+
+        let now = Utc::now();
+        let expire_at = now + ttl;
+        let header = JwtHeader {
+            alg: signing_key.algorithm(),
+            typ: "JWT".into(),
+            kid: signing_key.kid().to_string(),
+        };
+        let claims = JwtClaims {
+            iss: self.issuer.clone(),
+            sub: subject.to_string(),
+            aud: audience,
+            iat: now.timestamp(),
+            nbf: now.timestamp(),
+            exp: expire_at.timestamp(),
+            custom,
+        };
+
+        let header = B64.encode(serde_json::to_vec(&header).map_err(JwtError::Encode)?);
+        let claims = B64.encode(serde_json::to_vec(&claims).map_err(JwtError::Encode)?);
+        let message = format!("{header}.{claims}");
+        let signature = signing_key.sign(&self.random, message.as_bytes())?;
+
+        Ok(IssuedJwt {
+            token: format!("{message}.{}", B64.encode(signature)),
+            expire_at,
+        })
+    }
+
+    /// Validate the signature, the issuer, the audience and the time constraints of a token.
+    pub fn validate<T: DeserializeOwned>(&self, token: &str) -> Result<JwtClaims<T>, JwtError> {
+        let mut parts = token.split('.');
+        let (Some(raw_header), Some(raw_claims), Some(raw_signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(JwtError::Malformed);
+        };
+
+        let header: JwtHeader = B64
+            .decode(raw_header)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .ok_or(JwtError::Malformed)?;
+        let key = self
+            .verifying_keys
+            .get(&header.kid)
+            .ok_or_else(|| JwtError::UnknownKey(header.kid.clone()))?;
+        if key.algorithm() != header.alg {
+            return Err(JwtError::AlgorithmMismatch);
+        }
+
+        let signature = B64.decode(raw_signature).map_err(|_| JwtError::Malformed)?;
+        let message = &token[..raw_header.len() + 1 + raw_claims.len()];
+        key.verify(message.as_bytes(), &signature)?;
+
+        let claims: JwtClaims<T> = B64
+            .decode(raw_claims)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .ok_or(JwtError::Malformed)?;
+
+        let now = Utc::now().timestamp();
+        if claims.exp + self.leeway < now {
+            return Err(JwtError::Expired);
+        }
+        if claims.nbf - self.leeway > now {
+            return Err(JwtError::NotYetValid);
+        }
+        if claims.iss != self.issuer {
+            return Err(JwtError::InvalidIssuer);
+        }
+        if !claims.aud.contains(&self.audience) {
+            return Err(JwtError::InvalidAudience);
+        }
+
+        Ok(claims)
+    }
+
+    /// The public keys in JWKS format.
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys: Vec<_> = self.verifying_keys.values().map(|key| key.to_jwk()).collect();
+        keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+        JwkSet { keys }
+    }
+
+    pub fn create_router<S>(self: &Arc<Self>) -> OpenApiRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        OpenApiRouter::new()
+            .routes(routes!(get_jwks))
+            .layer(Extension(self.clone()))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    description = "Get the public keys to verify the issued tokens.",
+    responses(
+        (status = OK, body = JwkSet)
+    )
+)]
+async fn get_jwks(Extension(service): Extension<Arc<JwtService>>) -> Json<JwkSet> {
+    Json(service.jwks())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::web::TokenKeyConfig;
+    use serde::{Deserialize, Serialize};
+    use shine_test::test;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Custom {
+        data: String,
+    }
+
+    fn create_service(algorithm: JwtAlgorithm, kid: &str) -> JwtService {
+        let pkcs8 = JwtSigningKey::generate_pkcs8(algorithm).unwrap();
+        let key = JwtSigningKey::from_pkcs8(kid, algorithm, &pkcs8).unwrap();
+        JwtService::new("test", "service", Duration::seconds(60)).with_signing_key(key)
+    }
+
+    #[test]
+    fn issue_and_validate() {
+        for algorithm in [JwtAlgorithm::EdDSA, JwtAlgorithm::ES256] {
+            let service = create_service(algorithm, "k1");
+            let token = service.issue("user", Custom { data: "value".into() }).unwrap();
+            let claims = service.validate::<Custom>(&token.token).unwrap();
+            assert_eq!(claims.sub, "user");
+            assert_eq!(claims.custom, Custom { data: "value".into() });
+        }
+    }
+
+    #[test]
+    fn tampered_token_is_rejected() {
+        let service = create_service(JwtAlgorithm::EdDSA, "k1");
+        let token = service.issue("user", Custom { data: "value".into() }).unwrap();
+        let other = service.issue("admin", Custom { data: "value".into() }).unwrap();
+
+        let mut parts: Vec<_> = token.token.split('.').collect();
+        parts[1] = other.token.split('.').nth(1).unwrap();
+        let tampered = parts.join(".");
+
+        assert!(matches!(
+            service.validate::<Custom>(&tampered),
+            Err(JwtError::KeyError(JwtKeyError::InvalidSignature))
+        ));
+        assert!(matches!(service.validate::<Custom>("abc"), Err(JwtError::Malformed)));
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let service = create_service(JwtAlgorithm::ES256, "k1").with_leeway(0);
+        let token = service
+            .issue_with(
+                "user",
+                JwtAudience::One("service".into()),
+                Duration::seconds(-10),
+                Custom { data: "value".into() },
+            )
+            .unwrap();
+        assert!(matches!(
+            service.validate::<Custom>(&token.token),
+            Err(JwtError::Expired)
+        ));
+    }
+
+    #[test]
+    fn audience_and_key_are_checked() {
+        let service = create_service(JwtAlgorithm::EdDSA, "k1");
+        let token = service
+            .issue_with(
+                "user",
+                JwtAudience::Many(vec!["other".into()]),
+                Duration::seconds(60),
+                Custom { data: "value".into() },
+            )
+            .unwrap();
+        assert!(matches!(
+            service.validate::<Custom>(&token.token),
+            Err(JwtError::InvalidAudience)
+        ));
+
+        let other_service = create_service(JwtAlgorithm::EdDSA, "k2");
+        let token = other_service.issue("user", Custom { data: "value".into() }).unwrap();
+        assert!(matches!(
+            service.validate::<Custom>(&token.token),
+            Err(JwtError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn verify_only_service() {
+        let issuer = create_service(JwtAlgorithm::ES256, "k1");
+        let verifier = JwtService::new("test", "service", Duration::seconds(60))
+            .with_verifying_key(issuer.signing_key.as_ref().unwrap().verifying_key());
+
+        let token = issuer.issue("user", Custom { data: "value".into() }).unwrap();
+        assert!(verifier.validate::<Custom>(&token.token).is_ok());
+        assert!(matches!(
+            verifier.issue("user", Custom { data: "value".into() }),
+            Err(JwtError::MissingSigningKey)
+        ));
+
+        let jwks = verifier.jwks();
+        assert_eq!(jwks.keys.len(), 1);
+        assert_eq!(jwks.keys[0].kty, "EC");
+        assert!(jwks.keys[0].y.is_some());
+    }
+
+    #[test]
+    fn malformed_public_key_is_rejected() {
+        let issuer = create_service(JwtAlgorithm::ES256, "k1");
+        let jwk = &issuer.jwks().keys[0];
+        let public_key = [
+            &[0x04_u8][..],
+            &B64.decode(&jwk.x).unwrap(),
+            &B64.decode(jwk.y.as_ref().unwrap()).unwrap(),
+        ]
+        .concat();
+
+        let config = |algorithm: JwtAlgorithm, public_key: &[u8]| TokenConfig {
+            issuer: "test".into(),
+            audience: "service".into(),
+            ttl: 60,
+            leeway: None,
+            signing_key: None,
+            keys: vec![TokenKeyConfig {
+                kid: "k1".into(),
+                algorithm,
+                private_key: None,
+                public_key: Some(B64.encode(public_key)),
+            }],
+        };
+
+        let verifier = JwtService::from_config(&config(JwtAlgorithm::ES256, &public_key)).unwrap();
+        assert_eq!(verifier.jwks().keys[0].x, jwk.x);
+        assert_eq!(verifier.jwks().keys[0].y, jwk.y);
+
+        for (algorithm, public_key) in [
+            (JwtAlgorithm::ES256, &public_key[..1]),
+            (JwtAlgorithm::ES256, &public_key[..64]),
+            (JwtAlgorithm::ES256, &public_key[1..]),
+            (JwtAlgorithm::EdDSA, &public_key[..]),
+        ] {
+            assert!(matches!(
+                JwtService::from_config(&config(algorithm, public_key)),
+                Err(JwtError::KeyError(JwtKeyError::InvalidKey(_)))
+            ));
+        }
+    }
+}
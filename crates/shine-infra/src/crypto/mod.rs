@@ -9,7 +9,17 @@ mod harsh_id_encoder;
 pub use self::harsh_id_encoder::*;
 mod prefixed_id_encoder;
 pub use self::prefixed_id_encoder::*;
+mod feistel_id_encoder;
+pub use self::feistel_id_encoder::*;
+mod sqids_id_encoder;
+pub use self::sqids_id_encoder::*;
+mod checksum_id_encoder;
+pub use self::checksum_id_encoder::*;
 mod data_protection;
 pub use self::data_protection::*;
+mod jwt_key;
+pub use self::jwt_key::*;
+mod jwt_service;
+pub use self::jwt_service::*;
 
 pub mod random;
@@ -3,6 +3,8 @@ use super::{IdEncoder, IdEncoderError, Optimus};
 pub struct OptimusIdEncoder(Optimus);
 
 impl OptimusIdEncoder {
+    pub const ALPHABET: &'static str = "0123456789";
+
     pub fn new(prime: u64, random: u64) -> Self {
         Self(Optimus::new(prime, random))
     }
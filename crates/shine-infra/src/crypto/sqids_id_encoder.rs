@@ -0,0 +1,83 @@
+use sqids::Sqids;
+use std::collections::HashSet;
+
+use super::{IdEncoder, IdEncoderError};
+
+/// Sqids based encoder. Ids containing a word of the blocklist are never generated, the builtin profanity list
+/// is always used and it can be extended with custom words.
+pub struct SqidsIdEncoder(Sqids);
+
+impl SqidsIdEncoder {
+    pub const DEFAULT_ALPHABET: &'static str = "abcdefghijklmnopqrstuvwxyz1234567890";
+
+    pub fn new(alphabet: &str, min_length: u8, blocklist: &[String]) -> Result<Self, IdEncoderError> {
+        let mut words: HashSet<String> = sqids::default_blocklist();
+        words.extend(blocklist.iter().map(|word| word.to_lowercase()));
+
+        let sqids = Sqids::builder()
+            .alphabet(alphabet.chars().collect())
+            .min_length(min_length)
+            .blocklist(words)
+            .build()
+            .map_err(|err| IdEncoderError::InvalidConfig(format!("{err}")))?;
+        Ok(Self(sqids))
+    }
+}
+
+impl IdEncoder for SqidsIdEncoder {
+    fn obfuscate(&self, id: u64) -> Result<String, IdEncoderError> {
+        self.0
+            .encode(&[id])
+            .map_err(|err| IdEncoderError::InvalidConfig(format!("{err}")))
+    }
+
+    fn deobfuscate(&self, id: &str) -> Result<u64, IdEncoderError> {
+        let n = self.0.decode(id);
+        let [n] = n[..] else {
+            return Err(IdEncoderError::InvalidObfuscatedId(
+                "Id is not a single number".to_string(),
+            ));
+        };
+
+        // multiple ids may decode to the same number, accept only the canonical form
+        if self.obfuscate(n)? != id {
+            return Err(IdEncoderError::InvalidObfuscatedId("Non-canonical id".to_string()));
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    fn encode_decode() {
+        let encoder = SqidsIdEncoder::new(SqidsIdEncoder::DEFAULT_ALPHABET, 6, &[]).unwrap();
+        for i in (0..10_000).chain(u64::MAX - 100..u64::MAX) {
+            let id = encoder.obfuscate(i).unwrap();
+            assert!(id.len() >= 6);
+            assert_eq!(encoder.deobfuscate(&id).unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn blocklist() {
+        let plain = SqidsIdEncoder::new(SqidsIdEncoder::DEFAULT_ALPHABET, 6, &[]).unwrap();
+        let id = plain.obfuscate(42).unwrap();
+
+        let encoder = SqidsIdEncoder::new(SqidsIdEncoder::DEFAULT_ALPHABET, 6, &[id.to_uppercase()]).unwrap();
+        let blocked = encoder.obfuscate(42).unwrap();
+        assert_ne!(blocked, id);
+        assert_eq!(encoder.deobfuscate(&blocked).unwrap(), 42);
+        assert!(encoder.deobfuscate(&id).is_err());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let encoder = SqidsIdEncoder::new(SqidsIdEncoder::DEFAULT_ALPHABET, 6, &[]).unwrap();
+        assert!(encoder.deobfuscate("").is_err());
+        assert!(encoder.deobfuscate("ABC-!").is_err());
+    }
+}
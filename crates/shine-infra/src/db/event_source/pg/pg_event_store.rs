@@ -4,9 +4,14 @@ use crate::{
         DBError, PGClient, PGErrorChecks,
     },
     pg_query,
+    telemetry::{metrics, MetricSet},
+};
+use opentelemetry::{
+    metrics::{Histogram, Meter},
+    KeyValue,
 };
 use postgres_from_row::FromRow;
-use std::{borrow::Cow, marker::PhantomData};
+use std::{borrow::Cow, marker::PhantomData, time::Instant};
 use tokio_postgres::IsolationLevel;
 
 pg_query!( CreateStream =>
@@ -160,67 +165,47 @@ where
     }
 }
 
-impl<E, S> EventStore for PgEventDbContext<'_, E, S>
-where
-    E: Event,
-    S: StreamId,
-{
-    type Event = E;
-    type StreamId = S;
-
-    async fn create_stream(&mut self, aggregate_id: &Self::StreamId) -> Result<(), EventSourceError> {
-        if let Err(err) = self
-            .stmts_store
-            .create_stream
-            .execute(&self.client, &aggregate_id.to_string().as_str())
-            .await
-        {
-            if err.is_constraint(
-                &format!("es_heads_{}", <E as Event>::NAME),
-                &format!("es_heads_{}_pkey", <E as Event>::NAME),
-            ) {
-                Err(EventSourceError::Conflict)
-            } else {
-                Err(DBError::from(err).into())
-            }
-        } else {
-            Ok(())
-        }
-    }
+struct EventStoreMetrics {
+    append_duration: Histogram<f64>,
+}
 
-    async fn get_stream_version(&mut self, aggregate_id: &Self::StreamId) -> Result<Option<usize>, EventSourceError> {
-        match self
-            .stmts_store
-            .get_version
-            .query_opt(&self.client, &aggregate_id.to_string().as_str())
-            .await
-            .map_err(DBError::from)?
-        {
-            Some(v) => Ok(Some(v as usize)),
-            None => Ok(None),
+impl MetricSet for EventStoreMetrics {
+    fn new(meter: &Meter) -> Self {
+        Self {
+            append_duration: meter
+                .f64_histogram("event_store_append_duration")
+                .with_unit("s")
+                .with_description("Duration of appending events to a stream")
+                .build(),
         }
     }
+}
 
-    async fn delete_stream(&mut self, aggregate_id: &Self::StreamId) -> Result<(), EventSourceError> {
-        if self
-            .stmts_store
-            .delete_stream
-            .execute(&self.client, &aggregate_id.to_string().as_str())
-            .await
-            .map_err(DBError::from)?
-            != 1
-        {
-            Err(EventSourceError::StreamNotFound)
-        } else {
-            Ok(())
-        }
-    }
+fn record_append<E: Event, T>(start: Instant, result: &Result<T, EventSourceError>) {
+    let result = match result {
+        Ok(_) => "ok",
+        Err(EventSourceError::Conflict) => "conflict",
+        Err(_) => "error",
+    };
+    metrics::<EventStoreMetrics>().append_duration.record(
+        start.elapsed().as_secs_f64(),
+        &[
+            KeyValue::new("event", <E as Event>::NAME),
+            KeyValue::new("result", result),
+        ],
+    );
+}
 
-    async fn store_events(
+impl<E, S> PgEventDbContext<'_, E, S>
+where
+    E: Event,
+    S: StreamId,
+{
+    async fn try_store_events(
         &mut self,
-        aggregate_id: &Self::StreamId,
+        aggregate_id: &S,
         expected_version: usize,
-        event: &[Self::Event],
+        event: &[E],
     ) -> Result<usize, EventSourceError> {
         let transaction = self
             .client
@@ -294,11 +279,7 @@ where
         Ok(new_version)
     }
 
-    async fn unchecked_store_events(
-        &mut self,
-        aggregate_id: &Self::StreamId,
-        event: &[Self::Event],
-    ) -> Result<usize, EventSourceError> {
+    async fn try_unchecked_store_events(&mut self, aggregate_id: &S, event: &[E]) -> Result<usize, EventSourceError> {
         let mut version = None;
         for event in event.iter() {
             let data = serde_json::to_string(event).map_err(EventSourceError::EventSerialization)?;
@@ -333,6 +314,86 @@ where
             }
         }
     }
+}
+
+impl<E, S> EventStore for PgEventDbContext<'_, E, S>
+where
+    E: Event,
+    S: StreamId,
+{
+    type Event = E;
+    type StreamId = S;
+
+    async fn create_stream(&mut self, aggregate_id: &Self::StreamId) -> Result<(), EventSourceError> {
+        if let Err(err) = self
+            .stmts_store
+            .create_stream
+            .execute(&self.client, &aggregate_id.to_string().as_str())
+            .await
+        {
+            if err.is_constraint(
+                &format!("es_heads_{}", <E as Event>::NAME),
+                &format!("es_heads_{}_pkey", <E as Event>::NAME),
+            ) {
+                Err(EventSourceError::Conflict)
+            } else {
+                Err(DBError::from(err).into())
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn get_stream_version(&mut self, aggregate_id: &Self::StreamId) -> Result<Option<usize>, EventSourceError> {
+        match self
+            .stmts_store
+            .get_version
+            .query_opt(&self.client, &aggregate_id.to_string().as_str())
+            .await
+            .map_err(DBError::from)?
+        {
+            Some(v) => Ok(Some(v as usize)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete_stream(&mut self, aggregate_id: &Self::StreamId) -> Result<(), EventSourceError> {
+        if self
+            .stmts_store
+            .delete_stream
+            .execute(&self.client, &aggregate_id.to_string().as_str())
+            .await
+            .map_err(DBError::from)?
+            != 1
+        {
+            Err(EventSourceError::StreamNotFound)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn store_events(
+        &mut self,
+        aggregate_id: &Self::StreamId,
+        expected_version: usize,
+        event: &[Self::Event],
+    ) -> Result<usize, EventSourceError> {
+        let start = Instant::now();
+        let result = self.try_store_events(aggregate_id, expected_version, event).await;
+        record_append::<E, _>(start, &result);
+        result
+    }
+
+    async fn unchecked_store_events(
+        &mut self,
+        aggregate_id: &Self::StreamId,
+        event: &[Self::Event],
+    ) -> Result<usize, EventSourceError> {
+        let start = Instant::now();
+        let result = self.try_unchecked_store_events(aggregate_id, event).await;
+        record_append::<E, _>(start, &result);
+        result
+    }
 
     async fn get_events(
         &mut self,
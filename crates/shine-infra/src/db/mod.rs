@@ -2,6 +2,8 @@ mod cacerts;
 pub use self::cacerts::*;
 mod db_error;
 pub use self::db_error::*;
+mod query_tracer;
+pub use self::query_tracer::*;
 mod redis;
 pub use self::redis::*;
 mod postgres;
@@ -75,8 +75,10 @@ macro_rules! pg_query {
             where
                 T: $crate::db::PGRawConnection
             {
-                let statement = self.statement(client).await?;
-                let rows = client.query(&statement, &[$($pid,)*]).await?;
+                let rows = client.traced("query", stringify!($id), async {
+                    let statement = self.statement(client).await?;
+                    client.query(&statement, &[$($pid,)*]).await
+                }).await?;
 
                 rows.into_iter().map(|row| row.try_get(&stringify!($rid))).collect::<Result<Vec<_>,_>>()
             }
@@ -91,8 +93,10 @@ macro_rules! pg_query {
             where
                 T: $crate::db::PGRawConnection
             {
-                let statement = self.statement(client).await?;
-                let row = client.query_one(&statement, &[$($pid,)*]).await?;
+                let row = client.traced("query_one", stringify!($id), async {
+                    let statement = self.statement(client).await?;
+                    client.query_one(&statement, &[$($pid,)*]).await
+                }).await?;
                 let value: $rty = row.try_get(&stringify!($rid))?;
                 Ok(value)
             }
@@ -107,8 +111,10 @@ macro_rules! pg_query {
             where
                 T: $crate::db::PGRawConnection
             {
-                let statement = self.statement(client).await?;
-                client.query_opt(&statement, &[$($pid,)*])
+                client.traced("query_opt", stringify!($id), async {
+                    let statement = self.statement(client).await?;
+                    client.query_opt(&statement, &[$($pid,)*]).await
+                })
                     .await?
                     .map(|r| r.try_get(&stringify!($rid)))
                     .transpose()
@@ -134,8 +140,10 @@ macro_rules! pg_query {
             where
                 T: $crate::db::PGRawConnection
             {
-                let statement = self.statement(client).await?;
-                let rows = client.query(&statement, &[$($pid,)*]).await?;
+                let rows = client.traced("query", stringify!($id), async {
+                    let statement = self.statement(client).await?;
+                    client.query(&statement, &[$($pid,)*]).await
+                }).await?;
 
                 rows.into_iter()
                     .map(|row| <$oty as postgres_from_row::FromRow>::try_from_row(&row))
@@ -152,10 +160,10 @@ macro_rules! pg_query {
             where
                 T: $crate::db::PGRawConnection
             {
-                let statement = self.statement(client).await?;
-                let row = client
-                    .query_one(&statement, &[$($pid,)*])
-                    .await?;
+                let row = client.traced("query_one", stringify!($id), async {
+                    let statement = self.statement(client).await?;
+                    client.query_one(&statement, &[$($pid,)*]).await
+                }).await?;
                 <$oty as postgres_from_row::FromRow>::try_from_row(&row)
             }
 
@@ -169,8 +177,10 @@ macro_rules! pg_query {
             where
                 T: $crate::db::PGRawConnection
             {
-                let statement = self.statement(client).await?;
-                client.query_opt(&statement, &[$($pid,)*])
+                client.traced("query_opt", stringify!($id), async {
+                    let statement = self.statement(client).await?;
+                    client.query_opt(&statement, &[$($pid,)*]).await
+                })
                     .await?
                     .map(|row| <$oty as postgres_from_row::FromRow>::try_from_row(&row) )
                     .transpose()
@@ -195,8 +205,10 @@ macro_rules! pg_query {
             where
                 T: $crate::db::PGRawConnection
             {
-                let statement = self.statement(client).await?;
-                client.execute(&statement, &[$($pid,)*]).await
+                client.traced("execute", stringify!($id), async {
+                    let statement = self.statement(client).await?;
+                    client.execute(&statement, &[$($pid,)*]).await
+                }).await
             }
         }
     };
@@ -1,6 +1,6 @@
 use crate::db::cacerts::{get_root_cert_store, CertError};
-use crate::db::DBError;
-use crate::health::StatusProvider;
+use crate::db::{DBError, QueryTracer};
+use crate::health::{HealthStatus, StatusProvider};
 use async_trait::async_trait;
 use bb8::{ManageConnection, Pool as BB8Pool, PooledConnection, RunError};
 use bb8_postgres::PostgresConnectionManager;
@@ -9,7 +9,7 @@ use std::ops::Deref;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::{collections::HashMap, ops::DerefMut};
+use std::{collections::HashMap, future::Future, ops::DerefMut};
 use thiserror::Error as ThisError;
 use tokio::sync::RwLock;
 use tokio_postgres::{tls::MakeTlsConnect, GenericClient, IsolationLevel, Statement};
@@ -34,9 +34,19 @@ where
     prepared_statements: Arc<RwLock<HashMap<usize, Statement>>>,
     client: T,
     listener: PGListener,
+    tracer: QueryTracer,
 }
 
 impl<T: PGRawConnection> PGConnection<T> {
+    /// Run an operation of a named statement in a traced span.
+    #[inline]
+    pub async fn traced<F, R>(&self, operation: &str, statement: &str, query: F) -> Result<R, PGError>
+    where
+        F: Future<Output = Result<R, PGError>>,
+    {
+        self.tracer.trace(operation, statement, query).await
+    }
+
     #[inline]
     pub async fn create_prepared_statement(&self, stmt: &str, types: Vec<PGType>) -> PGStatementId {
         let id = self.prepared_statement_id.fetch_add(1, Ordering::Relaxed);
@@ -87,6 +97,7 @@ impl PGConnection<PGRawClient> {
         listener: PGListener,
         prepared_statement_id: Arc<AtomicUsize>,
         prepared_statements_builder: Arc<RwLock<HashMap<usize, PreparedStatementBuilder>>>,
+        tracer: QueryTracer,
     ) -> Self {
         Self {
             prepared_statement_id,
@@ -94,6 +105,7 @@ impl PGConnection<PGRawClient> {
             prepared_statements: Arc::new(RwLock::new(HashMap::default())),
             client: pg_client,
             listener,
+            tracer,
         }
     }
 
@@ -126,24 +138,30 @@ impl PGConnection<PGRawClient> {
         if let Some(level) = isolation_level {
             transaction_builder = transaction_builder.isolation_level(level);
         }
-        let transaction = transaction_builder.start().await?;
+        let tracer = self.tracer;
+        let transaction = tracer
+            .trace("BEGIN", "transaction", transaction_builder.start())
+            .await?;
         Ok(PGConnection {
             prepared_statement_id: self.prepared_statement_id.clone(),
             prepared_statements_builder: self.prepared_statements_builder.clone(),
             prepared_statements: self.prepared_statements.clone(),
             client: transaction,
             listener: self.listener.clone(),
+            tracer,
         })
     }
 }
 
 impl PGConnection<PGRawTransaction<'_>> {
     pub async fn commit(self) -> Result<(), PGError> {
-        self.client.commit().await
+        self.tracer.trace("COMMIT", "transaction", self.client.commit()).await
     }
 
     pub async fn rollback(self) -> Result<(), PGError> {
-        self.client.rollback().await
+        self.tracer
+            .trace("ROLLBACK", "transaction", self.client.rollback())
+            .await
     }
 }
 
@@ -168,10 +186,11 @@ pub struct PGConnectionManager {
     prepared_statement_id: Arc<AtomicUsize>,
     prepared_statements_builder: Arc<RwLock<HashMap<usize, PreparedStatementBuilder>>>,
     listener: PGListener,
+    tracer: QueryTracer,
 }
 
 impl PGConnectionManager {
-    pub fn new(config: PGConfig, tls: MakeRustlsConnect) -> Self {
+    pub fn new(config: PGConfig, tls: MakeRustlsConnect, tracer: QueryTracer) -> Self {
         let connection_manager = PostgresConnectionManager::new(config.clone(), tls.clone());
         let listener = PGListener::new(config, tls);
 
@@ -180,6 +199,7 @@ impl PGConnectionManager {
             prepared_statement_id: Arc::new(AtomicUsize::new(1)),
             prepared_statements_builder: Arc::new(RwLock::new(HashMap::default())),
             listener,
+            tracer,
         }
     }
 }
@@ -195,6 +215,7 @@ impl bb8::ManageConnection for PGConnectionManager {
             self.listener.clone(),
             self.prepared_statement_id.clone(),
             self.prepared_statements_builder.clone(),
+            self.tracer,
         ))
     }
 
@@ -247,16 +268,27 @@ impl PostgresPoolStatus {
 
 #[async_trait]
 impl StatusProvider for PostgresPoolStatus {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "postgres"
     }
 
-    async fn status(&self) -> serde_json::Value {
+    async fn status(&self) -> HealthStatus {
+        let probe = match self.pool.get().await {
+            Ok(conn) => conn.simple_query("SELECT 1").await.map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
         let state = self.pool.state();
-        serde_json::json!({
+        let details = serde_json::json!({
             "connections": state.connections,
             "idleConnections": state.idle_connections
-        })
+        });
+        match probe {
+            Ok(_) => HealthStatus::healthy(details),
+            Err(err) => {
+                log::warn!("Postgres health probe failed: {err}");
+                HealthStatus::unhealthy(details)
+            }
+        }
     }
 }
 
@@ -269,15 +301,17 @@ pub async fn create_postgres_pool(cns: &str) -> Result<PGConnectionPool, PGCreat
     let tls = MakeRustlsConnect::new(tls_config);
 
     // Parse connection string
-    // Format: postgres://...?connect_timeout=3&pool_timeout=5
+    // Format: postgres://...?connect_timeout=3&pool_timeout=5&slow_query=500
     // - connect_timeout: PostgreSQL native parameter in SECONDS (TCP connection establishment)
     // - pool_timeout: custom parameter in SECONDS for bb8 pool (acquiring connection from pool, including waiting for connection to be established if pool is exhausted)
+    // - slow_query: custom parameter in MILLISECONDS, the queries taking longer are logged and counted
 
     let (pool_timeout_opt, cns_clean) = crate::db::extract_and_strip_param(cns, "pool_timeout");
     let pool_timeout_secs = pool_timeout_opt.unwrap_or(30); // Default: 30s
+    let (tracer, cns_clean) = QueryTracer::from_cns("postgresql", &cns_clean);
 
     let pg_config = PGConfig::from_str(&cns_clean)?;
-    let postgres_manager = PGConnectionManager::new(pg_config, tls);
+    let postgres_manager = PGConnectionManager::new(pg_config, tls, tracer);
     let postgres = bb8::Pool::builder()
         .max_size(10) // Set the maximum number of connections in the pool
         .connection_timeout(std::time::Duration::from_secs(pool_timeout_secs))
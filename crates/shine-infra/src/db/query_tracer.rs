@@ -0,0 +1,160 @@
+use crate::telemetry::{metrics, MetricSet};
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tracing::{field::Empty, Instrument};
+
+/// Default threshold of the slow queries in milliseconds.
+pub const DEFAULT_SLOW_QUERY_MS: u64 = 500;
+
+struct QueryMetrics {
+    slow_queries: Counter<u64>,
+}
+
+impl MetricSet for QueryMetrics {
+    fn new(meter: &Meter) -> Self {
+        Self {
+            slow_queries: meter
+                .u64_counter("db_slow_queries")
+                .with_description("Number of the queries exceeding the slow query threshold")
+                .build(),
+        }
+    }
+}
+
+/// Classify the database errors for the `error.type` span attribute. The error messages may contain
+/// the values of the rows (ex. the conflicting key of a constraint violation), thus only the error code
+/// or kind is recorded.
+pub trait QueryErrorType {
+    fn error_type(&self) -> String;
+}
+
+impl QueryErrorType for tokio_postgres::Error {
+    fn error_type(&self) -> String {
+        match self.code() {
+            Some(code) => code.code().to_string(),
+            None if self.is_closed() => "closed".to_string(),
+            None => "client".to_string(),
+        }
+    }
+}
+
+impl QueryErrorType for redis::RedisError {
+    fn error_type(&self) -> String {
+        self.code().unwrap_or_else(|| self.category()).to_string()
+    }
+}
+
+/// Trace the database operations following the OpenTelemetry database semantic conventions.
+/// The statements are identified by their name (or command), the parameters are never recorded.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryTracer {
+    system: &'static str,
+    slow_query: Duration,
+}
+
+impl QueryTracer {
+    pub fn new(system: &'static str, slow_query: Duration) -> Self {
+        Self { system, slow_query }
+    }
+
+    /// Create a tracer with the slow query threshold (in milliseconds) of the connection string,
+    /// the `slow_query` parameter is stripped from the returned connection string.
+    pub(crate) fn from_cns(system: &'static str, cns: &str) -> (Self, String) {
+        let (slow_query_opt, cns_clean) = crate::db::extract_and_strip_param(cns, "slow_query");
+        let slow_query = Duration::from_millis(slow_query_opt.unwrap_or(DEFAULT_SLOW_QUERY_MS));
+        (Self::new(system, slow_query), cns_clean)
+    }
+
+    pub fn slow_query(&self) -> Duration {
+        self.slow_query
+    }
+
+    /// Run the operation in a client span and report it if it exceeds the slow query threshold.
+    pub async fn trace<F, T, E>(&self, operation: &str, statement: &str, query: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: QueryErrorType,
+    {
+        let span = tracing::info_span!(
+            "db_query",
+            otel.name = format!("{operation} {statement}"),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            db.system.name = self.system,
+            db.operation.name = operation,
+            db.query.summary = statement,
+            error.type = Empty,
+        );
+
+        let start = Instant::now();
+        let result = query.instrument(span.clone()).await;
+        let elapsed = start.elapsed();
+
+        if let Err(err) = &result {
+            span.record("otel.status_code", "ERROR");
+            span.record("error.type", err.error_type());
+        }
+
+        if elapsed >= self.slow_query {
+            log::warn!(
+                "Slow {} query, {operation} {statement} took {}ms",
+                self.system,
+                elapsed.as_millis()
+            );
+            metrics::<QueryMetrics>().slow_queries.add(
+                1,
+                &[
+                    KeyValue::new("db.system.name", self.system),
+                    KeyValue::new("db.query.summary", statement.to_string()),
+                ],
+            );
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[derive(Debug, PartialEq)]
+    struct Conflict(String);
+
+    impl QueryErrorType for Conflict {
+        fn error_type(&self) -> String {
+            "conflict".to_string()
+        }
+    }
+
+    #[test]
+    fn parse_slow_query_threshold() {
+        let (tracer, cns) = QueryTracer::from_cns("postgresql", "postgres://db?slow_query=250&connect_timeout=3");
+        assert_eq!(tracer.slow_query(), Duration::from_millis(250));
+        assert_eq!(cns, "postgres://db?connect_timeout=3");
+
+        let (tracer, cns) = QueryTracer::from_cns("redis", "redis://host:6379");
+        assert_eq!(tracer.slow_query(), Duration::from_millis(DEFAULT_SLOW_QUERY_MS));
+        assert_eq!(cns, "redis://host:6379");
+    }
+
+    #[test]
+    async fn trace_forwards_result() {
+        let tracer = QueryTracer::new("postgresql", Duration::ZERO);
+        let ok = tracer.trace("query", "GetUser", async { Ok::<_, Conflict>(42) }).await;
+        assert_eq!(ok, Ok(42));
+        let err = tracer
+            .trace("execute", "DeleteUser", async {
+                Err::<u64, _>(Conflict("user@me.com".to_string()))
+            })
+            .await;
+        assert_eq!(err, Err(Conflict("user@me.com".to_string())));
+    }
+}
@@ -1,10 +1,100 @@
-use crate::health::StatusProvider;
+use crate::{
+    db::QueryTracer,
+    health::{HealthStatus, StatusProvider},
+};
 use async_trait::async_trait;
 use bb8::{ManageConnection, Pool as BB8Pool, PooledConnection, RunError};
+use redis::{
+    aio::{ConnectionLike, MultiplexedConnection},
+    Arg, Cmd, IntoConnectionInfo, Pipeline, RedisError, RedisFuture, Value,
+};
 
-pub use bb8_redis::RedisConnectionManager;
 pub use shine_infra_macros::RedisJsonValue;
 
+/// Redis connection tracing the commands.
+pub struct RedisConnection {
+    connection: MultiplexedConnection,
+    tracer: QueryTracer,
+}
+
+fn command_name(cmd: &Cmd) -> String {
+    match cmd.args_iter().next() {
+        Some(Arg::Simple(name)) => String::from_utf8_lossy(name).to_uppercase(),
+        _ => "UNKNOWN".to_string(),
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let tracer = self.tracer;
+        Box::pin(async move {
+            let name = command_name(cmd);
+            tracer
+                .trace(&name, &name, self.connection.req_packed_command(cmd))
+                .await
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        let tracer = self.tracer;
+        Box::pin(async move {
+            let names = cmd.cmd_iter().map(command_name).collect::<Vec<_>>().join(" ");
+            tracer
+                .trace(
+                    "PIPELINE",
+                    &names,
+                    self.connection.req_packed_commands(cmd, offset, count),
+                )
+                .await
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.connection.get_db()
+    }
+}
+
+/// Manage the traced connections of the Redis pool.
+#[derive(Clone, Debug)]
+pub struct RedisConnectionManager {
+    manager: bb8_redis::RedisConnectionManager,
+    tracer: QueryTracer,
+}
+
+impl RedisConnectionManager {
+    pub fn new<T: IntoConnectionInfo>(info: T, tracer: QueryTracer) -> Result<Self, RedisError> {
+        Ok(Self {
+            manager: bb8_redis::RedisConnectionManager::new(info)?,
+            tracer,
+        })
+    }
+}
+
+impl ManageConnection for RedisConnectionManager {
+    type Connection = RedisConnection;
+    type Error = RedisError;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(RedisConnection {
+            connection: self.manager.connect().await?,
+            tracer: self.tracer,
+        })
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.manager.is_valid(&mut conn.connection).await
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        self.manager.has_broken(&mut conn.connection)
+    }
+}
+
 pub type RedisConnectionError = RunError<<RedisConnectionManager as ManageConnection>::Error>;
 pub type RedisConnectionPool = BB8Pool<RedisConnectionManager>;
 pub type RedisPooledConnection<'a> = PooledConnection<'a, RedisConnectionManager>;
@@ -21,29 +111,45 @@ impl RedisPoolStatus {
 
 #[async_trait]
 impl StatusProvider for RedisPoolStatus {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "redis"
     }
 
-    async fn status(&self) -> serde_json::Value {
+    async fn status(&self) -> HealthStatus {
+        let probe = match self.pool.get().await {
+            Ok(mut conn) => redis::cmd("PING")
+                .query_async::<String>(&mut *conn)
+                .await
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
         let state = self.pool.state();
-        serde_json::json!({
+        let details = serde_json::json!({
             "connections": state.connections,
             "idleConnections": state.idle_connections
-        })
+        });
+        match probe {
+            Ok(_) => HealthStatus::healthy(details),
+            Err(err) => {
+                log::warn!("Redis health probe failed: {err}");
+                HealthStatus::unhealthy(details)
+            }
+        }
     }
 }
 
 pub async fn create_redis_pool(cns: &str) -> Result<RedisConnectionPool, RedisConnectionError> {
     // Parse connection string
-    // Format: redis://host:port?timeout=3000&pool_timeout=5000
+    // Format: redis://host:port?timeout=3000&pool_timeout=5000&slow_query=500
     // - timeout: Redis native parameter in MILLISECONDS (TCP connection and command timeout)
     // - pool_timeout: custom parameter in MILLISECONDS for bb8 pool (acquiring connection from pool, including waiting for connection to be established if pool is exhausted)
+    // - slow_query: custom parameter in MILLISECONDS, the commands taking longer are logged and counted
 
     let (pool_timeout_opt, cns_clean) = crate::db::extract_and_strip_param(cns, "pool_timeout");
     let pool_timeout_ms = pool_timeout_opt.unwrap_or(30000); // Default: 30s
+    let (tracer, cns_clean) = QueryTracer::from_cns("redis", &cns_clean);
 
-    let redis_manager = RedisConnectionManager::new(cns_clean)?;
+    let redis_manager = RedisConnectionManager::new(cns_clean, tracer)?;
     let redis = bb8::Pool::builder()
         .max_size(10)
         .connection_timeout(std::time::Duration::from_millis(pool_timeout_ms))
@@ -58,3 +164,11 @@ pub async fn create_redis_pool(cns: &str) -> Result<RedisConnectionPool, RedisCo
 
     Ok(redis)
 }
+
+/// Create a dedicated connection for the pub/sub messages. Pool related parameters of the connection string are ignored.
+pub async fn create_redis_pubsub(cns: &str) -> Result<redis::aio::PubSub, redis::RedisError> {
+    let (_, cns_clean) = crate::db::extract_and_strip_param(cns, "pool_timeout");
+    let (_, cns_clean) = crate::db::extract_and_strip_param(&cns_clean, "slow_query");
+    let client = redis::Client::open(cns_clean)?;
+    client.get_async_pubsub().await
+}
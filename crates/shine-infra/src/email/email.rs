@@ -1,4 +1,4 @@
-use crate::email::normalizer::normalize_email;
+use crate::email::normalizer::{email_normalizer, EmailNormalizer};
 use hex;
 use ring::digest;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -48,7 +48,7 @@ impl Email {
         if !trimmed.validate_email() {
             return Err(ValidationError::new("email"));
         }
-        let (raw, normalized) = normalize_email(input);
+        let (raw, normalized) = email_normalizer().normalize(input);
         Ok(Self { raw, normalized })
     }
 
@@ -73,9 +73,21 @@ impl Email {
         hash_email(&self.normalized)
     }
 
+    /// The hash of the email normalized by the given (ex. a previous version of the) rules, if it differs from
+    /// the current hash. The rows not migrated to the current rules are stored with the hash of their rules.
+    pub fn hash_with(&self, normalizer: &EmailNormalizer) -> Option<String> {
+        let (_, normalized) = normalizer.normalize(&self.raw);
+        (normalized != self.normalized).then(|| hash_email(&normalized))
+    }
+
     pub fn raw_hash(&self) -> String {
         hash_email(&self.raw)
     }
+
+    /// Check if the address belongs to a known disposable email domain.
+    pub fn is_disposable(&self) -> bool {
+        email_normalizer().is_disposable(&self.raw)
+    }
 }
 
 impl fmt::Display for Email {
@@ -112,6 +124,7 @@ impl Serialize for Email {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::email::EmailProviderRule;
 
     #[test]
     fn test_email_raw_is_lowercase_trimmed() {
@@ -125,6 +138,27 @@ mod tests {
         assert_eq!(e.normalized(), "userna@gmail.com");
     }
 
+    #[test]
+    fn test_email_hash_with() {
+        let previous = EmailNormalizer::previous_builtin();
+        let email = Email::new("user+tag@me.com").unwrap();
+        assert_eq!(email.hash_with(&previous), Some(hash_email("user+tag@me.com")));
+        assert_eq!(Email::new("u.ser+tag@gmail.com").unwrap().hash_with(&previous), None);
+
+        let configured = EmailNormalizer::with_rules(
+            "2-configured",
+            vec![EmailProviderRule {
+                domains: vec!["gmail.com".into()],
+                strip_plus_tag: true,
+                ..Default::default()
+            }],
+        );
+        assert_eq!(
+            Email::new("u.ser+tag@gmail.com").unwrap().hash_with(&configured),
+            Some(hash_email("u.ser@gmail.com"))
+        );
+    }
+
     #[test]
     fn test_email_hash_is_of_normalized() {
         let base = Email::new("user@gmail.com").unwrap();
@@ -159,6 +193,12 @@ mod tests {
         assert_eq!(e.normalized(), "user@gmail.com");
     }
 
+    #[test]
+    fn test_email_is_disposable() {
+        assert!(Email::new("user@mailinator.com").unwrap().is_disposable());
+        assert!(!Email::new("user@gmail.com").unwrap().is_disposable());
+    }
+
     #[test]
     fn test_invalid_email_rejected() {
         assert!(Email::new("invalid").is_err());
@@ -1,110 +1,403 @@
-use std::collections::HashMap;
-use std::sync::OnceLock;
+use ring::digest;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    sync::{Arc, LazyLock, RwLock},
+};
+use thiserror::Error as ThisError;
+use validator::Validate;
+
+/// Version of the builtin normalization rules, bump when they change.
+/// The version of the configured rules is derived from this and a fingerprint of the configuration. All rows whose
+/// `encrypted_normalized_email` was encrypted with a different version are stale and must be re-normalized. Until
+/// then they can be found only by the hash of the rules they were normalized with, thus the services shall store
+/// the [EmailNormalizer::rules] of each version (see [EmailNormalizer::with_rules]).
+pub const NORM_EMAIL_VERSION: &str = "2";
+/// Version of the previous builtin normalization rules, the email hashes of the not yet migrated rows were created
+/// with these rules.
+pub const PREVIOUS_NORM_EMAIL_VERSION: &str = "1";
+
+/// Normalization rule of an email provider.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailProviderRule {
+    /// The domains of the provider, the rule applies to all of them.
+    #[validate(length(min = 1))]
+    pub domains: Vec<String>,
+    /// Rewrite the domain aliases to this domain, ex. googlemail.com to gmail.com. Default: the domain is kept.
+    #[serde(default)]
+    pub canonical_domain: Option<String>,
+    /// Remove the `+tag` suffix of the local part. Default: false.
+    #[serde(default)]
+    pub strip_plus_tag: bool,
+    /// Remove the `-tag` suffix of the local part. Default: false.
+    #[serde(default)]
+    pub strip_dash_tag: bool,
+    /// Remove the dots of the local part, applied after the tags are removed. Default: false.
+    #[serde(default)]
+    pub remove_dots: bool,
+}
+
+impl EmailProviderRule {
+    fn new(domains: &[&str], canonical_domain: Option<&str>) -> Self {
+        Self {
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            canonical_domain: canonical_domain.map(|d| d.to_string()),
+            ..Default::default()
+        }
+    }
 
-/// Bump when normalization rules change. All rows whose
-/// `encrypted_normalized_email` decrypts to a different version are stale
-/// and must be re-normalized before use.
-pub const NORM_EMAIL_VERSION: &str = "1";
+    fn with_plus_tag(self) -> Self {
+        Self { strip_plus_tag: true, ..self }
+    }
+
+    fn with_dash_tag(self) -> Self {
+        Self { strip_dash_tag: true, ..self }
+    }
 
-type NormStep = fn(&mut String);
+    fn with_dots_removed(self) -> Self {
+        Self { remove_dots: true, ..self }
+    }
 
-struct ProviderRule {
-    steps: &'static [NormStep],
-    canonical_domain: Option<&'static str>,
+    fn apply(&self, local: &mut String) {
+        if self.strip_plus_tag {
+            if let Some(pos) = local.find('+') {
+                local.truncate(pos);
+            }
+        }
+        if self.strip_dash_tag {
+            if let Some(pos) = local.find('-') {
+                local.truncate(pos);
+            }
+        }
+        if self.remove_dots {
+            local.retain(|c| c != '.');
+        }
+    }
 }
 
-fn strip_plus_tag(local: &mut String) {
-    if let Some(pos) = local.find('+') {
-        local.truncate(pos);
+/// The builtin provider rules.
+pub fn builtin_provider_rules() -> Vec<EmailProviderRule> {
+    vec![
+        // Spec: https://support.google.com/mail/answer/7436150
+        EmailProviderRule::new(&["gmail.com", "googlemail.com"], Some("gmail.com"))
+            .with_plus_tag()
+            .with_dots_removed(),
+        // Spec: https://help.yahoo.com/kb/SLN35441.html
+        EmailProviderRule::new(&["yahoo.com", "yahoo.co.uk", "ymail.com"], None).with_dash_tag(),
+        // Spec: https://support.microsoft.com/en-us/office/plus-addressing-in-outlook
+        EmailProviderRule::new(&["outlook.com", "hotmail.com", "live.com"], None).with_plus_tag(),
+        // me.com and mac.com are aliases of the iCloud mailbox
+        EmailProviderRule::new(&["icloud.com", "me.com", "mac.com"], Some("icloud.com")).with_plus_tag(),
+        // pm.me and the legacy protonmail domains are aliases of the Proton mailbox
+        EmailProviderRule::new(
+            &["proton.me", "protonmail.com", "protonmail.ch", "pm.me"],
+            Some("proton.me"),
+        )
+        .with_plus_tag(),
+        EmailProviderRule::new(&["fastmail.com", "fastmail.fm"], None).with_plus_tag(),
+        EmailProviderRule::new(&["gmx.com", "gmx.net", "gmx.de"], None).with_plus_tag(),
+        // All the regional domains are aliases of the Yandex mailbox
+        EmailProviderRule::new(
+            &[
+                "yandex.ru",
+                "yandex.com",
+                "yandex.by",
+                "yandex.kz",
+                "yandex.ua",
+                "ya.ru",
+            ],
+            Some("yandex.ru"),
+        )
+        .with_plus_tag(),
+    ]
+}
+
+/// The builtin provider rules of the [PREVIOUS_NORM_EMAIL_VERSION].
+fn previous_builtin_provider_rules() -> Vec<EmailProviderRule> {
+    vec![
+        EmailProviderRule::new(&["gmail.com", "googlemail.com"], Some("gmail.com"))
+            .with_plus_tag()
+            .with_dots_removed(),
+        EmailProviderRule::new(&["yahoo.com", "yahoo.co.uk", "ymail.com"], None).with_dash_tag(),
+        EmailProviderRule::new(&["outlook.com", "hotmail.com", "live.com"], None).with_plus_tag(),
+    ]
+}
+
+/// Some of the well known disposable email domains. For a complete coverage configure a maintained blocklist.
+const BUILTIN_DISPOSABLE_DOMAINS: &[&str] = &[
+    "10minutemail.com",
+    "dispostable.com",
+    "getnada.com",
+    "guerrillamail.com",
+    "mailinator.com",
+    "maildrop.cc",
+    "sharklasers.com",
+    "temp-mail.org",
+    "trashmail.com",
+    "yopmail.com",
+];
+
+/// Email normalization and disposable domain detection.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailNormalizationConfig {
+    /// Apply the builtin rules of Gmail, Yahoo, Outlook, iCloud, Proton, Fastmail, GMX and Yandex. Default: true.
+    #[serde(default)]
+    pub builtin_rules: Option<bool>,
+    /// Additional provider rules, a rule overrides the builtin and the preceding rules of the same domains.
+    #[serde(default)]
+    #[validate(nested)]
+    pub providers: Vec<EmailProviderRule>,
+    /// Include the builtin list of disposable email domains. Default: true.
+    #[serde(default)]
+    pub builtin_disposable_domains: Option<bool>,
+    /// Additional disposable email domains.
+    #[serde(default)]
+    pub disposable_domains: Vec<String>,
+    /// Blocklist files with a disposable domain in each line. Empty lines and lines starting with `#` are ignored.
+    #[serde(default)]
+    pub disposable_domain_files: Vec<String>,
+}
+
+#[derive(Debug, ThisError)]
+pub enum EmailNormalizerError {
+    #[error("Failed to read the disposable domain list {0}")]
+    DisposableDomainFile(String, #[source] io::Error),
+}
+
+/// Source of the disposable (throwaway) email domains.
+pub trait DisposableDomains: Send + Sync + 'static {
+    /// Check if the (lowercase) domain is disposable, the subdomains are checked separately.
+    fn contains(&self, domain: &str) -> bool;
+}
+
+/// A fixed set of disposable domains.
+#[derive(Default)]
+pub struct DisposableDomainList(HashSet<String>);
+
+impl DisposableDomainList {
+    pub fn new<I, S>(domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut list = Self::default();
+        list.extend(domains);
+        list
+    }
+
+    pub fn builtin() -> Self {
+        Self::new(BUILTIN_DISPOSABLE_DOMAINS)
+    }
+
+    pub fn extend<I, S>(&mut self, domains: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.0.extend(
+            domains
+                .into_iter()
+                .map(|d| d.as_ref().trim().to_lowercase())
+                .filter(|d| !d.is_empty() && !d.starts_with('#')),
+        );
+    }
+
+    /// Add the domains of a blocklist file.
+    pub fn load(&mut self, path: &str) -> Result<(), EmailNormalizerError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| EmailNormalizerError::DisposableDomainFile(path.to_string(), err))?;
+        self.extend(content.lines());
+        Ok(())
     }
 }
 
-fn strip_dash_tag(local: &mut String) {
-    if let Some(pos) = local.find('-') {
-        local.truncate(pos);
+impl DisposableDomains for DisposableDomainList {
+    fn contains(&self, domain: &str) -> bool {
+        self.0.contains(domain)
     }
 }
 
-fn remove_dots(local: &mut String) {
-    local.retain(|c| c != '.');
+/// Normalize the email addresses using the provider rules to detect the aliases of the same mailbox.
+pub struct EmailNormalizer {
+    version: String,
+    provider_rules: Vec<EmailProviderRule>,
+    rules: HashMap<String, Arc<EmailProviderRule>>,
+    disposable_domains: Arc<dyn DisposableDomains>,
 }
 
-/// Gmail normalization rules. Steps: strip plus-tag first, then remove dots.
-/// Domain alias: googlemail.com → gmail.com
-/// Spec: https://support.google.com/mail/answer/7436150
-static GMAIL_RULE: ProviderRule = ProviderRule {
-    steps: &[strip_plus_tag, remove_dots],
-    canonical_domain: Some("gmail.com"),
-};
+impl EmailNormalizer {
+    /// Normalizer with the builtin rules and disposable domains.
+    pub fn builtin() -> Self {
+        Self::with_rules(NORM_EMAIL_VERSION, builtin_provider_rules())
+    }
 
-/// Yahoo normalization rules. Uses dash-tag for disposable addresses.
-/// Spec: https://help.yahoo.com/kb/SLN35441.html
-static YAHOO_RULE: ProviderRule = ProviderRule {
-    steps: &[strip_dash_tag],
-    canonical_domain: None,
-};
+    /// Normalizer with the previous version of the builtin rules to find the rows that are not migrated yet.
+    pub fn previous_builtin() -> Self {
+        Self::with_rules(PREVIOUS_NORM_EMAIL_VERSION, previous_builtin_provider_rules())
+    }
 
-/// Outlook/Hotmail/Live normalization rules.
-/// Spec: https://support.microsoft.com/en-us/office/plus-addressing-in-outlook
-static OUTLOOK_RULE: ProviderRule = ProviderRule {
-    steps: &[strip_plus_tag],
-    canonical_domain: None,
-};
+    /// Normalizer of a stored version of the rules (see [EmailNormalizer::rules]) with the builtin disposable
+    /// domains, ex. to find the rows normalized by a previous configuration.
+    pub fn with_rules(version: &str, rules: Vec<EmailProviderRule>) -> Self {
+        let mut normalizer = Self {
+            version: version.to_string(),
+            provider_rules: Vec::new(),
+            rules: HashMap::new(),
+            disposable_domains: Arc::new(DisposableDomainList::builtin()),
+        };
+        for rule in rules {
+            normalizer.add_rule(rule);
+        }
+        normalizer
+    }
 
-static DEFAULT_RULE: ProviderRule = ProviderRule {
-    steps: &[],
-    canonical_domain: None,
-};
+    pub fn new(config: &EmailNormalizationConfig) -> Result<Self, EmailNormalizerError> {
+        let builtin_rules = config.builtin_rules.unwrap_or(true);
 
-fn registry() -> &'static HashMap<&'static str, &'static ProviderRule> {
-    static REGISTRY: OnceLock<HashMap<&'static str, &'static ProviderRule>> = OnceLock::new();
-    REGISTRY.get_or_init(|| {
-        let mut m = HashMap::new();
-        m.insert("gmail.com", &GMAIL_RULE);
-        m.insert("googlemail.com", &GMAIL_RULE);
-        m.insert("yahoo.com", &YAHOO_RULE);
-        m.insert("yahoo.co.uk", &YAHOO_RULE);
-        m.insert("ymail.com", &YAHOO_RULE);
-        m.insert("outlook.com", &OUTLOOK_RULE);
-        m.insert("hotmail.com", &OUTLOOK_RULE);
-        m.insert("live.com", &OUTLOOK_RULE);
-        m
-    })
-}
+        let version = if builtin_rules && config.providers.is_empty() {
+            NORM_EMAIL_VERSION.to_string()
+        } else {
+            // the configuration is the source of the rules, any change of it creates a new version
+            let rules =
+                serde_json::to_string(&(builtin_rules, &config.providers)).expect("Rules shall be serializable");
+            let fingerprint = digest::digest(&digest::SHA256, rules.as_bytes());
+            format!("{NORM_EMAIL_VERSION}-{}", hex::encode(&fingerprint.as_ref()[..4]))
+        };
 
-/// Returns (raw, normalized):
-///   raw        = trimmed + lowercased; use for display and sending
-///   normalized = raw + provider steps + canonical domain rewrite; use for uniqueness and lookup
-pub(super) fn normalize_email(input: &str) -> (String, String) {
-    let lowered = input.trim().to_lowercase();
+        let mut disposable_domains = if config.builtin_disposable_domains.unwrap_or(true) {
+            DisposableDomainList::builtin()
+        } else {
+            DisposableDomainList::default()
+        };
+        disposable_domains.extend(&config.disposable_domains);
+        for path in &config.disposable_domain_files {
+            disposable_domains.load(path)?;
+        }
 
-    let at = match lowered.rfind('@') {
-        Some(pos) => pos,
-        None => return (lowered.clone(), lowered),
-    };
+        let mut normalizer = Self {
+            version,
+            provider_rules: Vec::new(),
+            rules: HashMap::new(),
+            disposable_domains: Arc::new(disposable_domains),
+        };
+        if builtin_rules {
+            for rule in builtin_provider_rules() {
+                normalizer.add_rule(rule);
+            }
+        }
+        for rule in &config.providers {
+            normalizer.add_rule(rule.clone());
+        }
 
-    let local = &lowered[..at];
-    let domain = &lowered[at + 1..];
-    let raw = lowered.clone();
+        Ok(normalizer)
+    }
 
-    let rule = registry().get(domain).copied().unwrap_or(&DEFAULT_RULE);
+    fn add_rule(&mut self, rule: EmailProviderRule) {
+        self.provider_rules.push(rule.clone());
+        let rule = Arc::new(EmailProviderRule {
+            canonical_domain: rule.canonical_domain.as_ref().map(|d| d.to_lowercase()),
+            ..rule
+        });
+        for domain in &rule.domains {
+            self.rules.insert(domain.to_lowercase(), rule.clone());
+        }
+    }
 
-    let mut norm_local = local.to_string();
-    for step in rule.steps {
-        step(&mut norm_local);
+    /// Replace the source of the disposable domains, ex. to use an external service.
+    pub fn with_disposable_domains<D: DisposableDomains>(self, disposable_domains: D) -> Self {
+        Self {
+            disposable_domains: Arc::new(disposable_domains),
+            ..self
+        }
     }
-    let norm_domain = rule.canonical_domain.unwrap_or(domain);
-    let normalized = format!("{}@{}", norm_local, norm_domain);
 
-    (raw, normalized)
+    /// The version of the rules stored along with the normalized emails.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The provider rules in the order of precedence, the later rules override the former ones of the same domain.
+    pub fn rules(&self) -> &[EmailProviderRule] {
+        &self.provider_rules
+    }
+
+    /// Returns (raw, normalized):
+    ///   raw        = trimmed + lowercased; use for display and sending
+    ///   normalized = raw + provider steps + canonical domain rewrite; use for uniqueness and lookup
+    pub fn normalize(&self, input: &str) -> (String, String) {
+        let lowered = input.trim().to_lowercase();
+
+        let at = match lowered.rfind('@') {
+            Some(pos) => pos,
+            None => return (lowered.clone(), lowered),
+        };
+
+        let local = &lowered[..at];
+        let domain = &lowered[at + 1..];
+
+        let normalized = match self.rules.get(domain) {
+            Some(rule) => {
+                let mut norm_local = local.to_string();
+                rule.apply(&mut norm_local);
+                let norm_domain = rule.canonical_domain.as_deref().unwrap_or(domain);
+                format!("{norm_local}@{norm_domain}")
+            }
+            None => lowered.clone(),
+        };
+
+        (lowered, normalized)
+    }
+
+    /// Check if the domain of the email, or any of its parent domains is a known disposable domain.
+    pub fn is_disposable(&self, email: &str) -> bool {
+        let email = email.trim().to_lowercase();
+        let Some((_, mut domain)) = email.rsplit_once('@') else {
+            return false;
+        };
+
+        loop {
+            if self.disposable_domains.contains(domain) {
+                return true;
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => domain = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Use this normalizer for all the [Email](crate::email::Email) created from now.
+    pub fn install(self) {
+        log::info!("Email normalization rules version: {}", self.version);
+        *NORMALIZER.write().unwrap() = Arc::new(self);
+    }
+}
+
+static NORMALIZER: LazyLock<RwLock<Arc<EmailNormalizer>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(EmailNormalizer::builtin())));
+
+static PREVIOUS_NORMALIZER: LazyLock<EmailNormalizer> = LazyLock::new(EmailNormalizer::previous_builtin);
+
+/// Get the normalizer of the previous version of the builtin rules.
+pub fn previous_email_normalizer() -> &'static EmailNormalizer {
+    &PREVIOUS_NORMALIZER
+}
+
+/// Get the installed normalizer, or the builtin one if none was installed.
+pub fn email_normalizer() -> Arc<EmailNormalizer> {
+    NORMALIZER.read().unwrap().clone()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn check(input: &str, expected_raw: &str, expected_normalized: &str) {
-        let (raw, normalized) = normalize_email(input);
+    fn check_with(normalizer: &EmailNormalizer, input: &str, expected_raw: &str, expected_normalized: &str) {
+        let (raw, normalized) = normalizer.normalize(input);
         assert_eq!(raw, expected_raw, "raw mismatch for input {input:?}");
         assert_eq!(
             normalized, expected_normalized,
@@ -112,6 +405,10 @@ mod tests {
         );
     }
 
+    fn check(input: &str, expected_raw: &str, expected_normalized: &str) {
+        check_with(&EmailNormalizer::builtin(), input, expected_raw, expected_normalized);
+    }
+
     #[test]
     fn test_default() {
         check("  USER@EXAMPLE.COM  ", "user@example.com", "user@example.com");
@@ -139,4 +436,124 @@ mod tests {
         check("user+tag@hotmail.com", "user+tag@hotmail.com", "user@hotmail.com");
         check("user+tag@live.com", "user+tag@live.com", "user@live.com");
     }
+
+    #[test]
+    fn test_other_providers() {
+        check("u.ser+tag@me.com", "u.ser+tag@me.com", "u.ser@icloud.com");
+        check("user+tag@pm.me", "user+tag@pm.me", "user@proton.me");
+        check("user+tag@protonmail.ch", "user+tag@protonmail.ch", "user@proton.me");
+        check("user+tag@fastmail.fm", "user+tag@fastmail.fm", "user@fastmail.fm");
+        check("user+tag@gmx.de", "user+tag@gmx.de", "user@gmx.de");
+        check("user+tag@ya.ru", "user+tag@ya.ru", "user@yandex.ru");
+    }
+
+    #[test]
+    fn test_previous_rules() {
+        let previous = EmailNormalizer::previous_builtin();
+        assert_eq!(previous.version(), PREVIOUS_NORM_EMAIL_VERSION);
+        check_with(
+            &previous,
+            "U.ser+tag@Googlemail.Com",
+            "u.ser+tag@googlemail.com",
+            "user@gmail.com",
+        );
+        check_with(
+            &previous,
+            "user+tag@outlook.com",
+            "user+tag@outlook.com",
+            "user@outlook.com",
+        );
+        check_with(&previous, "u.ser+tag@me.com", "u.ser+tag@me.com", "u.ser+tag@me.com");
+    }
+
+    #[test]
+    fn test_configured_rules() {
+        let default = EmailNormalizer::new(&EmailNormalizationConfig::default()).unwrap();
+        assert_eq!(default.version(), NORM_EMAIL_VERSION);
+
+        let config = EmailNormalizationConfig {
+            providers: vec![EmailProviderRule {
+                domains: vec!["Example.com".into(), "example.org".into()],
+                canonical_domain: Some("example.com".into()),
+                strip_plus_tag: true,
+                remove_dots: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let normalizer = EmailNormalizer::new(&config).unwrap();
+        assert_ne!(normalizer.version(), NORM_EMAIL_VERSION);
+        assert!(normalizer.version().starts_with(NORM_EMAIL_VERSION));
+        assert_eq!(normalizer.version(), EmailNormalizer::new(&config).unwrap().version());
+        check_with(
+            &normalizer,
+            "u.ser+tag@example.org",
+            "u.ser+tag@example.org",
+            "user@example.com",
+        );
+        check_with(
+            &normalizer,
+            "u.ser+tag@gmail.com",
+            "u.ser+tag@gmail.com",
+            "user@gmail.com",
+        );
+
+        let config = EmailNormalizationConfig {
+            builtin_rules: Some(false),
+            ..config
+        };
+        let normalizer = EmailNormalizer::new(&config).unwrap();
+        check_with(
+            &normalizer,
+            "u.ser+tag@example.org",
+            "u.ser+tag@example.org",
+            "user@example.com",
+        );
+        check_with(
+            &normalizer,
+            "u.ser+tag@gmail.com",
+            "u.ser+tag@gmail.com",
+            "u.ser+tag@gmail.com",
+        );
+
+        // the stored rules reproduce the normalization of the version
+        let rules = serde_json::to_string(normalizer.rules()).unwrap();
+        let stored = EmailNormalizer::with_rules(normalizer.version(), serde_json::from_str(&rules).unwrap());
+        assert_eq!(stored.version(), normalizer.version());
+        check_with(
+            &stored,
+            "u.ser+tag@example.org",
+            "u.ser+tag@example.org",
+            "user@example.com",
+        );
+        check_with(
+            &stored,
+            "u.ser+tag@gmail.com",
+            "u.ser+tag@gmail.com",
+            "u.ser+tag@gmail.com",
+        );
+    }
+
+    #[test]
+    fn test_disposable_domains() {
+        let normalizer = EmailNormalizer::builtin();
+        assert!(normalizer.is_disposable("user@mailinator.com"));
+        assert!(normalizer.is_disposable("User@Mail.Mailinator.Com"));
+        assert!(!normalizer.is_disposable("user@gmail.com"));
+        assert!(!normalizer.is_disposable("user@com"));
+        assert!(!normalizer.is_disposable("invalid"));
+
+        let config = EmailNormalizationConfig {
+            builtin_disposable_domains: Some(false),
+            disposable_domains: vec!["Throwaway.Example".into()],
+            ..Default::default()
+        };
+        let normalizer = EmailNormalizer::new(&config).unwrap();
+        assert!(!normalizer.is_disposable("user@mailinator.com"));
+        assert!(normalizer.is_disposable("user@throwaway.example"));
+
+        let normalizer = normalizer.with_disposable_domains(DisposableDomainList::new(["blocked.example"]));
+        assert!(!normalizer.is_disposable("user@throwaway.example"));
+        assert!(normalizer.is_disposable("user@blocked.example"));
+    }
 }
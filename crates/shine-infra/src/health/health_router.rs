@@ -1,10 +1,10 @@
 use crate::{
-    health::StatusProviders,
-    session::{permissions, CheckedCurrentUser, CorePermissions},
-    web::responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
+    health::{HealthProbe, HealthState, HealthStatus, Readiness},
+    session::{permissions, RequirePermission},
 };
-use axum::{Extension, Json};
+use axum::{http::StatusCode, Extension, Json};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -14,19 +14,61 @@ pub struct ServiceVersion {
     pub version: String,
 }
 
+#[derive(Debug, Clone, ToSchema, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub state: HealthState,
+    pub providers: BTreeMap<String, HealthState>,
+}
+
 #[utoipa::path(
     get,
-    path = "/info/ready",
+    path = "/info/live",
     tag = "health",
-    description = "Health check.",
+    description = "Liveness check, the process is running. It does not depend on the external services.",
     responses(
-        (status = OK, description = "Healthy.")
+        (status = OK, description = "Alive.")
     )
 )]
-pub async fn get_ready() -> String {
+pub async fn get_live() -> String {
     "Ok".into()
 }
 
+#[utoipa::path(
+    get,
+    path = "/info/ready",
+    tag = "health",
+    description = "Readiness check, the service and its dependencies can serve the requests. The dependencies are probed in the background, the result of the last probe is reported.",
+    responses(
+        (status = OK, body = ReadinessReport, description = "Ready."),
+        (status = SERVICE_UNAVAILABLE, body = ReadinessReport, description = "Not ready or shutting down.")
+    )
+)]
+pub async fn get_ready(
+    Extension(probe): Extension<HealthProbe>,
+    Extension(readiness): Extension<Readiness>,
+) -> (StatusCode, Json<ReadinessReport>) {
+    let statuses = probe.latest();
+    let state = HealthProbe::aggregate(statuses.iter().map(|(_, status)| status));
+    let ready = readiness.is_ready() && state != HealthState::Unhealthy;
+
+    let report = ReadinessReport {
+        ready,
+        state,
+        providers: statuses
+            .into_iter()
+            .map(|(name, status)| (name, status.state))
+            .collect(),
+    };
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
 #[utoipa::path(
     get,
     path = "/info/version",
@@ -44,37 +86,35 @@ pub async fn get_version(Extension(version): Extension<ServiceVersion>) -> Json<
     get,
     path = "/info/status",
     tag = "health",
-    description = "Get the operational status of the service.",
+    description = "Get the operational status of the service from the last background probe.",
     responses(
         (status = OK, description = "Service status")
     )
 )]
 pub async fn get_status(
-    Extension(problem_config): Extension<ProblemConfig>,
-    Extension(providers): Extension<StatusProviders>,
-    user: CheckedCurrentUser,
-) -> Result<Json<serde_json::Value>, ProblemResponse> {
-    user.core_permissions()
-        .check(permissions::READ_TRACE)
-        .map_err(|err| err.into_response(&problem_config))?;
-
-    let providers = providers.read().unwrap().clone();
-    let mut status = serde_json::Map::new();
-    for provider in providers.iter() {
-        status.insert(provider.name().to_string(), provider.status().await);
-    }
+    Extension(probe): Extension<HealthProbe>,
+    _user: RequirePermission<permissions::ReadTrace>,
+) -> Json<serde_json::Value> {
+    let statuses = probe.latest();
+    let state = HealthProbe::aggregate(statuses.iter().map(|(_, status)| status));
+    let providers = statuses.into_iter().collect::<BTreeMap<String, HealthStatus>>();
 
-    Ok(Json(serde_json::Value::Object(status)))
+    Json(serde_json::json!({
+        "state": state,
+        "providers": providers
+    }))
 }
 
-pub(super) fn build_router<S>(version: ServiceVersion, providers: StatusProviders) -> OpenApiRouter<S>
+pub(super) fn build_router<S>(version: ServiceVersion, probe: HealthProbe, readiness: Readiness) -> OpenApiRouter<S>
 where
     S: Clone + Send + Sync + 'static,
 {
     OpenApiRouter::new()
+        .routes(routes!(get_live))
         .routes(routes!(get_ready))
         .routes(routes!(get_version))
         .routes(routes!(get_status))
         .layer(Extension(version))
-        .layer(Extension(providers))
+        .layer(Extension(probe))
+        .layer(Extension(readiness))
 }
@@ -1,24 +1,121 @@
 use crate::{
-    health::{health_router::build_router, ServiceVersion},
+    health::{health_router::build_router, HealthState, HealthStatus, ServiceVersion},
     web::{FeatureConfig, WebAppConfig},
 };
 use anyhow::Error as AnyError;
 use async_trait::async_trait;
 use axum::Extension;
-use std::sync::{Arc, RwLock};
+use futures::future::join_all;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
 use utoipa_axum::router::OpenApiRouter;
 
+const DEFAULT_PROBE_TIMEOUT: u64 = 3000;
+const DEFAULT_PROBE_INTERVAL: u64 = 10;
+
 #[async_trait]
 pub trait StatusProvider: Send + Sync + 'static {
-    fn name(&self) -> &'static str;
-    async fn status(&self) -> serde_json::Value;
+    fn name(&self) -> &str;
+    async fn status(&self) -> HealthStatus;
+
+    /// The state of the provider if the status is not reported within the timeout. The optional dependencies
+    /// (ex. the external providers) shall report the same state as on failure.
+    fn timeout_state(&self) -> HealthState {
+        HealthState::Unhealthy
+    }
 }
 
 pub type StatusProviders = Arc<RwLock<Vec<Arc<dyn StatusProvider>>>>;
+type ProbeResults = Arc<RwLock<Vec<(String, HealthStatus)>>>;
+
+/// The readiness of the service to accept new requests. It is cleared on shutdown to let the load balancers
+/// drain the traffic.
+#[derive(Clone)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicBool::new(true)))
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set_draining(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Query the status of the providers concurrently. The providers not responding within the timeout are in their
+/// [StatusProvider::timeout_state].
+/// The results of the last (background) check are kept to serve the health endpoints without probing the
+/// dependencies on each request.
+#[derive(Clone)]
+pub struct HealthProbe {
+    providers: StatusProviders,
+    timeout: Duration,
+    results: ProbeResults,
+}
+
+impl HealthProbe {
+    pub async fn check(&self) -> Vec<(String, HealthStatus)> {
+        let providers = self.providers.read().unwrap().clone();
+        let checks = providers.iter().map(|provider| async move {
+            let status = match tokio::time::timeout(self.timeout, provider.status()).await {
+                Ok(status) => status,
+                Err(_) => {
+                    log::warn!("Health probe of {} timed out", provider.name());
+                    HealthStatus::new(provider.timeout_state(), serde_json::json!({ "error": "timeout" }))
+                }
+            };
+            (provider.name().to_string(), status)
+        });
+        join_all(checks).await
+    }
+
+    /// Check the providers and store the results.
+    pub async fn refresh(&self) {
+        let statuses = self.check().await;
+        *self.results.write().unwrap() = statuses;
+    }
+
+    /// The status of the providers from the last refresh.
+    pub fn latest(&self) -> Vec<(String, HealthStatus)> {
+        self.results.read().unwrap().clone()
+    }
+
+    /// The worst state of the providers.
+    pub fn aggregate<'a, I>(statuses: I) -> HealthState
+    where
+        I: IntoIterator<Item = &'a HealthStatus>,
+    {
+        statuses
+            .into_iter()
+            .map(|status| status.state)
+            .max()
+            .unwrap_or(HealthState::Healthy)
+    }
+}
 
 pub struct HealthService {
     version: ServiceVersion,
     providers: StatusProviders,
+    probe_timeout: Duration,
+    probe_interval: Duration,
+    results: ProbeResults,
+    readiness: Readiness,
 }
 
 impl HealthService {
@@ -26,12 +123,19 @@ impl HealthService {
     where
         F: FeatureConfig,
     {
+        let probe_timeout = config.service.health.probe_timeout.unwrap_or(DEFAULT_PROBE_TIMEOUT);
+        let probe_interval = config.service.health.probe_interval.unwrap_or(DEFAULT_PROBE_INTERVAL);
+
         Ok(Self {
             version: ServiceVersion {
                 app_name: feature_name.to_string(),
                 version: config.core.version.clone(),
             },
             providers: Arc::new(RwLock::new(Vec::new())),
+            probe_timeout: Duration::from_millis(probe_timeout),
+            probe_interval: Duration::from_secs(probe_interval),
+            results: Arc::new(RwLock::new(Vec::new())),
+            readiness: Readiness::new(),
         })
     }
 
@@ -39,6 +143,42 @@ impl HealthService {
         self.providers.write().unwrap().push(Arc::new(provider));
     }
 
+    pub fn readiness(&self) -> Readiness {
+        self.readiness.clone()
+    }
+
+    pub fn probe(&self) -> HealthProbe {
+        HealthProbe {
+            providers: self.providers.clone(),
+            timeout: self.probe_timeout,
+            results: self.results.clone(),
+        }
+    }
+
+    /// Check the providers and keep refreshing the results in the background until the probes (routes) are dropped.
+    pub async fn start_probing(&self) {
+        self.probe().refresh().await;
+
+        let providers = self.providers.clone();
+        let timeout = self.probe_timeout;
+        let results = Arc::downgrade(&self.results);
+        let interval = self.probe_interval;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(results) = results.upgrade() else {
+                    break;
+                };
+                let probe = HealthProbe {
+                    providers: providers.clone(),
+                    timeout,
+                    results,
+                };
+                probe.refresh().await;
+            }
+        });
+    }
+
     pub fn create_layer(&self) -> Extension<ServiceVersion> {
         Extension(self.version.clone())
     }
@@ -47,6 +187,155 @@ impl HealthService {
     where
         S: Clone + Send + Sync + 'static,
     {
-        build_router(self.version.clone(), self.providers.clone())
+        build_router(self.version.clone(), self.probe(), self.readiness())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+    use std::sync::atomic::AtomicUsize;
+
+    struct TestStatus(&'static str, HealthState, Duration);
+
+    #[async_trait]
+    impl StatusProvider for TestStatus {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn status(&self) -> HealthStatus {
+            tokio::time::sleep(self.2).await;
+            HealthStatus::new(self.1, serde_json::Value::Null)
+        }
+    }
+
+    struct OptionalStatus;
+
+    #[async_trait]
+    impl StatusProvider for OptionalStatus {
+        fn name(&self) -> &str {
+            "optional"
+        }
+
+        async fn status(&self) -> HealthStatus {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            HealthStatus::healthy(serde_json::Value::Null)
+        }
+
+        fn timeout_state(&self) -> HealthState {
+            HealthState::Degraded
+        }
+    }
+
+    #[test]
+    async fn timeout_state_of_provider() {
+        let providers: StatusProviders = Arc::new(RwLock::new(vec![
+            Arc::new(TestStatus("a", HealthState::Healthy, Duration::ZERO)) as Arc<dyn StatusProvider>,
+            Arc::new(OptionalStatus),
+        ]));
+        let probe = HealthProbe {
+            providers,
+            timeout: Duration::from_millis(100),
+            results: Arc::new(RwLock::new(Vec::new())),
+        };
+
+        let statuses = probe.check().await;
+        assert_eq!(statuses[1].0, "optional");
+        assert_eq!(statuses[1].1.state, HealthState::Degraded);
+        assert_eq!(
+            HealthProbe::aggregate(statuses.iter().map(|(_, status)| status)),
+            HealthState::Degraded
+        );
+    }
+
+    #[test]
+    async fn aggregate_with_timeout() {
+        let providers: StatusProviders = Arc::new(RwLock::new(Vec::new()));
+        let probe = HealthProbe {
+            providers: providers.clone(),
+            timeout: Duration::from_millis(100),
+            results: Arc::new(RwLock::new(Vec::new())),
+        };
+        assert_eq!(HealthProbe::aggregate(std::iter::empty()), HealthState::Healthy);
+
+        providers.write().unwrap().extend([
+            Arc::new(TestStatus("a", HealthState::Healthy, Duration::ZERO)) as Arc<dyn StatusProvider>,
+            Arc::new(TestStatus("b", HealthState::Degraded, Duration::ZERO)),
+        ]);
+        let statuses = probe.check().await;
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(
+            HealthProbe::aggregate(statuses.iter().map(|(_, status)| status)),
+            HealthState::Degraded
+        );
+
+        providers
+            .write()
+            .unwrap()
+            .push(Arc::new(TestStatus("c", HealthState::Healthy, Duration::from_secs(10))));
+        let statuses = probe.check().await;
+        assert_eq!(statuses[2].0, "c");
+        assert_eq!(statuses[2].1.state, HealthState::Unhealthy);
+        assert_eq!(
+            HealthProbe::aggregate(statuses.iter().map(|(_, status)| status)),
+            HealthState::Unhealthy
+        );
+    }
+
+    struct CountingStatus(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl StatusProvider for CountingStatus {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        async fn status(&self) -> HealthStatus {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            HealthStatus::healthy(serde_json::Value::Null)
+        }
+    }
+
+    #[test]
+    async fn probe_in_background() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut service = HealthService {
+            version: ServiceVersion {
+                app_name: "test".into(),
+                version: "0".into(),
+            },
+            providers: Arc::new(RwLock::new(Vec::new())),
+            probe_timeout: Duration::from_millis(100),
+            probe_interval: Duration::from_millis(50),
+            results: Arc::new(RwLock::new(Vec::new())),
+            readiness: Readiness::new(),
+        };
+        service.add_provider(CountingStatus(count.clone()));
+        let probe = service.probe();
+        assert!(probe.latest().is_empty());
+
+        service.start_probing().await;
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+        let statuses = probe.latest();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].0, "counting");
+        assert_eq!(
+            count.load(Ordering::Relaxed),
+            1,
+            "Latest result should not probe the providers"
+        );
+
+        tokio::time::sleep(Duration::from_millis(175)).await;
+        assert!(count.load(Ordering::Relaxed) >= 3);
+
+        // stop probing once the results are not used
+        drop(service);
+        drop(probe);
+        tokio::time::sleep(Duration::from_millis(75)).await;
+        let stopped = count.load(Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(count.load(Ordering::Relaxed), stopped);
     }
 }
@@ -0,0 +1,39 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// The health of a dependency, the states are ordered from the best to the worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthState {
+    Healthy,
+    /// The service is operational with reduced functionality.
+    Degraded,
+    /// The service cannot serve the requests.
+    Unhealthy,
+}
+
+/// The state of a [StatusProvider](crate::health::StatusProvider) with some free-form details.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStatus {
+    pub state: HealthState,
+    pub details: serde_json::Value,
+}
+
+impl HealthStatus {
+    pub fn new(state: HealthState, details: serde_json::Value) -> Self {
+        Self { state, details }
+    }
+
+    pub fn healthy(details: serde_json::Value) -> Self {
+        Self::new(HealthState::Healthy, details)
+    }
+
+    pub fn degraded(details: serde_json::Value) -> Self {
+        Self::new(HealthState::Degraded, details)
+    }
+
+    pub fn unhealthy(details: serde_json::Value) -> Self {
+        Self::new(HealthState::Unhealthy, details)
+    }
+}
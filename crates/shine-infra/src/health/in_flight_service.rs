@@ -1,4 +1,4 @@
-use crate::health::StatusProvider;
+use crate::health::{HealthStatus, StatusProvider};
 use async_trait::async_trait;
 use axum::{body::Body, http::Request, response::Response};
 use futures::future::BoxFuture;
@@ -40,14 +40,14 @@ impl Default for InFlightService {
 
 #[async_trait]
 impl StatusProvider for InFlightService {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "http"
     }
 
-    async fn status(&self) -> serde_json::Value {
-        serde_json::json!({
+    async fn status(&self) -> HealthStatus {
+        HealthStatus::healthy(serde_json::json!({
             "inFlightRequests": self.get()
-        })
+        }))
     }
 }
 
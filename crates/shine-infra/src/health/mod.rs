@@ -1,5 +1,7 @@
 mod health_router;
 pub use self::health_router::*;
+mod health_status;
+pub use self::health_status::*;
 mod health_service;
 pub use self::health_service::*;
 mod in_flight_service;
@@ -1,4 +1,4 @@
-use crate::health::StatusProvider;
+use crate::health::{HealthStatus, StatusProvider};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use tokio::time::Instant;
@@ -25,15 +25,15 @@ impl Default for UptimeStatus {
 
 #[async_trait]
 impl StatusProvider for UptimeStatus {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "uptime"
     }
 
-    async fn status(&self) -> serde_json::Value {
+    async fn status(&self) -> HealthStatus {
         let uptime_seconds = self.start_instant.elapsed().as_secs();
-        serde_json::json!({
+        HealthStatus::healthy(serde_json::json!({
             "startTime": self.start_time.to_rfc3339(),
             "uptimeSeconds": uptime_seconds
-        })
+        }))
     }
 }
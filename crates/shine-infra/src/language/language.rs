@@ -0,0 +1,215 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{borrow::Cow, fmt, str::FromStr};
+use thiserror::Error as ThisError;
+use utoipa::{
+    openapi::{schema::Schema, ObjectBuilder, Type},
+    PartialSchema, ToSchema,
+};
+
+#[derive(Debug, ThisError)]
+#[error("Invalid language tag: {0}")]
+pub struct InvalidLanguageTag(pub String);
+
+/// A BCP 47 language tag of the language, script, region and variant subtags, ex. `hu-HU` or `sr-Latn-RS`.
+/// The tag is stored in the canonical casing, the extension and private use subtags are dropped.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Language(Cow<'static, str>);
+
+impl Language {
+    /// The maximum length of the canonical tag (RFC 5646 recommends supporting at least 35 characters).
+    pub const MAX_LENGTH: usize = 35;
+
+    pub const EN: Language = Language(Cow::Borrowed("en"));
+    pub const HU: Language = Language(Cow::Borrowed("hu"));
+
+    /// Parse a language tag, the `_` separator of the POSIX locales is also accepted.
+    /// Tags longer than [`Language::MAX_LENGTH`] (without the extensions) are rejected.
+    pub fn parse(tag: &str) -> Result<Self, InvalidLanguageTag> {
+        let invalid = || InvalidLanguageTag(tag.to_string());
+
+        let mut subtags = tag.trim().split(['-', '_']).peekable();
+        let language = subtags
+            .next()
+            .filter(|s| (2..=3).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphabetic()))
+            .ok_or_else(invalid)?;
+        let mut canonical = language.to_ascii_lowercase();
+
+        if let Some(script) = subtags.next_if(|s| s.len() == 4 && s.chars().all(|c| c.is_ascii_alphabetic())) {
+            canonical.push('-');
+            canonical.push_str(&script[..1].to_ascii_uppercase());
+            canonical.push_str(&script[1..].to_ascii_lowercase());
+        }
+
+        if let Some(region) = subtags.next_if(|s| {
+            (s.len() == 2 && s.chars().all(|c| c.is_ascii_alphabetic()))
+                || (s.len() == 3 && s.chars().all(|c| c.is_ascii_digit()))
+        }) {
+            canonical.push('-');
+            canonical.push_str(&region.to_ascii_uppercase());
+        }
+
+        while let Some(variant) = subtags.next_if(|s| {
+            s.chars().all(|c| c.is_ascii_alphanumeric())
+                && ((5..=8).contains(&s.len()) || (s.len() == 4 && s.starts_with(|c: char| c.is_ascii_digit())))
+        }) {
+            canonical.push('-');
+            canonical.push_str(&variant.to_ascii_lowercase());
+        }
+
+        // the extensions and private use sections start with a single character subtag
+        match subtags.next() {
+            None => {}
+            Some(singleton) if singleton.len() == 1 && singleton.chars().all(|c| c.is_ascii_alphanumeric()) => {}
+            Some(_) => return Err(invalid()),
+        }
+
+        if canonical.len() > Self::MAX_LENGTH {
+            return Err(invalid());
+        }
+
+        Ok(Self(Cow::Owned(canonical)))
+    }
+
+    /// Parse a language tag, `None` if the tag is not valid.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        Self::parse(tag).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The primary language subtag, ex. `hu` of `hu-HU`.
+    pub fn primary(&self) -> &str {
+        self.0.split('-').next().unwrap_or(&self.0)
+    }
+
+    /// The lookup fallback chain of the tag starting with the tag itself by removing the subtags from the end,
+    /// ex. `sr-Latn-RS`, `sr-Latn`, `sr`.
+    pub fn fallbacks(&self) -> impl Iterator<Item = Language> + '_ {
+        let tag = self.as_str();
+        tag.match_indices('-')
+            .map(|(pos, _)| pos)
+            .chain(std::iter::once(tag.len()))
+            .rev()
+            .map(move |end| Language(Cow::Owned(tag[..end].to_string())))
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self::EN
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Language({})", self.0)
+    }
+}
+
+impl FromStr for Language {
+    type Err = InvalidLanguageTag;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl AsRef<str> for Language {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialSchema for Language {
+    fn schema() -> utoipa::openapi::RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .max_length(Some(Self::MAX_LENGTH))
+            .description(Some("BCP 47 language tag"))
+            .examples(Some(serde_json::Value::String("hu-HU".to_string())))
+            .build()
+            .into()
+    }
+}
+
+impl ToSchema for Language {}
+
+impl Serialize for Language {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let tag = String::deserialize(deserializer)?;
+        Language::parse(&tag).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    fn parse_language_tags() {
+        assert_eq!(Language::parse("hu").unwrap(), Language::HU);
+        assert_eq!(Language::parse("EN").unwrap(), Language::EN);
+        assert_eq!(Language::parse("hu-hu").unwrap().as_str(), "hu-HU");
+        assert_eq!(Language::parse("pt_br").unwrap().as_str(), "pt-BR");
+        assert_eq!(Language::parse("SR-LATN-rs").unwrap().as_str(), "sr-Latn-RS");
+        assert_eq!(Language::parse("es-419").unwrap().as_str(), "es-419");
+        assert_eq!(Language::parse("sl-rozaj-biske").unwrap().as_str(), "sl-rozaj-biske");
+        assert_eq!(Language::parse("de-DE-u-co-phonebk").unwrap().as_str(), "de-DE");
+        assert_eq!(Language::parse("hu-HU").unwrap().primary(), "hu");
+
+        assert!(Language::parse("").is_err());
+        assert!(Language::parse("*").is_err());
+        assert!(Language::parse("hungarian").is_err());
+        assert!(Language::parse("hu-HU-").is_err());
+        assert!(Language::parse("hu-toolongvariant").is_err());
+        assert_eq!(
+            Language::parse("sl-Latn-IT-rozaj-biske-1994-x-private-use-subtags")
+                .unwrap()
+                .as_str(),
+            "sl-Latn-IT-rozaj-biske-1994"
+        );
+        assert!(Language::parse("sl-Latn-IT-rozaj-biske-1994-alalc97-fonipa").is_err());
+    }
+
+    #[test]
+    fn language_fallbacks() {
+        let tags = |tag: &str| {
+            Language::parse(tag)
+                .unwrap()
+                .fallbacks()
+                .map(|l| l.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tags("hu"), vec!["hu"]);
+        assert_eq!(tags("hu-HU"), vec!["hu-HU", "hu"]);
+        assert_eq!(tags("sr-Latn-RS"), vec!["sr-Latn-RS", "sr-Latn", "sr"]);
+    }
+
+    #[test]
+    fn language_serde() {
+        let lang: Language = serde_json::from_str(r#""hu-hu""#).unwrap();
+        assert_eq!(serde_json::to_string(&lang).unwrap(), r#""hu-HU""#);
+        assert!(serde_json::from_str::<Language>(r#""not a tag""#).is_err());
+    }
+}
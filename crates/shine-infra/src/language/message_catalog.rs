@@ -0,0 +1,267 @@
+use crate::language::{format_message, Language, MessageArgs};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+    sync::Arc,
+};
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+pub enum MessageCatalogError {
+    #[error("Failed to read the translation file {0}")]
+    Io(String, #[source] io::Error),
+    #[error("Invalid translation file {0}")]
+    Json(String, #[source] serde_json::Error),
+    #[error("The name of the translation file {0} is not a language tag")]
+    InvalidLanguage(String),
+}
+
+/// Localized messages keyed by an identifier, ex. the `type` of a problem.
+/// The default language is always supported, the other languages are supported once they have some messages.
+/// The messages are looked up along the fallback chain of the language tag, ex. `hu-HU`, `hu`.
+#[derive(Clone, Debug, Default)]
+pub struct MessageCatalog {
+    default_language: Language,
+    messages: BTreeMap<Language, HashMap<String, String>>,
+}
+
+impl MessageCatalog {
+    pub fn new(default_language: Language) -> Self {
+        Self {
+            default_language,
+            messages: BTreeMap::new(),
+        }
+    }
+
+    /// The built-in messages of the common problem types. The english messages are the details
+    /// given by the problems, thus only the translations are registered.
+    pub fn core() -> Self {
+        Self::new(Language::EN).with_messages(
+            Language::HU,
+            [
+                ("not-found", "A keresett erőforrás nem található"),
+                ("unauthorized", "Hitelesítés szükséges"),
+                ("step-up-required", "A művelethez újbóli hitelesítés szükséges"),
+                ("forbidden", "Nincs jogosultság a művelethez"),
+                ("service-unavailable", "A szolgáltatás átmenetileg nem érhető el"),
+                ("server-error", "Belső szerverhiba"),
+                ("input-path-format", "Érvénytelen útvonal paraméter"),
+                ("input-query-format", "Érvénytelen lekérdezési paraméter"),
+                ("input-body-format", "Érvénytelen kérés törzs"),
+                ("input-validation", "A bemenet ellenőrzése sikertelen"),
+                ("rate-limited", "Túl sok kérés, próbálja újra később"),
+                ("unsupported-api-version", "Nem támogatott API verzió"),
+                (
+                    "idempotency-key-reused",
+                    "Az idempotencia kulcsot egy másik kéréshez már felhasználták",
+                ),
+                (
+                    "idempotency-key-in-progress",
+                    "Az idempotencia kulcshoz tartozó kérés még folyamatban van",
+                ),
+            ],
+        )
+    }
+
+    pub fn default_language(&self) -> &Language {
+        &self.default_language
+    }
+
+    pub fn with_messages<K, M>(mut self, language: Language, messages: impl IntoIterator<Item = (K, M)>) -> Self
+    where
+        K: Into<String>,
+        M: Into<String>,
+    {
+        self.add_messages(language, messages);
+        self
+    }
+
+    pub fn add_messages<K, M>(&mut self, language: Language, messages: impl IntoIterator<Item = (K, M)>)
+    where
+        K: Into<String>,
+        M: Into<String>,
+    {
+        self.messages
+            .entry(language)
+            .or_default()
+            .extend(messages.into_iter().map(|(key, message)| (key.into(), message.into())));
+    }
+
+    /// Add the messages of a flat json object, ex. an embedded translation file.
+    pub fn add_json(&mut self, language: Language, json: &str) -> Result<(), serde_json::Error> {
+        let messages = serde_json::from_str::<HashMap<String, String>>(json)?;
+        self.add_messages(language, messages);
+        Ok(())
+    }
+
+    /// Add the translation files of a directory. The files are flat json objects named by the
+    /// language tag, ex. `hu.json` or `hu-HU.json`.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), MessageCatalogError> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir).map_err(|err| MessageCatalogError::Io(dir.display().to_string(), err))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|err| MessageCatalogError::Io(dir.display().to_string(), err))?
+                .path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let file = path.display().to_string();
+            let language = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(Language::from_tag)
+                .ok_or_else(|| MessageCatalogError::InvalidLanguage(file.clone()))?;
+            let json = fs::read_to_string(&path).map_err(|err| MessageCatalogError::Io(file.clone(), err))?;
+            self.add_json(language, &json)
+                .map_err(|err| MessageCatalogError::Json(file, err))?;
+        }
+        Ok(())
+    }
+
+    /// Merge the messages of the other catalog, on conflict the messages of the other catalog are kept.
+    pub fn merge(&mut self, other: MessageCatalog) {
+        for (language, messages) in other.messages {
+            self.add_messages(language, messages);
+        }
+    }
+
+    fn has_messages(&self, language: &Language) -> bool {
+        *language == self.default_language || self.messages.get(language).is_some_and(|m| !m.is_empty())
+    }
+
+    /// Find the first supported language in the fallback chain of the language.
+    fn find_supported(&self, language: &Language) -> Option<Language> {
+        language.fallbacks().find(|lang| self.has_messages(lang))
+    }
+
+    pub fn is_supported(&self, language: &Language) -> bool {
+        self.find_supported(language).is_some()
+    }
+
+    /// The supported languages, starting with the default language.
+    pub fn languages(&self) -> impl Iterator<Item = &Language> + '_ {
+        std::iter::once(&self.default_language).chain(
+            self.messages
+                .iter()
+                .filter(|(lang, messages)| **lang != self.default_language && !messages.is_empty())
+                .map(|(lang, _)| lang),
+        )
+    }
+
+    /// Return the supported language of the fallback chain, the default language if none is supported.
+    pub fn resolve(&self, language: Option<&Language>) -> Language {
+        language
+            .and_then(|lang| self.find_supported(lang))
+            .unwrap_or_else(|| self.default_language.clone())
+    }
+
+    /// The message along the fallback chain of the language without falling back to the default language.
+    pub fn message(&self, language: &Language, key: &str) -> Option<&str> {
+        language
+            .fallbacks()
+            .find_map(|lang| self.messages.get(&lang)?.get(key))
+            .map(String::as_str)
+    }
+
+    /// The formatted message along the fallback chain of the language, then in the default language.
+    /// See [format_message] for the supported syntax.
+    pub fn format(&self, language: &Language, key: &str, args: &MessageArgs) -> Option<String> {
+        if let Some(message) = self.message(language, key) {
+            return Some(format_message(language, message, args));
+        }
+        let message = self.message(&self.default_language, key)?;
+        Some(format_message(&self.default_language, message, args))
+    }
+
+    /// Create a tera function to translate the messages in the templates:
+    /// `{{ t(key="login-title", lang=lang, app=app) }}`. The `lang` is optional, the other arguments are the
+    /// arguments of the message. If the message is not found, the key is returned.
+    pub fn tera_function(self: &Arc<Self>) -> impl tera::Function {
+        let catalog = self.clone();
+        move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+            let key = args
+                .get("key")
+                .and_then(|key| key.as_str())
+                .ok_or_else(|| tera::Error::msg("Missing the key argument of the translation"))?;
+            let language = match args.get("lang").and_then(|lang| lang.as_str()) {
+                Some(lang) => Language::parse(lang).map_err(|err| tera::Error::msg(err.to_string()))?,
+                None => catalog.default_language.clone(),
+            };
+            let message = catalog.format(&language, key, args).unwrap_or_else(|| key.to_string());
+            Ok(tera::Value::String(message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use shine_test::test;
+
+    #[test]
+    fn catalog_languages() {
+        let mut catalog = MessageCatalog::new(Language::EN);
+        assert_eq!(catalog.languages().collect::<Vec<_>>(), vec![&Language::EN]);
+        assert_eq!(catalog.resolve(Some(&Language::HU)), Language::EN);
+
+        catalog.merge(MessageCatalog::core());
+        catalog
+            .add_json(Language::HU, r#"{ "not-found": "Nincs meg" }"#)
+            .unwrap();
+        assert_eq!(
+            catalog.languages().collect::<Vec<_>>(),
+            vec![&Language::EN, &Language::HU]
+        );
+        assert_eq!(catalog.resolve(Some(&Language::HU)), Language::HU);
+        assert_eq!(catalog.resolve(None), Language::EN);
+        assert_eq!(catalog.message(&Language::HU, "not-found"), Some("Nincs meg"));
+        assert_eq!(catalog.message(&Language::EN, "not-found"), None);
+    }
+
+    #[test]
+    fn catalog_fallbacks() {
+        let hu_hu = Language::parse("hu-HU").unwrap();
+        let catalog = MessageCatalog::new(Language::EN)
+            .with_messages(Language::EN, [("greeting", "Hello {user}"), ("bye", "Bye")])
+            .with_messages(Language::HU, [("greeting", "Szia {user}")])
+            .with_messages(hu_hu.clone(), [("region", "Magyarország")]);
+
+        assert!(catalog.is_supported(&Language::parse("hu-AT").unwrap()));
+        assert!(!catalog.is_supported(&Language::parse("de-DE").unwrap()));
+        assert_eq!(catalog.resolve(Some(&Language::parse("hu-AT").unwrap())), Language::HU);
+        assert_eq!(catalog.resolve(Some(&hu_hu)), hu_hu);
+
+        let args = serde_json::from_value::<MessageArgs>(json!({ "user": "Bob" })).unwrap();
+        assert_eq!(catalog.format(&hu_hu, "region", &args).as_deref(), Some("Magyarország"));
+        assert_eq!(catalog.format(&hu_hu, "greeting", &args).as_deref(), Some("Szia Bob"));
+        assert_eq!(catalog.format(&hu_hu, "bye", &args).as_deref(), Some("Bye"));
+        assert_eq!(catalog.format(&hu_hu, "missing", &args), None);
+    }
+
+    #[test]
+    fn catalog_tera_function() {
+        let catalog = Arc::new(
+            MessageCatalog::new(Language::EN)
+                .with_messages(Language::EN, [("items", "{n, plural, one {# item} other {# items}}")])
+                .with_messages(Language::HU, [("items", "{n} elem")]),
+        );
+        let mut tera = tera::Tera::default();
+        tera.register_function("t", catalog.tera_function());
+        tera.add_raw_template("test", r#"{{ t(key="items", lang=lang, n=n) }}"#)
+            .unwrap();
+
+        let render = |lang: &str, n: i64| {
+            let mut context = tera::Context::new();
+            context.insert("lang", lang);
+            context.insert("n", &n);
+            tera.render("test", &context).unwrap()
+        };
+        assert_eq!(render("en-GB", 1), "1 item");
+        assert_eq!(render("en", 3), "3 items");
+        assert_eq!(render("hu-HU", 3), "3 elem");
+    }
+}
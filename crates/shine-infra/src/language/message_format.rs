@@ -0,0 +1,320 @@
+use crate::language::Language;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The arguments of a message, ex. the named arguments of a tera function call.
+pub type MessageArgs = HashMap<String, Value>;
+
+/// The CLDR plural categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PluralCategory::Zero => "zero",
+            PluralCategory::One => "one",
+            PluralCategory::Two => "two",
+            PluralCategory::Few => "few",
+            PluralCategory::Many => "many",
+            PluralCategory::Other => "other",
+        }
+    }
+
+    /// The CLDR cardinal plural category of an integer. The languages without a rule have only the `other` category.
+    pub fn of(language: &Language, n: i64) -> Self {
+        let n = n.unsigned_abs();
+        let (n10, n100) = (n % 10, n % 100);
+        match language.primary() {
+            "en" | "hu" | "de" | "nl" | "sv" | "da" | "nb" | "nn" | "no" | "fi" | "et" | "it" | "es" | "el" | "tr"
+            | "bg" | "ca" | "eu" | "gl" => {
+                if n == 1 {
+                    Self::One
+                } else {
+                    Self::Other
+                }
+            }
+            "fr" | "pt" => {
+                if n <= 1 {
+                    Self::One
+                } else {
+                    Self::Other
+                }
+            }
+            "cs" | "sk" => match n {
+                1 => Self::One,
+                2..=4 => Self::Few,
+                _ => Self::Other,
+            },
+            "pl" => match n {
+                1 => Self::One,
+                _ if (2..=4).contains(&n10) && !(12..=14).contains(&n100) => Self::Few,
+                _ => Self::Many,
+            },
+            "ru" | "uk" | "be" => match n {
+                _ if n10 == 1 && n100 != 11 => Self::One,
+                _ if (2..=4).contains(&n10) && !(12..=14).contains(&n100) => Self::Few,
+                _ => Self::Many,
+            },
+            "ar" => match n {
+                0 => Self::Zero,
+                1 => Self::One,
+                2 => Self::Two,
+                _ if (3..=10).contains(&n100) => Self::Few,
+                _ if (11..=99).contains(&n100) => Self::Many,
+                _ => Self::Other,
+            },
+            _ => Self::Other,
+        }
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// The characters starting a quoted literal after an apostrophe, otherwise an apostrophe is a literal character.
+fn is_quotable(c: char) -> bool {
+    matches!(c, '{' | '}' | '#' | '|')
+}
+
+/// Find the closing brace of a block starting after an opening brace, the quoted braces are skipped.
+fn find_block_end(pattern: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '\'' if chars.next_if(|(_, c)| *c == '\'').is_some() => {}
+            '\'' if quoted => quoted = false,
+            '\'' if chars.peek().is_some_and(|(_, c)| is_quotable(*c)) => quoted = true,
+            _ if quoted => {}
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(pos),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the `key {message}` branches of a plural or select argument.
+fn parse_branches(mut branches: &str) -> Option<Vec<(&str, &str)>> {
+    let mut result = Vec::new();
+    loop {
+        branches = branches.trim_start();
+        if branches.is_empty() {
+            return Some(result);
+        }
+        let open = branches.find('{')?;
+        let key = branches[..open].trim();
+        let end = open + 1 + find_block_end(&branches[open + 1..])?;
+        result.push((key, &branches[open + 1..end]));
+        branches = &branches[end + 1..];
+    }
+}
+
+fn format_argument(language: &Language, argument: &str, args: &MessageArgs, out: &mut String) -> Option<()> {
+    let mut parts = argument.splitn(3, ',');
+    let name = parts.next()?.trim();
+    let value = args.get(name);
+
+    match parts.next().map(str::trim) {
+        None => {
+            match value {
+                Some(value) => out.push_str(&format_value(value)),
+                None => {
+                    // keep the missing arguments visible
+                    out.push('{');
+                    out.push_str(name);
+                    out.push('}');
+                }
+            }
+            Some(())
+        }
+        Some("plural") => {
+            let branches = parse_branches(parts.next()?)?;
+            let n = value.and_then(Value::as_i64).unwrap_or(0);
+            let exact = format!("={n}");
+            let category = PluralCategory::of(language, n).as_str();
+            let (_, message) = branches
+                .iter()
+                .find(|(key, _)| *key == exact)
+                .or_else(|| branches.iter().find(|(key, _)| *key == category))
+                .or_else(|| branches.iter().find(|(key, _)| *key == "other"))?;
+            format_into(language, message, Some(n), args, out)
+        }
+        Some("select") => {
+            let branches = parse_branches(parts.next()?)?;
+            let selector = value.map(format_value).unwrap_or_default();
+            let (_, message) = branches
+                .iter()
+                .find(|(key, _)| *key == selector)
+                .or_else(|| branches.iter().find(|(key, _)| *key == "other"))?;
+            format_into(language, message, None, args, out)
+        }
+        Some(_) => None,
+    }
+}
+
+/// Format a (sub)message, the `#` is replaced by the number of the enclosing plural argument
+/// only at the top level of the message, not within the nested arguments.
+fn format_into(
+    language: &Language,
+    pattern: &str,
+    plural: Option<i64>,
+    args: &MessageArgs,
+    out: &mut String,
+) -> Option<()> {
+    let mut quoted = false;
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '\'' if chars.next_if(|(_, c)| *c == '\'').is_some() => out.push('\''),
+            '\'' if quoted => quoted = false,
+            '\'' if chars.peek().is_some_and(|(_, c)| is_quotable(*c)) => quoted = true,
+            c if quoted => out.push(c),
+            '{' => {
+                let end = pos + 1 + find_block_end(&pattern[pos + 1..])?;
+                format_argument(language, &pattern[pos + 1..end], args, out)?;
+                while chars.next_if(|(p, _)| *p <= end).is_some() {}
+            }
+            '#' => match plural {
+                Some(n) => out.push_str(&n.to_string()),
+                None => out.push('#'),
+            },
+            c => out.push(c),
+        }
+    }
+    Some(())
+}
+
+/// Format a message of the ICU MessageFormat syntax. The simple `{name}`, the `{n, plural, ...}` with the exact
+/// (`=0`) and the CLDR category (`one`, `other`, ...) branches, and the `{name, select, ...}` arguments are supported.
+/// The apostrophe quoting follows ICU: `''` is a literal apostrophe and an apostrophe before a `{`, `}`, `#` or `|`
+/// starts a literal text till the next apostrophe.
+/// A malformed message is returned without formatting.
+pub fn format_message(language: &Language, pattern: &str, args: &MessageArgs) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    match format_into(language, pattern, None, args, &mut out) {
+        Some(()) => out,
+        None => {
+            log::warn!("Malformed message: {pattern}");
+            pattern.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use shine_test::test;
+
+    fn args(value: Value) -> MessageArgs {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn plural_categories() {
+        let ru = Language::parse("ru").unwrap();
+        let cases = [
+            (1, "one"),
+            (2, "few"),
+            (5, "many"),
+            (11, "many"),
+            (21, "one"),
+            (22, "few"),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(PluralCategory::of(&ru, n).as_str(), expected, "ru {n}");
+        }
+        assert_eq!(PluralCategory::of(&Language::HU, 1), PluralCategory::One);
+        assert_eq!(PluralCategory::of(&Language::HU, 0), PluralCategory::Other);
+        assert_eq!(
+            PluralCategory::of(&Language::parse("fr").unwrap(), 0),
+            PluralCategory::One
+        );
+        assert_eq!(
+            PluralCategory::of(&Language::parse("ja").unwrap(), 1),
+            PluralCategory::Other
+        );
+    }
+
+    #[test]
+    fn format_messages() {
+        let en = Language::EN;
+        let pattern = "{user} has {count, plural, =0 {no items} one {# item} other {# items}} in {place}";
+        assert_eq!(
+            format_message(
+                &en,
+                pattern,
+                &args(json!({ "user": "Bob", "count": 0, "place": "home" }))
+            ),
+            "Bob has no items in home"
+        );
+        assert_eq!(
+            format_message(&en, pattern, &args(json!({ "user": "Bob", "count": 1 }))),
+            "Bob has 1 item in {place}"
+        );
+        assert_eq!(
+            format_message(&en, pattern, &args(json!({ "user": "Bob", "count": 12 }))),
+            "Bob has 12 items in {place}"
+        );
+
+        let pattern = "{kind, select, login {Login to {app}} other {Welcome}}";
+        assert_eq!(
+            format_message(&en, pattern, &args(json!({ "kind": "login", "app": "Shine" }))),
+            "Login to Shine"
+        );
+        assert_eq!(format_message(&en, pattern, &args(json!({}))), "Welcome");
+
+        assert_eq!(
+            format_message(&en, "Broken {message", &args(json!({}))),
+            "Broken {message"
+        );
+    }
+
+    #[test]
+    fn format_nested_plurals() {
+        let en = Language::EN;
+        let pattern = "{n, plural, one {# of {total, plural, other {# total}}} other {# of {kind, select, other {#}}}}";
+        assert_eq!(
+            format_message(&en, pattern, &args(json!({ "n": 1, "total": 5 }))),
+            "1 of 5 total"
+        );
+        assert_eq!(format_message(&en, pattern, &args(json!({ "n": 2 }))), "2 of #");
+    }
+
+    #[test]
+    fn format_quoted_messages() {
+        let en = Language::EN;
+        assert_eq!(
+            format_message(&en, "It''s '{name}' for {name}", &args(json!({ "name": "Bob" }))),
+            "It's {name} for Bob"
+        );
+        assert_eq!(
+            format_message(
+                &en,
+                "Don't '#' {n, plural, other {'#' #, '{}'}}",
+                &args(json!({ "n": 3 }))
+            ),
+            "Don't # # 3, {}"
+        );
+        assert_eq!(
+            format_message(&en, "{n, plural, other {'{it''s}' #}}", &args(json!({ "n": 2 }))),
+            "{it's} 2"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+#![allow(clippy::module_inception)]
+
+mod language;
+pub use self::language::*;
+mod message_format;
+pub use self::message_format::*;
+mod message_catalog;
+pub use self::message_catalog::*;
@@ -3,6 +3,7 @@ pub mod crypto;
 pub mod db;
 pub mod email;
 pub mod language;
+pub mod secrets;
 pub mod serde;
 pub mod sync;
 pub mod web;
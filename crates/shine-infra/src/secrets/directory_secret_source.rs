@@ -0,0 +1,73 @@
+use crate::secrets::{SecretSource, SecretSourceError};
+use async_trait::async_trait;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+/// Secrets stored as files in a directory, one secret per file (ex. Docker and Kubernetes secret mounts).
+/// The name of the file is the configuration path, ex. `service.sessionSecret`. Hidden files and
+/// subdirectories are ignored.
+#[derive(Debug)]
+pub struct DirectorySecretSource {
+    path: PathBuf,
+    origin: String,
+}
+
+impl DirectorySecretSource {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        Self {
+            origin: format!("dir://{}", path.display()),
+            path,
+        }
+    }
+}
+
+#[async_trait]
+impl SecretSource for DirectorySecretSource {
+    fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    async fn load(&self) -> Result<HashMap<String, String>, SecretSourceError> {
+        let io_error = |err| SecretSourceError::Io(self.origin.clone(), err);
+
+        let mut secrets = HashMap::new();
+        for entry in fs::read_dir(&self.path).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?;
+            let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+            // the metadata follows the symlinks of the kubernetes mounts
+            if name.starts_with('.') || !fs::metadata(entry.path()).map_err(io_error)?.is_file() {
+                continue;
+            }
+
+            let value = fs::read_to_string(entry.path()).map_err(io_error)?;
+            secrets.insert(name, value.trim_end_matches(['\r', '\n']).to_string());
+        }
+
+        Ok(secrets)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    async fn load_secrets() {
+        let dir = std::env::temp_dir().join(format!("shine-secrets-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("..data")).unwrap();
+        fs::write(dir.join("service.sessionSecret"), "secret\n").unwrap();
+        fs::write(dir.join("identity.db.sqlCns"), "postgres://localhost").unwrap();
+        fs::write(dir.join(".hidden"), "hidden").unwrap();
+
+        let secrets = DirectorySecretSource::new(&dir).load().await;
+        fs::remove_dir_all(&dir).unwrap();
+
+        let secrets = secrets.unwrap();
+        assert_eq!(secrets.len(), 2);
+        assert_eq!(secrets["service.sessionSecret"], "secret");
+        assert_eq!(secrets["identity.db.sqlCns"], "postgres://localhost");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    crypto::DataProtectionUtils,
+    secrets::{SecretSource, SecretSourceError},
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
+use std::{collections::HashMap, env, fmt, fs, path::PathBuf};
+
+/// The environment variable holding the (base64 url-safe encoded, 32 bytes) key of the encrypted secret files.
+pub const SECRETS_KEY_ENV: &str = "SHINE_SECRETS_KEY";
+
+/// Secrets stored in a local json file with encrypted values and plain keys (similar to sops).
+/// The nested objects are flattened into configuration paths, all the string values are encrypted with
+/// [DataProtectionUtils], other values are kept as they are.
+pub struct EncryptedFileSecretSource {
+    path: PathBuf,
+    origin: String,
+    protection: DataProtectionUtils,
+}
+
+impl fmt::Debug for EncryptedFileSecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedFileSecretSource")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl EncryptedFileSecretSource {
+    pub fn new<P: Into<PathBuf>>(path: P, key: &[u8]) -> Result<Self, SecretSourceError> {
+        let path = path.into();
+        let origin = format!("enc://{}", path.display());
+        // only the encryption is used, the hmac key is irrelevant
+        let protection = DataProtectionUtils::new(key, key)
+            .map_err(|err| SecretSourceError::InvalidStore(origin.clone(), err.to_string()))?;
+        Ok(Self { path, origin, protection })
+    }
+
+    /// Create the source with the key from the [SECRETS_KEY_ENV] environment variable.
+    pub fn from_env<P: Into<PathBuf>>(path: P) -> Result<Self, SecretSourceError> {
+        let key = env::var(SECRETS_KEY_ENV).map_err(|_| SecretSourceError::MissingEnvironment(SECRETS_KEY_ENV))?;
+        let key = B64
+            .decode(key)
+            .map_err(|_| SecretSourceError::MissingEnvironment(SECRETS_KEY_ENV))?;
+        Self::new(path, &key)
+    }
+
+    /// Encrypt a value to be stored in the secret file.
+    pub fn encrypt(&self, value: &str) -> Result<String, SecretSourceError> {
+        self.protection
+            .encrypt(value)
+            .map_err(|err| SecretSourceError::Provider(Box::new(err)))
+    }
+
+    fn flatten(
+        &self,
+        prefix: &str,
+        value: serde_json::Value,
+        secrets: &mut HashMap<String, String>,
+    ) -> Result<(), SecretSourceError> {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    let path = if prefix.is_empty() {
+                        key
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    self.flatten(&path, value, secrets)?;
+                }
+            }
+            serde_json::Value::String(encrypted) => {
+                let value = self
+                    .protection
+                    .decrypt(&encrypted)
+                    .map_err(|_| SecretSourceError::InvalidSecret(prefix.to_string()))?;
+                secrets.insert(prefix.to_string(), value);
+            }
+            serde_json::Value::Null => {}
+            value => {
+                secrets.insert(prefix.to_string(), value.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SecretSource for EncryptedFileSecretSource {
+    fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    async fn load(&self) -> Result<HashMap<String, String>, SecretSourceError> {
+        let content = fs::read_to_string(&self.path).map_err(|err| SecretSourceError::Io(self.origin.clone(), err))?;
+        let content = serde_json::from_str::<serde_json::Value>(&content)
+            .map_err(|err| SecretSourceError::InvalidStore(self.origin.clone(), err.to_string()))?;
+        if !content.is_object() {
+            return Err(SecretSourceError::InvalidStore(
+                self.origin.clone(),
+                "root is not an object".to_string(),
+            ));
+        }
+
+        let mut secrets = HashMap::new();
+        self.flatten("", content, &mut secrets)?;
+        Ok(secrets)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    async fn load_secrets() {
+        let path = std::env::temp_dir().join(format!("shine-secrets-{}.json", uuid::Uuid::new_v4()));
+        let key = [7u8; 32];
+        let source = EncryptedFileSecretSource::new(&path, &key).unwrap();
+
+        let content = serde_json::json!({
+            "service": {
+                "sessionSecret": source.encrypt("secret").unwrap(),
+                "port": 8080
+            },
+            "identity.db.sqlCns": source.encrypt("postgres://localhost").unwrap()
+        });
+        fs::write(&path, content.to_string()).unwrap();
+        let secrets = source.load().await;
+        let other_key = EncryptedFileSecretSource::new(&path, &[8u8; 32]).unwrap().load().await;
+        fs::remove_file(&path).unwrap();
+
+        let secrets = secrets.unwrap();
+        assert_eq!(secrets.len(), 3);
+        assert_eq!(secrets["service.sessionSecret"], "secret");
+        assert_eq!(secrets["service.port"], "8080");
+        assert_eq!(secrets["identity.db.sqlCns"], "postgres://localhost");
+        assert!(matches!(other_key, Err(SecretSourceError::InvalidSecret(_))));
+    }
+}
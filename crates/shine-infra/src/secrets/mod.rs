@@ -0,0 +1,8 @@
+mod secret_source;
+pub use self::secret_source::*;
+mod directory_secret_source;
+pub use self::directory_secret_source::*;
+mod encrypted_file_secret_source;
+pub use self::encrypted_file_secret_source::*;
+mod vault_secret_source;
+pub use self::vault_secret_source::*;
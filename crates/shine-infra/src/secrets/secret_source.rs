@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use config::{
+    AsyncSource as ConfigAsyncSource, ConfigError, Map as ConfigMap, Value as ConfigValue, ValueKind as ConfigValueKind,
+};
+use std::{collections::HashMap, error::Error as StdError, fmt::Debug};
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+pub enum SecretSourceError {
+    #[error("Failed to read secrets from {0}")]
+    Io(String, #[source] std::io::Error),
+    #[error("Missing environment variable {0}")]
+    MissingEnvironment(&'static str),
+    #[error("Invalid secret store {0}: {1}")]
+    InvalidStore(String, String),
+    #[error("Invalid secret {0}")]
+    InvalidSecret(String),
+    #[error("Secret store error")]
+    Provider(#[source] Box<dyn StdError + Send + Sync>),
+}
+
+impl From<SecretSourceError> for ConfigError {
+    fn from(err: SecretSourceError) -> Self {
+        log::error!("{err:?}");
+        ConfigError::Foreign(Box::new(err))
+    }
+}
+
+/// A store of secrets that can be used as a configuration layer.
+#[async_trait]
+pub trait SecretSource: Debug + Send + Sync + 'static {
+    /// The location of the store used as the origin of the configuration values.
+    fn origin(&self) -> &str;
+
+    /// Load all the secrets. The keys are the configuration paths using `.` as the separator.
+    async fn load(&self) -> Result<HashMap<String, String>, SecretSourceError>;
+}
+
+/// Adapter to add a [SecretSource] as an (async) configuration layer.
+#[derive(Debug)]
+pub struct SecretConfigSource<S: SecretSource>(S);
+
+impl<S: SecretSource> SecretConfigSource<S> {
+    pub fn new(source: S) -> Self {
+        Self(source)
+    }
+}
+
+#[async_trait]
+impl<S: SecretSource> ConfigAsyncSource for SecretConfigSource<S> {
+    async fn collect(&self) -> Result<ConfigMap<String, ConfigValue>, ConfigError> {
+        let origin = self.0.origin().to_string();
+        log::info!("Loading secrets from {origin} ...");
+
+        let config = self
+            .0
+            .load()
+            .await?
+            .into_iter()
+            .map(|(path, value)| {
+                log::debug!("Reading secret {path:?}");
+                // try to parse value, as conversion from string to a concrete type is not automatic.
+                let value = if let Ok(parsed) = value.parse::<i64>() {
+                    ConfigValueKind::I64(parsed)
+                } else {
+                    ConfigValueKind::String(value)
+                };
+                (path, ConfigValue::new(Some(&origin), value))
+            })
+            .collect();
+
+        Ok(config)
+    }
+}
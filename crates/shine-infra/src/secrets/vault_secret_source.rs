@@ -0,0 +1,160 @@
+use crate::secrets::{SecretSource, SecretSourceError};
+use async_trait::async_trait;
+use reqwest::{Client as HttpClient, StatusCode};
+use serde::Deserialize;
+use std::{collections::HashMap, env, fmt};
+
+/// The environment variable holding the token used to access the vault.
+pub const VAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+/// The optional environment variable holding the vault (enterprise) namespace.
+pub const VAULT_NAMESPACE_ENV: &str = "VAULT_NAMESPACE";
+
+#[derive(Deserialize)]
+struct KvResponse {
+    data: KvData,
+}
+
+#[derive(Deserialize)]
+struct KvData {
+    data: HashMap<String, serde_json::Value>,
+}
+
+/// Secrets stored in a single HashiCorp Vault KV (version 2) secret. The keys of the secret are the configuration
+/// paths, ex. `service.sessionSecret`.
+pub struct VaultSecretSource {
+    client: HttpClient,
+    url: String,
+    origin: String,
+    token: String,
+    namespace: Option<String>,
+}
+
+impl fmt::Debug for VaultSecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultSecretSource").field("url", &self.url).finish()
+    }
+}
+
+impl VaultSecretSource {
+    /// Create a source for the `address` (ex. `https://vault:8200`) and the `location` given as `mount/path`.
+    pub fn new(address: &str, location: &str, token: String) -> Result<Self, SecretSourceError> {
+        let origin = format!("{address}/{location}");
+        let (mount, path) = location
+            .trim_matches('/')
+            .split_once('/')
+            .filter(|(mount, path)| !mount.is_empty() && !path.is_empty())
+            .ok_or_else(|| SecretSourceError::InvalidStore(origin.clone(), "expected mount/path".to_string()))?;
+
+        Ok(Self {
+            client: HttpClient::new(),
+            url: format!("{}/v1/{mount}/data/{path}", address.trim_end_matches('/')),
+            origin,
+            token,
+            namespace: None,
+        })
+    }
+
+    /// Create a source with the token and namespace from the [VAULT_TOKEN_ENV] and [VAULT_NAMESPACE_ENV]
+    /// environment variables.
+    pub fn from_env(address: &str, location: &str) -> Result<Self, SecretSourceError> {
+        let token = env::var(VAULT_TOKEN_ENV).map_err(|_| SecretSourceError::MissingEnvironment(VAULT_TOKEN_ENV))?;
+        Ok(Self::new(address, location, token)?.with_namespace(env::var(VAULT_NAMESPACE_ENV).ok()))
+    }
+
+    pub fn with_namespace(self, namespace: Option<String>) -> Self {
+        Self { namespace, ..self }
+    }
+}
+
+#[async_trait]
+impl SecretSource for VaultSecretSource {
+    fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    async fn load(&self) -> Result<HashMap<String, String>, SecretSourceError> {
+        let mut request = self.client.get(&self.url).header("X-Vault-Token", &self.token);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|err| SecretSourceError::Provider(Box::new(err)))?;
+        match response.status() {
+            StatusCode::OK => {}
+            status => {
+                return Err(SecretSourceError::InvalidStore(
+                    self.origin.clone(),
+                    format!("unexpected status {status}"),
+                ))
+            }
+        }
+
+        let response = response
+            .json::<KvResponse>()
+            .await
+            .map_err(|err| SecretSourceError::InvalidStore(self.origin.clone(), err.to_string()))?;
+
+        let secrets = response
+            .data
+            .data
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                serde_json::Value::String(value) => Some((key, value)),
+                serde_json::Value::Null => None,
+                value => Some((key, value.to_string())),
+            })
+            .collect();
+        Ok(secrets)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{http::HeaderMap, routing::get, Json, Router};
+    use shine_test::test;
+    use tokio::net::TcpListener;
+
+    async fn get_secret(headers: HeaderMap) -> Result<Json<serde_json::Value>, StatusCode> {
+        if headers.get("X-Vault-Token").and_then(|token| token.to_str().ok()) != Some("token") {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Ok(Json(serde_json::json!({
+            "data": {
+                "data": {
+                    "service.sessionSecret": "secret",
+                    "service.port": 8080
+                },
+                "metadata": { "version": 3 }
+            }
+        })))
+    }
+
+    #[test]
+    async fn load_secrets() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route("/v1/secret/data/shine/identity", get(get_secret));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let secrets = VaultSecretSource::new(&address, "secret/shine/identity", "token".to_string())
+            .unwrap()
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(secrets.len(), 2);
+        assert_eq!(secrets["service.sessionSecret"], "secret");
+        assert_eq!(secrets["service.port"], "8080");
+
+        let forbidden = VaultSecretSource::new(&address, "secret/shine/identity", "invalid".to_string())
+            .unwrap()
+            .load()
+            .await;
+        assert!(matches!(forbidden, Err(SecretSourceError::InvalidStore(..))));
+
+        assert!(VaultSecretSource::new(&address, "secret", "token".to_string()).is_err());
+    }
+}
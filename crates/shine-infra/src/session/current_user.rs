@@ -2,6 +2,7 @@ use crate::{
     session::{serde_session_key, CurrentUserService, SessionKey, UserSessionError},
     web::{
         extracts::ClientFingerprint,
+        middlewares::ReissuedSessionCookie,
         responses::{ErrorResponse, ProblemConfig},
     },
 };
@@ -22,6 +23,10 @@ pub struct CurrentUser {
     pub key: SessionKey,
     pub session_start: DateTime<Utc>,
     pub session_end: DateTime<Utc>,
+    /// The time of the last authentication of the user. The sessions started by a remember-me token keep the time
+    /// of the original authentication. (The users of the earlier cookies are treated as authenticated long ago.)
+    #[serde(default)]
+    pub authenticated_at: DateTime<Utc>,
     pub name: String,
     pub is_email_confirmed: bool,
     pub is_linked: bool,
@@ -85,10 +90,7 @@ where
             .extract::<Extension<Arc<CurrentUserService>>>()
             .await
             .expect("Missing CurrentUserService extension");
-        let fingerprint = parts
-            .extract::<ClientFingerprint>()
-            .await
-            .map_err(|err| ErrorResponse::new(&problem_config, UserSessionError::from(err.problem)))?;
+        let Ok(fingerprint) = parts.extract::<ClientFingerprint>().await;
 
         let jar = SignedCookieJar::from_headers(&parts.headers, session_service.cookie_secret().clone());
         let session_cookie = jar
@@ -97,7 +99,7 @@ where
             .ok_or_else(|| ErrorResponse::new(&problem_config, UserSessionError::Unauthenticated))?;
 
         log::debug!("Checking fingerprint: {:?}", session_cookie.user_id);
-        if session_cookie.fingerprint != fingerprint.as_str() {
+        if !fingerprint.matches(&session_cookie.fingerprint) {
             return Err(ErrorResponse::new(
                 &problem_config,
                 UserSessionError::SessionCompromised,
@@ -105,10 +107,89 @@ where
         }
 
         log::debug!("Finding user session: {:?}", session_cookie.user_id);
-        let current_user = session_service
+        let mut current_user = session_service
             .get_current_user(session_cookie.user_id, session_cookie.key)
             .await
             .map_err(|err| ErrorResponse::new(&problem_config, err))?;
+
+        if fingerprint.is_upgrade_of(&current_user.fingerprint) {
+            log::debug!("Upgrading legacy fingerprint: {:?}", current_user.user_id);
+            match session_service
+                .update_fingerprint(&current_user, fingerprint.as_str())
+                .await
+            {
+                Ok(()) => {
+                    current_user.fingerprint = fingerprint.into_string();
+                    // re-issue the cookie, otherwise the legacy fingerprint of the cookie would remain forever
+                    let session_cookie = SessionCookie {
+                        fingerprint: current_user.fingerprint.clone(),
+                        ..session_cookie
+                    };
+                    if let (Some(reissued), Some(set_cookie)) = (
+                        parts.extensions.get::<ReissuedSessionCookie>(),
+                        session_service.session_cookie_header(&session_cookie),
+                    ) {
+                        reissued.set(session_service.cookie_name(), set_cookie);
+                    }
+                }
+                Err(err) => log::warn!("Failed to upgrade the fingerprint of the session: {err}"),
+            }
+        }
+
         Ok(CheckedCurrentUser(current_user))
     }
 }
+
+/// Extractor for the CurrentUser of sensitive operations. If step-up is enabled (see step-up age of the
+/// [CurrentUserService]), the user must have been authenticated recently, otherwise a new login is required.
+pub struct StepUpCurrentUser(CurrentUser);
+
+impl StepUpCurrentUser {
+    pub fn into_user(self) -> CurrentUser {
+        self.0
+    }
+}
+
+impl From<StepUpCurrentUser> for CurrentUser {
+    fn from(value: StepUpCurrentUser) -> Self {
+        value.0
+    }
+}
+
+impl ops::Deref for StepUpCurrentUser {
+    type Target = CurrentUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for StepUpCurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse<UserSessionError>;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = CheckedCurrentUser::from_request_parts(parts, state).await?.into_user();
+
+        let Extension(problem_config) = parts
+            .extract::<Extension<ProblemConfig>>()
+            .await
+            .expect("Missing ProblemConfig extension");
+        let Extension(session_service) = parts
+            .extract::<Extension<Arc<CurrentUserService>>>()
+            .await
+            .expect("Missing CurrentUserService extension");
+
+        if session_service
+            .step_up_age()
+            .is_some_and(|step_up_age| Utc::now() - user.authenticated_at > step_up_age)
+        {
+            log::debug!("Step-up is required for user: {:?}", user.user_id);
+            return Err(ErrorResponse::new(&problem_config, UserSessionError::StepUpRequired));
+        }
+
+        Ok(StepUpCurrentUser(user))
+    }
+}
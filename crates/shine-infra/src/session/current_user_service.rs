@@ -1,10 +1,22 @@
+#[cfg(feature = "test-support")]
+use crate::web::extracts::SiteInfo;
 use crate::{
+    crypto::DataProtectionUtils,
     db::RedisConnectionPool,
-    session::{CurrentUser, SessionKey, UserSessionError},
+    session::{
+        CurrentUser, SessionCache, SessionCookie, SessionInvalidation, SessionKey, SessionLifetime, UserSessionError,
+    },
     web::ServiceConfig,
 };
-use axum::Extension;
-use axum_extra::extract::cookie::Key;
+use axum::{
+    http::{header, HeaderValue},
+    response::IntoResponse,
+    Extension,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, Expiration, Key, SameSite},
+    SignedCookieJar,
+};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
 use chrono::{DateTime, Duration, Utc};
 use redis::AsyncCommands;
@@ -14,13 +26,50 @@ use shine_infra_macros::RedisJsonValue;
 use std::sync::Arc;
 use uuid::Uuid;
 
+#[derive(Serialize, Deserialize, Debug, RedisJsonValue)]
+#[serde(rename_all = "camelCase")]
+struct SessionSentinel {
+    pub created_at: DateTime<Utc>,
+    /// The time of the authentication, the sessions created before it was tracked use the creation time.
+    #[serde(default)]
+    pub authenticated_at: Option<DateTime<Utc>>,
+    pub fingerprint: String,
+}
+
+/// The sentinel in the full format of the identity service.
+#[cfg(feature = "test-support")]
+#[derive(Serialize, Deserialize, Debug, RedisJsonValue)]
+#[serde(rename_all = "camelCase")]
+struct IdentitySessionSentinel {
+    pub created_at: DateTime<Utc>,
+    pub authenticated_at: DateTime<Utc>,
+    pub fingerprint: String,
+    pub agent: String,
+    pub country: Option<String>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, RedisJsonValue)]
+#[serde(rename_all = "camelCase")]
+struct SessionData {
+    pub name: String,
+    pub is_email_confirmed: bool,
+    pub is_linked: bool,
+    pub roles: Vec<String>,
+}
+
 /// Handle the user data query in the redis cache.
 pub struct CurrentUserService {
     cookie_name: String,
     cookie_secret: Key,
+    cookie_domain: Option<String>,
+    token_protection: DataProtectionUtils,
     key_prefix: String,
-    ttl_session: i64,
+    lifetime: SessionLifetime,
+    step_up_age: Option<Duration>,
     redis: RedisConnectionPool,
+    cache: Option<Arc<SessionCache>>,
 }
 
 impl CurrentUserService {
@@ -28,7 +77,8 @@ impl CurrentUserService {
         name_suffix: Option<&str>,
         cookie_secret: &str,
         key_prefix: &str,
-        ttl_session: u64,
+        lifetime: SessionLifetime,
+        step_up_age: Option<Duration>,
         redis: RedisConnectionPool,
     ) -> Result<Self, UserSessionError> {
         let name_suffix = name_suffix.unwrap_or_default();
@@ -38,16 +88,18 @@ impl CurrentUserService {
                 .map_err(|err| UserSessionError::InvalidSecret(format!("{err}")))?;
             Key::try_from(&key[..]).map_err(|err| UserSessionError::InvalidSecret(format!("{err}")))?
         };
-        let ttl_session = ttl_session
-            .try_into()
-            .map_err(|err| UserSessionError::InvalidTtl(format!("{err}")))?;
-
+        let token_protection = DataProtectionUtils::new(cookie_secret.encryption(), cookie_secret.signing())
+            .map_err(|err| UserSessionError::InvalidSecret(format!("{err}")))?;
         Ok(Self {
             cookie_name: format!("sid{name_suffix}"),
             cookie_secret,
+            cookie_domain: None,
+            token_protection,
             key_prefix: key_prefix.to_string(),
-            ttl_session,
+            lifetime,
+            step_up_age,
             redis,
+            cache: None,
         })
     }
 
@@ -59,17 +111,175 @@ impl CurrentUserService {
         &self.cookie_secret
     }
 
+    /// Set the domain of the session cookie to re-issue the cookie of the upgraded sessions.
+    pub fn with_cookie_domain(self, domain: &str) -> Self {
+        Self {
+            cookie_domain: Some(domain.to_string()),
+            ..self
+        }
+    }
+
+    /// Create the `Set-Cookie` header of the session cookie as the identity service would do. If the cookie domain
+    /// is not set, the cookie cannot be issued and None is returned.
+    pub fn session_cookie_header(&self, session_cookie: &SessionCookie) -> Option<HeaderValue> {
+        let domain = self.cookie_domain.as_ref()?;
+        let raw_data = serde_json::to_string(session_cookie).expect("Failed to serialize session cookie");
+        let mut cookie = Cookie::new(self.cookie_name.clone(), raw_data);
+        cookie.set_expires(Expiration::Session);
+        cookie.set_secure(true);
+        cookie.set_domain(domain.clone());
+        cookie.set_path("/");
+        cookie.set_http_only(true);
+        cookie.set_same_site(SameSite::Lax);
+
+        let response = SignedCookieJar::new(self.cookie_secret.clone())
+            .add(cookie)
+            .into_response();
+        response.headers().get(header::SET_COOKIE).cloned()
+    }
+
+    /// Enable the in-process cache of the sessions.
+    pub fn with_cache(self, cache: Arc<SessionCache>) -> Self {
+        Self { cache: Some(cache), ..self }
+    }
+
+    /// Create an opaque id of the session for the session tokens, the session key is not exposed in the token.
+    pub fn session_token_id(&self, user: &CurrentUser) -> String {
+        self.token_protection
+            .encrypt(&format!("{}.{}", user.user_id.as_simple(), user.key.to_hex()))
+            .expect("Failed to encrypt the session key")
+    }
+
+    /// Find the session key of an id created by [session_token_id](Self::session_token_id) for the user.
+    pub fn session_key_from_token_id(&self, user_id: Uuid, sid: &str) -> Option<SessionKey> {
+        let data = self.token_protection.decrypt(sid).ok()?;
+        let (token_user_id, key) = data.split_once('.')?;
+        if token_user_id != user_id.as_simple().to_string() {
+            return None;
+        }
+        SessionKey::from_hex(key).ok()
+    }
+
+    pub fn lifetime(&self) -> &SessionLifetime {
+        &self.lifetime
+    }
+
+    /// The maximum age of the login for the sensitive operations, if step-up is enabled.
+    pub fn step_up_age(&self) -> Option<Duration> {
+        self.step_up_age
+    }
+
     pub async fn from_config(config: &ServiceConfig) -> Result<Self, UserSessionError> {
         let redis = crate::db::create_redis_pool(config.session_redis_cns.as_str())
             .await
             .map_err(UserSessionError::RedisPoolError)?;
-        Self::new(None, &config.session_secret, "", config.session_ttl, redis)
+        let lifetime = SessionLifetime::from_config(config)?;
+        let step_up_age = config
+            .session_step_up_age
+            .map(|age| i64::try_from(age).map_err(|err| UserSessionError::InvalidTtl(format!("{err}"))))
+            .transpose()?
+            .map(Duration::seconds);
+        let mut service = Self::new(None, &config.session_secret, "", lifetime, step_up_age, redis)?;
+        if let Some(domain) = &config.session_cookie_domain {
+            service = service.with_cookie_domain(domain);
+        }
+
+        match &config.session_cache {
+            Some(cache_config) => {
+                let cache = Arc::new(SessionCache::from_config(cache_config));
+                cache.start_invalidation_listener(&config.session_redis_cns, "");
+                Ok(service.with_cache(cache))
+            }
+            None => Ok(service),
+        }
     }
 
     pub fn create_layer(self) -> Extension<Arc<Self>> {
         Extension(Arc::new(self))
     }
 
+    fn session_keys(&self, user_id: Uuid, key_hash: &str) -> (String, String) {
+        let prefix = format!("{}session:{}:{}", self.key_prefix, user_id.as_simple(), key_hash);
+        let sentinel_key = format!("{prefix}:sentinel");
+        let key = format!("{prefix}:data");
+        (sentinel_key, key)
+    }
+
+    /// Store a new session of the user as the identity service would do. The sessions are created by the identity
+    /// service, it is used to start sessions without it in the tests.
+    #[cfg(feature = "test-support")]
+    pub async fn store_session(&self, user: &CurrentUser, site_info: &SiteInfo) -> Result<(), UserSessionError> {
+        let key_hash = hex::encode(digest::digest(&digest::SHA256, user.key.as_bytes()));
+        let (sentinel_key, key) = self.session_keys(user.user_id, &key_hash);
+        let ttl = self
+            .lifetime
+            .ttl(user.session_start, Utc::now())
+            .ok_or(UserSessionError::SessionExpired)?;
+
+        let sentinel = IdentitySessionSentinel {
+            created_at: user.session_start,
+            authenticated_at: user.authenticated_at,
+            fingerprint: user.fingerprint.clone(),
+            agent: site_info.agent.clone(),
+            country: site_info.country.clone(),
+            region: site_info.region.clone(),
+            city: site_info.city.clone(),
+        };
+        let data = SessionData {
+            name: user.name.clone(),
+            is_email_confirmed: user.is_email_confirmed,
+            is_linked: user.is_linked,
+            roles: user.roles.clone(),
+        };
+
+        let mut client = self.redis.get().await.map_err(UserSessionError::RedisPoolError)?;
+        let _: () = redis::pipe()
+            .set(&sentinel_key, &sentinel)
+            .expire(&sentinel_key, ttl)
+            .set(&key, &data)
+            .expire(&key, ttl)
+            .query_async(&mut *client)
+            .await
+            .map_err(UserSessionError::RedisError)?;
+        Ok(())
+    }
+
+    /// Replace the fingerprint the session is bound to, ex. to upgrade a legacy fingerprint.
+    /// The other fields of the sentinel (owned by the identity service) are preserved.
+    pub async fn update_fingerprint(&self, user: &CurrentUser, fingerprint: &str) -> Result<(), UserSessionError> {
+        let key_hash = hex::encode(digest::digest(&digest::SHA256, user.key.as_bytes()));
+        let (sentinel_key, _) = self.session_keys(user.user_id, &key_hash);
+
+        let mut client = self.redis.get().await.map_err(UserSessionError::RedisPoolError)?;
+        let sentinel: Option<String> = client.get(&sentinel_key).await.map_err(UserSessionError::RedisError)?;
+        let Some(mut sentinel) =
+            sentinel.and_then(|sentinel| serde_json::from_str::<serde_json::Value>(&sentinel).ok())
+        else {
+            return Err(UserSessionError::SessionExpired);
+        };
+        sentinel["fingerprint"] = serde_json::Value::String(fingerprint.to_string());
+
+        let _: () = redis::cmd("SET")
+            .arg(&sentinel_key)
+            .arg(sentinel.to_string())
+            .arg("XX")
+            .arg("KEEPTTL")
+            .query_async(&mut *client)
+            .await
+            .map_err(UserSessionError::RedisError)?;
+
+        if let Some(cache) = &self.cache {
+            cache.insert(
+                &key_hash,
+                CurrentUser {
+                    fingerprint: fingerprint.to_string(),
+                    ..user.clone()
+                },
+            );
+        }
+        Ok(())
+    }
+
     /// Refresh the session data in the cache. It should be in sync with the identity service
     /// and introduce any breaking change with great care as that can break authentication in all the service.
     pub async fn get_current_user(
@@ -77,31 +287,42 @@ impl CurrentUserService {
         user_id: Uuid,
         session_key: SessionKey,
     ) -> Result<CurrentUser, UserSessionError> {
-        #[derive(Serialize, Deserialize, Debug, RedisJsonValue)]
-        #[serde(rename_all = "camelCase")]
-        struct SessionSentinel {
-            pub created_at: DateTime<Utc>,
-            pub fingerprint: String,
-        }
+        let key_hash = hex::encode(digest::digest(&digest::SHA256, session_key.as_bytes()));
+        let (sentinel_key, key) = self.session_keys(user_id, &key_hash);
 
-        #[derive(Serialize, Deserialize, Debug, RedisJsonValue)]
-        #[serde(rename_all = "camelCase")]
-        struct SessionData {
-            pub name: String,
-            pub is_email_confirmed: bool,
-            pub is_linked: bool,
-            pub roles: Vec<String>,
-        }
+        if let Some(cache) = &self.cache {
+            if let Some((user, extend)) = cache.get(user_id, &key_hash) {
+                if !extend {
+                    return Ok(user);
+                }
 
-        let (sentinel_key, key) = {
-            let key_hash = digest::digest(&digest::SHA256, session_key.as_bytes());
-            let key_hash = hex::encode(key_hash);
+                // extend the expiration lazily, it also detects if the session was removed without notification
+                let now = Utc::now();
+                let ttl = self.lifetime.ttl(user.session_start, now);
+                let extended = match ttl {
+                    Some(ttl) => {
+                        let mut client = self.redis.get().await.map_err(UserSessionError::RedisPoolError)?;
+                        let (sentinel_extended, data_extended): (bool, bool) = redis::pipe()
+                            .expire(&sentinel_key, ttl)
+                            .expire(&key, ttl)
+                            .query_async(&mut *client)
+                            .await
+                            .map_err(UserSessionError::RedisError)?;
+                        sentinel_extended && data_extended
+                    }
+                    None => false,
+                };
 
-            let prefix = format!("{}session:{}:{}", self.key_prefix, user_id.as_simple(), key_hash);
-            let sentinel_key = format!("{prefix}:sentinel");
-            let key = format!("{prefix}:data");
-            (sentinel_key, key)
-        };
+                if !extended {
+                    cache.invalidate(&SessionInvalidation::Session(user_id, key_hash));
+                    return Err(UserSessionError::SessionExpired);
+                }
+
+                let session_end = now + Duration::seconds(ttl.unwrap_or_default());
+                cache.set_extended(&key_hash, session_end);
+                return Ok(CurrentUser { session_end, ..user });
+            }
+        }
 
         let mut client = self.redis.get().await.map_err(UserSessionError::RedisPoolError)?;
 
@@ -117,25 +338,36 @@ impl CurrentUserService {
             _ => return Err(UserSessionError::SessionExpired),
         };
 
-        // extend session expiration
-        //todo: should we have a maximum session lifetime based on the creation time?
+        // extend session expiration, but never beyond the absolute lifetime
+        let now = Utc::now();
+        let ttl = self
+            .lifetime
+            .ttl(sentinel.created_at, now)
+            .ok_or(UserSessionError::SessionExpired)?;
         let _: () = redis::pipe()
-            .expire(&sentinel_key, self.ttl_session)
-            .expire(&key, self.ttl_session)
+            .expire(&sentinel_key, ttl)
+            .expire(&key, ttl)
             .query_async(&mut *client)
             .await
             .map_err(UserSessionError::RedisError)?;
 
-        Ok(CurrentUser {
+        let user = CurrentUser {
             user_id,
             key: session_key,
             session_start: sentinel.created_at,
-            session_end: Utc::now() + Duration::seconds(self.ttl_session),
+            session_end: now + Duration::seconds(ttl),
+            authenticated_at: sentinel.authenticated_at.unwrap_or(sentinel.created_at),
             name: data.name,
             is_email_confirmed: data.is_email_confirmed,
             is_linked: data.is_linked,
             roles: data.roles,
             fingerprint: sentinel.fingerprint.clone(),
-        })
+        };
+
+        if let Some(cache) = &self.cache {
+            cache.insert(&key_hash, user.clone());
+        }
+
+        Ok(user)
     }
 }
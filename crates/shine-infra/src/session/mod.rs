@@ -1,11 +1,19 @@
 mod permission_set;
 pub use self::permission_set::*;
+mod policy;
+pub use self::policy::*;
 
 mod session_key;
 pub use self::session_key::*;
+mod session_lifetime;
+pub use self::session_lifetime::*;
 mod current_user;
 pub use self::current_user::*;
 mod user_session_error;
 pub use self::user_session_error::*;
+mod session_cache;
+pub use self::session_cache::*;
 mod current_user_service;
 pub use self::current_user_service::*;
+mod token_current_user;
+pub use self::token_current_user::*;
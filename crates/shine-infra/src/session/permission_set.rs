@@ -1,6 +1,7 @@
-use crate::{session::CurrentUser, web::responses::Problem};
+use crate::web::responses::Problem;
 use std::collections::HashSet;
 use thiserror::Error as ThisError;
+use uuid::Uuid;
 
 /// Global user roles used by the services.
 pub mod roles {
@@ -14,6 +15,18 @@ pub mod permissions {
     pub const READ_TRACE: &str = "ReadTrace";
     /// Allow to update tracing configuration
     pub const UPDATE_TRACE: &str = "UpdateTrace";
+
+    /// Marker of the [READ_TRACE] permission for the [RequirePermission](crate::session::RequirePermission) extractor
+    pub struct ReadTrace;
+    impl crate::session::Permission for ReadTrace {
+        const NAME: &'static str = READ_TRACE;
+    }
+
+    /// Marker of the [UPDATE_TRACE] permission for the [RequirePermission](crate::session::RequirePermission) extractor
+    pub struct UpdateTrace;
+    impl crate::session::Permission for UpdateTrace {
+        const NAME: &'static str = UPDATE_TRACE;
+    }
 }
 
 #[derive(Debug, ThisError)]
@@ -32,9 +45,12 @@ impl From<PermissionError> for Problem {
     }
 }
 
-#[derive(Default)]
+/// The effective permissions of a user.
+#[derive(Clone, Debug, Default)]
 pub struct PermissionSet {
-    permission: HashSet<&'static str>,
+    user_id: Option<Uuid>,
+    permission: HashSet<String>,
+    owner_permission: HashSet<String>,
 }
 
 impl PermissionSet {
@@ -42,16 +58,51 @@ impl PermissionSet {
         Self::default()
     }
 
-    pub fn add(&mut self, permission: &'static str) {
-        self.permission.insert(permission);
+    /// Create an empty set for the given user. Owner permissions are evaluated against this user.
+    pub fn for_user(user_id: Uuid) -> Self {
+        Self {
+            user_id: Some(user_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn user_id(&self) -> Option<Uuid> {
+        self.user_id
+    }
+
+    pub fn add(&mut self, permission: &str) {
+        self.permission.insert(permission.to_owned());
     }
 
-    pub fn remove(&mut self, permission: &'static str) {
+    pub fn remove(&mut self, permission: &str) {
         self.permission.remove(permission);
     }
 
+    /// Add a permission that is granted only for the resources owned by the user.
+    pub fn add_owner(&mut self, permission: &str) {
+        self.owner_permission.insert(permission.to_owned());
+    }
+
+    pub fn contains(&self, permission: &str) -> bool {
+        self.permission.contains(permission)
+    }
+
+    /// Return the permissions in a sorted order.
+    pub fn permissions(&self) -> Vec<String> {
+        let mut permissions: Vec<_> = self.permission.iter().cloned().collect();
+        permissions.sort();
+        permissions
+    }
+
+    /// Return the owner permissions in a sorted order.
+    pub fn owner_permissions(&self) -> Vec<String> {
+        let mut permissions: Vec<_> = self.owner_permission.iter().cloned().collect();
+        permissions.sort();
+        permissions
+    }
+
     pub fn require(&self, permission: &'static str) -> Result<(), PermissionError> {
-        if self.permission.contains(&permission) {
+        if self.permission.contains(permission) {
             Ok(())
         } else {
             Err(PermissionError::MissingPermission(permission))
@@ -61,27 +112,20 @@ impl PermissionSet {
     pub fn check(&self, permission: &'static str) -> Result<(), Problem> {
         Ok(self.require(permission)?)
     }
-}
 
-pub trait CorePermissions {
-    fn core_permissions(&self) -> PermissionSet;
-}
-
-impl CorePermissions for CurrentUser {
-    fn core_permissions(&self) -> PermissionSet {
-        let mut permission = PermissionSet::new();
-
-        for role in &self.roles {
-            #[allow(clippy::single_match)]
-            match role.as_str() {
-                roles::SUPER_ADMIN => {
-                    permission.add(permissions::READ_TRACE);
-                    permission.add(permissions::UPDATE_TRACE);
-                }
-                _ => {}
-            };
+    /// Require a permission on a resource owned by the given user. The global permission grants access to
+    /// any resource, the owner permission only to the resources of the user.
+    pub fn require_for_owner(&self, permission: &'static str, owner_id: Uuid) -> Result<(), PermissionError> {
+        if self.permission.contains(permission)
+            || (self.user_id == Some(owner_id) && self.owner_permission.contains(permission))
+        {
+            Ok(())
+        } else {
+            Err(PermissionError::MissingPermission(permission))
         }
+    }
 
-        permission
+    pub fn check_for_owner(&self, permission: &'static str, owner_id: Uuid) -> Result<(), Problem> {
+        Ok(self.require_for_owner(permission, owner_id)?)
     }
 }
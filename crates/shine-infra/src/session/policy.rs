@@ -0,0 +1,385 @@
+use crate::{
+    session::{permissions, roles, CheckedCurrentUser, CurrentUser, PermissionSet},
+    web::responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
+};
+use axum::{extract::FromRequestParts, http::request::Parts, Extension, Json, RequestPartsExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    ops,
+    sync::Arc,
+};
+use thiserror::Error as ThisError;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+#[derive(Debug, ThisError)]
+pub enum PolicyError {
+    #[error("Role {0:?} inherits from the unknown role {1:?}")]
+    UnknownRole(String, String),
+    #[error("Cyclic role inheritance through {0:?}")]
+    CyclicInheritance(String),
+}
+
+/// Permissions granted by a role.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RolePolicyConfig {
+    /// All the permissions of these roles are also granted.
+    #[serde(default)]
+    pub inherits: Vec<String>,
+    /// Permissions granted for any resource.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// Permissions granted only for the resources owned by the user.
+    #[serde(default)]
+    pub owner_permissions: Vec<String>,
+}
+
+impl RolePolicyConfig {
+    pub fn merge(&mut self, other: RolePolicyConfig) {
+        fn extend(target: &mut Vec<String>, source: Vec<String>) {
+            for item in source {
+                if !target.contains(&item) {
+                    target.push(item);
+                }
+            }
+        }
+
+        extend(&mut self.inherits, other.inherits);
+        extend(&mut self.permissions, other.permissions);
+        extend(&mut self.owner_permissions, other.owner_permissions);
+    }
+}
+
+/// Declarative role to permission mapping.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyConfig {
+    /// Permissions of every authenticated user independent of the roles.
+    #[serde(default)]
+    pub authenticated: RolePolicyConfig,
+    #[serde(default)]
+    pub roles: HashMap<String, RolePolicyConfig>,
+}
+
+impl PolicyConfig {
+    /// The policy of the permissions shared by all the services.
+    pub fn core() -> Self {
+        Self::default().with_role(
+            roles::SUPER_ADMIN,
+            RolePolicyConfig {
+                permissions: vec![permissions::READ_TRACE.into(), permissions::UPDATE_TRACE.into()],
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn with_role<S: ToString>(mut self, role: S, policy: RolePolicyConfig) -> Self {
+        self.roles.entry(role.to_string()).or_default().merge(policy);
+        self
+    }
+
+    /// Extend the policy, the permissions and inheritance of the roles are united.
+    pub fn merge(&mut self, other: PolicyConfig) {
+        self.authenticated.merge(other.authenticated);
+        for (role, policy) in other.roles {
+            self.roles.entry(role).or_default().merge(policy);
+        }
+    }
+}
+
+#[derive(Default)]
+struct RolePermissions {
+    permissions: HashSet<String>,
+    owner_permissions: HashSet<String>,
+}
+
+/// Evaluate the roles of the users into permissions.
+pub struct PolicyService {
+    authenticated: RolePermissions,
+    roles: HashMap<String, RolePermissions>,
+}
+
+impl PolicyService {
+    pub fn new(config: &PolicyConfig) -> Result<Self, PolicyError> {
+        fn resolve(
+            config: &PolicyConfig,
+            role: &str,
+            visiting: &mut Vec<String>,
+            resolved: &mut HashMap<String, RolePermissions>,
+        ) -> Result<(), PolicyError> {
+            if resolved.contains_key(role) {
+                return Ok(());
+            }
+            if visiting.iter().any(|r| r == role) {
+                return Err(PolicyError::CyclicInheritance(role.to_owned()));
+            }
+
+            let policy = &config.roles[role];
+            let mut permissions = RolePermissions {
+                permissions: policy.permissions.iter().cloned().collect(),
+                owner_permissions: policy.owner_permissions.iter().cloned().collect(),
+            };
+
+            visiting.push(role.to_owned());
+            for parent in &policy.inherits {
+                if !config.roles.contains_key(parent) {
+                    return Err(PolicyError::UnknownRole(role.to_owned(), parent.clone()));
+                }
+                resolve(config, parent, visiting, resolved)?;
+                let parent = &resolved[parent];
+                permissions.permissions.extend(parent.permissions.iter().cloned());
+                permissions
+                    .owner_permissions
+                    .extend(parent.owner_permissions.iter().cloned());
+            }
+            visiting.pop();
+
+            resolved.insert(role.to_owned(), permissions);
+            Ok(())
+        }
+
+        let mut roles = HashMap::new();
+        for role in config.roles.keys() {
+            resolve(config, role, &mut Vec::new(), &mut roles)?;
+        }
+
+        let authenticated = RolePermissions {
+            permissions: config.authenticated.permissions.iter().cloned().collect(),
+            owner_permissions: config.authenticated.owner_permissions.iter().cloned().collect(),
+        };
+
+        Ok(Self { authenticated, roles })
+    }
+
+    pub fn into_layer(self) -> Extension<Arc<Self>> {
+        Extension(Arc::new(self))
+    }
+
+    /// Return the effective permissions of a user. Unknown roles grant no permission.
+    pub fn permissions(&self, user: &CurrentUser) -> PermissionSet {
+        let mut permission_set = PermissionSet::for_user(user.user_id);
+
+        let roles = user.roles.iter().filter_map(|role| self.roles.get(role));
+        for role in std::iter::once(&self.authenticated).chain(roles) {
+            for permission in &role.permissions {
+                permission_set.add(permission);
+            }
+            for permission in &role.owner_permissions {
+                permission_set.add_owner(permission);
+            }
+        }
+
+        permission_set
+    }
+
+    pub fn create_router<S>(&self) -> OpenApiRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        OpenApiRouter::new().routes(routes!(get_effective_permissions))
+    }
+}
+
+/// Marker type of a permission used by the [RequirePermission] extractor.
+pub trait Permission: Send + Sync + 'static {
+    const NAME: &'static str;
+}
+
+/// Extractor for the current user (from the session cookie) with the given permission.
+pub struct RequirePermission<P: Permission> {
+    user: CurrentUser,
+    permissions: PermissionSet,
+    _permission: PhantomData<P>,
+}
+
+impl<P: Permission> RequirePermission<P> {
+    pub fn into_user(self) -> CurrentUser {
+        self.user
+    }
+
+    /// All the effective permissions of the user.
+    pub fn permissions(&self) -> &PermissionSet {
+        &self.permissions
+    }
+}
+
+impl<P: Permission> ops::Deref for RequirePermission<P> {
+    type Target = CurrentUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user
+    }
+}
+
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = ProblemResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(problem_config) = parts
+            .extract::<Extension<ProblemConfig>>()
+            .await
+            .expect("Missing ProblemConfig extension");
+        let Extension(policy) = parts
+            .extract::<Extension<Arc<PolicyService>>>()
+            .await
+            .expect("Missing PolicyService extension");
+
+        let user = CheckedCurrentUser::from_request_parts(parts, state)
+            .await
+            .map_err(|err| err.problem.into_response(&problem_config))?
+            .into_user();
+
+        let permissions = policy.permissions(&user);
+        permissions
+            .check(P::NAME)
+            .map_err(|err| err.into_response(&problem_config))?;
+
+        Ok(Self {
+            user,
+            permissions,
+            _permission: PhantomData,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectivePermissions {
+    roles: Vec<String>,
+    permissions: Vec<String>,
+    owner_permissions: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/policy/permissions",
+    tag = "auth",
+    description = "Get the effective permissions of the current user.",
+    responses(
+        (status = OK, body = EffectivePermissions)
+    )
+)]
+async fn get_effective_permissions(
+    Extension(policy): Extension<Arc<PolicyService>>,
+    user: CheckedCurrentUser,
+) -> Json<EffectivePermissions> {
+    let permissions = policy.permissions(&user);
+    Json(EffectivePermissions {
+        roles: user.roles.clone(),
+        permissions: permissions.permissions(),
+        owner_permissions: permissions.owner_permissions(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::session::SessionKey;
+    use chrono::Utc;
+    use ring::rand::SystemRandom;
+    use shine_test::test;
+    use uuid::Uuid;
+
+    fn user(roles: &[&str]) -> CurrentUser {
+        CurrentUser {
+            user_id: Uuid::new_v4(),
+            key: SessionKey::new_random(&SystemRandom::new()).unwrap(),
+            session_start: Utc::now(),
+            session_end: Utc::now(),
+            authenticated_at: Utc::now(),
+            name: "test".into(),
+            is_email_confirmed: false,
+            is_linked: false,
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            fingerprint: String::new(),
+        }
+    }
+
+    fn role(inherits: &[&str], permissions: &[&str], owner_permissions: &[&str]) -> RolePolicyConfig {
+        RolePolicyConfig {
+            inherits: inherits.iter().map(|r| r.to_string()).collect(),
+            permissions: permissions.iter().map(|r| r.to_string()).collect(),
+            owner_permissions: owner_permissions.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn inheritance() {
+        let config = PolicyConfig::default()
+            .with_role("Admin", role(&["Moderator"], &["Delete"], &[]))
+            .with_role("Moderator", role(&["Member"], &["Edit"], &[]))
+            .with_role("Member", role(&[], &["Read"], &["Edit"]));
+        let policy = PolicyService::new(&config).unwrap();
+
+        let admin = policy.permissions(&user(&["Admin"]));
+        assert_eq!(admin.permissions(), ["Delete", "Edit", "Read"]);
+        assert_eq!(admin.owner_permissions(), ["Edit"]);
+
+        let member = policy.permissions(&user(&["Member", "Unknown"]));
+        assert_eq!(member.permissions(), ["Read"]);
+
+        let guest = policy.permissions(&user(&[]));
+        assert!(guest.permissions().is_empty());
+    }
+
+    #[test]
+    fn owner_permissions() {
+        let config = PolicyConfig {
+            authenticated: role(&[], &[], &["Edit"]),
+            ..Default::default()
+        }
+        .with_role("Admin", role(&[], &["Edit"], &[]));
+        let policy = PolicyService::new(&config).unwrap();
+
+        let owner = user(&[]);
+        let other = user(&[]);
+        let admin = user(&["Admin"]);
+
+        let permissions = policy.permissions(&owner);
+        assert!(permissions.require("Edit").is_err());
+        assert!(permissions.require_for_owner("Edit", owner.user_id).is_ok());
+        assert!(permissions.require_for_owner("Edit", other.user_id).is_err());
+        assert!(permissions.require_for_owner("Delete", owner.user_id).is_err());
+
+        let permissions = policy.permissions(&admin);
+        assert!(permissions.require_for_owner("Edit", owner.user_id).is_ok());
+    }
+
+    #[test]
+    fn merge() {
+        let mut config = PolicyConfig::core();
+        config.merge(PolicyConfig::default().with_role(roles::SUPER_ADMIN, role(&[], &["Extra"], &[])));
+        let policy = PolicyService::new(&config).unwrap();
+
+        let admin = policy.permissions(&user(&[roles::SUPER_ADMIN]));
+        assert_eq!(
+            admin.permissions(),
+            ["Extra", permissions::READ_TRACE, permissions::UPDATE_TRACE]
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        let config = PolicyConfig::default().with_role("A", role(&["B"], &[], &[]));
+        assert!(matches!(
+            PolicyService::new(&config),
+            Err(PolicyError::UnknownRole(_, _))
+        ));
+
+        let config = PolicyConfig::default()
+            .with_role("A", role(&["B"], &[], &[]))
+            .with_role("B", role(&["A"], &[], &[]));
+        assert!(matches!(
+            PolicyService::new(&config),
+            Err(PolicyError::CyclicInheritance(_))
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    db::create_redis_pubsub,
+    session::{CurrentUser, UserSessionError},
+    web::SessionCacheConfig,
+};
+use futures::StreamExt;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Message published by the owner of the sessions (identity service) when sessions are updated or revoked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionInvalidation {
+    /// All the sessions of the user
+    User(Uuid),
+    /// A single session given by the hash of the session key
+    Session(Uuid, String),
+}
+
+impl SessionInvalidation {
+    /// The pub/sub channel of the invalidation messages.
+    pub fn channel(key_prefix: &str) -> String {
+        format!("{key_prefix}session:invalidate")
+    }
+
+    pub fn to_message(&self) -> String {
+        match self {
+            SessionInvalidation::User(user_id) => user_id.as_simple().to_string(),
+            SessionInvalidation::Session(user_id, key_hash) => format!("{}:{key_hash}", user_id.as_simple()),
+        }
+    }
+
+    pub fn from_message(message: &str) -> Option<Self> {
+        match message.split_once(':') {
+            Some((user_id, key_hash)) => Some(Self::Session(Uuid::parse_str(user_id).ok()?, key_hash.to_string())),
+            None => Some(Self::User(Uuid::parse_str(message).ok()?)),
+        }
+    }
+}
+
+struct CacheEntry {
+    user: CurrentUser,
+    cached_at: Instant,
+    extended_at: Instant,
+}
+
+/// Short living in-process cache of the sessions keyed by the hash of the session key.
+pub struct SessionCache {
+    ttl: Duration,
+    extend_interval: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration, extend_interval: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            extend_interval,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &SessionCacheConfig) -> Self {
+        Self::new(
+            Duration::from_secs(config.ttl),
+            Duration::from_secs(config.extend_interval.unwrap_or(60)),
+            config.capacity.unwrap_or(10000),
+        )
+    }
+
+    /// Return the cached session and whether the expiration in Redis should be extended.
+    pub fn get(&self, user_id: Uuid, key_hash: &str) -> Option<(CurrentUser, bool)> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key_hash) {
+            Some(entry) if entry.user.user_id == user_id && now.duration_since(entry.cached_at) < self.ttl => {
+                let extend = now.duration_since(entry.extended_at) >= self.extend_interval;
+                Some((entry.user.clone(), extend))
+            }
+            Some(_) => {
+                entries.remove(key_hash);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key_hash: &str, user: CurrentUser) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= self.capacity && !entries.contains_key(key_hash) {
+            entries.retain(|_, entry| now.duration_since(entry.cached_at) < self.ttl);
+            if entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.cached_at)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+
+        entries.insert(
+            key_hash.to_string(),
+            CacheEntry {
+                user,
+                cached_at: now,
+                extended_at: now,
+            },
+        );
+    }
+
+    /// Record that the expiration of the session has been extended in Redis.
+    pub fn set_extended(&self, key_hash: &str, session_end: chrono::DateTime<chrono::Utc>) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key_hash) {
+            entry.extended_at = Instant::now();
+            entry.user.session_end = session_end;
+        }
+    }
+
+    pub fn invalidate(&self, invalidation: &SessionInvalidation) {
+        let mut entries = self.entries.lock().unwrap();
+        match invalidation {
+            SessionInvalidation::User(user_id) => entries.retain(|_, entry| entry.user.user_id != *user_id),
+            SessionInvalidation::Session(_, key_hash) => {
+                entries.remove(key_hash);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Start a background task to process the invalidation messages. As messages may be lost while
+    /// the connection is down, the cache is cleared on each (re)connection.
+    pub fn start_invalidation_listener(self: &Arc<Self>, redis_cns: &str, key_prefix: &str) {
+        let cache = Arc::clone(self);
+        let redis_cns = redis_cns.to_string();
+        let channel = SessionInvalidation::channel(key_prefix);
+
+        tokio::spawn(async move {
+            loop {
+                match Self::listen(&cache, &redis_cns, &channel).await {
+                    Ok(()) => log::warn!("Session invalidation channel closed, reconnecting..."),
+                    Err(err) => log::warn!("Session invalidation channel failed, reconnecting...: {err:?}"),
+                }
+                cache.clear();
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+
+    async fn listen(cache: &Self, redis_cns: &str, channel: &str) -> Result<(), UserSessionError> {
+        let mut pubsub = create_redis_pubsub(redis_cns)
+            .await
+            .map_err(UserSessionError::RedisError)?;
+        pubsub.subscribe(channel).await.map_err(UserSessionError::RedisError)?;
+        cache.clear();
+        log::info!("Listening for session invalidation on {channel}");
+
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            let payload: String = match msg.get_payload() {
+                Ok(payload) => payload,
+                Err(err) => {
+                    log::warn!("Invalid session invalidation message: {err:?}");
+                    continue;
+                }
+            };
+            match SessionInvalidation::from_message(&payload) {
+                Some(invalidation) => {
+                    log::debug!("Session invalidation: {invalidation:?}");
+                    cache.invalidate(&invalidation);
+                }
+                None => log::warn!("Invalid session invalidation message: {payload}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::session::SessionKey;
+    use chrono::Utc;
+    use ring::rand::SystemRandom;
+    use shine_test::test;
+
+    fn user(user_id: Uuid) -> CurrentUser {
+        CurrentUser {
+            user_id,
+            key: SessionKey::new_random(&SystemRandom::new()).unwrap(),
+            session_start: Utc::now(),
+            session_end: Utc::now(),
+            authenticated_at: Utc::now(),
+            name: "test".into(),
+            is_email_confirmed: false,
+            is_linked: false,
+            roles: vec![],
+            fingerprint: String::new(),
+        }
+    }
+
+    #[test]
+    fn invalidation_message() {
+        let user_id = Uuid::new_v4();
+        for invalidation in [
+            SessionInvalidation::User(user_id),
+            SessionInvalidation::Session(user_id, "abcd".into()),
+        ] {
+            let message = invalidation.to_message();
+            assert_eq!(SessionInvalidation::from_message(&message), Some(invalidation));
+        }
+        assert_eq!(SessionInvalidation::from_message("invalid"), None);
+    }
+
+    #[test]
+    fn get_and_invalidate() {
+        let cache = SessionCache::new(Duration::from_secs(100), Duration::from_secs(100), 10);
+        let (u1, u2) = (Uuid::new_v4(), Uuid::new_v4());
+        cache.insert("a", user(u1));
+        cache.insert("b", user(u1));
+        cache.insert("c", user(u2));
+
+        assert!(matches!(cache.get(u1, "a"), Some((_, false))));
+        assert!(cache.get(u2, "a").is_none(), "user mismatch");
+        assert!(cache.get(u1, "a").is_none(), "mismatch evicts the entry");
+
+        cache.invalidate(&SessionInvalidation::Session(u2, "c".into()));
+        assert!(cache.get(u2, "c").is_none());
+        assert!(cache.get(u1, "b").is_some());
+
+        cache.insert("a", user(u1));
+        cache.invalidate(&SessionInvalidation::User(u1));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn expiration() {
+        let cache = SessionCache::new(Duration::ZERO, Duration::ZERO, 10);
+        let u1 = Uuid::new_v4();
+        cache.insert("a", user(u1));
+        assert!(cache.get(u1, "a").is_none());
+
+        let cache = SessionCache::new(Duration::from_secs(100), Duration::ZERO, 10);
+        cache.insert("a", user(u1));
+        assert!(matches!(cache.get(u1, "a"), Some((_, true))));
+    }
+
+    #[test]
+    fn capacity() {
+        let cache = SessionCache::new(Duration::from_secs(100), Duration::from_secs(100), 2);
+        let u1 = Uuid::new_v4();
+        cache.insert("a", user(u1));
+        std::thread::sleep(Duration::from_millis(1));
+        cache.insert("b", user(u1));
+        std::thread::sleep(Duration::from_millis(1));
+        cache.insert("c", user(u1));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(u1, "a").is_none());
+        assert!(cache.get(u1, "c").is_some());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{session::UserSessionError, web::ServiceConfig};
+use chrono::{DateTime, Duration, Utc};
+
+/// Expiration rules of the user sessions. It is shared by the services reading the session (sliding the
+/// idle timeout) and the identity service creating and updating them, to keep the expiration consistent.
+#[derive(Clone, Copy, Debug)]
+pub struct SessionLifetime {
+    idle_timeout: Duration,
+    max_lifetime: Option<Duration>,
+}
+
+impl SessionLifetime {
+    pub fn new(idle_timeout: Duration, max_lifetime: Option<Duration>) -> Self {
+        Self { idle_timeout, max_lifetime }
+    }
+
+    pub fn from_config(config: &ServiceConfig) -> Result<Self, UserSessionError> {
+        fn to_duration(value: u64) -> Result<Duration, UserSessionError> {
+            let seconds = i64::try_from(value).map_err(|err| UserSessionError::InvalidTtl(format!("{err}")))?;
+            Duration::try_seconds(seconds).ok_or_else(|| UserSessionError::InvalidTtl(format!("{value} is too large")))
+        }
+
+        let idle_timeout = to_duration(config.session_ttl)?;
+        let max_lifetime = config.session_max_lifetime.map(to_duration).transpose()?;
+        if let Some(max_lifetime) = max_lifetime {
+            if max_lifetime < idle_timeout {
+                return Err(UserSessionError::InvalidTtl(
+                    "Maximum lifetime is shorter than the idle timeout".to_string(),
+                ));
+            }
+        }
+        Ok(Self::new(idle_timeout, max_lifetime))
+    }
+
+    /// The session expires if it is not accessed for this period.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// The session expires after this period since the login independent of the activity.
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime
+    }
+
+    /// Return when the session expires when it is accessed at `now`.
+    /// If the absolute lifetime has already elapsed, None is returned.
+    pub fn expire_at(&self, created_at: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let idle_end = now + self.idle_timeout;
+        match self.max_lifetime {
+            Some(max_lifetime) => {
+                let end = created_at + max_lifetime;
+                (end > now).then(|| end.min(idle_end))
+            }
+            None => Some(idle_end),
+        }
+    }
+
+    /// Return the time to live in seconds when the session is accessed at `now`.
+    /// If the absolute lifetime has already elapsed, None is returned.
+    pub fn ttl(&self, created_at: DateTime<Utc>, now: DateTime<Utc>) -> Option<i64> {
+        self.expire_at(created_at, now)
+            .map(|expire_at| (expire_at - now).num_seconds().max(1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    fn idle_timeout_only() {
+        let lifetime = SessionLifetime::new(Duration::seconds(100), None);
+        let created_at = Utc::now() - Duration::days(1000);
+        let now = Utc::now();
+
+        assert_eq!(lifetime.ttl(created_at, now), Some(100));
+    }
+
+    #[test]
+    fn absolute_lifetime() {
+        let lifetime = SessionLifetime::new(Duration::seconds(100), Some(Duration::seconds(1000)));
+        let now = Utc::now();
+
+        assert_eq!(lifetime.ttl(now, now), Some(100));
+        assert_eq!(lifetime.ttl(now - Duration::seconds(500), now), Some(100));
+        assert_eq!(lifetime.ttl(now - Duration::seconds(950), now), Some(50));
+        assert_eq!(lifetime.ttl(now - Duration::seconds(1000), now), None);
+        assert_eq!(lifetime.ttl(now - Duration::seconds(2000), now), None);
+    }
+}
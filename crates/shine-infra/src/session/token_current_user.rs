@@ -0,0 +1,114 @@
+use crate::{
+    crypto::{IssuedJwt, JwtError, JwtService},
+    session::{CurrentUser, CurrentUserService, UserSessionError},
+    web::{
+        extracts::ClientFingerprint,
+        responses::{ErrorResponse, ProblemConfig},
+    },
+};
+use axum::{extract::FromRequestParts, http::request::Parts, Extension, RequestPartsExt};
+use axum_extra::{
+    headers::{authorization::Bearer, Authorization},
+    TypedHeader,
+};
+use serde::{Deserialize, Serialize};
+use std::{ops, sync::Arc};
+use uuid::Uuid;
+
+/// The private claims of a session token. The subject of the token is the user id.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionTokenClaims {
+    /// The opaque id of the session, see [CurrentUserService::session_token_id].
+    pub sid: String,
+}
+
+impl JwtService {
+    /// Issue a token bound to the session of the user.
+    pub fn issue_session_token(
+        &self,
+        user_service: &CurrentUserService,
+        user: &CurrentUser,
+    ) -> Result<IssuedJwt, JwtError> {
+        let sid = user_service.session_token_id(user);
+        self.issue(&user.user_id.to_string(), SessionTokenClaims { sid })
+    }
+}
+
+/// Extractor for the CurrentUser using a signed bearer token instead of the session cookie.
+/// The token refers to a session and the session data is fetched the same way as for the cookie, thus
+/// revoking the session also revokes the token.
+pub struct TokenCurrentUser(CurrentUser);
+
+impl TokenCurrentUser {
+    pub fn into_user(self) -> CurrentUser {
+        self.0
+    }
+}
+
+impl From<TokenCurrentUser> for CurrentUser {
+    fn from(value: TokenCurrentUser) -> Self {
+        value.0
+    }
+}
+
+impl ops::Deref for TokenCurrentUser {
+    type Target = CurrentUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for TokenCurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse<UserSessionError>;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(problem_config) = parts
+            .extract::<Extension<ProblemConfig>>()
+            .await
+            .expect("Missing ProblemConfig extension");
+        let Extension(session_service) = parts
+            .extract::<Extension<Arc<CurrentUserService>>>()
+            .await
+            .expect("Missing CurrentUserService extension");
+        let Ok(fingerprint) = parts.extract::<ClientFingerprint>().await;
+        let Extension(jwt_service) = parts
+            .extract::<Extension<Arc<JwtService>>>()
+            .await
+            .map_err(|_| ErrorResponse::new(&problem_config, UserSessionError::Unauthenticated))?;
+
+        let TypedHeader(Authorization(bearer)) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| ErrorResponse::new(&problem_config, UserSessionError::Unauthenticated))?;
+
+        let claims = jwt_service
+            .validate::<SessionTokenClaims>(bearer.token())
+            .map_err(|err| ErrorResponse::new(&problem_config, UserSessionError::InvalidToken(err)))?;
+        let user_id = Uuid::parse_str(&claims.sub)
+            .map_err(|_| ErrorResponse::new(&problem_config, UserSessionError::InvalidToken(JwtError::Malformed)))?;
+
+        let key = session_service
+            .session_key_from_token_id(user_id, &claims.custom.sid)
+            .ok_or_else(|| ErrorResponse::new(&problem_config, UserSessionError::InvalidToken(JwtError::Malformed)))?;
+
+        log::debug!("Finding user session for token: {user_id:?}");
+        let current_user = session_service
+            .get_current_user(user_id, key)
+            .await
+            .map_err(|err| ErrorResponse::new(&problem_config, err))?;
+
+        log::debug!("Checking fingerprint: {user_id:?}");
+        if !fingerprint.matches(&current_user.fingerprint) {
+            return Err(ErrorResponse::new(
+                &problem_config,
+                UserSessionError::SessionCompromised,
+            ));
+        }
+
+        Ok(TokenCurrentUser(current_user))
+    }
+}
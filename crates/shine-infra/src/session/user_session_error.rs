@@ -1,4 +1,5 @@
 use crate::{
+    crypto::JwtError,
     db::RedisConnectionError,
     web::{extracts::ClientFingerprintError, responses::Problem},
 };
@@ -18,6 +19,10 @@ pub enum UserSessionError {
     ClientFingerprintError(#[from] ClientFingerprintError),
     #[error("Session is compromised")]
     SessionCompromised,
+    #[error("Invalid session token")]
+    InvalidToken(#[source] JwtError),
+    #[error("Recent authentication is required")]
+    StepUpRequired,
 
     #[error("Failed to get redis connection")]
     RedisPoolError(#[source] RedisConnectionError),
@@ -46,6 +51,12 @@ impl From<UserSessionError> for Problem {
             UserSessionError::SessionCompromised => Problem::unauthorized()
                 .with_detail(value.to_string())
                 .with_sensitive("sessionCompromised"),
+            UserSessionError::InvalidToken(_) => Problem::unauthorized()
+                .with_detail(value.to_string())
+                .with_sensitive_dbg(value),
+            UserSessionError::StepUpRequired => Problem::unauthorized_ty("step-up-required")
+                .with_detail(value.to_string())
+                .with_sensitive("stepUpRequired"),
             UserSessionError::RedisPoolError(_) => Problem::service_unavailable()
                 .with_detail(value.to_string())
                 .with_sensitive_dbg(value),
@@ -0,0 +1,200 @@
+use crate::telemetry::LogRedactor;
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_log::NormalizeEvent;
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{
+    fmt::{format::Writer, FmtContext, FormatEvent, FormatFields},
+    registry::LookupSpan,
+};
+
+/// Format the events as a json object per line. The trace and span ids of the current OpenTelemetry span are
+/// included to correlate the logs with the traces, the sensitive fields are redacted.
+pub struct JsonLogFormat {
+    redactor: Option<LogRedactor>,
+}
+
+impl JsonLogFormat {
+    pub fn new(redactor: Option<LogRedactor>) -> Self {
+        Self { redactor }
+    }
+}
+
+struct JsonVisitor<'a> {
+    fields: Map<String, Value>,
+    redactor: Option<&'a LogRedactor>,
+}
+
+impl JsonVisitor<'_> {
+    fn insert_str(&mut self, field: &Field, value: &str) {
+        let value = match self.redactor {
+            Some(redactor) => redactor.redact_field(field.name(), value).into_owned(),
+            None => value.to_string(),
+        };
+        self.fields.insert(field.name().to_string(), Value::String(value));
+    }
+
+    fn insert_value(&mut self, field: &Field, value: Value) {
+        match self.redactor {
+            Some(redactor) if redactor.is_sensitive_field(field.name()) => self.insert_str(field, ""),
+            _ => {
+                self.fields.insert(field.name().to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert_str(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert_str(field, &format!("{value:?}"));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert_value(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert_value(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert_value(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert_value(field, value.into());
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonLogFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        // events of the log crate carry the metadata in fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        let mut visitor = JsonVisitor {
+            fields: Map::new(),
+            redactor: self.redactor.as_ref(),
+        };
+        event.record(&mut visitor);
+        let mut fields = visitor.fields;
+        fields.retain(|name, _| !name.starts_with("log."));
+
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".into(),
+            Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true).into(),
+        );
+        line.insert("level".into(), metadata.level().as_str().into());
+        line.insert("target".into(), metadata.target().into());
+        if let Some(message) = fields.remove("message") {
+            line.insert("message".into(), message);
+        }
+        if !fields.is_empty() {
+            line.insert("fields".into(), Value::Object(fields));
+        }
+        if let Some(span) = ctx.lookup_current() {
+            line.insert("span".into(), span.name().into());
+            if let Some(otel) = span.extensions().get::<OtelData>() {
+                if let Some(trace_id) = otel.trace_id() {
+                    line.insert("trace_id".into(), trace_id.to_string().into());
+                }
+                if let Some(span_id) = otel.span_id() {
+                    line.insert("span_id".into(), span_id.to_string().into());
+                }
+            }
+        }
+
+        let line = serde_json::to_string(&line).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{line}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::telemetry::LogRedactionConfig;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use shine_test::test;
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+    use tracing_subscriber::{layer::SubscriberExt, registry};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn format_json_lines() {
+        let buffer = Buffer::default();
+        let redactor = LogRedactor::new(&LogRedactionConfig::default()).unwrap();
+        let tracer = SdkTracerProvider::builder().build().tracer("test");
+        let writer = buffer.clone();
+        let subscriber = registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .event_format(JsonLogFormat::new(Some(redactor)))
+                    .with_writer(move || writer.clone()),
+            );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(user_id = 42, "Started");
+            tracing::info_span!("request").in_scope(|| {
+                tracing::warn!(
+                    email = "a@b.com",
+                    password = 1234,
+                    attempt = 2,
+                    "Login of {}",
+                    "c@d.org"
+                );
+            });
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["message"], "Started");
+        assert_eq!(lines[0]["fields"]["user_id"], 42);
+        assert!(lines[0].get("trace_id").is_none());
+
+        assert_eq!(lines[1]["level"], "WARN");
+        assert_eq!(lines[1]["message"], "Login of [redacted]");
+        assert_eq!(lines[1]["fields"]["email"], "[redacted]");
+        assert_eq!(lines[1]["fields"]["password"], "[redacted]");
+        assert_eq!(lines[1]["fields"]["attempt"], 2);
+        assert_eq!(lines[1]["span"], "request");
+        assert_eq!(lines[1]["trace_id"].as_str().unwrap().len(), 32);
+        assert_eq!(lines[1]["span_id"].as_str().unwrap().len(), 16);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::telemetry::LogRedactionConfig;
+use opentelemetry::{
+    logs::{AnyValue, LogRecord, Logger, LoggerProvider},
+    InstrumentationScope, Key,
+};
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    logs::{LogProcessor, SdkLogRecord, SdkLoggerProvider},
+    Resource,
+};
+use regex::Regex;
+use std::{borrow::Cow, time::Duration};
+
+const REDACTED: &str = "[redacted]";
+const SENSITIVE_FIELDS: &[&str] = &["password", "secret", "token", "authorization", "cookie", "email"];
+const SENSITIVE_PATTERNS: &[&str] = &[
+    // email
+    r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+    // bearer token
+    r"(?i)bearer\s+[A-Za-z0-9._~+/=-]+",
+    // jwt
+    r"eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*",
+];
+
+/// Remove the sensitive data (emails, tokens) from the logs.
+#[derive(Clone, Debug)]
+pub struct LogRedactor {
+    fields: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl LogRedactor {
+    pub fn new(config: &LogRedactionConfig) -> Result<Self, regex::Error> {
+        let fields = SENSITIVE_FIELDS
+            .iter()
+            .map(|field| field.to_string())
+            .chain(config.fields.iter().map(|field| field.to_lowercase()))
+            .collect();
+        let patterns = SENSITIVE_PATTERNS
+            .iter()
+            .copied()
+            .chain(config.patterns.iter().map(String::as_str))
+            .map(Regex::new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { fields, patterns })
+    }
+
+    pub fn is_sensitive_field(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.fields.iter().any(|field| name.contains(field.as_str()))
+    }
+
+    /// Replace the sensitive parts of a value.
+    pub fn redact<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        let mut value = Cow::Borrowed(value);
+        for pattern in &self.patterns {
+            if let Cow::Owned(redacted) = pattern.replace_all(&value, REDACTED) {
+                value = Cow::Owned(redacted);
+            }
+        }
+        value
+    }
+
+    /// Replace the value of a sensitive field or the sensitive parts of the value.
+    pub fn redact_field<'a>(&self, name: &str, value: &'a str) -> Cow<'a, str> {
+        if self.is_sensitive_field(name) {
+            Cow::Borrowed(REDACTED)
+        } else {
+            self.redact(value)
+        }
+    }
+
+    fn redact_value(&self, value: &AnyValue) -> Option<AnyValue> {
+        match value {
+            AnyValue::String(value) => match self.redact(value.as_str()) {
+                Cow::Owned(redacted) => Some(AnyValue::String(redacted.into())),
+                Cow::Borrowed(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn redact_attribute(&self, name: &str, value: &AnyValue) -> Option<AnyValue> {
+        if self.is_sensitive_field(name) {
+            Some(AnyValue::String(REDACTED.into()))
+        } else {
+            self.redact_value(value)
+        }
+    }
+}
+
+/// Log processor redacting the records before passing them to the exporting processor.
+#[derive(Debug)]
+pub struct RedactingLogProcessor<P> {
+    redactor: LogRedactor,
+    // The attributes of the records cannot be updated, the redacted records are rebuilt from this blank record.
+    blank: SdkLogRecord,
+    inner: P,
+}
+
+impl<P> RedactingLogProcessor<P>
+where
+    P: LogProcessor,
+{
+    pub fn new(redactor: LogRedactor, inner: P) -> Self {
+        let blank = SdkLoggerProvider::builder()
+            .build()
+            .logger("redaction")
+            .create_log_record();
+        Self { redactor, blank, inner }
+    }
+
+    fn redact(&self, record: &SdkLogRecord) -> Option<SdkLogRecord> {
+        let body = record.body().and_then(|body| self.redactor.redact_value(body));
+        let attributes = record
+            .attributes_iter()
+            .map(|(key, value)| self.redactor.redact_attribute(key.as_str(), value))
+            .collect::<Vec<_>>();
+        if body.is_none() && attributes.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let mut redacted = self.blank.clone();
+        if let Some(name) = record.event_name() {
+            redacted.set_event_name(name);
+        }
+        if let Some(target) = record.target() {
+            redacted.set_target(target.clone());
+        }
+        if let Some(timestamp) = record.timestamp() {
+            redacted.set_timestamp(timestamp);
+        }
+        if let Some(timestamp) = record.observed_timestamp() {
+            redacted.set_observed_timestamp(timestamp);
+        }
+        if let Some(context) = record.trace_context() {
+            redacted.set_trace_context(context.trace_id, context.span_id, context.trace_flags);
+        }
+        if let Some(text) = record.severity_text() {
+            redacted.set_severity_text(text);
+        }
+        if let Some(severity) = record.severity_number() {
+            redacted.set_severity_number(severity);
+        }
+        if let Some(body) = body.or_else(|| record.body().cloned()) {
+            redacted.set_body(body);
+        }
+        for ((key, value), redacted_value) in record.attributes_iter().zip(attributes) {
+            redacted.add_attribute(Key::clone(key), redacted_value.unwrap_or_else(|| value.clone()));
+        }
+
+        Some(redacted)
+    }
+}
+
+impl<P> LogProcessor for RedactingLogProcessor<P>
+where
+    P: LogProcessor,
+{
+    fn emit(&self, record: &mut SdkLogRecord, scope: &InstrumentationScope) {
+        if let Some(redacted) = self.redact(record) {
+            *record = redacted;
+        }
+        self.inner.emit(record, scope);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    fn redact_sensitive_data() {
+        let config = LogRedactionConfig {
+            enabled: None,
+            fields: vec!["SessionKey".into()],
+            patterns: vec![r"sk_[0-9a-f]{8}".into()],
+        };
+        let redactor = LogRedactor::new(&config).unwrap();
+
+        assert_eq!(
+            redactor.redact("Sending email to john.doe@example.com"),
+            "Sending email to [redacted]"
+        );
+        assert_eq!(
+            redactor.redact("authorization: Bearer abc.def-123 received"),
+            "authorization: [redacted] received"
+        );
+        assert_eq!(
+            redactor.redact("token eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.sig_1 and sk_0123abcd"),
+            "token [redacted] and [redacted]"
+        );
+        assert!(matches!(redactor.redact("nothing to hide"), Cow::Borrowed(_)));
+
+        assert_eq!(redactor.redact_field("access_token", "abc"), REDACTED);
+        assert_eq!(redactor.redact_field("user_sessionkey", "abc"), REDACTED);
+        assert_eq!(redactor.redact_field("user_id", "abc"), "abc");
+    }
+
+    #[test]
+    fn redact_log_record() {
+        #[derive(Debug)]
+        struct NoopProcessor;
+        impl LogProcessor for NoopProcessor {
+            fn emit(&self, _record: &mut SdkLogRecord, _scope: &InstrumentationScope) {}
+            fn force_flush(&self) -> OTelSdkResult {
+                Ok(())
+            }
+        }
+
+        let redactor = LogRedactor::new(&LogRedactionConfig::default()).unwrap();
+        let processor = RedactingLogProcessor::new(redactor, NoopProcessor);
+
+        let mut record = processor.blank.clone();
+        record.set_severity_text("INFO");
+        record.set_body(AnyValue::String("Login of a@b.com".into()));
+        record.add_attribute("user_id", "42");
+        record.add_attribute("password", "secret");
+        processor.emit(&mut record, &InstrumentationScope::default());
+
+        assert_eq!(record.severity_text(), Some("INFO"));
+        assert_eq!(record.body(), Some(&AnyValue::String("Login of [redacted]".into())));
+        let attributes = record
+            .attributes_iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attributes,
+            vec![
+                ("user_id".to_string(), AnyValue::String("42".into())),
+                ("password".to_string(), AnyValue::String(REDACTED.into())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use crate::telemetry::instrumentation_scope;
+use opentelemetry::{global, metrics::Meter};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+/// A set of typed metrics (counters, histograms, ...) of a service.
+///
+/// ```ignore
+/// struct MailMetrics {
+///     sent: Counter<u64>,
+/// }
+///
+/// impl MetricSet for MailMetrics {
+///     fn new(meter: &Meter) -> Self {
+///         Self { sent: meter.u64_counter("emails_sent").build() }
+///     }
+/// }
+///
+/// metrics::<MailMetrics>().sent.add(1, &[]);
+/// ```
+pub trait MetricSet: Send + Sync + 'static {
+    fn new(meter: &Meter) -> Self;
+}
+
+#[derive(Default)]
+struct MetricsRegistry {
+    sets: RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+static REGISTRY: LazyLock<MetricsRegistry> = LazyLock::new(MetricsRegistry::default);
+
+/// Get the metric set of the given type. The instruments are created on the first use from the meter provider of
+/// the [TelemetryService](crate::telemetry::TelemetryService), the metric sets created before the service are not
+/// exported.
+pub fn metrics<M: MetricSet>() -> Arc<M> {
+    let type_id = TypeId::of::<M>();
+
+    if let Some(set) = REGISTRY.sets.read().unwrap().get(&type_id) {
+        return set.clone().downcast::<M>().expect("Metric set type mismatch");
+    }
+
+    let mut sets = REGISTRY.sets.write().unwrap();
+    let set = sets
+        .entry(type_id)
+        .or_insert_with(|| Arc::new(M::new(&global::meter_with_scope(instrumentation_scope()))))
+        .clone();
+    set.downcast::<M>().expect("Metric set type mismatch")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use opentelemetry::metrics::Counter;
+    use shine_test::test;
+
+    struct TestMetrics {
+        counter: Counter<u64>,
+    }
+
+    impl MetricSet for TestMetrics {
+        fn new(meter: &Meter) -> Self {
+            Self {
+                counter: meter.u64_counter("test_counter").build(),
+            }
+        }
+    }
+
+    #[test]
+    fn metric_sets_are_shared() {
+        let first = metrics::<TestMetrics>();
+        let second = metrics::<TestMetrics>();
+        assert!(Arc::ptr_eq(&first, &second));
+        first.counter.add(1, &[]);
+    }
+}
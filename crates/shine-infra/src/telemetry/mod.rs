@@ -1,4 +1,4 @@
-mod otel_http;
+pub(crate) mod otel_http;
 
 mod otel_layer;
 pub use self::otel_layer::*;
@@ -6,6 +6,14 @@ mod telemetry_error;
 pub use self::telemetry_error::*;
 mod telemetry_config;
 pub use self::telemetry_config::*;
+mod log_redactor;
+pub use self::log_redactor::*;
+mod json_log_format;
+pub use self::json_log_format::*;
+mod prometheus_exporter;
+pub use self::prometheus_exporter::*;
+mod metrics_registry;
+pub use self::metrics_registry::*;
 mod telemetry_service;
 pub use self::telemetry_service::*;
 mod telemetry_router;
@@ -0,0 +1,232 @@
+use crate::telemetry::TelemetryError;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    metrics::{
+        data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics},
+        reader::MetricReader,
+        InstrumentKind, ManualReader, Pipeline, Temporality,
+    },
+};
+use std::{
+    fmt::{self, Display, Write},
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+/// Pull based metric reader to expose the metrics in the Prometheus text format.
+#[derive(Clone, Debug)]
+pub struct PrometheusExporter {
+    reader: Arc<ManualReader>,
+}
+
+impl Default for PrometheusExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrometheusExporter {
+    pub fn new() -> Self {
+        Self {
+            reader: Arc::new(
+                ManualReader::builder()
+                    .with_temporality(Temporality::Cumulative)
+                    .build(),
+            ),
+        }
+    }
+
+    /// Collect the current value of the metrics in the Prometheus text format.
+    pub fn collect(&self) -> Result<String, TelemetryError> {
+        let mut metrics = ResourceMetrics::default();
+        self.reader
+            .collect(&mut metrics)
+            .map_err(TelemetryError::MetricsCollect)?;
+
+        let mut text = String::new();
+        encode_metrics(&mut text, &metrics).expect("Writing to a String cannot fail");
+        Ok(text)
+    }
+}
+
+impl MetricReader for PrometheusExporter {
+    fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+        self.reader.register_pipeline(pipeline)
+    }
+
+    fn collect(&self, metrics: &mut ResourceMetrics) -> OTelSdkResult {
+        self.reader.collect(metrics)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.reader.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.reader.shutdown_with_timeout(timeout)
+    }
+
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.reader.temporality(kind)
+    }
+}
+
+fn sanitize_name(name: &str) -> String {
+    let mut name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', r#"\""#).replace('\n', r"\n")
+}
+
+fn write_header(out: &mut String, name: &str, metric: &Metric, kind: &str) -> fmt::Result {
+    if !metric.description().is_empty() {
+        let help = metric.description().replace('\\', r"\\").replace('\n', r"\n");
+        writeln!(out, "# HELP {name} {help}")?;
+    }
+    writeln!(out, "# TYPE {name} {kind}")
+}
+
+fn write_sample<'a, T: Display>(
+    out: &mut String,
+    name: &str,
+    attributes: impl Iterator<Item = &'a KeyValue>,
+    bound: Option<&str>,
+    value: T,
+) -> fmt::Result {
+    let mut labels = attributes
+        .map(|kv| {
+            format!(
+                "{}=\"{}\"",
+                sanitize_name(kv.key.as_str()),
+                escape_label_value(&kv.value.to_string())
+            )
+        })
+        .collect::<Vec<_>>();
+    if let Some(bound) = bound {
+        labels.push(format!("le=\"{bound}\""));
+    }
+
+    if labels.is_empty() {
+        writeln!(out, "{name} {value}")
+    } else {
+        writeln!(out, "{name}{{{}}} {value}", labels.join(","))
+    }
+}
+
+fn encode_metric<T: Display + Copy>(out: &mut String, metric: &Metric, data: &MetricData<T>) -> fmt::Result {
+    let name = sanitize_name(metric.name());
+    match data {
+        MetricData::Gauge(gauge) => {
+            write_header(out, &name, metric, "gauge")?;
+            for point in gauge.data_points() {
+                write_sample(out, &name, point.attributes(), None, point.value())?;
+            }
+        }
+        MetricData::Sum(sum) if sum.is_monotonic() => {
+            let name = if name.ends_with("_total") {
+                name
+            } else {
+                format!("{name}_total")
+            };
+            write_header(out, &name, metric, "counter")?;
+            for point in sum.data_points() {
+                write_sample(out, &name, point.attributes(), None, point.value())?;
+            }
+        }
+        MetricData::Sum(sum) => {
+            write_header(out, &name, metric, "gauge")?;
+            for point in sum.data_points() {
+                write_sample(out, &name, point.attributes(), None, point.value())?;
+            }
+        }
+        MetricData::Histogram(histogram) => {
+            write_header(out, &name, metric, "histogram")?;
+            let bucket = format!("{name}_bucket");
+            for point in histogram.data_points() {
+                let mut count = 0;
+                for (bound, bucket_count) in point.bounds().zip(point.bucket_counts()) {
+                    count += bucket_count;
+                    write_sample(out, &bucket, point.attributes(), Some(&bound.to_string()), count)?;
+                }
+                write_sample(out, &bucket, point.attributes(), Some("+Inf"), point.count())?;
+                write_sample(out, &format!("{name}_sum"), point.attributes(), None, point.sum())?;
+                write_sample(out, &format!("{name}_count"), point.attributes(), None, point.count())?;
+            }
+        }
+        MetricData::ExponentialHistogram(_) => {
+            log::trace!("Exponential histogram ({name}) is not supported by the Prometheus exporter");
+        }
+    }
+    Ok(())
+}
+
+fn encode_metrics(out: &mut String, metrics: &ResourceMetrics) -> fmt::Result {
+    for scope in metrics.scope_metrics() {
+        for metric in scope.metrics() {
+            match metric.data() {
+                AggregatedMetrics::F64(data) => encode_metric(out, metric, data)?,
+                AggregatedMetrics::U64(data) => encode_metric(out, metric, data)?,
+                AggregatedMetrics::I64(data) => encode_metric(out, metric, data)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+    use shine_test::test;
+
+    #[test]
+    fn collect_text_format() {
+        let exporter = PrometheusExporter::new();
+        let provider = SdkMeterProvider::builder().with_reader(exporter.clone()).build();
+        let meter = provider.meter("test");
+
+        let logins = meter.u64_counter("logins").with_description("Number of logins").build();
+        logins.add(2, &[KeyValue::new("provider", "google")]);
+        logins.add(1, &[KeyValue::new("provider", "guest")]);
+        let sessions = meter.i64_up_down_counter("ws.sessions").build();
+        sessions.add(3, &[]);
+        sessions.add(-1, &[]);
+        let latency = meter
+            .f64_histogram("append_duration")
+            .with_boundaries(vec![0.1, 1.0])
+            .build();
+        latency.record(0.05, &[]);
+        latency.record(0.5, &[]);
+        latency.record(2.0, &[]);
+
+        let text = exporter.collect().unwrap();
+        log::info!("{text}");
+
+        assert!(text.contains("# HELP logins_total Number of logins\n# TYPE logins_total counter\n"));
+        assert!(text.contains("logins_total{provider=\"google\"} 2\n"));
+        assert!(text.contains("logins_total{provider=\"guest\"} 1\n"));
+        assert!(text.contains("# TYPE ws_sessions gauge\nws_sessions 2\n"));
+        assert!(text.contains("# TYPE append_duration histogram\n"));
+        assert!(text.contains("append_duration_bucket{le=\"0.1\"} 1\n"));
+        assert!(text.contains("append_duration_bucket{le=\"1\"} 2\n"));
+        assert!(text.contains("append_duration_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("append_duration_sum 2.55\n"));
+        assert!(text.contains("append_duration_count 3\n"));
+    }
+}
@@ -1,6 +1,12 @@
+use crate::web::{extracts::ValidationErrorEx, validate_patterns};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+use validator::{Validate, ValidationError};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type")]
 pub enum Metering {
@@ -13,7 +19,7 @@ pub enum Metering {
     OpenTelemetryProtocol { endpoint: String },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type")]
 pub enum Tracing {
@@ -37,12 +43,106 @@ pub enum Tracing {
     AppInsight { connection_string: String },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "type")]
+pub enum LogExport {
+    /// Disable the export of logs
+    #[default]
+    None,
+
+    /// Export the logs as OpenTelemetry log records to the standard output
+    StdOut,
+
+    /// Enable OpenTelemetry log exporter
+    #[cfg(feature = "ot_otlp")]
+    #[serde(rename = "otlp")]
+    OpenTelemetryProtocol { endpoint: String },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum LogFormat {
+    /// Human readable single line logs
+    #[default]
+    Compact,
+    /// Structured logs, a json object per line including the trace and span ids
+    Json,
+}
+
+/// Redaction of the sensitive data of the exported and structured logs
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRedactionConfig {
+    /// Redact the sensitive data. Default: true
+    pub enabled: Option<bool>,
+    /// Additional field names (case insensitive fragments) with sensitive values, the password, secret, token,
+    /// authorization, cookie and email fields are always redacted.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Additional regular expressions of sensitive values, the emails, bearer tokens and JWTs are always redacted.
+    #[serde(default)]
+    #[validate(custom(function = "validate_patterns"))]
+    pub patterns: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsConfig {
+    /// The exporter of the logs. Default: none
+    #[serde(default)]
+    pub export: LogExport,
+    /// The format of the console logs. Default: compact
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Level overrides of the targets (ex. `{"shine_identity": "debug"}`) added to the default level.
+    #[serde(default)]
+    #[validate(custom(function = "validate_levels"))]
+    pub levels: BTreeMap<String, String>,
+    #[serde(default)]
+    #[validate(nested)]
+    pub redaction: LogRedactionConfig,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct TelemetryConfig {
     pub enable_console_log: bool,
+    /// The tracing filter directives (ex. `warn,shine_infra=info`). Default: warn.
+    #[validate(custom(function = "validate_filter"))]
     pub default_level: Option<String>,
     pub allow_reconfigure: bool,
     pub metrics: Metering,
+    /// Expose the metrics in the Prometheus text format at the `/metrics` endpoint. Default: false.
+    #[serde(default)]
+    pub enable_prometheus: bool,
     pub tracing: Tracing,
+    /// Export, format and redaction of the logs.
+    #[serde(default)]
+    #[validate(nested)]
+    pub logs: LogsConfig,
+}
+
+fn validate_filter(filter: &str) -> Result<(), ValidationError> {
+    EnvFilter::builder()
+        .parse(filter)
+        .map(|_| ())
+        .map_err(|err| ValidationError::new("filter").with_message(err.to_string().into()))
+}
+
+/// Check if the targets and levels of the level overrides are valid.
+pub fn validate_levels(levels: &BTreeMap<String, String>) -> Result<(), ValidationError> {
+    for (target, level) in levels {
+        if target.is_empty() || target.contains([',', '=', '[', ']']) {
+            return Err(ValidationError::new("target")
+                .with_message("Invalid target".into())
+                .with_param("target", target));
+        }
+        if level.parse::<LevelFilter>().is_err() {
+            return Err(ValidationError::new("level")
+                .with_message("Invalid level".into())
+                .with_param("level", level));
+        }
+    }
+    Ok(())
 }
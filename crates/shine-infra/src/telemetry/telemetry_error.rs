@@ -1,5 +1,5 @@
 use crate::web::responses::Problem;
-use opentelemetry_sdk::trace::TraceError;
+use opentelemetry_sdk::{error::OTelSdkError, trace::TraceError};
 use thiserror::Error as ThisError;
 use tracing::subscriber::SetGlobalDefaultError;
 use tracing_subscriber::filter::ParseError;
@@ -24,6 +24,8 @@ pub enum TelemetryBuildError {
     ZipkinBuildError(#[from] opentelemetry_zipkin::ExporterBuildError),
     #[error(transparent)]
     TraceError(#[from] TraceError),
+    #[error("Invalid log redaction pattern")]
+    InvalidRedactionPattern(#[from] regex::Error),
 }
 
 #[derive(Debug, ThisError)]
@@ -32,6 +34,10 @@ pub enum TelemetryError {
     TraceUpdateConfig(String),
     #[error("Reconfigure is not enabled")]
     TraceNoReconfigure,
+    #[error("Prometheus exporter is not enabled")]
+    MetricsNoPrometheus,
+    #[error("Failed to collect the metrics")]
+    MetricsCollect(#[source] OTelSdkError),
 }
 
 impl From<TelemetryError> for Problem {
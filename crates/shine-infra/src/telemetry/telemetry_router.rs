@@ -1,16 +1,24 @@
 use crate::{
-    session::{permissions, CheckedCurrentUser, CorePermissions},
+    session::{permissions, RequirePermission},
     telemetry::{DynConfig, TelemetryService},
     web::responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
 };
-use axum::{Extension, Json};
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct TraceConfig {
     filter: String,
+    /// Level overrides of the targets, ex. `{ "sqlx": "debug" }`.
+    #[serde(default)]
+    levels: BTreeMap<String, String>,
 }
 
 #[utoipa::path(
@@ -26,16 +34,15 @@ pub struct TraceConfig {
 pub async fn put_telemetry_config(
     Extension(telemetry): Extension<TelemetryService>,
     Extension(problem_config): Extension<ProblemConfig>,
-    user: CheckedCurrentUser,
+    _user: RequirePermission<permissions::UpdateTrace>,
     Json(body): Json<TraceConfig>,
 ) -> Result<(), ProblemResponse> {
-    user.core_permissions()
-        .check(permissions::UPDATE_TRACE)
-        .map_err(|err| err.into_response(&problem_config))?;
-
     log::trace!("reconfigure telemetry: {body:#?}");
     telemetry
-        .set_configuration(DynConfig { filter: body.filter })
+        .set_configuration(DynConfig {
+            filter: body.filter,
+            levels: body.levels,
+        })
         .map_err(|err| err.into_response(&problem_config))?;
 
     Ok(())
@@ -53,25 +60,50 @@ pub async fn put_telemetry_config(
 pub async fn get_telemetry_config(
     Extension(telemetry): Extension<TelemetryService>,
     Extension(problem_config): Extension<ProblemConfig>,
-    user: CheckedCurrentUser,
+    _user: RequirePermission<permissions::ReadTrace>,
 ) -> Result<Json<TraceConfig>, ProblemResponse> {
-    user.core_permissions()
-        .check(permissions::READ_TRACE)
-        .map_err(|err| err.into_response(&problem_config))?;
-
     let config = telemetry
         .get_configuration()
         .map_err(|err| err.into_response(&problem_config))?;
 
-    Ok(Json(TraceConfig { filter: config.filter }))
+    Ok(Json(TraceConfig {
+        filter: config.filter,
+        levels: config.levels,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    description = "Get the metrics in the Prometheus text format.",
+    responses(
+        (status = OK, content_type = "text/plain", body = String)
+    )
+)]
+pub async fn get_metrics(
+    Extension(telemetry): Extension<TelemetryService>,
+    Extension(problem_config): Extension<ProblemConfig>,
+) -> Result<Response, ProblemResponse> {
+    let metrics = telemetry
+        .collect_metrics()
+        .map_err(|err| err.into_response(&problem_config))?;
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics).into_response())
 }
 
 pub(super) fn build_router<S>(service: TelemetryService) -> OpenApiRouter<S>
 where
     S: Clone + Send + Sync + 'static,
 {
-    OpenApiRouter::new()
+    let mut router = OpenApiRouter::new()
         .routes(routes!(get_telemetry_config))
-        .routes(routes!(put_telemetry_config))
-        .layer(Extension(service))
+        .routes(routes!(put_telemetry_config));
+
+    // the scrape endpoint is not authenticated, it is enabled only on demand
+    if service.is_prometheus_enabled() {
+        router = router.routes(routes!(get_metrics));
+    }
+
+    router.layer(Extension(service))
 }
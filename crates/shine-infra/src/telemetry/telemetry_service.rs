@@ -1,24 +1,55 @@
-use crate::telemetry::{Metering, OtelLayer, TelemetryBuildError, TelemetryConfig, TelemetryError, Tracing};
+use crate::telemetry::{
+    validate_levels, JsonLogFormat, LogExport, LogFormat, LogRedactor, Metering, OtelLayer, PrometheusExporter,
+    RedactingLogProcessor, TelemetryBuildError, TelemetryConfig, TelemetryError, Tracing,
+};
 use opentelemetry::{
     global,
     metrics::{Meter, MeterProvider},
     trace::TracerProvider,
     InstrumentationScope,
 };
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::{
+    logs::{BatchLogProcessor, LogProcessor, LoggerProviderBuilder, SdkLoggerProvider, SimpleLogProcessor},
     metrics::SdkMeterProvider,
+    propagation::TraceContextPropagator,
     trace::{Sampler, SdkTracerProvider},
     Resource,
 };
-use std::sync::{Arc, RwLock};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+use tokio::sync::watch;
 use tracing::{Dispatch, Subscriber};
 use tracing_opentelemetry::OpenTelemetryLayer;
-use tracing_subscriber::{filter::EnvFilter, layer::SubscriberExt, registry::LookupSpan, reload, Layer};
+use tracing_subscriber::{
+    filter::{filter_fn, EnvFilter},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    reload, Layer, Registry,
+};
+
+/// The targets of the exporters, their logs are not exported to avoid feedback loops.
+const LOG_EXPORT_IGNORED_TARGETS: &[&str] = &["opentelemetry", "tonic", "h2", "hyper", "reqwest"];
 
 #[derive(Debug, Clone)]
 pub struct DynConfig {
     pub filter: String,
+    /// Level overrides of the targets.
+    pub levels: BTreeMap<String, String>,
+}
+
+impl DynConfig {
+    /// The filter directives of the default level and the level overrides.
+    fn directives(&self) -> String {
+        self.levels
+            .iter()
+            .fold(self.filter.clone(), |directives, (target, level)| {
+                format!("{directives},{target}={level}")
+            })
+    }
 }
 
 trait DynHandle: Send + Sync {
@@ -42,7 +73,11 @@ where
 {
     fn set_configuration(&mut self, mut new_config: DynConfig) -> Result<(), String> {
         new_config.filter.retain(|c| !c.is_whitespace());
-        let new_filter = new_config.filter.parse::<EnvFilter>().map_err(|e| format!("{e}"))?;
+        validate_levels(&new_config.levels).map_err(|e| format!("{e}"))?;
+        let new_filter = new_config
+            .directives()
+            .parse::<EnvFilter>()
+            .map_err(|e| format!("{e}"))?;
         self.handle.reload(new_filter).map_err(|e| format!("{e}"))?;
         self.config = new_config;
         Ok(())
@@ -53,16 +88,24 @@ where
     }
 }
 
+/// The instrumentation scope of the service metrics and traces.
+pub(crate) fn instrumentation_scope() -> InstrumentationScope {
+    InstrumentationScope::builder("opentelemetry-instrumentation-shine")
+        .with_version(env!("CARGO_PKG_VERSION"))
+        .build()
+}
+
 #[derive(Clone)]
 struct Metrics {
     provider: SdkMeterProvider,
     meter: Meter,
+    prometheus: Option<PrometheusExporter>,
 }
 
 /// Telemetry service.
 /// Metrics:
-///  - Uses opentelemetry_sdk to provide metrics in Prometheus format.
-///  - The Meter type can be used to define new metrics.
+///  - Uses opentelemetry_sdk to export the metrics and optionally to provide them in Prometheus format.
+///  - The Meter type can be used to define new metrics, or a MetricSet can declare them once for the service.
 ///
 /// Tracings:
 ///  - The tracing crate is used as the frontend and some configured opentelemetry exporter is used as the backend.
@@ -70,9 +113,12 @@ struct Metrics {
 /// Logs
 ///  - The trace::trace,debug,info,warn,error! macros can be used
 ///  - For convenience, the log::trace,debug,info,warn,error! macros are also available and channelled to the tracing layer
+///  - The console logs are either compact or json lines, the logs can be exported as OpenTelemetry log records.
+///    The sensitive data is redacted from the json and the exported logs.
 #[derive(Clone)]
 pub struct TelemetryService {
     tracer_provider: Option<SdkTracerProvider>,
+    logger_provider: Option<SdkLoggerProvider>,
     metrics: Option<Metrics>,
     reconfigure: Option<Arc<RwLock<dyn DynHandle>>>,
 }
@@ -81,6 +127,7 @@ impl TelemetryService {
     pub async fn new(service_name: &'static str, config: &TelemetryConfig) -> Result<Self, TelemetryBuildError> {
         let mut service = TelemetryService {
             tracer_provider: None,
+            logger_provider: None,
             metrics: None,
             reconfigure: None,
         };
@@ -116,8 +163,11 @@ impl TelemetryService {
         S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync + 'static,
     {
         log::debug!("Registering fixed filter tracing layer...");
-        let filter = config.default_level.as_deref().unwrap_or("warn");
-        let env_filter = EnvFilter::builder().parse(filter)?;
+        let filter = DynConfig {
+            filter: config.default_level.clone().unwrap_or_else(|| "warn".to_string()),
+            levels: config.logs.levels.clone(),
+        };
+        let env_filter = EnvFilter::builder().parse(filter.directives())?;
 
         Ok(env_filter)
     }
@@ -128,26 +178,104 @@ impl TelemetryService {
     {
         log::debug!("Registering dynamic filter tracing layer...");
 
-        let filter = config.default_level.as_deref().unwrap_or("warn");
-        let env_filter = EnvFilter::builder().parse(filter)?;
+        let filter = DynConfig {
+            filter: config.default_level.clone().unwrap_or_else(|| "warn".to_string()),
+            levels: config.logs.levels.clone(),
+        };
+        let env_filter = EnvFilter::builder().parse(filter.directives())?;
 
         let (reload_env_filter, reload_handle) = reload::Layer::new(env_filter);
         self.reconfigure = Some(Arc::new(RwLock::new(WrapHandle {
             handle: reload_handle,
-            config: DynConfig { filter: filter.to_string() },
+            config: filter,
         })));
         Ok(reload_env_filter)
     }
 
-    fn tracing_console_log<S>(&mut self, _config: &TelemetryConfig) -> Result<impl Layer<S>, TelemetryBuildError>
+    fn log_redactor(config: &TelemetryConfig) -> Result<Option<LogRedactor>, TelemetryBuildError> {
+        let redaction = &config.logs.redaction;
+        if redaction.enabled.unwrap_or(true) {
+            Ok(Some(LogRedactor::new(redaction)?))
+        } else {
+            log::warn!("Log redaction is disabled");
+            Ok(None)
+        }
+    }
+
+    fn tracing_console_log<S>(
+        &mut self,
+        config: &TelemetryConfig,
+    ) -> Result<Box<dyn Layer<S> + Send + Sync>, TelemetryBuildError>
     where
         S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync + 'static,
     {
-        log::debug!("Registering console log tracing layer...");
-        let console_layer = tracing_subscriber::fmt::Layer::new().compact();
+        let console_layer = match config.logs.format {
+            LogFormat::Compact => {
+                log::debug!("Registering console log tracing layer...");
+                tracing_subscriber::fmt::Layer::new().compact().boxed()
+            }
+            LogFormat::Json => {
+                log::debug!("Registering json console log tracing layer...");
+                let format = JsonLogFormat::new(Self::log_redactor(config)?);
+                tracing_subscriber::fmt::Layer::new().event_format(format).boxed()
+            }
+        };
         Ok(console_layer)
     }
 
+    fn tracing_ot_logs<S>(
+        &mut self,
+        config: &TelemetryConfig,
+        resource: &Resource,
+    ) -> Result<impl Layer<S>, TelemetryBuildError>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync + 'static,
+    {
+        fn with_redaction<P>(
+            builder: LoggerProviderBuilder,
+            redactor: Option<LogRedactor>,
+            processor: P,
+        ) -> LoggerProviderBuilder
+        where
+            P: LogProcessor + 'static,
+        {
+            match redactor {
+                Some(redactor) => builder.with_log_processor(RedactingLogProcessor::new(redactor, processor)),
+                None => builder.with_log_processor(processor),
+            }
+        }
+
+        let redactor = Self::log_redactor(config)?;
+        let builder = SdkLoggerProvider::builder().with_resource(resource.clone());
+        let builder = match &config.logs.export {
+            LogExport::StdOut => {
+                log::info!("Registering StdOut log exporter...");
+                let exporter = opentelemetry_stdout::LogExporter::default();
+                with_redaction(builder, redactor, SimpleLogProcessor::new(exporter))
+            }
+            #[cfg(feature = "ot_otlp")]
+            LogExport::OpenTelemetryProtocol { endpoint } => {
+                log::info!("Registering OpenTelemetryProtocol log exporter...");
+                let exporter = opentelemetry_otlp::LogExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(endpoint)
+                    .build()?;
+                with_redaction(builder, redactor, BatchLogProcessor::builder(exporter).build())
+            }
+            LogExport::None => unreachable!("LogExport::None should not be used in this context"),
+        };
+
+        let provider = builder.build();
+        let bridge = OpenTelemetryTracingBridge::new(&provider);
+        self.logger_provider = Some(provider);
+
+        Ok(bridge.with_filter(filter_fn(|metadata| {
+            !LOG_EXPORT_IGNORED_TARGETS
+                .iter()
+                .any(|target| metadata.target().starts_with(target))
+        })))
+    }
+
     fn tracing_ot<S>(
         &mut self,
         config: &TelemetryConfig,
@@ -200,6 +328,9 @@ impl TelemetryService {
         let tracer = provider.tracer_with_scope(scope.clone());
         self.tracer_provider = Some(provider);
 
+        // propagate the trace context with the W3C headers
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
         Ok(OpenTelemetryLayer::new(tracer).with_tracked_inactivity(true))
     }
 
@@ -211,61 +342,39 @@ impl TelemetryService {
     ) -> Result<(), TelemetryBuildError> {
         use tracing_subscriber::registry;
 
+        let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = Vec::new();
+
+        if config.enable_console_log {
+            layers.push(self.tracing_console_log(config)?);
+        }
+
         if matches!(config.tracing, Tracing::None) {
             log::warn!("Trace is disabled");
+        } else {
+            layers.push(self.tracing_ot(config, resource, scope)?.boxed());
+        }
 
-            if config.enable_console_log {
-                if config.allow_reconfigure {
-                    Self::set_global_tracing_pipeline(
-                        registry()
-                            .with(self.tracing_dyn_filter(config)?)
-                            .with(self.tracing_console_log(config)?),
-                    )?;
-                } else {
-                    Self::set_global_tracing_pipeline(
-                        registry()
-                            .with(self.tracing_fixed_filter(config)?)
-                            .with(self.tracing_console_log(config)?),
-                    )?;
-                }
-            } else {
-                log::warn!("Service is configured for silent mode");
-            }
+        if matches!(config.logs.export, LogExport::None) {
+            log::info!("Log export is disabled");
         } else {
-            match (config.enable_console_log, config.allow_reconfigure) {
-                (true, true) => {
-                    Self::set_global_tracing_pipeline(
-                        registry()
-                            .with(self.tracing_dyn_filter(config)?)
-                            .with(self.tracing_console_log(config)?)
-                            .with(self.tracing_ot(config, resource, scope)?),
-                    )?;
-                }
-                (true, false) => {
-                    Self::set_global_tracing_pipeline(
-                        registry()
-                            .with(self.tracing_fixed_filter(config)?)
-                            .with(self.tracing_console_log(config)?)
-                            .with(self.tracing_ot(config, resource, scope)?),
-                    )?;
-                }
-                (false, true) => {
-                    Self::set_global_tracing_pipeline(
-                        registry()
-                            .with(self.tracing_dyn_filter(config)?)
-                            .with(self.tracing_ot(config, resource, scope)?),
-                    )?;
-                }
-                (false, false) => {
-                    Self::set_global_tracing_pipeline(
-                        registry()
-                            .with(self.tracing_fixed_filter(config)?)
-                            .with(self.tracing_ot(config, resource, scope)?),
-                    )?;
-                }
-            }
+            layers.push(self.tracing_ot_logs(config, resource)?.boxed());
+        }
+
+        if layers.is_empty() {
+            log::warn!("Service is configured for silent mode");
+            return Ok(());
         }
 
+        // the filter is the first layer to apply it globally
+        let filter = if config.allow_reconfigure {
+            self.tracing_dyn_filter(config)?.boxed()
+        } else {
+            self.tracing_fixed_filter(config)?.boxed()
+        };
+        layers.insert(0, filter);
+
+        Self::set_global_tracing_pipeline(registry().with(layers))?;
+
         Ok(())
     }
 
@@ -276,10 +385,14 @@ impl TelemetryService {
         scope: &InstrumentationScope,
     ) -> Result<(), TelemetryBuildError> {
         // Install meter provider for opentelemetry
-        let provider = match &config.metrics {
+        let builder = SdkMeterProvider::builder().with_resource(resource.clone());
+        let builder = match &config.metrics {
             Metering::None => {
-                log::warn!("Metrics are disabled");
-                return Ok(());
+                if !config.enable_prometheus {
+                    log::warn!("Metrics are disabled");
+                    return Ok(());
+                }
+                builder
             }
             #[cfg(feature = "ot_otlp")]
             Metering::OpenTelemetryProtocol { endpoint } => {
@@ -288,16 +401,22 @@ impl TelemetryService {
                     .with_tonic()
                     .with_endpoint(endpoint)
                     .build()?;
-                SdkMeterProvider::builder()
-                    .with_resource(resource.clone())
-                    .with_periodic_exporter(exporter)
-                    .build()
+                builder.with_periodic_exporter(exporter)
             }
         };
 
+        let (builder, prometheus) = if config.enable_prometheus {
+            log::info!("Registering Prometheus metric exporter...");
+            let exporter = PrometheusExporter::new();
+            (builder.with_reader(exporter.clone()), Some(exporter))
+        } else {
+            (builder, None)
+        };
+
+        let provider = builder.build();
         global::set_meter_provider(provider.clone());
         let meter = provider.meter_with_scope(scope.clone());
-        self.metrics = Some(Metrics { provider, meter });
+        self.metrics = Some(Metrics { provider, meter, prometheus });
         Ok(())
     }
 
@@ -307,9 +426,7 @@ impl TelemetryService {
         config: &TelemetryConfig,
     ) -> Result<(), TelemetryBuildError> {
         let resource = Resource::builder().with_service_name(service_name).build();
-        let scope = InstrumentationScope::builder("opentelemetry-instrumentation-shine")
-            .with_version(env!("CARGO_PKG_VERSION"))
-            .build();
+        let scope = instrumentation_scope();
 
         self.install_metrics(config, &resource, &scope)?;
         self.install_trace(config, &resource, &scope)?;
@@ -338,6 +455,31 @@ impl TelemetryService {
         }
     }
 
+    /// Apply the changes of the default trace level of the configuration. It requires `allowReconfigure`.
+    pub fn watch_default_level(&self, mut default_level: watch::Receiver<Option<String>>) {
+        if self.reconfigure.is_none() {
+            return;
+        }
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            while default_level.changed().await.is_ok() {
+                let filter = default_level
+                    .borrow_and_update()
+                    .clone()
+                    .unwrap_or_else(|| "warn".to_string());
+                let levels = service
+                    .get_configuration()
+                    .map(|config| config.levels)
+                    .unwrap_or_default();
+                match service.set_configuration(DynConfig { filter: filter.clone(), levels }) {
+                    Ok(()) => log::info!("Trace filter changed to {filter}"),
+                    Err(err) => log::error!("Failed to change the trace filter to {filter}: {err}"),
+                }
+            }
+        });
+    }
+
     pub fn create_meter(&self, metrics_scope: &'static str) -> Option<Meter> {
         self.metrics.as_ref().map(|m| m.provider.meter(metrics_scope))
     }
@@ -346,6 +488,19 @@ impl TelemetryService {
         self.metrics.as_ref().map(|m| &m.meter)
     }
 
+    pub fn is_prometheus_enabled(&self) -> bool {
+        self.metrics.as_ref().is_some_and(|m| m.prometheus.is_some())
+    }
+
+    /// Collect the metrics in the Prometheus text format.
+    pub fn collect_metrics(&self) -> Result<String, TelemetryError> {
+        self.metrics
+            .as_ref()
+            .and_then(|m| m.prometheus.as_ref())
+            .ok_or(TelemetryError::MetricsNoPrometheus)?
+            .collect()
+    }
+
     pub fn create_router<S>(&self) -> utoipa_axum::router::OpenApiRouter<S>
     where
         S: Clone + Send + Sync + 'static,
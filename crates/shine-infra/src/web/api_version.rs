@@ -0,0 +1,379 @@
+use crate::web::{
+    middlewares::Deprecated as DeprecatedLayer,
+    responses::{Problem, ProblemConfig, ProblemResponse},
+};
+use axum::{
+    body::Body,
+    http::{uri::PathAndQuery, HeaderName, Request, Uri},
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use std::{
+    collections::BTreeSet,
+    convert::Infallible,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+use utoipa::openapi::{path::Operation, Deprecated, OpenApi as OpenApiDoc};
+use utoipa_axum::router::OpenApiRouter;
+
+/// The request header to select the version of the api on the unversioned paths.
+pub const API_VERSION_HEADER: HeaderName = HeaderName::from_static("api-version");
+
+struct ApiVersion<S> {
+    version: u16,
+    router: OpenApiRouter<S>,
+    deprecated: Option<DeprecatedLayer>,
+}
+
+/// Route sets of the different versions of an api. The routes of the version `n` are mounted under `/v{n}`, the
+/// default version is also mounted without the version prefix to keep the existing clients working.
+///
+/// ```ignore
+/// let api = VersionedApi::new()
+///     .deprecated_version(1, v1_routes, Deprecated::new(since).with_sunset(sunset))
+///     .version(2, v2_routes)
+///     .with_default(1)
+///     .into_router();
+/// ```
+pub struct VersionedApi<S> {
+    versions: Vec<ApiVersion<S>>,
+    default: Option<u16>,
+}
+
+impl<S> Default for VersionedApi<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> VersionedApi<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            versions: Vec::new(),
+            default: None,
+        }
+    }
+
+    pub fn version(mut self, version: u16, router: OpenApiRouter<S>) -> Self {
+        self.versions.push(ApiVersion {
+            version,
+            router,
+            deprecated: None,
+        });
+        self
+    }
+
+    /// Add a version where all the routes are deprecated.
+    pub fn deprecated_version(mut self, version: u16, router: OpenApiRouter<S>, deprecated: DeprecatedLayer) -> Self {
+        self.versions.push(ApiVersion {
+            version,
+            router,
+            deprecated: Some(deprecated),
+        });
+        self
+    }
+
+    /// The version served on the unversioned paths. Default: the latest version.
+    pub fn with_default(self, version: u16) -> Self {
+        Self { default: Some(version), ..self }
+    }
+
+    pub fn into_router(self) -> OpenApiRouter<S> {
+        let default = self.default.or_else(|| self.versions.iter().map(|v| v.version).max());
+
+        let mut router = OpenApiRouter::new();
+        for ApiVersion {
+            version,
+            router: mut version_router,
+            deprecated,
+        } in self.versions
+        {
+            if let Some(deprecated) = deprecated {
+                mark_deprecated(version_router.get_openapi_mut());
+                version_router = version_router.layer(deprecated);
+            }
+
+            if Some(version) == default {
+                router = router.merge(version_router.clone());
+            }
+            router = router.nest(&format!("/v{version}"), version_router);
+        }
+        router
+    }
+}
+
+fn mark_deprecated(doc: &mut OpenApiDoc) {
+    for item in doc.paths.paths.values_mut() {
+        let operations: [&mut Option<Operation>; 8] = [
+            &mut item.get,
+            &mut item.put,
+            &mut item.post,
+            &mut item.delete,
+            &mut item.options,
+            &mut item.head,
+            &mut item.patch,
+            &mut item.trace,
+        ];
+        for operation in operations.into_iter().flatten() {
+            operation.deprecated = Some(Deprecated::True);
+        }
+    }
+}
+
+/// Parse the version from the `/{prefix}/v{n}/...` paths.
+fn path_version(prefix: &str, path: &str) -> Option<u16> {
+    let rest = path.strip_prefix(prefix)?.strip_prefix("/v")?;
+    let (version, _) = rest.split_once('/')?;
+    version.parse().ok()
+}
+
+/// Split the api documentation into the unversioned (default) and the per-version documents.
+pub fn split_versioned_openapi(prefix: &str, doc: OpenApiDoc) -> (OpenApiDoc, Vec<(u16, OpenApiDoc)>) {
+    let versions = doc
+        .paths
+        .paths
+        .keys()
+        .filter_map(|path| path_version(prefix, path))
+        .collect::<BTreeSet<_>>();
+
+    let version_docs = versions
+        .into_iter()
+        .map(|version| {
+            let mut version_doc = doc.clone();
+            version_doc
+                .paths
+                .paths
+                .retain(|path, _| path_version(prefix, path) == Some(version));
+            (version, version_doc)
+        })
+        .collect();
+
+    let mut doc = doc;
+    doc.paths.paths.retain(|path, _| path_version(prefix, path).is_none());
+    (doc, version_docs)
+}
+
+/// Check if the path matches the route template, the `{param}` segments match any segment.
+fn matches_route(route: &str, path: &str) -> bool {
+    let mut route = route.split('/');
+    let mut path = path.split('/');
+    loop {
+        match (route.next(), path.next()) {
+            (None, None) => return true,
+            (Some(expected), Some(segment)) if expected == segment || expected.starts_with('{') => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Select the version of the unversioned paths by the `Api-Version` request header. The request is routed to the
+/// `/{prefix}/v{n}/...` path, thus this layer has to wrap the router. Only the requests matching a versioned route
+/// are affected, ex. the health checks ignore the header.
+#[derive(Clone)]
+pub struct ApiVersionSelector {
+    prefix: Arc<str>,
+    /// The versioned routes without the prefix and the version
+    routes: Arc<Vec<(u16, String)>>,
+    problem_config: ProblemConfig,
+}
+
+impl ApiVersionSelector {
+    /// Create a selector for the versioned routes found in the api documentation.
+    pub fn from_openapi(prefix: &str, doc: &OpenApiDoc, problem_config: ProblemConfig) -> Self {
+        let routes = doc
+            .paths
+            .paths
+            .keys()
+            .filter_map(|path| {
+                let version = path_version(prefix, path)?;
+                let route = path[prefix.len()..].strip_prefix(&format!("/v{version}"))?;
+                Some((version, route.to_string()))
+            })
+            .collect();
+        Self {
+            prefix: prefix.into(),
+            routes: Arc::new(routes),
+            problem_config,
+        }
+    }
+
+    pub fn versions(&self) -> BTreeSet<u16> {
+        self.routes.iter().map(|(version, _)| *version).collect()
+    }
+
+    /// The path of the requested version, None if the request is not routed to a version.
+    fn select(&self, request: &Request<Body>) -> Result<Option<Uri>, Problem> {
+        let Some(version) = request.headers().get(API_VERSION_HEADER) else {
+            return Ok(None);
+        };
+
+        let path = request.uri().path();
+        let Some(rest) = path.strip_prefix(&*self.prefix) else {
+            return Ok(None);
+        };
+        // the explicitly versioned and the not versioned routes are not altered
+        if !self.routes.iter().any(|(_, route)| matches_route(route, rest)) {
+            return Ok(None);
+        }
+
+        let version = version
+            .to_str()
+            .ok()
+            .map(|version| version.trim_start_matches(['v', 'V']))
+            .and_then(|version| version.parse::<u16>().ok())
+            .filter(|version| {
+                self.routes
+                    .iter()
+                    .any(|(route_version, route)| route_version == version && matches_route(route, rest))
+            })
+            .ok_or_else(|| {
+                Problem::bad_request("unsupported-api-version")
+                    .with_detail(format!("Supported versions: {:?}", self.versions()))
+            })?;
+
+        let path_and_query = match request.uri().query() {
+            Some(query) => format!("{}/v{version}{rest}?{query}", self.prefix),
+            None => format!("{}/v{version}{rest}", self.prefix),
+        };
+        let mut parts = request.uri().clone().into_parts();
+        parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).map_err(|_| Problem::internal_error())?);
+        Ok(Some(Uri::from_parts(parts).map_err(|_| Problem::internal_error())?))
+    }
+}
+
+impl<S> Layer<S> for ApiVersionSelector {
+    type Service = ApiVersionSelectorMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ApiVersionSelectorMiddleware { inner, layer: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct ApiVersionSelectorMiddleware<S> {
+    inner: S,
+    layer: ApiVersionSelector,
+}
+
+impl<S> Service<Request<Body>> for ApiVersionSelectorMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        match self.layer.select(&request) {
+            Ok(Some(uri)) => *request.uri_mut() = uri,
+            Ok(None) => {}
+            Err(problem) => {
+                let problem_config = self.layer.problem_config.for_request(request.uri(), request.headers());
+                let response = ProblemResponse::new(&problem_config, problem).into_response();
+                return Box::pin(async move { Ok(response) });
+            }
+        }
+
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{http::StatusCode, Router};
+    use shine_test::test;
+    use tower::ServiceExt;
+    use utoipa_axum::routes;
+
+    #[utoipa::path(get, path = "/api/items", responses((status = OK)))]
+    async fn get_items_v1() -> &'static str {
+        "v1"
+    }
+
+    #[utoipa::path(get, path = "/api/items", responses((status = OK)))]
+    async fn get_items_v2() -> &'static str {
+        "v2"
+    }
+
+    async fn get(router: &Router, path: &str, version: Option<&str>) -> (Response, String) {
+        let mut request = Request::get(path);
+        if let Some(version) = version {
+            request = request.header(API_VERSION_HEADER, version);
+        }
+        let response = router
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (
+            Response::from_parts(parts, Body::empty()),
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[test]
+    async fn versioned_routes() {
+        let since = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let router = VersionedApi::<()>::new()
+            .deprecated_version(
+                1,
+                OpenApiRouter::new().routes(routes!(get_items_v1)),
+                DeprecatedLayer::new(since).with_sunset(since),
+            )
+            .version(2, OpenApiRouter::new().routes(routes!(get_items_v2)))
+            .with_default(1)
+            .into_router();
+        let (router, doc) = OpenApiRouter::new().nest("/feature", router).split_for_parts();
+
+        let selector = ApiVersionSelector::from_openapi("/feature", &doc, ProblemConfig::new(false));
+        let router = Router::new().fallback_service(selector.layer(router));
+
+        let (response, body) = get(&router, "/feature/api/items", None).await;
+        assert_eq!(body, "v1");
+        assert_eq!(response.headers()["deprecation"], "@1700000000");
+        assert_eq!(response.headers()["sunset"], "Tue, 14 Nov 2023 22:13:20 GMT");
+
+        let (response, body) = get(&router, "/feature/v2/api/items", None).await;
+        assert_eq!(body, "v2");
+        assert!(response.headers().get("deprecation").is_none());
+
+        let (_, body) = get(&router, "/feature/api/items", Some("2")).await;
+        assert_eq!(body, "v2");
+        let (_, body) = get(&router, "/feature/v1/api/items", Some("v2")).await;
+        assert_eq!(body, "v1");
+
+        let (response, _) = get(&router, "/feature/api/items", Some("3")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let (response, _) = get(&router, "/feature/info/ready", Some("3")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let (doc, version_docs) = split_versioned_openapi("/feature", doc);
+        assert_eq!(doc.paths.paths.len(), 1);
+        assert!(doc.paths.paths.contains_key("/feature/api/items"));
+        assert_eq!(version_docs.len(), 2);
+        assert_eq!(version_docs[0].0, 1);
+        let v1_item = &version_docs[0].1.paths.paths["/feature/v1/api/items"];
+        assert!(matches!(
+            v1_item.get.as_ref().unwrap().deprecated,
+            Some(Deprecated::True)
+        ));
+        assert!(version_docs[1].1.paths.paths.contains_key("/feature/v2/api/items"));
+    }
+}
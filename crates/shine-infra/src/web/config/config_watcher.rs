@@ -0,0 +1,215 @@
+use crate::web::{FeatureConfig, WebAppConfig};
+use anyhow::Error as AnyError;
+use serde::de::DeserializeOwned;
+use std::{
+    fmt::Debug,
+    fs,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{sync::watch, task::JoinHandle, time::MissedTickBehavior};
+use validator::Validate;
+
+const DEFAULT_WATCH_INTERVAL: u64 = 5;
+
+type ConfigValidator<F> = Box<dyn Fn(&WebAppConfig<F>) -> Result<(), AnyError> + Send + Sync>;
+
+/// Keep the configuration up to date. The file layers are watched for changes and the remote layers are refreshed
+/// periodically. A reload that fails to load or deserialize is rejected and the previous configuration is kept.
+///
+/// The subscribers receive only the part of the configuration they are interested in and they are notified only
+/// if that part has changed.
+pub struct ConfigWatcher<F>
+where
+    F: FeatureConfig,
+{
+    sender: watch::Sender<Arc<WebAppConfig<F>>>,
+    validators: RwLock<Vec<ConfigValidator<F>>>,
+}
+
+impl<F> ConfigWatcher<F>
+where
+    F: FeatureConfig + DeserializeOwned + Validate + Debug + Send + Sync + 'static,
+{
+    pub fn new(config: WebAppConfig<F>) -> Self {
+        let (sender, _) = watch::channel(Arc::new(config));
+        Self {
+            sender,
+            validators: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn current(&self) -> Arc<WebAppConfig<F>> {
+        self.sender.borrow().clone()
+    }
+
+    /// Add a check for the reloaded configurations. The configuration is rejected if any of the checks fails.
+    pub fn add_validator<V>(&self, validator: V)
+    where
+        V: Fn(&WebAppConfig<F>) -> Result<(), AnyError> + Send + Sync + 'static,
+    {
+        self.validators.write().unwrap().push(Box::new(validator));
+    }
+
+    /// Reload all the layers. On failure the current configuration is kept.
+    pub async fn reload(&self) -> Result<(), AnyError> {
+        let core = self.current().core.clone();
+        let config = WebAppConfig::<F>::load_layers(&core).await?;
+        for validator in self.validators.read().unwrap().iter() {
+            validator(&config)?;
+        }
+        self.sender.send_replace(Arc::new(config));
+        log::info!("Configuration reloaded");
+        Ok(())
+    }
+
+    /// Subscribe to a part of the configuration. The receiver is notified when the selected value changes.
+    pub fn subscribe<T, M>(&self, select: M) -> watch::Receiver<T>
+    where
+        T: PartialEq + Send + Sync + 'static,
+        M: Fn(&WebAppConfig<F>) -> T + Send + 'static,
+    {
+        let (sender, receiver) = watch::channel(select(&self.current()));
+        let mut source = self.sender.subscribe();
+        tokio::spawn(async move {
+            while source.changed().await.is_ok() {
+                let value = select(&source.borrow_and_update());
+                sender.send_if_modified(|current| {
+                    if *current != value {
+                        *current = value;
+                        true
+                    } else {
+                        false
+                    }
+                });
+                if sender.is_closed() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Start watching the configuration as set in the `configReload` section. Return None if reload is disabled.
+    pub fn start(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let config = self.current();
+        let reload_config = config.service.config_reload.as_ref()?;
+        let watch_interval = Duration::from_secs(reload_config.watch_interval.unwrap_or(DEFAULT_WATCH_INTERVAL).max(1));
+        let refresh_interval = reload_config
+            .refresh_interval
+            .filter(|_| config.core.has_remote_layers())
+            .map(Duration::from_secs);
+
+        let files = config.core.file_layers();
+        log::info!("Watching configuration files: {files:?}");
+
+        let watcher = self.clone();
+        Some(tokio::spawn(async move {
+            let mut modified = modification_times(&files);
+            let mut last_refresh = Instant::now();
+            let mut interval = tokio::time::interval(watch_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let current = modification_times(&files);
+                let files_changed = current != modified;
+                modified = current;
+                let refresh = refresh_interval.is_some_and(|refresh| last_refresh.elapsed() >= refresh);
+
+                if files_changed || refresh {
+                    last_refresh = Instant::now();
+                    if let Err(err) = watcher.reload().await {
+                        log::error!("Configuration reload rejected, keeping the previous configuration: {err:#}");
+                    }
+                }
+            }
+        }))
+    }
+}
+
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use shine_test::test;
+
+    #[derive(Debug, Deserialize, JsonSchema, Validate)]
+    #[serde(rename_all = "camelCase")]
+    struct Feature {
+        greeting: String,
+    }
+
+    impl FeatureConfig for Feature {
+        const NAME: &'static str = "feature";
+    }
+
+    fn config_file(greeting: &str) -> String {
+        serde_json::json!({
+            "beforeLayers": [],
+            "afterLayers": [],
+            "service": {
+                "port": 80,
+                "allowedOrigins": [],
+                "fullProblemResponse": false,
+                "captchaSecret": "captcha",
+                "sessionSecret": "session",
+                "sessionRedisCns": "redis://localhost",
+                "sessionTtl": 60,
+                "configReload": { "watchInterval": 1 }
+            },
+            "telemetry": {
+                "enableConsoleLog": true,
+                "allowReconfigure": false,
+                "metrics": { "type": "none" },
+                "tracing": { "type": "none" }
+            },
+            "feature": { "greeting": greeting }
+        })
+        .to_string()
+    }
+
+    #[test]
+    async fn reload_changed_file() {
+        let dir = std::env::temp_dir().join(format!("shine-config-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let root_file = dir.join("server_config.test.json");
+        fs::write(&root_file, config_file("Hello")).unwrap();
+
+        let config = WebAppConfig::<Feature>::load("test", Some(root_file.clone()))
+            .await
+            .unwrap();
+        let watcher = Arc::new(ConfigWatcher::new(config));
+        let mut greeting = watcher.subscribe(|config| config.feature.greeting.clone());
+        let session_ttl = watcher.subscribe(|config| config.service.session_ttl);
+        let task = watcher.start().unwrap();
+
+        fs::write(&root_file, config_file("Hi")).unwrap();
+        // make sure the change is detected even with a coarse timestamp resolution
+        let file = fs::File::options().write(true).open(&root_file).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), greeting.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*greeting.borrow_and_update(), "Hi");
+        assert_eq!(watcher.current().feature.greeting, "Hi");
+        // the unchanged parts are not notified
+        assert!(!session_ttl.has_changed().unwrap());
+
+        task.abort();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
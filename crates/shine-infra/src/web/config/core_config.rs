@@ -1,4 +1,8 @@
-use crate::{azure::azure_keyvault_config::AzureKeyvaultConfigSource, web::Environment};
+use crate::{
+    azure::azure_keyvault_config::AzureKeyvaultConfigSource,
+    secrets::{DirectorySecretSource, EncryptedFileSecretSource, SecretConfigSource, VaultSecretSource},
+    web::{extracts::ValidationErrorEx, Environment},
+};
 use azure_core::credentials::TokenCredential;
 use azure_identity::{AzureCliCredential, ClientSecretCredential};
 use config::{builder::AsyncState, Config, ConfigBuilder, ConfigError, File};
@@ -8,16 +12,42 @@ use std::{
     path::{Path, PathBuf},
     sync::Arc,
 };
+use validator::{Validate, ValidationError};
 
 pub const DEFAULT_VERSION_CONFIG_FILE: &str = "server_version.json";
 
+/// The layers stored on the local file system.
+const LOCAL_SCHEMAS: [&str; 4] = ["file://", "file?://", "dir://", "enc://"];
+/// All the supported layer schemas.
+const LAYER_SCHEMAS: [&str; 7] = ["file", "file?", "dir", "enc", "azk", "vault", "vault+http"];
+
+fn validate_layers(layers: &[String]) -> Result<(), ValidationError> {
+    for layer in layers {
+        if layer == "environment" {
+            continue;
+        }
+        match layer.split_once("://") {
+            Some((schema, path)) if LAYER_SCHEMAS.contains(&schema) && !path.is_empty() => {}
+            _ => {
+                return Err(ValidationError::new("layer")
+                    .with_message("Unsupported or incomplete configuration layer".into())
+                    .with_param("layer", layer))
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Partial configuration required for early setup.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct CoreConfig {
+    #[validate(length(min = 1))]
     pub stage: String,
     pub version: String,
+    #[validate(custom(function = "validate_layers"))]
     pub before_layers: Vec<String>,
+    #[validate(custom(function = "validate_layers"))]
     pub after_layers: Vec<String>,
     pub root_file: String,
 }
@@ -142,7 +172,40 @@ impl CoreConfig {
                     let azure_credentials = azure_credentials.clone().unwrap();
                     let keyvault_url = format!("https://{path}");
                     let keyvault = AzureKeyvaultConfigSource::new(azure_credentials.clone(), &keyvault_url)?;
-                    builder = builder.add_async_source(keyvault);
+                    builder = builder.add_async_source(SecretConfigSource::new(keyvault));
+                }
+                Layer::Config("dir", url, path) => {
+                    let path = path.ok_or(ConfigError::FileParse {
+                        uri: Some(url.to_owned()),
+                        cause: "Missing secret directory".into(),
+                    })?;
+                    let secrets = DirectorySecretSource::new(path);
+                    builder = builder.add_async_source(SecretConfigSource::new(secrets));
+                }
+                Layer::Config("enc", url, path) => {
+                    let path = path.ok_or(ConfigError::FileParse {
+                        uri: Some(url.to_owned()),
+                        cause: "Missing file path".into(),
+                    })?;
+                    let secrets = EncryptedFileSecretSource::from_env(path)?;
+                    builder = builder.add_async_source(SecretConfigSource::new(secrets));
+                }
+                Layer::Config(schema @ ("vault" | "vault+http"), url, path) => {
+                    let path = path.ok_or(ConfigError::FileParse {
+                        uri: Some(url.to_owned()),
+                        cause: "Missing vault location".into(),
+                    })?;
+                    let (host, location) = path.split_once('/').ok_or(ConfigError::FileParse {
+                        uri: Some(url.to_owned()),
+                        cause: "Missing vault secret path".into(),
+                    })?;
+                    let address = if schema == "vault" {
+                        format!("https://{host}")
+                    } else {
+                        format!("http://{host}")
+                    };
+                    let secrets = VaultSecretSource::from_env(&address, location)?;
+                    builder = builder.add_async_source(SecretConfigSource::new(secrets));
                 }
                 Layer::Config(schema, url, _) => {
                     return Err(ConfigError::FileParse {
@@ -161,4 +224,68 @@ impl CoreConfig {
 
         Ok(builder)
     }
+
+    fn layers(&self) -> impl Iterator<Item = &str> {
+        self.before_layers
+            .iter()
+            .chain(self.after_layers.iter())
+            .map(|layer| layer.as_str())
+    }
+
+    /// The local files of the configuration including the root, the optional files and the local secret stores.
+    pub fn file_layers(&self) -> Vec<PathBuf> {
+        let files = self.layers().filter_map(|layer| {
+            LOCAL_SCHEMAS
+                .iter()
+                .find_map(|schema| layer.strip_prefix(schema))
+                .map(PathBuf::from)
+        });
+        std::iter::once(PathBuf::from(&self.root_file)).chain(files).collect()
+    }
+
+    /// Indicates if some of the layers are loaded from a remote source (ex. key vault).
+    pub fn has_remote_layers(&self) -> bool {
+        self.layers()
+            .any(|layer| layer != "environment" && !LOCAL_SCHEMAS.iter().any(|schema| layer.starts_with(schema)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    #[test]
+    fn layers() {
+        let mut config = CoreConfig {
+            stage: "test".to_string(),
+            version: "1".to_string(),
+            before_layers: vec!["file://base.json".to_string(), "azk://vault".to_string()],
+            after_layers: vec![
+                "environment".to_string(),
+                "file?://local.json".to_string(),
+                "dir:///run/secrets".to_string(),
+            ],
+            root_file: "server_config.test.json".to_string(),
+        };
+
+        assert_eq!(
+            config.file_layers(),
+            vec![
+                PathBuf::from("server_config.test.json"),
+                PathBuf::from("base.json"),
+                PathBuf::from("local.json"),
+                PathBuf::from("/run/secrets")
+            ]
+        );
+        assert!(config.has_remote_layers());
+
+        assert!(config.validate().is_ok());
+
+        config.before_layers.pop();
+        assert!(!config.has_remote_layers());
+
+        config.after_layers.push("ftp://remote.json".to_string());
+        assert!(config.validate().is_err());
+    }
 }
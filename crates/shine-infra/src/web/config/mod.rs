@@ -2,3 +2,5 @@ mod core_config;
 pub use self::core_config::*;
 mod environment_config;
 pub use self::environment_config::*;
+mod config_watcher;
+pub use self::config_watcher::*;
@@ -0,0 +1,109 @@
+use crate::{
+    language::{Language, MessageCatalog},
+    web::responses::ProblemConfig,
+};
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, Uri},
+};
+use std::convert::Infallible;
+
+/// The language of the response negotiated against the supported languages of the message catalog.
+/// The explicit `lang` query parameter takes precedence over the `Accept-Language` header, if none of them
+/// is supported, the default language of the catalog is used. The requested tag is kept (ex. `hu-HU`), a language is
+/// supported if any of its fallbacks is supported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AcceptLanguage(pub Language);
+
+impl AcceptLanguage {
+    pub fn negotiate(catalog: &MessageCatalog, uri: &Uri, headers: &HeaderMap) -> Self {
+        let query_language = uri.query().and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "lang")
+                .and_then(|(_, value)| Language::from_tag(&value))
+                .filter(|lang| catalog.is_supported(lang))
+        });
+
+        let language = query_language.or_else(|| {
+            headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| Self::parse_header(value).find(|lang| catalog.is_supported(lang)))
+        });
+
+        Self(language.unwrap_or_else(|| catalog.default_language().clone()))
+    }
+
+    /// Parse the valid language tags of an `Accept-Language` header in the order of preference.
+    pub fn parse_header(value: &str) -> impl Iterator<Item = Language> {
+        let mut languages = value
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let language = Language::from_tag(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+                (quality > 0.0).then_some((language, quality))
+            })
+            .collect::<Vec<_>>();
+        // stable sort, the order of the header is kept for equal quality
+        languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+        languages.into_iter().map(|(language, _)| language)
+    }
+}
+
+impl<S> FromRequestParts<S> for AcceptLanguage
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(language) = parts.extensions.get::<AcceptLanguage>() {
+            return Ok(language.clone());
+        }
+
+        let language = match parts.extensions.get::<ProblemConfig>() {
+            Some(problem_config) => Self::negotiate(problem_config.catalog(), &parts.uri, &parts.headers),
+            None => Self::negotiate(&MessageCatalog::core(), &parts.uri, &parts.headers),
+        };
+        Ok(language)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+    use shine_test::test;
+
+    #[test]
+    fn negotiate_language() {
+        let catalog = MessageCatalog::core();
+        let negotiate = |uri: &str, accept: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(accept) = accept {
+                headers.insert(header::ACCEPT_LANGUAGE, HeaderValue::from_str(accept).unwrap());
+            }
+            AcceptLanguage::negotiate(&catalog, &uri.parse().unwrap(), &headers).0
+        };
+
+        let hu_hu = Language::parse("hu-HU").unwrap();
+        assert_eq!(negotiate("/api", None), Language::EN);
+        assert_eq!(negotiate("/api", Some("hu-HU,hu;q=0.9,en;q=0.8")), hu_hu);
+        assert_eq!(negotiate("/api", Some("de-DE, en;q=0.5, hu;q=0.7")), Language::HU);
+        assert_eq!(negotiate("/api", Some("hu;q=0, fr")), Language::EN);
+        assert_eq!(negotiate("/api?lang=hu", Some("en")), Language::HU);
+        assert_eq!(negotiate("/api?lang=hu-hu", Some("en")), hu_hu);
+        assert_eq!(negotiate("/api?lang=de", Some("hu")), Language::HU);
+
+        let english_only = MessageCatalog::new(Language::EN);
+        let headers = HeaderMap::from_iter([(header::ACCEPT_LANGUAGE, HeaderValue::from_static("hu"))]);
+        assert_eq!(
+            AcceptLanguage::negotiate(&english_only, &"/api".parse().unwrap(), &headers).0,
+            Language::EN
+        );
+    }
+}
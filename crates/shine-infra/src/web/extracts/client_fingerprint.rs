@@ -1,11 +1,23 @@
-use crate::web::responses::{ErrorResponse, Problem, ProblemConfig};
-use axum::{extract::FromRequestParts, http::request::Parts, Extension, RequestPartsExt};
-use axum_extra::{headers::UserAgent, TypedHeader};
+use crate::web::{
+    extracts::{ClientIp, ClientIpResolver},
+    responses::Problem,
+    FingerprintConfig,
+};
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+    Extension,
+};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
+use ipnet::IpNet;
 use reqwest::StatusCode;
 use ring::digest::{self, Context};
+use std::{convert::Infallible, fmt, net::IpAddr, sync::Arc};
 use thiserror::Error as ThisError;
 
+const FINGERPRINT_VERSION: &str = "fp2";
+const DEVICE_ID_HEADER: &str = "x-device-id";
+
 #[derive(Debug, ThisError)]
 pub enum ClientFingerprintError {
     #[error("Missing user agent")]
@@ -20,62 +32,488 @@ impl From<ClientFingerprintError> for Problem {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Strategy to create and compare the fingerprints of the clients.
+pub trait FingerprintStrategy: Send + Sync {
+    /// Create the fingerprint of a request.
+    fn fingerprint(&self, headers: &HeaderMap, client_ip: &ClientIp) -> String;
+
+    /// The similarity of a bound and a current fingerprint in the [0,1] range.
+    fn similarity(&self, bound: &str, current: &str) -> f32;
+
+    /// The minimum similarity to accept the client as the owner of the bound fingerprint.
+    fn min_similarity(&self) -> f32;
+}
+
+/// The hashed components of a fingerprint.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Components {
+    agent: Option<String>,
+    platform: Option<String>,
+    language: Option<String>,
+    device: Option<String>,
+    network: Option<String>,
+}
+
+impl Components {
+    fn from_request(headers: &HeaderMap, client_ip: &ClientIp) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        // Versions are ignored to survive the browser updates
+        let agent = match header("sec-ch-ua") {
+            Some(brands) => Some(client_hint_brands(brands)),
+            None => header(header::USER_AGENT.as_str())
+                .map(|agent| agent.chars().filter(|c| !c.is_ascii_digit()).collect::<String>()),
+        };
+        let platform = match (header("sec-ch-ua-platform"), header("sec-ch-ua-mobile")) {
+            (None, None) => None,
+            (platform, mobile) => Some(format!(
+                "{}|{}",
+                platform.unwrap_or_default(),
+                mobile.unwrap_or_default()
+            )),
+        };
+        let language = header(header::ACCEPT_LANGUAGE.as_str()).map(language_tags);
+        let device = header(DEVICE_ID_HEADER).map(str::to_string);
+        let network = client_ip.ip.map(network_prefix);
+
+        Self {
+            agent: agent.as_deref().map(hash_component),
+            platform: platform.as_deref().map(hash_component),
+            language: language.as_deref().map(hash_component),
+            device: device.as_deref().map(hash_component),
+            network: network.as_deref().map(hash_component),
+        }
+    }
+
+    fn parse(fingerprint: &str) -> Option<Self> {
+        let mut parts = fingerprint.split('.');
+        if parts.next()? != FINGERPRINT_VERSION {
+            return None;
+        }
+        let mut next = || {
+            parts
+                .next()
+                .map(|part| Some(part.to_string()).filter(|part| !part.is_empty()))
+        };
+        let components = Self {
+            agent: next()?,
+            platform: next()?,
+            language: next()?,
+            device: next()?,
+            network: next()?,
+        };
+        next().is_none().then_some(components)
+    }
+
+    fn format(&self) -> String {
+        [&self.agent, &self.platform, &self.language, &self.device, &self.network]
+            .iter()
+            .fold(FINGERPRINT_VERSION.to_string(), |mut fingerprint, component| {
+                fingerprint.push('.');
+                fingerprint.push_str(component.as_deref().unwrap_or_default());
+                fingerprint
+            })
+    }
+}
+
+/// The brand names of the `Sec-CH-UA` client hint without the versions and the GREASE brands,
+/// ex. `"Chromium";v="128", "Not;A=Brand";v="24"`.
+fn client_hint_brands(brands: &str) -> String {
+    let mut brands = brands
+        .split(',')
+        .filter_map(|brand| Some(brand.trim().strip_prefix('"')?.split_once('"')?.0))
+        .filter(|brand| !brand.is_empty() && !brand.contains("Brand"))
+        .collect::<Vec<_>>();
+    brands.sort_unstable();
+    brands.join("|")
+}
+
+/// The language tags of the Accept-Language header without the weights.
+fn language_tags(accept_language: &str) -> String {
+    accept_language
+        .split(',')
+        .filter_map(|tag| tag.split(';').next())
+        .map(|tag| tag.trim().to_ascii_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// The network of the address, a /24 prefix for IPv4 and a /48 prefix for IPv6 addresses.
+fn network_prefix(ip: IpAddr) -> String {
+    let prefix = if ip.is_ipv4() { 24 } else { 48 };
+    IpNet::new(ip, prefix)
+        .map(|network| network.trunc().to_string())
+        .unwrap_or_else(|_| ip.to_string())
+}
+
+fn hash_component(value: &str) -> String {
+    let mut context = Context::new(&digest::SHA256);
+    context.update(value.as_bytes());
+    B64.encode(&context.finish().as_ref()[..12])
+}
+
+/// Fingerprint strategy comparing the weighted components of the fingerprints. A differing device id is
+/// considered a compromise regardless of the other components. The fingerprints of other formats have to match exactly.
+pub struct WeightedFingerprint {
+    min_similarity: f32,
+    agent: f32,
+    platform: f32,
+    language: f32,
+    device: f32,
+    network: f32,
+}
+
+impl WeightedFingerprint {
+    pub fn new(config: &FingerprintConfig) -> Self {
+        let weights = &config.weights;
+        Self {
+            min_similarity: config.min_similarity.unwrap_or(0.8),
+            agent: weights.agent.unwrap_or(0.5),
+            platform: weights.platform.unwrap_or(0.2),
+            language: weights.language.unwrap_or(0.1),
+            device: weights.device.unwrap_or(0.5),
+            network: weights.network.unwrap_or(0.15),
+        }
+    }
+
+    pub fn into_layer(self) -> Extension<Arc<dyn FingerprintStrategy>> {
+        Extension(Arc::new(self))
+    }
+}
+
+impl Default for WeightedFingerprint {
+    fn default() -> Self {
+        Self::new(&FingerprintConfig::default())
+    }
+}
+
+impl FingerprintStrategy for WeightedFingerprint {
+    fn fingerprint(&self, headers: &HeaderMap, client_ip: &ClientIp) -> String {
+        Components::from_request(headers, client_ip).format()
+    }
+
+    fn similarity(&self, bound: &str, current: &str) -> f32 {
+        let (Some(bound), Some(current)) = (Components::parse(bound), Components::parse(current)) else {
+            return if bound == current { 1.0 } else { 0.0 };
+        };
+
+        if bound.device.is_some() && current.device.is_some() && bound.device != current.device {
+            return 0.0;
+        }
+
+        let (matching, total) = [
+            (self.agent, &bound.agent, &current.agent),
+            (self.platform, &bound.platform, &current.platform),
+            (self.language, &bound.language, &current.language),
+            (self.device, &bound.device, &current.device),
+            (self.network, &bound.network, &current.network),
+        ]
+        .into_iter()
+        .filter(|(_, bound, current)| bound.is_some() || current.is_some())
+        .fold((0.0, 0.0), |(matching, total), (weight, bound, current)| {
+            let matching = if bound == current { matching + weight } else { matching };
+            (matching, total + weight)
+        });
+
+        if total > 0.0 {
+            matching / total
+        } else {
+            1.0
+        }
+    }
+
+    fn min_similarity(&self) -> f32 {
+        self.min_similarity
+    }
+}
+
+/// The fingerprint of the sessions and tokens bound before the versioned fingerprints: the hash of the user agent.
+fn hash_agent(agent: &str) -> String {
+    let mut context = Context::new(&digest::SHA256);
+    context.update(agent.as_bytes());
+    B64.encode(context.finish().as_ref())
+}
+
+fn is_legacy(fingerprint: &str) -> bool {
+    fingerprint.len() == 43 && !fingerprint.contains('.')
+}
+
 /// Some fingerprinting of the client site to detect token stealing.
-pub struct ClientFingerprint(String);
+#[derive(Clone)]
+pub struct ClientFingerprint {
+    value: String,
+    legacy: Option<String>,
+    strategy: Arc<dyn FingerprintStrategy>,
+}
 
 impl ClientFingerprint {
+    pub fn new(strategy: Arc<dyn FingerprintStrategy>, headers: &HeaderMap, client_ip: &ClientIp) -> Self {
+        let value = strategy.fingerprint(headers, client_ip);
+        let legacy = headers
+            .get(header::USER_AGENT)
+            .and_then(|agent| agent.to_str().ok())
+            .filter(|agent| !agent.is_empty())
+            .map(hash_agent);
+        Self { value, legacy, strategy }
+    }
+
     pub fn unknown() -> Self {
-        Self("unknown".to_string())
+        Self {
+            value: "unknown".to_string(),
+            legacy: None,
+            strategy: Arc::new(WeightedFingerprint::default()),
+        }
     }
 
+    /// Create a fingerprint from the hash of the user agent only.
     pub fn from_agent(agent: String) -> Result<Self, ClientFingerprintError> {
         if agent.is_empty() {
             Err(ClientFingerprintError::MissingUserAgent)
         } else {
-            let mut context = Context::new(&digest::SHA256);
-            context.update(agent.as_bytes());
-            let hash = B64.encode(context.finish().as_ref());
-            Ok(Self(hash))
+            let hash = hash_agent(&agent);
+            Ok(Self {
+                value: hash.clone(),
+                legacy: Some(hash),
+                strategy: Arc::new(WeightedFingerprint::default()),
+            })
         }
     }
 
+    /// The similarity of the client to a (previously) bound fingerprint. The legacy fingerprints are
+    /// compared to the hash of the user agent of the client.
+    pub fn similarity(&self, bound: &str) -> f32 {
+        if bound == self.value {
+            1.0
+        } else if is_legacy(bound) {
+            if self.legacy.as_deref() == Some(bound) {
+                1.0
+            } else {
+                0.0
+            }
+        } else {
+            self.strategy.similarity(bound, &self.value)
+        }
+    }
+
+    /// Check if a matching bound fingerprint is of a legacy format and should be replaced by the current one.
+    pub fn is_upgrade_of(&self, bound: &str) -> bool {
+        bound != self.value && is_legacy(bound)
+    }
+
+    /// Check if the client can be the owner of the bound fingerprint.
+    pub fn matches(&self, bound: &str) -> bool {
+        let similarity = self.similarity(bound);
+        let is_match = similarity >= self.strategy.min_similarity();
+        if is_match && similarity < 1.0 {
+            log::info!("Client fingerprint drift accepted, similarity: {similarity:.2}");
+        }
+        is_match
+    }
+
     pub fn as_str(&self) -> &str {
-        &self.0
+        &self.value
     }
 
     pub fn into_string(self) -> String {
-        self.0
+        self.value
     }
 
     #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
-        self.0.clone()
+        self.value.clone()
+    }
+}
+
+impl fmt::Debug for ClientFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ClientFingerprint").field(&self.value).finish()
     }
 }
 
+impl PartialEq for ClientFingerprint {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for ClientFingerprint {}
+
 impl<S> FromRequestParts<S> for ClientFingerprint
 where
     S: Send + Sync,
 {
-    type Rejection = ErrorResponse<ClientFingerprintError>;
+    type Rejection = Infallible;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let Extension(problem_config) = parts
-            .extract::<Extension<ProblemConfig>>()
-            .await
-            .expect("Missing ProblemConfig extension");
+        let strategy = parts
+            .extensions
+            .get::<Arc<dyn FingerprintStrategy>>()
+            .cloned()
+            .unwrap_or_else(|| Arc::new(WeightedFingerprint::default()));
+        let client_ip = ClientIpResolver::from_request(&parts.extensions, &parts.headers);
+
+        Ok(ClientFingerprint::new(strategy, &parts.headers, &client_ip))
+    }
+}
 
-        let agent = parts
-            .extract::<TypedHeader<UserAgent>>()
-            .await
-            .map(|u| u.to_string())
-            .unwrap_or_default();
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+    use shine_test::test;
 
-        if agent.is_empty() {
-            Ok(ClientFingerprint::unknown())
-        } else {
-            ClientFingerprint::from_agent(agent).map_err(|err| ErrorResponse::new(&problem_config, err))
-        }
+    const CHROME_128: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.36";
+    const CHROME_129: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36";
+    const FIREFOX: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:130.0) Gecko/20100101 Firefox/130.0";
+
+    fn fingerprint(ip: &str, headers: &[(&'static str, &str)]) -> ClientFingerprint {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_str(value).unwrap()))
+            .collect::<HeaderMap>();
+        let client_ip = ClientIp {
+            ip: Some(ip.parse().unwrap()),
+            ..Default::default()
+        };
+        ClientFingerprint::new(Arc::new(WeightedFingerprint::default()), &headers, &client_ip)
+    }
+
+    #[test]
+    fn format_fingerprint() {
+        let fp = fingerprint("1.2.3.4", &[("user-agent", CHROME_128), ("x-device-id", "device")]);
+        let components = Components::parse(fp.as_str()).unwrap();
+        assert!(components.agent.is_some());
+        assert!(components.platform.is_none());
+        assert!(components.language.is_none());
+        assert!(components.device.is_some());
+        assert!(components.network.is_some());
+        assert_eq!(components.format(), fp.as_str());
+
+        assert_eq!(Components::parse("fp2...."), None);
+        assert_eq!(Components::parse("fp2......"), None);
+        assert_eq!(Components::parse("fp2....."), Some(Components::default()));
+        assert_eq!(Components::parse("unknown"), None);
+    }
+
+    #[test]
+    fn tolerate_benign_drift() {
+        let bound = fingerprint(
+            "1.2.3.4",
+            &[
+                ("user-agent", CHROME_128),
+                ("sec-ch-ua", "\"Chromium\";v=\"128\", \"Not;A=Brand\";v=\"24\""),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("accept-language", "en-US,en;q=0.9"),
+            ],
+        );
+
+        // browser update
+        let current = fingerprint(
+            "1.2.3.4",
+            &[
+                ("user-agent", CHROME_129),
+                ("sec-ch-ua", "\"Chromium\";v=\"129\", \"Not=A?Brand\";v=\"8\""),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("accept-language", "en-US,en;q=0.8"),
+            ],
+        );
+        assert_eq!(current.similarity(bound.as_str()), 1.0);
+
+        // new network
+        let current = fingerprint(
+            "1.2.4.5",
+            &[
+                ("sec-ch-ua", "\"Chromium\";v=\"129\""),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("accept-language", "en-US,en;q=0.7"),
+            ],
+        );
+        assert!(current.matches(bound.as_str()));
+
+        // an added language on the same network
+        let current = fingerprint(
+            "1.2.3.5",
+            &[
+                ("sec-ch-ua", "\"Chromium\";v=\"129\""),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("accept-language", "en-US,en;q=0.9,hu;q=0.5"),
+            ],
+        );
+        assert!(current.matches(bound.as_str()));
+
+        // user agent only, the version is ignored
+        let bound = fingerprint("1.2.3.4", &[("user-agent", CHROME_128)]);
+        assert!(fingerprint("1.2.3.4", &[("user-agent", CHROME_129)]).matches(bound.as_str()));
+    }
+
+    #[test]
+    fn detect_compromise() {
+        let bound = fingerprint(
+            "1.2.3.4",
+            &[
+                ("user-agent", CHROME_128),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("x-device-id", "device-1"),
+            ],
+        );
+
+        // different browser
+        let current = fingerprint(
+            "1.2.3.4",
+            &[
+                ("user-agent", FIREFOX),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("x-device-id", "device-1"),
+            ],
+        );
+        assert!(!current.matches(bound.as_str()));
+
+        // different device
+        let current = fingerprint(
+            "1.2.3.4",
+            &[
+                ("user-agent", CHROME_128),
+                ("sec-ch-ua-platform", "\"Windows\""),
+                ("x-device-id", "device-2"),
+            ],
+        );
+        assert_eq!(current.similarity(bound.as_str()), 0.0);
+
+        // new network, an other language and no client hints
+        let current = fingerprint("5.6.7.8", &[("user-agent", CHROME_128), ("accept-language", "hu")]);
+        assert!(!current.matches(bound.as_str()));
+
+        // replayed from an other network matching only the user agent
+        let bound = fingerprint("1.2.3.4", &[("user-agent", CHROME_128)]);
+        assert!(!fingerprint("5.6.7.8", &[("user-agent", CHROME_129)]).matches(bound.as_str()));
+        assert!(!fingerprint("5.6.7.8", &[("user-agent", CHROME_128)]).matches(bound.as_str()));
+
+        // legacy fingerprint of another browser
+        let legacy = ClientFingerprint::from_agent(CHROME_128.to_string()).unwrap();
+        assert!(!fingerprint("1.2.3.4", &[("user-agent", CHROME_129)]).matches(legacy.as_str()));
+        assert!(!fingerprint("1.2.3.4", &[("sec-ch-ua", "\"Chromium\";v=\"128\"")]).matches(legacy.as_str()));
+    }
+
+    #[test]
+    fn upgrade_legacy_fingerprint() {
+        let legacy = ClientFingerprint::from_agent(CHROME_128.to_string()).unwrap();
+        assert!(legacy.matches(legacy.as_str()));
+        assert!(!legacy.is_upgrade_of(legacy.as_str()));
+
+        let current = fingerprint("1.2.3.4", &[("user-agent", CHROME_128), ("x-device-id", "device")]);
+        assert_eq!(current.similarity(legacy.as_str()), 1.0);
+        assert!(current.matches(legacy.as_str()));
+        assert!(current.is_upgrade_of(legacy.as_str()));
+
+        assert!(!current.is_upgrade_of(current.as_str()));
+        let bound = fingerprint("1.2.3.4", &[("user-agent", CHROME_129)]);
+        assert!(!current.is_upgrade_of(bound.as_str()));
     }
 }
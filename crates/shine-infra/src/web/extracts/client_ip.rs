@@ -0,0 +1,435 @@
+use crate::web::ClientIpConfig;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, Extensions, HeaderMap},
+    Extension,
+};
+use ipnet::IpNet;
+use maxminddb::{geoip2, Reader as GeoIpReader};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+};
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+pub enum ClientIpError {
+    #[error("Invalid trusted proxy range {0}")]
+    InvalidProxyRange(String),
+    #[error("Failed to open the GeoIP database {0}: {1}")]
+    GeoIpDatabase(String, String),
+}
+
+/// The location of the client.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GeoLocation {
+    pub country: Option<String>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+}
+
+/// Resolve the address of the clients behind the trusted reverse proxies.
+pub struct ClientIpResolver {
+    trusted_proxies: Vec<IpNet>,
+    cloudflare_proxies: Vec<IpNet>,
+    geo_ip: Option<GeoIpReader<Vec<u8>>>,
+}
+
+impl ClientIpResolver {
+    pub fn new(config: &ClientIpConfig) -> Result<Self, ClientIpError> {
+        let parse_ranges = |ranges: &[String]| {
+            ranges
+                .iter()
+                .map(|range| {
+                    range
+                        .parse::<IpNet>()
+                        .map_err(|_| ClientIpError::InvalidProxyRange(range.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let trusted_proxies = parse_ranges(&config.trusted_proxies)?;
+        let cloudflare_proxies = parse_ranges(&config.cloudflare_proxies)?;
+
+        let geo_ip = match &config.geo_ip_database {
+            Some(path) => Some(
+                GeoIpReader::open_readfile(path)
+                    .map_err(|err| ClientIpError::GeoIpDatabase(path.clone(), err.to_string()))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            trusted_proxies,
+            cloudflare_proxies,
+            geo_ip,
+        })
+    }
+
+    pub fn into_layer(self) -> Extension<Arc<Self>> {
+        Extension(Arc::new(self))
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.trusted_proxies.iter().any(|range| range.contains(&ip))
+    }
+
+    pub fn is_cloudflare(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.cloudflare_proxies.iter().any(|range| range.contains(&ip))
+    }
+
+    /// Resolve the client of a request. If the peer is a trusted proxy, the client is the rightmost untrusted
+    /// address of the forwarding chain, taken from the Forwarded header if present, from the X-Forwarded-For header
+    /// otherwise. If this address (or the peer itself) is a Cloudflare proxy, the client is taken from the
+    /// CF-Connecting-IP header.
+    pub fn resolve(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> ClientIp {
+        let Some(peer) = peer.map(|ip| ip.to_canonical()) else {
+            return ClientIp::default();
+        };
+
+        let hop = if self.is_cloudflare(peer) {
+            peer
+        } else if self.is_trusted(peer) {
+            self.client_of_chain(forwarded_chain(headers)).unwrap_or(peer)
+        } else {
+            return ClientIp {
+                ip: Some(peer),
+                ..Default::default()
+            };
+        };
+
+        if self.is_cloudflare(hop) {
+            let connecting_ip = headers
+                .get("cf-connecting-ip")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<IpAddr>().ok())
+                .map(|ip| ip.to_canonical());
+            ClientIp {
+                ip: Some(connecting_ip.unwrap_or(hop)),
+                trusted_proxy: true,
+                cloudflare: true,
+            }
+        } else {
+            ClientIp {
+                ip: Some(hop),
+                trusted_proxy: true,
+                cloudflare: false,
+            }
+        }
+    }
+
+    fn client_of_chain(&self, chain: Vec<IpAddr>) -> Option<IpAddr> {
+        chain
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted(**ip))
+            .or(chain.first())
+            .copied()
+    }
+
+    /// Find the location of the address in the GeoIP database.
+    pub fn locate(&self, ip: IpAddr) -> Option<GeoLocation> {
+        let city = self.geo_ip.as_ref()?.lookup::<geoip2::City>(ip).ok()?;
+        let english_name = |names: Option<std::collections::BTreeMap<&str, &str>>| {
+            names.and_then(|names| names.get("en").map(|name| name.to_string()))
+        };
+
+        Some(GeoLocation {
+            country: city.country.and_then(|country| country.iso_code).map(str::to_string),
+            region: city
+                .subdivisions
+                .and_then(|subdivisions| subdivisions.into_iter().next())
+                .and_then(|subdivision| english_name(subdivision.names)),
+            city: city.city.and_then(|city| english_name(city.names)),
+        })
+    }
+
+    /// Resolve the client of the request using the resolver of the request extensions. Without a resolver
+    /// no proxy is trusted.
+    pub fn from_request(extensions: &Extensions, headers: &HeaderMap) -> ClientIp {
+        if let Some(client_ip) = extensions.get::<ClientIp>() {
+            return *client_ip;
+        }
+
+        let peer = extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        match extensions.get::<Arc<ClientIpResolver>>() {
+            Some(resolver) => resolver.resolve(peer, headers),
+            None => ClientIp {
+                ip: peer.map(|ip| ip.to_canonical()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The addresses of the forwarding chain. The `for` parameters of the RFC 7239 Forwarded header are used if the
+/// header is present, the X-Forwarded-For header otherwise. The obfuscated and unknown nodes are skipped.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<IpAddr> {
+    if headers.contains_key(header::FORWARDED) {
+        headers
+            .get_all(header::FORWARDED)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (name, value) = pair.split_once('=')?;
+                    name.trim().eq_ignore_ascii_case("for").then_some(value)
+                })
+            })
+            .filter_map(parse_forwarded_node)
+            .map(|ip| ip.to_canonical())
+            .collect()
+    } else {
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|node| node.trim().parse::<IpAddr>().ok())
+            .map(|ip| ip.to_canonical())
+            .collect()
+    }
+}
+
+/// Parse a node of the Forwarded header: `1.2.3.4`, `1.2.3.4:80`, `"[2001:db8::1]"` or `"[2001:db8::1]:80"`.
+fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    let node = node
+        .strip_prefix('"')
+        .and_then(|node| node.strip_suffix('"'))
+        .unwrap_or(node);
+
+    if let Some(node) = node.strip_prefix('[') {
+        let (ip, port) = node.split_once(']')?;
+        if !port.is_empty() && port.strip_prefix(':').is_none_or(|port| port.parse::<u16>().is_err()) {
+            return None;
+        }
+        ip.parse::<Ipv6Addr>().ok().map(IpAddr::V6)
+    } else {
+        node.parse::<Ipv4Addr>()
+            .ok()
+            .or_else(|| node.parse::<SocketAddrV4>().ok().map(|addr| *addr.ip()))
+            .map(IpAddr::V4)
+    }
+}
+
+/// The address of the client resolved through the trusted proxies.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClientIp {
+    /// The address of the client, it is not known if the connection info is not available.
+    pub ip: Option<IpAddr>,
+    /// Indicates if the request was received through a trusted proxy, thus the forwarded headers can be trusted.
+    pub trusted_proxy: bool,
+    /// Indicates if the request was received through a Cloudflare proxy, thus the Cloudflare headers
+    /// (CF-Connecting-IP, CF-IPCountry, ...) can be trusted.
+    pub cloudflare: bool,
+}
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let client_ip = ClientIpResolver::from_request(&parts.extensions, &parts.headers);
+        parts.extensions.insert(client_ip);
+        Ok(client_ip)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+    use shine_test::test;
+
+    fn resolve(resolver: &ClientIpResolver, peer: &str, headers: &[(&'static str, &str)]) -> ClientIp {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_str(value).unwrap()))
+            .collect::<HeaderMap>();
+        resolver.resolve(Some(peer.parse().unwrap()), &headers)
+    }
+
+    #[test]
+    fn resolve_client_ip() {
+        let config = ClientIpConfig {
+            trusted_proxies: vec!["10.0.0.0/8".to_string()],
+            ..Default::default()
+        };
+        let resolver = ClientIpResolver::new(&config).unwrap();
+        let ip = |peer: &str, headers: &[(&'static str, &str)]| {
+            let client = resolve(&resolver, peer, headers);
+            (client.ip.unwrap().to_string(), client.trusted_proxy)
+        };
+
+        assert_eq!(
+            ip("1.2.3.4", &[("x-forwarded-for", "5.6.7.8")]),
+            ("1.2.3.4".into(), false)
+        );
+        assert_eq!(
+            ip("10.0.0.1", &[("x-forwarded-for", "9.9.9.9, 5.6.7.8, 10.0.0.2")]),
+            ("5.6.7.8".into(), true)
+        );
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[("x-forwarded-for", "9.9.9.9"), ("x-forwarded-for", "5.6.7.8")]
+            ),
+            ("5.6.7.8".into(), true)
+        );
+        assert_eq!(
+            ip("10.0.0.1", &[("x-forwarded-for", "invalid")]),
+            ("10.0.0.1".into(), true)
+        );
+        // Cloudflare headers are accepted only from the Cloudflare proxies
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[("cf-connecting-ip", "1.1.1.1"), ("x-forwarded-for", "5.6.7.8")]
+            ),
+            ("5.6.7.8".into(), true)
+        );
+        assert_eq!(
+            ip("1.2.3.4", &[("cf-connecting-ip", "1.1.1.1")]),
+            ("1.2.3.4".into(), false)
+        );
+        assert_eq!(resolver.resolve(None, &HeaderMap::new()), ClientIp::default());
+    }
+
+    #[test]
+    fn resolve_forwarded_client_ip() {
+        let config = ClientIpConfig {
+            trusted_proxies: vec!["10.0.0.0/8".to_string(), "fd00::/8".to_string()],
+            ..Default::default()
+        };
+        let resolver = ClientIpResolver::new(&config).unwrap();
+        let ip = |peer: &str, headers: &[(&'static str, &str)]| {
+            let client = resolve(&resolver, peer, headers);
+            (client.ip.unwrap().to_string(), client.trusted_proxy)
+        };
+
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[("forwarded", "for=9.9.9.9, for=5.6.7.8;proto=https, for=10.0.0.2")]
+            ),
+            ("5.6.7.8".into(), true)
+        );
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[
+                    ("forwarded", "for=9.9.9.9"),
+                    ("forwarded", "proto=https;For=\"5.6.7.8:4711\"")
+                ]
+            ),
+            ("5.6.7.8".into(), true)
+        );
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[("forwarded", "for=\"[2001:db8:cafe::17]:4711\", for=\"[fd00::1]\"")]
+            ),
+            ("2001:db8:cafe::17".into(), true)
+        );
+        // the obfuscated and unknown nodes are skipped
+        assert_eq!(
+            ip("10.0.0.1", &[("forwarded", "for=5.6.7.8, for=_hidden, for=unknown")]),
+            ("5.6.7.8".into(), true)
+        );
+        // the Forwarded header takes precedence over the X-Forwarded-For header
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[("forwarded", "for=5.6.7.8"), ("x-forwarded-for", "9.9.9.9")]
+            ),
+            ("5.6.7.8".into(), true)
+        );
+        // the header is ignored if the peer is not a trusted proxy
+        assert_eq!(
+            ip("1.2.3.4", &[("forwarded", "for=5.6.7.8")]),
+            ("1.2.3.4".into(), false)
+        );
+        assert_eq!(
+            ip("10.0.0.1", &[("forwarded", "for=\"[2001:db8::1]:port\"")]),
+            ("10.0.0.1".into(), true)
+        );
+    }
+
+    #[test]
+    fn resolve_cloudflare_client_ip() {
+        let config = ClientIpConfig {
+            trusted_proxies: vec!["10.0.0.0/8".to_string()],
+            cloudflare_proxies: vec!["173.245.48.0/20".to_string()],
+            ..Default::default()
+        };
+        let resolver = ClientIpResolver::new(&config).unwrap();
+        let ip = |peer: &str, headers: &[(&'static str, &str)]| {
+            let client = resolve(&resolver, peer, headers);
+            (client.ip.unwrap().to_string(), client.cloudflare)
+        };
+
+        assert_eq!(
+            ip("173.245.48.1", &[("cf-connecting-ip", "1.1.1.1")]),
+            ("1.1.1.1".into(), true)
+        );
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[
+                    ("cf-connecting-ip", "1.1.1.1"),
+                    ("x-forwarded-for", "2.2.2.2, 173.245.48.1")
+                ]
+            ),
+            ("1.1.1.1".into(), true)
+        );
+        assert_eq!(
+            ip("173.245.48.1", &[("x-forwarded-for", "2.2.2.2")]),
+            ("173.245.48.1".into(), true)
+        );
+        // a Cloudflare address forged into the forwarding chain is not the last hop
+        assert_eq!(
+            ip(
+                "10.0.0.1",
+                &[
+                    ("cf-connecting-ip", "1.1.1.1"),
+                    ("x-forwarded-for", "173.245.48.1, 5.6.7.8")
+                ]
+            ),
+            ("5.6.7.8".into(), false)
+        );
+        // without the CF-Connecting-IP header the Cloudflare proxy is the client
+        assert_eq!(
+            ip("10.0.0.1", &[("x-forwarded-for", "1.1.1.1, 173.245.48.1")]),
+            ("173.245.48.1".into(), true)
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        let config = ClientIpConfig {
+            trusted_proxies: vec!["10.0.0.0/33".to_string()],
+            ..Default::default()
+        };
+        assert!(ClientIpResolver::new(&config).is_err());
+
+        let config = ClientIpConfig {
+            cloudflare_proxies: vec!["invalid".to_string()],
+            ..Default::default()
+        };
+        assert!(ClientIpResolver::new(&config).is_err());
+
+        let config = ClientIpConfig {
+            geo_ip_database: Some("missing.mmdb".to_string()),
+            ..Default::default()
+        };
+        assert!(ClientIpResolver::new(&config).is_err());
+    }
+}
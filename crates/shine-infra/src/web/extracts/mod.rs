@@ -4,3 +4,7 @@ mod client_fingerprint;
 pub use self::client_fingerprint::*;
 mod validated;
 pub use self::validated::*;
+mod accept_language;
+pub use self::accept_language::*;
+mod client_ip;
+pub use self::client_ip::*;
@@ -1,13 +1,11 @@
-use axum::{
-    extract::FromRequestParts,
-    http::{request::Parts, HeaderMap},
-    RequestPartsExt,
-};
+use crate::web::extracts::{ClientIpResolver, GeoLocation};
+use axum::{extract::FromRequestParts, http::request::Parts, RequestPartsExt};
 use axum_extra::{headers::UserAgent, TypedHeader};
-use std::convert::Infallible;
+use std::{convert::Infallible, sync::Arc};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-/// General client info used for human readable site identification
+/// General client info used for human readable site identification. The location is taken from the headers
+/// of a Cloudflare proxy or from the GeoIP database.
 pub struct SiteInfo {
     pub agent: String,
     pub country: Option<String>,
@@ -28,19 +26,38 @@ where
             .map(|u| u.to_string())
             .unwrap_or_default();
 
-        let headers = parts.extract::<HeaderMap>().await.unwrap_or_default();
+        let client_ip = ClientIpResolver::from_request(&parts.extensions, &parts.headers);
+        parts.extensions.insert(client_ip);
+
+        // the geo headers can be forged, they are accepted only from a Cloudflare proxy
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .map(|c| c.to_str().unwrap_or_default().to_string())
+        };
+        let mut location = if client_ip.cloudflare {
+            GeoLocation {
+                country: header("cf-ipcountry"),
+                region: header("cf-region"),
+                city: header("cf-ipcity"),
+            }
+        } else {
+            GeoLocation::default()
+        };
+
+        if location.country.is_none() {
+            let resolver = parts.extensions.get::<Arc<ClientIpResolver>>();
+            if let Some(geo_location) = resolver.zip(client_ip.ip).and_then(|(r, ip)| r.locate(ip)) {
+                location = geo_location;
+            }
+        }
 
         Ok(SiteInfo {
             agent,
-            country: headers
-                .get("cf-ipcountry")
-                .map(|c| c.to_str().unwrap_or_default().to_string()),
-            region: headers
-                .get("cf-region")
-                .map(|c| c.to_str().unwrap_or_default().to_string()),
-            city: headers
-                .get("cf-ipcity")
-                .map(|c| c.to_str().unwrap_or_default().to_string()),
+            country: location.country,
+            region: location.region,
+            city: location.city,
         })
     }
 }
@@ -0,0 +1,161 @@
+use crate::web::{
+    responses::{Problem, ProblemConfig, ProblemResponse},
+    RequestLimitsConfig,
+};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::future::{ready, Either, Ready};
+use http_body_util::Limited;
+use regex::Regex;
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+use thiserror::Error as ThisError;
+use tower::{Layer, Service};
+
+pub const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, ThisError)]
+pub enum BodyLimitError {
+    #[error("Invalid route pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+}
+
+/// Limit the size of the request bodies. Requests with a larger Content-Length are rejected immediately,
+/// the streamed bodies fail once the limit is reached.
+/// As the limit is applied by this layer, the default limit of axum shall be disabled.
+#[derive(Clone)]
+pub struct BodyLimit {
+    max_body_size: usize,
+    routes: Arc<Vec<(Regex, usize)>>,
+}
+
+impl BodyLimit {
+    pub fn new(config: &RequestLimitsConfig) -> Result<Self, BodyLimitError> {
+        let routes = config
+            .routes
+            .iter()
+            .map(|route| Ok((Regex::new(&route.path)?, route.max_body_size)))
+            .collect::<Result<Vec<_>, BodyLimitError>>()?;
+
+        Ok(Self {
+            max_body_size: config.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE),
+            routes: Arc::new(routes),
+        })
+    }
+
+    pub fn limit_of(&self, path: &str) -> usize {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(path))
+            .map(|(_, limit)| *limit)
+            .unwrap_or(self.max_body_size)
+    }
+}
+
+impl<S> Layer<S> for BodyLimit {
+    type Service = BodyLimitMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BodyLimitMiddleware { inner, layer: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct BodyLimitMiddleware<S> {
+    inner: S,
+    layer: BodyLimit,
+}
+
+impl<S> Service<Request<Body>> for BodyLimitMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let limit = self.layer.limit_of(request.uri().path());
+
+        let content_length = request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok());
+        if content_length.is_some_and(|length| length > limit) {
+            let problem_config = request
+                .extensions()
+                .get::<ProblemConfig>()
+                .cloned()
+                .expect("Missing ProblemConfig extension");
+            let problem = Problem::new(StatusCode::PAYLOAD_TOO_LARGE, "payload-too-large")
+                .with_detail(format!("The request body exceeds the limit of {limit} bytes"));
+            let response = ProblemResponse::new(&problem_config, problem).into_response();
+            return Either::Right(ready(Ok(response)));
+        }
+
+        let request = request.map(|body| Body::new(Limited::new(body, limit)));
+        Either::Left(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::web::BodyLimitRouteConfig;
+    use axum::{extract::DefaultBodyLimit, routing::post, Router};
+    use shine_test::test;
+    use tower::ServiceExt;
+
+    async fn send(router: &Router, path: &str, body: Vec<u8>, chunked: bool) -> StatusCode {
+        let body = if chunked {
+            Body::from_stream(futures::stream::iter([Ok::<_, std::io::Error>(body)]))
+        } else {
+            Body::from(body)
+        };
+        let request = Request::post(path).body(body).unwrap();
+        router.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[test]
+    async fn limit_body() {
+        let config = RequestLimitsConfig {
+            max_body_size: Some(16),
+            routes: vec![BodyLimitRouteConfig {
+                path: "^/upload".into(),
+                max_body_size: 64,
+            }],
+            timeout: None,
+        };
+        let echo = |body: axum::body::Bytes| async move { body };
+        let router = Router::new()
+            .route("/item", post(echo))
+            .route("/upload", post(echo))
+            .layer(BodyLimit::new(&config).unwrap())
+            .layer(DefaultBodyLimit::disable())
+            .layer(ProblemConfig::new(false).into_layer());
+
+        for chunked in [false, true] {
+            assert_eq!(send(&router, "/item", vec![0; 16], chunked).await, StatusCode::OK);
+            assert_eq!(
+                send(&router, "/item", vec![0; 17], chunked).await,
+                StatusCode::PAYLOAD_TOO_LARGE
+            );
+            assert_eq!(send(&router, "/upload", vec![0; 64], chunked).await, StatusCode::OK);
+            assert_eq!(
+                send(&router, "/upload", vec![0; 65], chunked).await,
+                StatusCode::PAYLOAD_TOO_LARGE
+            );
+        }
+    }
+}
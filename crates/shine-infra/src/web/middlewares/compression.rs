@@ -0,0 +1,138 @@
+use crate::web::{CompressionAlgorithm, CompressionConfig};
+use axum::{
+    body::HttpBody,
+    http::{header, Response},
+};
+use std::sync::Arc;
+use tower_http::compression::{predicate::SizeAbove, CompressionLayer, Predicate};
+
+const DEFAULT_MIN_SIZE: u16 = 1024;
+const DEFAULT_CONTENT_TYPES: &[&str] = &[
+    "text/html",
+    "text/css",
+    "text/plain",
+    "text/javascript",
+    "application/json",
+    "application/problem+json",
+    "application/javascript",
+    "image/svg+xml",
+];
+
+/// Compress only the responses with an allowed content type. The streamed (SSE, NDJSON) responses should not
+/// be allowed as the compression delays the events until a block is filled.
+#[derive(Clone)]
+pub struct ContentTypeAllowlist {
+    content_types: Arc<Vec<String>>,
+}
+
+impl ContentTypeAllowlist {
+    pub fn new<I, S>(content_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            content_types: Arc::new(content_types.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl Predicate for ContentTypeAllowlist {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let Some(content_type) = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+        self.content_types
+            .iter()
+            .any(|allowed| content_type.starts_with(allowed.as_str()))
+    }
+}
+
+pub type CompressionPredicate = tower_http::compression::predicate::And<SizeAbove, ContentTypeAllowlist>;
+
+/// Create the response compression layer. When the compression is disabled, all the algorithms are turned off
+/// and the responses are passed through.
+pub fn create_compression_layer(config: &CompressionConfig) -> CompressionLayer<CompressionPredicate> {
+    let algorithms = match config.enabled.unwrap_or(true) {
+        true => config.algorithms.as_deref().unwrap_or(&[
+            CompressionAlgorithm::Gzip,
+            CompressionAlgorithm::Br,
+            CompressionAlgorithm::Zstd,
+        ]),
+        false => &[],
+    };
+    let content_types = match &config.content_types {
+        Some(content_types) => ContentTypeAllowlist::new(content_types.iter().cloned()),
+        None => ContentTypeAllowlist::new(DEFAULT_CONTENT_TYPES.iter().copied()),
+    };
+    let predicate = SizeAbove::new(config.min_size.unwrap_or(DEFAULT_MIN_SIZE)).and(content_types);
+
+    CompressionLayer::new()
+        .no_deflate()
+        .gzip(algorithms.contains(&CompressionAlgorithm::Gzip))
+        .br(algorithms.contains(&CompressionAlgorithm::Br))
+        .zstd(algorithms.contains(&CompressionAlgorithm::Zstd))
+        .compress_when(predicate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use shine_test::test;
+    use tower::ServiceExt;
+
+    async fn encoding(router: &Router, path: &str, accept: &str) -> Option<String> {
+        let request = Request::get(path)
+            .header(header::ACCEPT_ENCODING, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    async fn compress_allowed_content() {
+        let large = "x".repeat(4096);
+        let router = |config: &CompressionConfig| {
+            let large = large.clone();
+            Router::new()
+                .route("/json", get(move || async move { axum::Json(large) }))
+                .route("/small", get(|| async { axum::Json("x") }))
+                .route(
+                    "/binary",
+                    get(|| async { ([(header::CONTENT_TYPE, "application/octet-stream")], vec![0u8; 4096]) }),
+                )
+                .layer(create_compression_layer(config))
+        };
+
+        let enabled = router(&CompressionConfig {
+            algorithms: Some(vec![CompressionAlgorithm::Gzip, CompressionAlgorithm::Zstd]),
+            ..Default::default()
+        });
+        assert_eq!(encoding(&enabled, "/json", "gzip").await.as_deref(), Some("gzip"));
+        assert_eq!(
+            encoding(&enabled, "/json", "br;q=1, zstd;q=0.5").await.as_deref(),
+            Some("zstd")
+        );
+        assert_eq!(encoding(&enabled, "/json", "br").await, None);
+        assert_eq!(encoding(&enabled, "/small", "gzip").await, None);
+        assert_eq!(encoding(&enabled, "/binary", "gzip").await, None);
+
+        let disabled = router(&CompressionConfig {
+            enabled: Some(false),
+            ..Default::default()
+        });
+        assert_eq!(encoding(&disabled, "/json", "gzip").await, None);
+    }
+}
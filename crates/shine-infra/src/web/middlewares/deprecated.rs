@@ -0,0 +1,156 @@
+use axum::{
+    body::Body,
+    http::{header::InvalidHeaderValue, HeaderName, HeaderValue, Request},
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+const DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET_HEADER: HeaderName = HeaderName::from_static("sunset");
+const LINK_HEADER: HeaderName = HeaderName::from_static("link");
+
+/// Mark the routes as deprecated with the `Deprecation` (RFC 9745) and `Sunset` (RFC 8594) response headers.
+#[derive(Clone)]
+pub struct Deprecated {
+    deprecation: HeaderValue,
+    sunset: Option<HeaderValue>,
+    link: Option<HeaderValue>,
+}
+
+impl Deprecated {
+    /// Deprecated since the given date.
+    pub fn new(since: DateTime<Utc>) -> Self {
+        Self {
+            deprecation: HeaderValue::from_str(&format!("@{}", since.timestamp())).unwrap(),
+            sunset: None,
+            link: None,
+        }
+    }
+
+    /// The date when the routes are expected to become unavailable.
+    pub fn with_sunset(self, sunset: DateTime<Utc>) -> Self {
+        let sunset = sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        Self {
+            sunset: Some(HeaderValue::from_str(&sunset).unwrap()),
+            ..self
+        }
+    }
+
+    /// A link to the documentation of the deprecation, ex. the migration guide.
+    pub fn with_link(self, url: &str) -> Result<Self, InvalidHeaderValue> {
+        Ok(Self {
+            link: Some(HeaderValue::from_str(&format!("<{url}>; rel=\"deprecation\""))?),
+            ..self
+        })
+    }
+}
+
+impl<S> Layer<S> for Deprecated {
+    type Service = DeprecatedMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeprecatedMiddleware { inner, layer: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct DeprecatedMiddleware<S> {
+    inner: S,
+    layer: Deprecated,
+}
+
+impl<S> Service<Request<Body>> for DeprecatedMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let layer = self.layer.clone();
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response: Response = future.await?;
+            let headers = response.headers_mut();
+            // the route specific deprecation takes precedence over the deprecation of the whole version
+            if !headers.contains_key(DEPRECATION_HEADER) {
+                headers.insert(DEPRECATION_HEADER, layer.deprecation);
+                if let Some(sunset) = layer.sunset {
+                    headers.insert(SUNSET_HEADER, sunset);
+                }
+                if let Some(link) = layer.link {
+                    headers.append(LINK_HEADER, link);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{routing::get, Router};
+    use chrono::DateTime;
+    use shine_test::test;
+    use tower::ServiceExt;
+
+    async fn get_headers(router: &Router, path: &str) -> axum::http::HeaderMap {
+        let response = router
+            .clone()
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.headers().clone()
+    }
+
+    #[test]
+    async fn deprecated_version_headers() {
+        let since = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let sunset = DateTime::from_timestamp(1_800_000_000, 0).unwrap();
+        let deprecated = Deprecated::new(since)
+            .with_sunset(sunset)
+            .with_link("https://example.com/migration")
+            .unwrap();
+
+        let v1 = Router::new()
+            .route("/items", get(|| async { "v1" }))
+            .route(
+                "/legacy",
+                get(|| async { ([(DEPRECATION_HEADER, "@1600000000")], "v1") }),
+            )
+            .layer(deprecated);
+        let v2 = Router::new().route("/items", get(|| async { "v2" }));
+        let router = Router::new().nest("/v1", v1).nest("/v2", v2);
+
+        let headers = get_headers(&router, "/v1/items").await;
+        assert_eq!(headers[DEPRECATION_HEADER], "@1700000000");
+        assert_eq!(headers[SUNSET_HEADER], "Fri, 15 Jan 2027 08:00:00 GMT");
+        assert_eq!(
+            headers[LINK_HEADER],
+            "<https://example.com/migration>; rel=\"deprecation\""
+        );
+
+        // the deprecation of the route takes precedence
+        let headers = get_headers(&router, "/v1/legacy").await;
+        assert_eq!(headers[DEPRECATION_HEADER], "@1600000000");
+        assert!(headers.get(SUNSET_HEADER).is_none());
+        assert!(headers.get(LINK_HEADER).is_none());
+
+        // the current version is not deprecated
+        let headers = get_headers(&router, "/v2/items").await;
+        assert!(headers.get(DEPRECATION_HEADER).is_none());
+        assert!(headers.get(SUNSET_HEADER).is_none());
+        assert!(headers.get(LINK_HEADER).is_none());
+    }
+}
@@ -0,0 +1,734 @@
+use crate::{
+    db::{RedisConnectionError, RedisConnectionPool},
+    web::{
+        middlewares::{api_key_hash, session_user, DEFAULT_REQUEST_TIMEOUT},
+        responses::{ErrorResponse, Problem, ProblemConfig},
+        IdempotencyConfig, ServiceConfig,
+    },
+};
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use chrono::Utc;
+use futures::future::BoxFuture;
+use redis::{AsyncCommands, RedisError, SetExpiry, SetOptions};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use thiserror::Error as ThisError;
+use tower::{Layer, Service};
+
+pub const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+pub const IDEMPOTENT_REPLAYED_HEADER: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const DEFAULT_TTL: u64 = 86400;
+const DEFAULT_MAX_BODY_SIZE: usize = 65536;
+const MAX_KEY_LENGTH: usize = 255;
+/// Maximum number of records kept by the in-memory fallback before the expired entries are purged.
+const MEMORY_PURGE_LIMIT: usize = 10000;
+/// The response headers that are not stored for replay: the cookies and the hop-by-hop headers.
+const NOT_REPLAYED_HEADERS: &[&str] = &[
+    "set-cookie",
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Debug, ThisError)]
+pub enum IdempotencyError {
+    #[error("Failed to get redis connection")]
+    RedisPoolError(#[source] RedisConnectionError),
+    #[error(transparent)]
+    RedisError(#[from] RedisError),
+    #[error("Invalid idempotency record")]
+    InvalidRecord(#[source] serde_json::Error),
+}
+
+/// The rejection of a request with an idempotency key.
+#[derive(Debug)]
+pub enum IdempotencyRejection {
+    InvalidKey,
+    PayloadTooLarge,
+    /// The key was used with a different request.
+    KeyReused,
+    /// The first request with the key has not completed yet.
+    InProgress,
+    /// The first request with the key has completed, but its response is not stored for replay.
+    Consumed,
+}
+
+impl From<IdempotencyRejection> for Problem {
+    fn from(value: IdempotencyRejection) -> Self {
+        match value {
+            IdempotencyRejection::InvalidKey => {
+                Problem::bad_request("idempotency-key-invalid").with_detail("Idempotency-Key header is malformed")
+            }
+            IdempotencyRejection::PayloadTooLarge => Problem::new(StatusCode::PAYLOAD_TOO_LARGE, "payload-too-large"),
+            IdempotencyRejection::KeyReused => Problem::conflict("idempotency-key-reused")
+                .with_detail("Idempotency-Key was used with a different request"),
+            IdempotencyRejection::InProgress => Problem::conflict("idempotency-key-in-progress")
+                .with_detail("A request with the same Idempotency-Key is in progress"),
+            IdempotencyRejection::Consumed => Problem::conflict("idempotency-key-consumed")
+                .with_detail("The response of the request with the same Idempotency-Key is not stored for replay"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+enum IdempotencyRecord {
+    Pending {
+        fingerprint: String,
+    },
+    /// The request is completed, but the response contains secrets (`Cache-Control: no-store`).
+    Consumed {
+        fingerprint: String,
+    },
+    Completed {
+        fingerprint: String,
+        status: u16,
+        headers: Vec<(String, String)>,
+        /// Base64 encoded body
+        body: String,
+    },
+}
+
+impl IdempotencyRecord {
+    fn fingerprint(&self) -> &str {
+        match self {
+            IdempotencyRecord::Pending { fingerprint } => fingerprint,
+            IdempotencyRecord::Consumed { fingerprint } => fingerprint,
+            IdempotencyRecord::Completed { fingerprint, .. } => fingerprint,
+        }
+    }
+
+    fn into_response(self) -> Option<Response> {
+        let IdempotencyRecord::Completed { status, headers, body, .. } = self else {
+            return None;
+        };
+
+        let mut response = Response::new(Body::from(B64.decode(body).ok()?));
+        *response.status_mut() = StatusCode::from_u16(status).ok()?;
+        for (name, value) in headers {
+            let name = HeaderName::try_from(name).ok()?;
+            if is_replayed_header(&name) {
+                let value = HeaderValue::try_from(value).ok()?;
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+            .headers_mut()
+            .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+        Some(response)
+    }
+}
+
+/// Store of the responses of the requests with an Idempotency-Key. The responses are shared through Redis among
+/// the instances of the service, if Redis is not available each instance falls back to a local store.
+pub struct IdempotencyStore {
+    key_prefix: String,
+    redis: Option<RedisConnectionPool>,
+    ttl: u64,
+    lease: u64,
+    max_body_size: usize,
+    memory: Mutex<HashMap<String, (i64, IdempotencyRecord)>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(key_prefix: &str, redis: Option<RedisConnectionPool>, config: &IdempotencyConfig) -> Self {
+        Self {
+            key_prefix: key_prefix.to_string(),
+            redis,
+            ttl: config.ttl.unwrap_or(DEFAULT_TTL),
+            lease: config.lease.unwrap_or(DEFAULT_REQUEST_TIMEOUT.as_secs()),
+            max_body_size: config.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE),
+            memory: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn from_config(config: &ServiceConfig) -> Result<Self, IdempotencyError> {
+        let idempotency = &config.idempotency;
+        let cns = idempotency.redis_cns.as_deref().unwrap_or(&config.session_redis_cns);
+        let redis = crate::db::create_redis_pool(cns)
+            .await
+            .map_err(IdempotencyError::RedisPoolError)?;
+
+        let lease = idempotency.lease.or(config.limits.timeout);
+        let store = Self::new("", Some(redis), idempotency);
+        Ok(Self {
+            lease: lease.unwrap_or(store.lease),
+            ..store
+        })
+    }
+
+    pub fn into_layer(self) -> Extension<Arc<Self>> {
+        Extension(Arc::new(self))
+    }
+
+    fn record_key(&self, scope: &str, key: &str) -> String {
+        format!("{}idempotency:{scope}:{key}", self.key_prefix)
+    }
+
+    /// Reserve the key for the request for the lease time. Return the existing record if the key is already in use.
+    async fn reserve(&self, key: &str, fingerprint: &str) -> Option<IdempotencyRecord> {
+        let record = IdempotencyRecord::Pending {
+            fingerprint: fingerprint.to_string(),
+        };
+        if let Some(redis) = &self.redis {
+            match self.reserve_redis(redis, key, &record).await {
+                Ok(existing) => return existing,
+                Err(err) => log::warn!("Idempotency store is not available, falling back to local store: {err}"),
+            }
+        }
+        self.reserve_memory(key, record)
+    }
+
+    /// Store the response of the request for replay.
+    async fn complete(&self, key: &str, record: IdempotencyRecord) {
+        if let Some(redis) = &self.redis {
+            match self.store_redis(redis, key, &record).await {
+                Ok(()) => return,
+                Err(err) => log::warn!("Idempotency store is not available, falling back to local store: {err}"),
+            }
+        }
+        let expire = Utc::now().timestamp_millis() + (self.ttl * 1000) as i64;
+        self.memory.lock().unwrap().insert(key.to_string(), (expire, record));
+    }
+
+    /// Remove the reservation to allow the retry of a failed request.
+    async fn release(&self, key: &str) {
+        if let Some(redis) = &self.redis {
+            let result = async {
+                let mut client = redis.get().await.map_err(IdempotencyError::RedisPoolError)?;
+                client.del::<_, ()>(key).await?;
+                Ok::<_, IdempotencyError>(())
+            }
+            .await;
+            if let Err(err) = result {
+                log::warn!("Failed to release idempotency key: {err}");
+            }
+        }
+        self.memory.lock().unwrap().remove(key);
+    }
+
+    async fn reserve_redis(
+        &self,
+        redis: &RedisConnectionPool,
+        key: &str,
+        record: &IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+        let mut client = redis.get().await.map_err(IdempotencyError::RedisPoolError)?;
+        let value = serde_json::to_string(record).map_err(IdempotencyError::InvalidRecord)?;
+        let options = SetOptions::default()
+            .conditional_set(redis::ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(self.lease));
+        let reserved: Option<String> = client.set_options(key, value, options).await?;
+        if reserved.is_some() {
+            return Ok(None);
+        }
+
+        let existing: Option<String> = client.get(key).await?;
+        existing
+            .map(|existing| serde_json::from_str(&existing).map_err(IdempotencyError::InvalidRecord))
+            .transpose()
+    }
+
+    async fn store_redis(
+        &self,
+        redis: &RedisConnectionPool,
+        key: &str,
+        record: &IdempotencyRecord,
+    ) -> Result<(), IdempotencyError> {
+        let mut client = redis.get().await.map_err(IdempotencyError::RedisPoolError)?;
+        let value = serde_json::to_string(record).map_err(IdempotencyError::InvalidRecord)?;
+        client.set_ex::<_, _, ()>(key, value, self.ttl).await?;
+        Ok(())
+    }
+
+    fn reserve_memory(&self, key: &str, record: IdempotencyRecord) -> Option<IdempotencyRecord> {
+        let now = Utc::now().timestamp_millis();
+        let mut memory = self.memory.lock().unwrap();
+
+        if memory.len() >= MEMORY_PURGE_LIMIT {
+            memory.retain(|_, (expire, _)| *expire > now);
+        }
+
+        match memory.get(key) {
+            Some((expire, existing)) if *expire > now => Some(existing.clone()),
+            _ => {
+                memory.insert(key.to_string(), (now + (self.lease * 1000) as i64, record));
+                None
+            }
+        }
+    }
+}
+
+/// The reservation of a key for a request in progress. If it is dropped before completion (ex. the client
+/// disconnected), the key is released to allow the retry of the request.
+struct Reservation {
+    store: Arc<IdempotencyStore>,
+    key: Option<String>,
+}
+
+impl Reservation {
+    async fn complete(mut self, record: IdempotencyRecord) {
+        if let Some(key) = self.key.take() {
+            self.store.complete(&key, record).await;
+        }
+    }
+
+    async fn release(mut self) {
+        if let Some(key) = self.key.take() {
+            self.store.release(&key).await;
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            log::debug!("Request was dropped, releasing the idempotency key");
+            let store = self.store.clone();
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(async move { store.release(&key).await });
+                }
+                Err(_) => log::warn!("Failed to release the idempotency key without a runtime"),
+            }
+        }
+    }
+}
+
+fn is_replayed_header(name: &HeaderName) -> bool {
+    !NOT_REPLAYED_HEADERS.contains(&name.as_str())
+}
+
+fn is_no_store(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-store"))
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LENGTH && key.bytes().all(|c| c.is_ascii_graphic())
+}
+
+fn request_fingerprint(method: &Method, uri: &str, body: &[u8]) -> String {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(method.as_str().as_bytes());
+    context.update(b" ");
+    context.update(uri.as_bytes());
+    context.update(b"\n");
+    context.update(body);
+    hex::encode(context.finish())
+}
+
+/// Replay the response of the mutating requests with an `Idempotency-Key` header. The keys are scoped to the user
+/// of the session or to the api key, requests of anonymous clients are not deduplicated. The store is taken from
+/// the [IdempotencyStore] extension, if it is missing the requests are not deduplicated.
+/// Only the successful responses are stored, on error the key is released and the request can be retried.
+/// The cookies and the hop-by-hop headers are not replayed. The responses with `Cache-Control: no-store` (ex. the ones
+/// containing a secret) are not stored, the retry of such a request is rejected.
+#[derive(Clone, Default)]
+pub struct Idempotent;
+
+impl Idempotent {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for Idempotent {
+    type Service = IdempotentMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotentMiddleware { inner }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct IdempotentMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for IdempotentMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // the service that was polled ready must be used
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        if request.method().is_safe() {
+            return Box::pin(inner.call(request));
+        }
+        let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER).cloned() else {
+            return Box::pin(inner.call(request));
+        };
+        let Some(store) = request.extensions().get::<Arc<IdempotencyStore>>().cloned() else {
+            return Box::pin(inner.call(request));
+        };
+        let Some(scope) = session_user(&request)
+            .map(|user_id| format!("user:{user_id}"))
+            .or_else(|| api_key_hash(request.headers()).map(|hash| format!("key:{hash}")))
+        else {
+            log::debug!("Idempotency-Key is ignored for anonymous request");
+            return Box::pin(inner.call(request));
+        };
+
+        Box::pin(async move {
+            let problem_config = request
+                .extensions()
+                .get::<ProblemConfig>()
+                .cloned()
+                .expect("Missing ProblemConfig extension");
+            let reject = |rejection| Ok(ErrorResponse::new(&problem_config, rejection).into_response());
+
+            let Some(key) = key.to_str().ok().filter(|key| is_valid_key(key)) else {
+                return reject(IdempotencyRejection::InvalidKey);
+            };
+            let key = store.record_key(&scope, key);
+
+            let (parts, body) = request.into_parts();
+            let Ok(body) = axum::body::to_bytes(body, store.max_body_size).await else {
+                return reject(IdempotencyRejection::PayloadTooLarge);
+            };
+            let fingerprint = request_fingerprint(&parts.method, &parts.uri.to_string(), &body);
+
+            if let Some(existing) = store.reserve(&key, &fingerprint).await {
+                if existing.fingerprint() != fingerprint {
+                    return reject(IdempotencyRejection::KeyReused);
+                }
+                return match existing {
+                    IdempotencyRecord::Pending { .. } => reject(IdempotencyRejection::InProgress),
+                    IdempotencyRecord::Consumed { .. } => reject(IdempotencyRejection::Consumed),
+                    existing => match existing.into_response() {
+                        Some(response) => Ok(response),
+                        None => reject(IdempotencyRejection::InProgress),
+                    },
+                };
+            }
+            let reservation = Reservation {
+                store: store.clone(),
+                key: Some(key),
+            };
+
+            let response = match inner.call(Request::from_parts(parts, Body::from(body))).await {
+                Ok(response) => response,
+                Err(err) => {
+                    reservation.release().await;
+                    return Err(err);
+                }
+            };
+
+            // only the successful responses are final, the errors (ex. rate limit, step-up, conflict) can be retried
+            if !response.status().is_success() {
+                reservation.release().await;
+                return Ok(response);
+            }
+
+            if is_no_store(response.headers()) {
+                reservation.complete(IdempotencyRecord::Consumed { fingerprint }).await;
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let body: Bytes = match axum::body::to_bytes(body, usize::MAX).await {
+                Ok(body) => body,
+                Err(err) => {
+                    log::warn!("Failed to read the response body: {err}");
+                    reservation.release().await;
+                    return Ok(Response::from_parts(parts, Body::empty()));
+                }
+            };
+
+            if body.len() > store.max_body_size {
+                log::warn!("Response is too large to store for idempotent replay");
+                reservation.release().await;
+            } else {
+                let headers = parts
+                    .headers
+                    .iter()
+                    .filter(|(name, _)| is_replayed_header(name))
+                    .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                    .collect();
+                let record = IdempotencyRecord::Completed {
+                    fingerprint,
+                    status: parts.status.as_u16(),
+                    headers,
+                    body: B64.encode(&body),
+                };
+                reservation.complete(record).await;
+            }
+
+            Ok(Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::web::{
+        middlewares::{RateLimit, RateLimiter},
+        RateLimitConfig, RateLimitKey, RateLimitRuleConfig,
+    };
+    use axum::{routing::post, Router};
+    use shine_test::test;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    async fn send(router: &Router, key: Option<&str>, api_key: Option<&str>, body: &str) -> (StatusCode, String) {
+        let mut request = Request::post("/items");
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        if let Some(api_key) = api_key {
+            request = request.header("x-api-key", api_key);
+        }
+        let response = router
+            .clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    async fn replay_responses() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let counter = counter.clone();
+            move |body: String| async move {
+                let count = counter.fetch_add(1, Ordering::Relaxed);
+                (StatusCode::CREATED, format!("{body}:{count}"))
+            }
+        };
+
+        let store = IdempotencyStore::new("test:", None, &IdempotencyConfig::default());
+        let router = Router::new()
+            .route("/items", post(handler))
+            .layer(Idempotent::new())
+            .layer(store.into_layer())
+            .layer(ProblemConfig::new(false).into_layer());
+
+        // without key or for anonymous users the requests are not deduplicated
+        assert_eq!(send(&router, None, Some("k1"), "a").await.1, "a:0");
+        assert_eq!(send(&router, Some("1"), None, "a").await.1, "a:1");
+        assert_eq!(send(&router, Some("1"), None, "a").await.1, "a:2");
+
+        assert_eq!(
+            send(&router, Some("1"), Some("k1"), "a").await,
+            (StatusCode::CREATED, "a:3".into())
+        );
+        assert_eq!(
+            send(&router, Some("1"), Some("k1"), "a").await,
+            (StatusCode::CREATED, "a:3".into())
+        );
+        // keys are scoped to the client
+        assert_eq!(send(&router, Some("1"), Some("k2"), "a").await.1, "a:4");
+        assert_eq!(send(&router, Some("2"), Some("k1"), "a").await.1, "a:5");
+
+        assert_eq!(send(&router, Some("1"), Some("k1"), "b").await.0, StatusCode::CONFLICT);
+        assert_eq!(
+            send(&router, Some("in valid"), Some("k1"), "a").await.0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(counter.load(Ordering::Relaxed), 6);
+    }
+
+    #[test]
+    async fn retry_failed_requests() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let counter = counter.clone();
+            move |body: String| async move {
+                let count = counter.fetch_add(1, Ordering::Relaxed);
+                (StatusCode::CREATED, format!("{body}:{count}"))
+            }
+        };
+
+        let config = RateLimitConfig {
+            redis_cns: None,
+            groups: HashMap::from([(
+                "items".to_string(),
+                RateLimitRuleConfig {
+                    key: RateLimitKey::ApiKey,
+                    limit: 1,
+                    period: 1,
+                    burst: None,
+                },
+            )]),
+        };
+        let limiter = RateLimiter::new("test:", None, &config).unwrap();
+        let store = IdempotencyStore::new("test:", None, &IdempotencyConfig::default());
+        let router = Router::new()
+            .route("/items", post(handler))
+            .layer(RateLimit::new("items"))
+            .layer(Idempotent::new())
+            .layer(limiter.into_layer())
+            .layer(store.into_layer())
+            .layer(ProblemConfig::new(false).into_layer());
+
+        assert_eq!(
+            send(&router, Some("1"), Some("k1"), "a").await,
+            (StatusCode::CREATED, "a:0".into())
+        );
+        assert_eq!(
+            send(&router, Some("2"), Some("k1"), "b").await.0,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // the rejection is not stored, the retry with the same key reaches the handler
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        assert_eq!(
+            send(&router, Some("2"), Some("k1"), "b").await,
+            (StatusCode::CREATED, "b:1".into())
+        );
+        assert_eq!(
+            send(&router, Some("2"), Some("k1"), "b").await,
+            (StatusCode::CREATED, "b:1".into())
+        );
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    async fn release_dropped_requests() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let counter = counter.clone();
+            move |body: String| async move {
+                let count = counter.fetch_add(1, Ordering::Relaxed);
+                if count == 0 {
+                    tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                }
+                (StatusCode::CREATED, format!("{body}:{count}"))
+            }
+        };
+
+        let store = IdempotencyStore::new("test:", None, &IdempotencyConfig::default());
+        let router = Router::new()
+            .route("/items", post(handler))
+            .layer(Idempotent::new())
+            .layer(store.into_layer())
+            .layer(ProblemConfig::new(false).into_layer());
+
+        // the client gives up the first request
+        let dropped = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            send(&router, Some("1"), Some("k1"), "a"),
+        )
+        .await;
+        assert!(dropped.is_err());
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        assert_eq!(
+            send(&router, Some("1"), Some("k1"), "a").await,
+            (StatusCode::CREATED, "a:1".into())
+        );
+        assert_eq!(
+            send(&router, Some("1"), Some("k1"), "a").await,
+            (StatusCode::CREATED, "a:1".into())
+        );
+    }
+
+    #[test]
+    async fn pending_key_expires_after_lease() {
+        let config = IdempotencyConfig {
+            lease: Some(1),
+            ..Default::default()
+        };
+        let store = IdempotencyStore::new("test:", None, &config);
+        let pending = IdempotencyRecord::Pending { fingerprint: "f".into() };
+
+        assert!(store.reserve_memory("key", pending.clone()).is_none());
+        assert!(store.reserve_memory("key", pending.clone()).is_some());
+        let expire = store.memory.lock().unwrap()["key"].0;
+        assert!(expire <= Utc::now().timestamp_millis() + 1000);
+    }
+
+    #[test]
+    async fn skip_cookies_and_secrets() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let counter = counter.clone();
+            move |body: String| async move {
+                let count = counter.fetch_add(1, Ordering::Relaxed);
+                let cache_control = if body == "secret" { "no-store" } else { "private" };
+                (
+                    StatusCode::CREATED,
+                    [
+                        (header::SET_COOKIE, "sid=value"),
+                        (header::CACHE_CONTROL, cache_control),
+                        (header::CONNECTION, "close"),
+                    ],
+                    format!("{body}:{count}"),
+                )
+            }
+        };
+
+        let store = IdempotencyStore::new("test:", None, &IdempotencyConfig::default());
+        let router = Router::new()
+            .route("/items", post(handler))
+            .layer(Idempotent::new())
+            .layer(store.into_layer())
+            .layer(ProblemConfig::new(false).into_layer());
+
+        let request = || {
+            Request::post("/items")
+                .header(IDEMPOTENCY_KEY_HEADER, "1")
+                .header("x-api-key", "k1")
+                .body(Body::from("a"))
+                .unwrap()
+        };
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert!(response.headers().contains_key(header::SET_COOKIE));
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "private");
+        assert!(!response.headers().contains_key(header::SET_COOKIE));
+        assert!(!response.headers().contains_key(header::CONNECTION));
+
+        // the responses with secrets are not stored
+        assert_eq!(
+            send(&router, Some("2"), Some("k1"), "secret").await,
+            (StatusCode::CREATED, "secret:1".into())
+        );
+        assert_eq!(
+            send(&router, Some("2"), Some("k1"), "secret").await.0,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+    }
+}
@@ -2,3 +2,17 @@ mod powered_by;
 pub use self::powered_by::*;
 mod security_headers;
 pub use self::security_headers::*;
+mod rate_limit;
+pub use self::rate_limit::*;
+mod deprecated;
+pub use self::deprecated::*;
+mod idempotency;
+pub use self::idempotency::*;
+mod body_limit;
+pub use self::body_limit::*;
+mod request_timeout;
+pub use self::request_timeout::*;
+mod compression;
+pub use self::compression::*;
+mod reissue_session_cookie;
+pub use self::reissue_session_cookie::*;
@@ -0,0 +1,441 @@
+use crate::{
+    db::{RedisConnectionError, RedisConnectionPool},
+    session::{CurrentUserService, SessionCookie},
+    web::{
+        extracts::{ClientFingerprint, ClientIpResolver, FingerprintStrategy, WeightedFingerprint},
+        responses::{ErrorResponse, Problem, ProblemConfig},
+        RateLimitConfig, RateLimitKey, RateLimitRuleConfig, ServiceConfig,
+    },
+};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Request},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::extract::SignedCookieJar;
+use chrono::Utc;
+use futures::future::BoxFuture;
+use redis::{RedisError, Script};
+use ring::digest;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use thiserror::Error as ThisError;
+use tower::{Layer, Service};
+
+/// Maximum number of clients tracked by the in-memory fallback before the expired entries are purged.
+const MEMORY_PURGE_LIMIT: usize = 10000;
+
+/// GCRA implemented as a single atomic step using the clock of the Redis server.
+/// Returns 0 if the request is allowed, the wait time in milliseconds otherwise.
+const GCRA_SCRIPT: &str = r#"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local emission_interval = tonumber(ARGV[1])
+local tolerance = tonumber(ARGV[2])
+local tat = tonumber(redis.call('GET', KEYS[1]) or now)
+if tat < now then
+    tat = now
+end
+local new_tat = tat + emission_interval
+local retry_after = new_tat - now - tolerance
+if retry_after > 0 then
+    return retry_after
+end
+redis.call('SET', KEYS[1], new_tat, 'PX', new_tat - now)
+return 0
+"#;
+
+#[derive(Debug, ThisError)]
+pub enum RateLimitError {
+    #[error("Invalid rate limit rule for {0}: {1}")]
+    InvalidRule(String, String),
+    #[error("Failed to get redis connection")]
+    RedisPoolError(#[source] RedisConnectionError),
+    #[error(transparent)]
+    RedisError(#[from] RedisError),
+}
+
+/// The request was rejected as the client has exceeded the rate limit.
+#[derive(Debug)]
+pub struct RateLimitExceeded {
+    /// Seconds to wait before a new request is accepted
+    pub retry_after: u64,
+}
+
+impl From<RateLimitExceeded> for Problem {
+    fn from(value: RateLimitExceeded) -> Self {
+        Problem::too_many_requests("rate-limited")
+            .with_extension(serde_json::json!({ "retryAfter": value.retry_after }))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RateLimitRule {
+    key: RateLimitKey,
+    /// Milliseconds between two requests at the sustained rate
+    emission_interval: i64,
+    /// Milliseconds of credit allowing the burst of requests
+    tolerance: i64,
+}
+
+impl RateLimitRule {
+    fn new(group: &str, config: &RateLimitRuleConfig) -> Result<Self, RateLimitError> {
+        let invalid = |msg: &str| RateLimitError::InvalidRule(group.to_string(), msg.to_string());
+
+        if config.limit == 0 {
+            return Err(invalid("limit must be positive"));
+        }
+        let period = i64::try_from(config.period)
+            .ok()
+            .and_then(|period| period.checked_mul(1000))
+            .filter(|period| *period > 0)
+            .ok_or_else(|| invalid("period is out of range"))?;
+        let emission_interval = (period / i64::from(config.limit)).max(1);
+        let burst = config.burst.unwrap_or(config.limit);
+        if burst == 0 {
+            return Err(invalid("burst must be positive"));
+        }
+
+        Ok(Self {
+            key: config.key,
+            emission_interval,
+            tolerance: emission_interval * i64::from(burst),
+        })
+    }
+
+    /// Generic cell rate algorithm. Given the theoretical arrival time of the client return the new arrival time
+    /// if request is allowed, or the milliseconds to wait otherwise.
+    fn check(&self, tat: Option<i64>, now: i64) -> Result<i64, i64> {
+        let new_tat = tat.unwrap_or(now).max(now) + self.emission_interval;
+        let retry_after = new_tat - now - self.tolerance;
+        if retry_after > 0 {
+            Err(retry_after)
+        } else {
+            Ok(new_tat)
+        }
+    }
+}
+
+/// Rate limiting of the route groups. The counters are shared through Redis among the instances
+/// of the service, if Redis is not available each instance falls back to a local counter.
+pub struct RateLimiter {
+    key_prefix: String,
+    redis: Option<RedisConnectionPool>,
+    rules: HashMap<String, RateLimitRule>,
+    script: Script,
+    memory: Mutex<HashMap<String, i64>>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        key_prefix: &str,
+        redis: Option<RedisConnectionPool>,
+        config: &RateLimitConfig,
+    ) -> Result<Self, RateLimitError> {
+        let rules = config
+            .groups
+            .iter()
+            .map(|(group, rule)| Ok((group.clone(), RateLimitRule::new(group, rule)?)))
+            .collect::<Result<HashMap<_, _>, RateLimitError>>()?;
+
+        Ok(Self {
+            key_prefix: key_prefix.to_string(),
+            redis,
+            rules,
+            script: Script::new(GCRA_SCRIPT),
+            memory: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn from_config(config: &ServiceConfig) -> Result<Self, RateLimitError> {
+        let rate_limit = &config.rate_limit;
+        let redis = if rate_limit.groups.is_empty() {
+            None
+        } else {
+            let cns = rate_limit.redis_cns.as_deref().unwrap_or(&config.session_redis_cns);
+            Some(
+                crate::db::create_redis_pool(cns)
+                    .await
+                    .map_err(RateLimitError::RedisPoolError)?,
+            )
+        };
+
+        Self::new("", redis, rate_limit)
+    }
+
+    pub fn into_layer(self) -> Extension<Arc<Self>> {
+        Extension(Arc::new(self))
+    }
+
+    /// The key used to identify the clients of the group or None if the group is not limited.
+    pub fn group_key(&self, group: &str) -> Option<RateLimitKey> {
+        self.rules.get(group).map(|rule| rule.key)
+    }
+
+    /// Register a request of the client in the group.
+    pub async fn check(&self, group: &str, client: &str) -> Result<(), RateLimitExceeded> {
+        let Some(rule) = self.rules.get(group) else {
+            return Ok(());
+        };
+
+        let key = format!("{}ratelimit:{group}:{client}", self.key_prefix);
+        let retry_after = match &self.redis {
+            Some(redis) => match self.check_redis(redis, rule, &key).await {
+                Ok(retry_after) => retry_after,
+                Err(err) => {
+                    log::warn!("Rate limit counter is not available, falling back to local counter: {err}");
+                    self.check_memory(rule, &key)
+                }
+            },
+            None => self.check_memory(rule, &key),
+        };
+
+        if retry_after > 0 {
+            log::info!("Rate limit exceeded for {key}");
+            Err(RateLimitExceeded {
+                retry_after: (retry_after as u64).div_ceil(1000),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn check_redis(
+        &self,
+        redis: &RedisConnectionPool,
+        rule: &RateLimitRule,
+        key: &str,
+    ) -> Result<i64, RateLimitError> {
+        let mut client = redis.get().await.map_err(RateLimitError::RedisPoolError)?;
+        let retry_after: i64 = self
+            .script
+            .key(key)
+            .arg(rule.emission_interval)
+            .arg(rule.tolerance)
+            .invoke_async(&mut *client)
+            .await?;
+        Ok(retry_after)
+    }
+
+    fn check_memory(&self, rule: &RateLimitRule, key: &str) -> i64 {
+        let now = Utc::now().timestamp_millis();
+        let mut memory = self.memory.lock().unwrap();
+
+        if memory.len() >= MEMORY_PURGE_LIMIT {
+            memory.retain(|_, tat| *tat > now);
+        }
+
+        match rule.check(memory.get(key).copied(), now) {
+            Ok(tat) => {
+                memory.insert(key.to_string(), tat);
+                0
+            }
+            Err(retry_after) => retry_after,
+        }
+    }
+}
+
+pub(crate) fn api_key_hash(headers: &HeaderMap) -> Option<String> {
+    let key = headers
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+        })
+        .filter(|key| !key.is_empty())?;
+    Some(hex::encode(digest::digest(&digest::SHA256, key.as_bytes())))
+}
+
+pub(crate) fn session_user(request: &Request<Body>) -> Option<String> {
+    let session_service = request.extensions().get::<Arc<CurrentUserService>>()?;
+    let jar = SignedCookieJar::from_headers(request.headers(), session_service.cookie_secret().clone());
+    let cookie = jar.get(session_service.cookie_name())?;
+    let session = serde_json::from_str::<SessionCookie>(cookie.value()).ok()?;
+    Some(session.user_id.as_simple().to_string())
+}
+
+/// Identify the client of the request by the given key. If the key is not present, the ip of the client is used.
+fn client_key(key: RateLimitKey, request: &Request<Body>) -> String {
+    let client_ip = ClientIpResolver::from_request(request.extensions(), request.headers());
+    let ip = match client_ip.ip {
+        Some(ip) => ip.to_string(),
+        None => "unknown".to_string(),
+    };
+
+    match key {
+        RateLimitKey::Ip => None,
+        RateLimitKey::Fingerprint => {
+            let strategy = request
+                .extensions()
+                .get::<Arc<dyn FingerprintStrategy>>()
+                .cloned()
+                .unwrap_or_else(|| Arc::new(WeightedFingerprint::default()));
+            let fingerprint = ClientFingerprint::new(strategy, request.headers(), &client_ip);
+            Some(format!("fp:{ip}:{}", fingerprint.as_str()))
+        }
+        RateLimitKey::User => session_user(request).map(|user_id| format!("user:{user_id}")),
+        RateLimitKey::ApiKey => api_key_hash(request.headers()).map(|hash| format!("key:{hash}")),
+    }
+    .unwrap_or_else(|| format!("ip:{ip}"))
+}
+
+/// Limit the request rate of a route group. The rule of the group is taken from the [RateLimiter] extension,
+/// if there is no rule for the group, requests are not limited.
+#[derive(Clone)]
+pub struct RateLimit {
+    group: Arc<str>,
+}
+
+impl RateLimit {
+    pub fn new(group: &str) -> Self {
+        Self { group: group.into() }
+    }
+}
+
+impl<S> Layer<S> for RateLimit {
+    type Service = RateLimitMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitMiddleware { inner, layer: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct RateLimitMiddleware<S> {
+    inner: S,
+    layer: RateLimit,
+}
+
+impl<S> Service<Request<Body>> for RateLimitMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // the service that was polled ready must be used
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let group = self.layer.group.clone();
+
+        let limiter = request.extensions().get::<Arc<RateLimiter>>().cloned();
+        let Some((limiter, key)) = limiter.and_then(|limiter| {
+            let key = limiter.group_key(&group)?;
+            Some((limiter, key))
+        }) else {
+            return Box::pin(inner.call(request));
+        };
+
+        let client = client_key(key, &request);
+        Box::pin(async move {
+            if let Err(exceeded) = limiter.check(&group, &client).await {
+                let problem_config = request
+                    .extensions()
+                    .get::<ProblemConfig>()
+                    .cloned()
+                    .expect("Missing ProblemConfig extension");
+                let retry_after = HeaderValue::from(exceeded.retry_after);
+                let mut response = ErrorResponse::new(&problem_config, exceeded).into_response();
+                response.headers_mut().insert(header::RETRY_AFTER, retry_after);
+                return Ok(response);
+            }
+
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+
+    fn rule(key: RateLimitKey, limit: u32, period: u64, burst: Option<u32>) -> RateLimitRuleConfig {
+        RateLimitRuleConfig { key, limit, period, burst }
+    }
+
+    #[test]
+    fn gcra() {
+        let rule = RateLimitRule::new("test", &rule(RateLimitKey::Ip, 5, 5, None)).unwrap();
+
+        let mut tat = None;
+        for _ in 0..5 {
+            tat = Some(rule.check(tat, 0).unwrap());
+        }
+        assert_eq!(rule.check(tat, 0), Err(1000));
+        assert_eq!(rule.check(tat, 500), Err(500));
+        assert!(rule.check(tat, 1000).is_ok());
+        assert!(rule.check(tat, 100000).is_ok());
+    }
+
+    #[test]
+    fn gcra_burst() {
+        let rule = RateLimitRule::new("test", &rule(RateLimitKey::Ip, 10, 10, Some(2))).unwrap();
+
+        let tat = rule.check(None, 0).unwrap();
+        let tat = rule.check(Some(tat), 0).unwrap();
+        assert_eq!(rule.check(Some(tat), 0), Err(1000));
+    }
+
+    #[test]
+    fn invalid_rule() {
+        assert!(RateLimitRule::new("test", &rule(RateLimitKey::Ip, 0, 10, None)).is_err());
+        assert!(RateLimitRule::new("test", &rule(RateLimitKey::Ip, 10, 0, None)).is_err());
+        assert!(RateLimitRule::new("test", &rule(RateLimitKey::Ip, 10, 10, Some(0))).is_err());
+    }
+
+    #[test]
+    fn fingerprint_key() {
+        let request = |ip: &str, agent: &str| {
+            let mut request = Request::get("/")
+                .header(header::USER_AGENT, agent)
+                .body(Body::empty())
+                .unwrap();
+            let peer: std::net::SocketAddr = format!("{ip}:1234").parse().unwrap();
+            request.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+            request
+        };
+        let key = |ip: &str, agent: &str| client_key(RateLimitKey::Fingerprint, &request(ip, agent));
+
+        assert!(key("1.2.3.4", "Chrome").starts_with("fp:1.2.3.4:fp2."));
+        assert_eq!(key("1.2.3.4", "Chrome"), key("1.2.3.4", "Chrome"));
+        // the clients of the same browser are not sharing the limit
+        assert_ne!(key("1.2.3.4", "Chrome"), key("1.2.3.5", "Chrome"));
+        assert_ne!(key("1.2.3.4", "Chrome"), key("1.2.3.4", "Firefox"));
+        assert_eq!(
+            client_key(RateLimitKey::Ip, &request("1.2.3.4", "Chrome")),
+            "ip:1.2.3.4"
+        );
+    }
+
+    #[test]
+    async fn local_counter() {
+        let config = RateLimitConfig {
+            redis_cns: None,
+            groups: HashMap::from([("login".to_string(), rule(RateLimitKey::Ip, 2, 60, None))]),
+        };
+        let limiter = RateLimiter::new("", None, &config).unwrap();
+
+        assert!(limiter.check("login", "a").await.is_ok());
+        assert!(limiter.check("login", "a").await.is_ok());
+        let exceeded = limiter.check("login", "a").await.unwrap_err();
+        assert_eq!(exceeded.retry_after, 30);
+        assert!(limiter.check("login", "b").await.is_ok());
+        assert!(limiter.check("other", "a").await.is_ok());
+    }
+}
@@ -0,0 +1,131 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Request},
+    response::Response,
+};
+use futures::future::BoxFuture;
+use std::{
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// The session cookie to re-issue with the response, ex. with an upgraded fingerprint.
+#[derive(Clone, Default)]
+pub(crate) struct ReissuedSessionCookie(Arc<Mutex<Option<(String, HeaderValue)>>>);
+
+impl ReissuedSessionCookie {
+    /// Set the cookie of the given name to the `Set-Cookie` header value.
+    pub(crate) fn set(&self, name: &str, set_cookie: HeaderValue) {
+        *self.0.lock().unwrap() = Some((name.to_string(), set_cookie));
+    }
+
+    fn take(&self) -> Option<(String, HeaderValue)> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Set the session cookie re-issued by the session extractors on the response. If the handler sets (or deletes)
+/// the same cookie, it takes precedence.
+#[derive(Clone, Default)]
+pub struct ReissueSessionCookie;
+
+impl ReissueSessionCookie {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for ReissueSessionCookie {
+    type Service = ReissueSessionCookieMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ReissueSessionCookieMiddleware { inner }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct ReissueSessionCookieMiddleware<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for ReissueSessionCookieMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        let reissued = ReissuedSessionCookie::default();
+        request.extensions_mut().insert(reissued.clone());
+
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response: Response = future.await?;
+            if let Some((name, set_cookie)) = reissued.take() {
+                let prefix = format!("{name}=");
+                let is_set = response
+                    .headers()
+                    .get_all(header::SET_COOKIE)
+                    .iter()
+                    .any(|value| value.as_bytes().starts_with(prefix.as_bytes()));
+                if !is_set {
+                    response.headers_mut().append(header::SET_COOKIE, set_cookie);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{routing::get, Extension, Router};
+    use shine_test::test;
+    use tower::ServiceExt;
+
+    #[test]
+    async fn handler_cookie_takes_precedence() {
+        async fn reissue(Extension(reissued): Extension<ReissuedSessionCookie>) -> &'static str {
+            reissued.set("sid", HeaderValue::from_static("sid=reissued"));
+            "ok"
+        }
+
+        async fn logout(Extension(reissued): Extension<ReissuedSessionCookie>) -> impl axum::response::IntoResponse {
+            reissued.set("sid", HeaderValue::from_static("sid=reissued"));
+            ([(header::SET_COOKIE, "sid=; Max-Age=0")], "ok")
+        }
+
+        let router = Router::new()
+            .route("/reissue", get(reissue))
+            .route("/logout", get(logout))
+            .layer(ReissueSessionCookie::new());
+
+        let response = router
+            .clone()
+            .oneshot(Request::get("/reissue").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::SET_COOKIE], "sid=reissued");
+
+        let response = router
+            .oneshot(Request::get("/logout").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let cookies = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(cookies, vec!["sid=; Max-Age=0"]);
+    }
+}
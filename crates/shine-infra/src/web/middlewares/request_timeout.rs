@@ -0,0 +1,115 @@
+use crate::web::responses::{Problem, ProblemConfig, ProblemResponse};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use std::{
+    task::{Context, Poll},
+    time::Duration,
+};
+use tower::{Layer, Service};
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Limit the time of producing the response. The streaming of the response body is not limited, thus
+/// the long living (SSE, NDJSON) responses are not affected once the handler returned.
+/// A timeout is reported as `503 Service Unavailable`, as it is the server that failed to respond in time,
+/// `408` would tell the client to repeat a request that was received in full.
+#[derive(Clone)]
+pub struct RequestTimeout {
+    timeout: Duration,
+}
+
+impl RequestTimeout {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for RequestTimeout {
+    type Service = RequestTimeoutMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestTimeoutMiddleware { inner, timeout: self.timeout }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct RequestTimeoutMiddleware<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S> Service<Request<Body>> for RequestTimeoutMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let problem_config = request
+            .extensions()
+            .get::<ProblemConfig>()
+            .cloned()
+            .expect("Missing ProblemConfig extension");
+        let timeout = self.timeout;
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            match tokio::time::timeout(timeout, future).await {
+                Ok(response) => response,
+                Err(_) => {
+                    log::warn!("Request timed out after {timeout:?}");
+                    let problem = Problem::new(StatusCode::SERVICE_UNAVAILABLE, "request-timeout")
+                        .with_detail(format!("The request was not completed in {}s", timeout.as_secs()));
+                    Ok(ProblemResponse::new(&problem_config, problem).into_response())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{routing::get, Router};
+    use shine_test::test;
+    use tower::ServiceExt;
+
+    #[test]
+    async fn timeout_request() {
+        let router = Router::new()
+            .route("/fast", get(|| async { "ok" }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "ok"
+                }),
+            )
+            .layer(RequestTimeout::new(Duration::from_millis(100)))
+            .layer(ProblemConfig::new(false).into_layer());
+
+        let request = Request::get("/fast").body(Body::empty()).unwrap();
+        assert_eq!(router.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+        let request = Request::get("/slow").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(
+            problem["type"].as_str().unwrap().ends_with("request-timeout"),
+            "{problem}"
+        );
+    }
+}
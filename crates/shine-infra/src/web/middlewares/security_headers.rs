@@ -1,20 +1,229 @@
+use crate::web::{HstsConfig, SecurityHeadersConfig, SecurityHeadersProfileConfig};
 use axum::{
     body::Body,
-    http::{HeaderValue, Request},
+    http::{HeaderMap, HeaderName, HeaderValue, Request},
     response::Response,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
 use futures::future::BoxFuture;
-use std::task::{Context, Poll};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+use thiserror::Error as ThisError;
 use tower::{Layer, Service};
 
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+const DEFAULT_HSTS_MAX_AGE: u64 = 31536000;
+
+tokio::task_local! {
+    static CSP_NONCE: CspNonce;
+}
+
+#[derive(Debug, ThisError)]
+pub enum SecurityHeadersError {
+    #[error("Invalid {1} header in the security profile {0}")]
+    InvalidHeader(String, &'static str),
+    #[error("Unknown security profile {0}")]
+    UnknownProfile(String),
+}
+
+/// The random nonce of the Content-Security-Policy of the response. It is available as a request extension
+/// and through [CspNonce::current] while the request is processed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn generate(random: &SystemRandom) -> Self {
+        let mut raw = [0u8; 16];
+        random.fill(&mut raw).unwrap();
+        Self(B64.encode(raw))
+    }
+
+    /// The nonce of the request processed by the current task, if the policy requires one.
+    pub fn current() -> Option<Self> {
+        CSP_NONCE.try_with(|nonce| nonce.clone()).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+enum HeaderTemplate {
+    Static(HeaderValue),
+    Nonce(String),
+}
+
+struct SecurityProfile {
+    headers: Vec<(HeaderName, HeaderTemplate)>,
+}
+
+impl SecurityProfile {
+    fn new(name: &str, config: &SecurityHeadersProfileConfig) -> Result<Self, SecurityHeadersError> {
+        let mut profile = Self { headers: Vec::new() };
+
+        profile.add(name, "x-content-type-options", Some("nosniff"))?;
+        let hsts = config.hsts.as_ref().map(hsts_value);
+        profile.add(name, "strict-transport-security", hsts.as_deref())?;
+        profile.add(name, "x-frame-options", config.frame_options.as_deref())?;
+        profile.add(name, "referrer-policy", config.referrer_policy.as_deref())?;
+        profile.add(name, "permissions-policy", config.permissions_policy.as_deref())?;
+        profile.add(
+            name,
+            "cross-origin-opener-policy",
+            config.cross_origin_opener_policy.as_deref(),
+        )?;
+        profile.add(
+            name,
+            "cross-origin-embedder-policy",
+            config.cross_origin_embedder_policy.as_deref(),
+        )?;
+        profile.add(
+            name,
+            "cross-origin-resource-policy",
+            config.cross_origin_resource_policy.as_deref(),
+        )?;
+        let csp_header = if config.content_security_policy_report_only.unwrap_or(false) {
+            "content-security-policy-report-only"
+        } else {
+            "content-security-policy"
+        };
+        profile.add(name, csp_header, config.content_security_policy.as_deref())?;
+
+        Ok(profile)
+    }
+
+    fn add(&mut self, profile: &str, header: &'static str, value: Option<&str>) -> Result<(), SecurityHeadersError> {
+        let Some(value) = value.filter(|value| !value.is_empty()) else {
+            return Ok(());
+        };
+
+        let invalid = || SecurityHeadersError::InvalidHeader(profile.to_string(), header);
+        let template = if value.contains(NONCE_PLACEHOLDER) {
+            // validate with a sample nonce, the nonce itself is always a valid header value
+            HeaderValue::from_str(&value.replace(NONCE_PLACEHOLDER, "nonce")).map_err(|_| invalid())?;
+            HeaderTemplate::Nonce(value.to_string())
+        } else {
+            HeaderTemplate::Static(HeaderValue::from_str(value).map_err(|_| invalid())?)
+        };
+        self.headers.push((HeaderName::from_static(header), template));
+        Ok(())
+    }
+
+    fn uses_nonce(&self) -> bool {
+        self.headers
+            .iter()
+            .any(|(_, template)| matches!(template, HeaderTemplate::Nonce(_)))
+    }
+
+    fn apply(&self, headers: &mut HeaderMap, nonce: Option<&CspNonce>) {
+        for (name, template) in &self.headers {
+            match template {
+                HeaderTemplate::Static(value) => {
+                    headers.insert(name.clone(), value.clone());
+                }
+                HeaderTemplate::Nonce(value) => {
+                    let nonce = nonce.map(|nonce| nonce.as_str()).unwrap_or_default();
+                    if let Ok(value) = HeaderValue::from_str(&value.replace(NONCE_PLACEHOLDER, nonce)) {
+                        headers.insert(name.clone(), value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn hsts_value(config: &HstsConfig) -> String {
+    let mut value = format!("max-age={}", config.max_age);
+    if config.include_sub_domains {
+        value.push_str("; includeSubDomains");
+    }
+    if config.preload {
+        value.push_str("; preload");
+    }
+    value
+}
+
+/// Override the fields of the base profile by the fields set in the profile.
+fn merge_profile(
+    base: &SecurityHeadersProfileConfig,
+    profile: &SecurityHeadersProfileConfig,
+) -> SecurityHeadersProfileConfig {
+    fn pick<T: Clone>(base: &Option<T>, value: &Option<T>) -> Option<T> {
+        value.as_ref().or(base.as_ref()).cloned()
+    }
+
+    SecurityHeadersProfileConfig {
+        content_security_policy: pick(&base.content_security_policy, &profile.content_security_policy),
+        content_security_policy_report_only: pick(
+            &base.content_security_policy_report_only,
+            &profile.content_security_policy_report_only,
+        ),
+        hsts: pick(&base.hsts, &profile.hsts),
+        permissions_policy: pick(&base.permissions_policy, &profile.permissions_policy),
+        cross_origin_opener_policy: pick(&base.cross_origin_opener_policy, &profile.cross_origin_opener_policy),
+        cross_origin_embedder_policy: pick(
+            &base.cross_origin_embedder_policy,
+            &profile.cross_origin_embedder_policy,
+        ),
+        cross_origin_resource_policy: pick(
+            &base.cross_origin_resource_policy,
+            &profile.cross_origin_resource_policy,
+        ),
+        frame_options: pick(&base.frame_options, &profile.frame_options),
+        referrer_policy: pick(&base.referrer_policy, &profile.referrer_policy),
+    }
+}
+
+/// The default profile of the configuration on top of the builtin defaults.
+fn default_profile(config: &SecurityHeadersConfig) -> SecurityHeadersProfileConfig {
+    let builtin = SecurityHeadersProfileConfig {
+        hsts: Some(HstsConfig {
+            max_age: DEFAULT_HSTS_MAX_AGE,
+            include_sub_domains: true,
+            preload: false,
+        }),
+        frame_options: Some("DENY".to_string()),
+        ..Default::default()
+    };
+    merge_profile(&builtin, &config.default)
+}
+
+/// Add the security headers to the responses. The profile of the response is selected by the
+/// [SecurityHeadersProfile] layer of the route, without it the default profile is used.
 #[derive(Clone)]
-pub struct SecurityHeaders;
+pub struct SecurityHeaders {
+    default: Arc<SecurityProfile>,
+    use_nonce: bool,
+    random: SystemRandom,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig) -> Result<Self, SecurityHeadersError> {
+        let default_config = default_profile(config);
+        let default = SecurityProfile::new("default", &default_config)?;
+
+        // validate all the profiles on startup, even if no route selects them
+        let mut use_nonce = default.uses_nonce();
+        for (name, profile) in &config.profiles {
+            use_nonce |= SecurityProfile::new(name, &merge_profile(&default_config, profile))?.uses_nonce();
+        }
+
+        Ok(Self {
+            default: Arc::new(default),
+            use_nonce,
+            random: SystemRandom::new(),
+        })
+    }
+}
 
 impl<S> Layer<S> for SecurityHeaders {
     type Service = SecurityHeadersMiddleware<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        SecurityHeadersMiddleware { inner }
+        SecurityHeadersMiddleware { inner, layer: self.clone() }
     }
 }
 
@@ -22,6 +231,7 @@ impl<S> Layer<S> for SecurityHeaders {
 #[must_use]
 pub struct SecurityHeadersMiddleware<S> {
     inner: S,
+    layer: SecurityHeaders,
 }
 
 impl<S> Service<Request<Body>> for SecurityHeadersMiddleware<S>
@@ -37,18 +247,187 @@ where
         self.inner.poll_ready(cx)
     }
 
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        let layer = self.layer.clone();
+        let nonce = layer.use_nonce.then(|| CspNonce::generate(&layer.random));
+        if let Some(nonce) = &nonce {
+            request.extensions_mut().insert(nonce.clone());
+        }
+
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let mut response: Response = match &nonce {
+                Some(nonce) => CSP_NONCE.scope(nonce.clone(), future).await?,
+                None => future.await?,
+            };
+            let profile = match response.extensions().get::<SelectedProfile>() {
+                Some(SelectedProfile(profile)) => profile.clone(),
+                None => layer.default.clone(),
+            };
+            profile.apply(response.headers_mut(), nonce.as_ref());
+            Ok(response)
+        })
+    }
+}
+
+/// The security profile selected for the response.
+#[derive(Clone)]
+struct SelectedProfile(Arc<SecurityProfile>);
+
+/// Select the security header profile of the routes. The profiles are defined in the [SecurityHeadersConfig]
+/// and applied by the [SecurityHeaders] layer.
+#[derive(Clone)]
+pub struct SecurityHeadersProfile {
+    profile: Arc<SecurityProfile>,
+}
+
+impl SecurityHeadersProfile {
+    /// Resolve the profile of the configuration, it fails if the profile is not defined.
+    pub fn new(config: &SecurityHeadersConfig, name: &str) -> Result<Self, SecurityHeadersError> {
+        let profile = config
+            .profiles
+            .get(name)
+            .ok_or_else(|| SecurityHeadersError::UnknownProfile(name.to_string()))?;
+        let profile = SecurityProfile::new(name, &merge_profile(&default_profile(config), profile))?;
+        Ok(Self { profile: Arc::new(profile) })
+    }
+}
+
+impl<S> Layer<S> for SecurityHeadersProfile {
+    type Service = SecurityHeadersProfileMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SecurityHeadersProfileMiddleware { inner, layer: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct SecurityHeadersProfileMiddleware<S> {
+    inner: S,
+    layer: SecurityHeadersProfile,
+}
+
+impl<S> Service<Request<Body>> for SecurityHeadersProfileMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let profile = self.layer.profile.clone();
         let future = self.inner.call(request);
         Box::pin(async move {
             let mut response: Response = future.await?;
-            let headers = response.headers_mut();
-            headers.insert(
-                "strict-transport-security",
-                HeaderValue::from_static("max-age=31536000; includeSubDomains"),
-            );
-            headers.insert("x-content-type-options", HeaderValue::from_static("nosniff"));
-            headers.insert("x-frame-options", HeaderValue::from_static("DENY"));
+            // the innermost profile wins
+            if response.extensions().get::<SelectedProfile>().is_none() {
+                response.extensions_mut().insert(SelectedProfile(profile));
+            }
             Ok(response)
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{routing::get, Extension, Router};
+    use shine_test::test;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    fn config() -> SecurityHeadersConfig {
+        SecurityHeadersConfig {
+            default: SecurityHeadersProfileConfig {
+                permissions_policy: Some("camera=()".to_string()),
+                ..Default::default()
+            },
+            profiles: HashMap::from([(
+                "page".to_string(),
+                SecurityHeadersProfileConfig {
+                    content_security_policy: Some("script-src 'nonce-{nonce}'".to_string()),
+                    hsts: Some(HstsConfig {
+                        max_age: 63072000,
+                        include_sub_domains: true,
+                        preload: true,
+                    }),
+                    frame_options: Some(String::new()),
+                    ..Default::default()
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut config = config();
+        config.default.referrer_policy = Some("no\nreferrer".to_string());
+        assert!(SecurityHeaders::new(&config).is_err());
+    }
+
+    #[test]
+    fn unknown_profile() {
+        let config = config();
+        assert!(SecurityHeadersProfile::new(&config, "page").is_ok());
+        assert!(matches!(
+            SecurityHeadersProfile::new(&config, "pages"),
+            Err(SecurityHeadersError::UnknownProfile(_))
+        ));
+    }
+
+    #[test]
+    async fn profiles() {
+        async fn page(Extension(nonce): Extension<CspNonce>) -> String {
+            assert_eq!(CspNonce::current().as_ref(), Some(&nonce));
+            nonce.as_str().to_string()
+        }
+
+        let config = config();
+        let router = Router::new()
+            .route("/page", get(page))
+            .layer(SecurityHeadersProfile::new(&config, "page").unwrap())
+            .route("/api", get(|| async { "api" }))
+            .layer(SecurityHeaders::new(&config).unwrap());
+
+        let response = router
+            .clone()
+            .oneshot(Request::get("/api").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let headers = response.headers();
+        assert_eq!(
+            headers["strict-transport-security"],
+            "max-age=31536000; includeSubDomains"
+        );
+        assert_eq!(headers["x-frame-options"], "DENY");
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert_eq!(headers["permissions-policy"], "camera=()");
+        assert!(headers.get("content-security-policy").is_none());
+
+        let response = router
+            .oneshot(Request::get("/page").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let nonce = String::from_utf8(body.to_vec()).unwrap();
+        assert!(!nonce.is_empty());
+        assert_eq!(
+            headers["content-security-policy"],
+            format!("script-src 'nonce-{nonce}'").as_str()
+        );
+        assert_eq!(
+            headers["strict-transport-security"],
+            "max-age=63072000; includeSubDomains; preload"
+        );
+        assert!(headers.get("x-frame-options").is_none());
+        assert_eq!(headers["permissions-policy"], "camera=()");
+    }
+}
@@ -1,5 +1,7 @@
 mod apiurl;
 pub use self::apiurl::*;
+mod api_version;
+pub use self::api_version::*;
 mod config;
 pub use self::config::*;
 mod service_config;
@@ -8,7 +10,10 @@ mod web_config;
 pub use self::web_config::*;
 mod web_app;
 pub use self::web_app::*;
+mod http_client;
+pub use self::http_client::*;
 
 pub mod extracts;
 pub mod middlewares;
+pub mod realtime;
 pub mod responses;
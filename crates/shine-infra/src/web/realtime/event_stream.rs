@@ -0,0 +1,174 @@
+use crate::{
+    session::{CheckedCurrentUser, CurrentUser, CurrentUserService},
+    web::{
+        realtime::{session_revoked, CloseReason, ConnectionGuard, ConnectionKind, RealtimeService},
+        responses::JsonEvents,
+    },
+};
+use axum::{
+    extract::FromRequestParts,
+    http::request::Parts,
+    response::{IntoResponse, Response},
+    Extension, RequestPartsExt,
+};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use serde::Serialize;
+use std::{future::Future, sync::Arc};
+
+/// Extractor of a server-sent event stream of an authenticated user. The session of the user is revalidated
+/// periodically and the stream is ended on revocation.
+pub struct AuthenticatedEvents {
+    user: CurrentUser,
+    user_service: Arc<CurrentUserService>,
+    realtime: Arc<RealtimeService>,
+}
+
+impl AuthenticatedEvents {
+    pub fn user(&self) -> &CurrentUser {
+        &self.user
+    }
+
+    /// Create the json event stream response of the items.
+    pub fn events<S, T>(self, stream: S) -> JsonEvents<BoxStream<'static, T>>
+    where
+        S: Stream<Item = T> + Send + 'static,
+        T: Serialize + Send + 'static,
+    {
+        let Self { user, user_service, realtime } = self;
+
+        let guard = realtime.connect(ConnectionKind::EventStream);
+        let revoked = session_revoked(user_service, user, realtime.revalidate_interval());
+        JsonEvents::new(revocable_events(stream, revoked, guard))
+    }
+}
+
+/// End the stream of the events when the session is revoked and track the sent events.
+fn revocable_events<S, T, R>(stream: S, revoked: R, guard: ConnectionGuard) -> BoxStream<'static, T>
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: Send + 'static,
+    R: Future<Output = ()> + Send + 'static,
+{
+    let events = futures::stream::unfold(
+        (stream.boxed(), Box::pin(revoked), guard),
+        |(mut stream, mut revoked, mut guard)| async move {
+            tokio::select! {
+                item = stream.next() => {
+                    let item = item?;
+                    guard.count_sent();
+                    Some((item, (stream, revoked, guard)))
+                }
+                _ = &mut revoked => {
+                    guard.set_reason(CloseReason::SessionRevoked);
+                    None
+                }
+            }
+        },
+    );
+    events.boxed()
+}
+
+impl<S> FromRequestParts<S> for AuthenticatedEvents
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = CheckedCurrentUser::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?
+            .into_user();
+        let Extension(user_service) = parts
+            .extract::<Extension<Arc<CurrentUserService>>>()
+            .await
+            .expect("Missing CurrentUserService extension");
+        let Extension(realtime) = parts
+            .extract::<Extension<Arc<RealtimeService>>>()
+            .await
+            .expect("Missing RealtimeService extension");
+
+        Ok(Self { user, user_service, realtime })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        telemetry::PrometheusExporter,
+        web::{realtime::find_metric, RealtimeConfig},
+    };
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+    use shine_test::test;
+    use std::time::Duration;
+
+    #[test]
+    async fn end_stream_on_revocation() {
+        let exporter = PrometheusExporter::new();
+        let provider = SdkMeterProvider::builder().with_reader(exporter.clone()).build();
+        let realtime = Arc::new(RealtimeService::with_meter(
+            &RealtimeConfig::default(),
+            &provider.meter("test"),
+        ));
+
+        // the source never ends on its own
+        let stream = futures::stream::iter([1, 2]).chain(futures::stream::pending());
+        let revoked = tokio::time::sleep(Duration::from_millis(10));
+        let guard = realtime.connect(ConnectionKind::EventStream);
+
+        let events = revocable_events(stream, revoked, guard).collect::<Vec<_>>().await;
+        assert_eq!(events, vec![1, 2]);
+
+        let text = exporter.collect().unwrap();
+        assert_eq!(
+            find_metric(&text, "realtime_connections", &[("kind", "sse")]),
+            Some("0"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(
+                &text,
+                "realtime_messages_total",
+                &[("direction", "sent"), ("kind", "sse")]
+            ),
+            Some("2"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(
+                &text,
+                "realtime_closed_total",
+                &[("kind", "sse"), ("reason", "session_revoked")]
+            ),
+            Some("1"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    async fn end_stream_with_source() {
+        let exporter = PrometheusExporter::new();
+        let provider = SdkMeterProvider::builder().with_reader(exporter.clone()).build();
+        let realtime = Arc::new(RealtimeService::with_meter(
+            &RealtimeConfig::default(),
+            &provider.meter("test"),
+        ));
+
+        let stream = futures::stream::iter([1, 2, 3]);
+        let guard = realtime.connect(ConnectionKind::EventStream);
+
+        let events = revocable_events(stream, futures::future::pending(), guard)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(events, vec![1, 2, 3]);
+
+        let text = exporter.collect().unwrap();
+        assert_eq!(
+            find_metric(&text, "realtime_closed_total", &[("kind", "sse"), ("reason", "closed")]),
+            Some("1"),
+            "{text}"
+        );
+    }
+}
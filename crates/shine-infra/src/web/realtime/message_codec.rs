@@ -0,0 +1,146 @@
+use axum::extract::ws::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+pub enum MessageCodecError {
+    #[error("Unsupported message type")]
+    UnsupportedMessage,
+    #[error("Message of {0} bytes exceeds the limit")]
+    TooLarge(usize),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Conversion between the WebSocket messages and the typed messages of a handler.
+pub trait MessageCodec: Send + Sync + 'static {
+    type In: Send + 'static;
+    type Out: Send + 'static;
+
+    fn decode(&self, message: Message) -> Result<Self::In, MessageCodecError>;
+    fn encode(&self, message: &Self::Out) -> Result<Message, MessageCodecError>;
+}
+
+/// Json encoded text messages.
+pub struct JsonCodec<In, Out> {
+    max_message_size: Option<usize>,
+    _ph: PhantomData<fn(In) -> Out>,
+}
+
+impl<In, Out> Default for JsonCodec<In, Out> {
+    fn default() -> Self {
+        Self {
+            max_message_size: None,
+            _ph: PhantomData,
+        }
+    }
+}
+
+impl<In, Out> JsonCodec<In, Out> {
+    /// Reject the received messages larger than the given size in bytes.
+    pub fn with_max_message_size(self, max_message_size: usize) -> Self {
+        Self {
+            max_message_size: Some(max_message_size),
+            ..self
+        }
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), MessageCodecError> {
+        match self.max_message_size {
+            Some(max_message_size) if size > max_message_size => Err(MessageCodecError::TooLarge(size)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<In, Out> MessageCodec for JsonCodec<In, Out>
+where
+    In: DeserializeOwned + Send + 'static,
+    Out: Serialize + Send + 'static,
+{
+    type In = In;
+    type Out = Out;
+
+    fn decode(&self, message: Message) -> Result<In, MessageCodecError> {
+        match message {
+            Message::Text(text) => {
+                self.check_size(text.len())?;
+                Ok(serde_json::from_str(&text)?)
+            }
+            Message::Binary(data) => {
+                self.check_size(data.len())?;
+                Ok(serde_json::from_slice(&data)?)
+            }
+            _ => Err(MessageCodecError::UnsupportedMessage),
+        }
+    }
+
+    fn encode(&self, message: &Out) -> Result<Message, MessageCodecError> {
+        Ok(Message::Text(serde_json::to_string(message)?.into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Bytes;
+    use serde::Deserialize;
+    use shine_test::test;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: u32,
+    }
+
+    #[test]
+    fn decode_messages() {
+        let codec = JsonCodec::<Item, Item>::default().with_max_message_size(16);
+
+        assert_eq!(
+            codec.decode(Message::Text("{\"id\":1}".into())).unwrap(),
+            Item { id: 1 }
+        );
+        assert_eq!(
+            codec
+                .decode(Message::Binary(Bytes::from_static(b"{\"id\":2}")))
+                .unwrap(),
+            Item { id: 2 }
+        );
+        assert_eq!(
+            codec.encode(&Item { id: 3 }).unwrap(),
+            Message::Text("{\"id\":3}".into())
+        );
+    }
+
+    #[test]
+    fn reject_invalid_messages() {
+        let codec = JsonCodec::<Item, Item>::default().with_max_message_size(16);
+
+        let oversized = format!("{{\"id\":1,\"padding\":\"{}\"}}", "x".repeat(16));
+        assert!(matches!(
+            codec.decode(Message::Text(oversized.clone().into())),
+            Err(MessageCodecError::TooLarge(size)) if size == oversized.len()
+        ));
+        assert!(matches!(
+            codec.decode(Message::Binary(oversized.into())),
+            Err(MessageCodecError::TooLarge(_))
+        ));
+        assert!(matches!(
+            codec.decode(Message::Text("{\"id\":".into())),
+            Err(MessageCodecError::Json(_))
+        ));
+        assert!(matches!(
+            codec.decode(Message::Text("{\"name\":1}".into())),
+            Err(MessageCodecError::Json(_))
+        ));
+        assert!(matches!(
+            codec.decode(Message::Ping(Bytes::new())),
+            Err(MessageCodecError::UnsupportedMessage)
+        ));
+        assert!(matches!(
+            codec.decode(Message::Close(None)),
+            Err(MessageCodecError::UnsupportedMessage)
+        ));
+    }
+}
@@ -0,0 +1,8 @@
+mod realtime_service;
+pub use self::realtime_service::*;
+mod message_codec;
+pub use self::message_codec::*;
+mod web_socket;
+pub use self::web_socket::*;
+mod event_stream;
+pub use self::event_stream::*;
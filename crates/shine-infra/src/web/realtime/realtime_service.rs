@@ -0,0 +1,299 @@
+use crate::{
+    session::{CurrentUser, CurrentUserService, UserSessionError},
+    telemetry::{metrics, MetricSet},
+    web::RealtimeConfig,
+};
+use axum::Extension;
+use opentelemetry::{
+    metrics::{Counter, Meter, UpDownCounter},
+    KeyValue,
+};
+use std::{sync::Arc, time::Duration};
+
+const DEFAULT_REVALIDATE_INTERVAL: u64 = 60;
+const DEFAULT_PING_INTERVAL: u64 = 30;
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+const DEFAULT_BUFFER_SIZE: usize = 32;
+
+/// The kind of a realtime connection used in the metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionKind {
+    WebSocket,
+    EventStream,
+}
+
+impl ConnectionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionKind::WebSocket => "ws",
+            ConnectionKind::EventStream => "sse",
+        }
+    }
+}
+
+/// The reason of closing a realtime connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The handler or the client closed the connection.
+    Closed,
+    /// The session of the user was revoked or has expired.
+    SessionRevoked,
+    /// No pong was received for a ping.
+    Unresponsive,
+    /// The handler could not keep up with the received messages.
+    Overloaded,
+    /// A transport error occurred.
+    Error,
+}
+
+impl CloseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::Closed => "closed",
+            CloseReason::SessionRevoked => "session_revoked",
+            CloseReason::Unresponsive => "unresponsive",
+            CloseReason::Overloaded => "overloaded",
+            CloseReason::Error => "error",
+        }
+    }
+}
+
+struct RealtimeMetrics {
+    connections: UpDownCounter<i64>,
+    messages: Counter<u64>,
+    closed: Counter<u64>,
+}
+
+impl MetricSet for RealtimeMetrics {
+    fn new(meter: &Meter) -> Self {
+        Self {
+            connections: meter.i64_up_down_counter("realtime_connections").build(),
+            messages: meter.u64_counter("realtime_messages").build(),
+            closed: meter.u64_counter("realtime_closed").build(),
+        }
+    }
+}
+
+/// Track an open connection in the metrics, the connection is counted as closed when the guard is dropped.
+pub struct ConnectionGuard {
+    service: Arc<RealtimeService>,
+    kind: ConnectionKind,
+    reason: CloseReason,
+}
+
+impl ConnectionGuard {
+    /// Set the reason reported when the connection is closed. Default: [CloseReason::Closed].
+    pub fn set_reason(&mut self, reason: CloseReason) {
+        self.reason = reason;
+    }
+
+    pub fn count_received(&self) {
+        self.service.count_message(self.kind, "received");
+    }
+
+    pub fn count_sent(&self) {
+        self.service.count_message(self.kind, "sent");
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let metrics = &self.service.metrics;
+        let kind = KeyValue::new("kind", self.kind.as_str());
+        metrics
+            .closed
+            .add(1, &[kind.clone(), KeyValue::new("reason", self.reason.as_str())]);
+        metrics.connections.add(-1, &[kind]);
+    }
+}
+
+/// Shared settings and metrics of the WebSocket and server-sent event connections.
+pub struct RealtimeService {
+    revalidate_interval: Duration,
+    ping_interval: Duration,
+    max_message_size: usize,
+    buffer_size: usize,
+    metrics: Arc<RealtimeMetrics>,
+}
+
+impl RealtimeService {
+    pub fn new(config: &RealtimeConfig) -> Self {
+        Self::with_metrics(config, metrics::<RealtimeMetrics>())
+    }
+
+    /// Create the service with metrics of the given meter, ex. to inspect the metrics in the tests.
+    #[cfg(test)]
+    pub(crate) fn with_meter(config: &RealtimeConfig, meter: &Meter) -> Self {
+        Self::with_metrics(config, Arc::new(RealtimeMetrics::new(meter)))
+    }
+
+    fn with_metrics(config: &RealtimeConfig, metrics: Arc<RealtimeMetrics>) -> Self {
+        Self {
+            revalidate_interval: Duration::from_secs(config.revalidate_interval.unwrap_or(DEFAULT_REVALIDATE_INTERVAL)),
+            ping_interval: Duration::from_secs(config.ping_interval.unwrap_or(DEFAULT_PING_INTERVAL)),
+            max_message_size: config.max_message_size.unwrap_or(DEFAULT_MAX_MESSAGE_SIZE),
+            buffer_size: config.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE),
+            metrics,
+        }
+    }
+
+    pub fn into_layer(self) -> Extension<Arc<Self>> {
+        Extension(Arc::new(self))
+    }
+
+    pub fn revalidate_interval(&self) -> Duration {
+        self.revalidate_interval
+    }
+
+    pub fn ping_interval(&self) -> Duration {
+        self.ping_interval
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn connect(self: &Arc<Self>, kind: ConnectionKind) -> ConnectionGuard {
+        self.metrics.connections.add(1, &[KeyValue::new("kind", kind.as_str())]);
+        ConnectionGuard {
+            service: self.clone(),
+            kind,
+            reason: CloseReason::Closed,
+        }
+    }
+
+    fn count_message(&self, kind: ConnectionKind, direction: &'static str) {
+        self.metrics.messages.add(
+            1,
+            &[
+                KeyValue::new("kind", kind.as_str()),
+                KeyValue::new("direction", direction),
+            ],
+        );
+    }
+}
+
+/// Complete when the session of the user is revoked or has expired. The session is checked periodically,
+/// transient errors of the session store are ignored.
+pub async fn session_revoked(user_service: Arc<CurrentUserService>, user: CurrentUser, interval: Duration) {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    loop {
+        ticker.tick().await;
+        match user_service.get_current_user(user.user_id, user.key).await {
+            Ok(_) => {}
+            Err(UserSessionError::RedisPoolError(err)) => {
+                log::warn!("[{}] Failed to revalidate the session: {err}", user.user_id);
+            }
+            Err(UserSessionError::RedisError(err)) => {
+                log::warn!("[{}] Failed to revalidate the session: {err}", user.user_id);
+            }
+            Err(err) => {
+                log::info!("[{}] Session is no longer valid: {err}", user.user_id);
+                return;
+            }
+        }
+    }
+}
+
+/// Find the value of a metric in the Prometheus text format, the labels are matched in any order.
+#[cfg(test)]
+pub(crate) fn find_metric<'a>(text: &'a str, name: &str, labels: &[(&str, &str)]) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let (labels_part, value) = line.strip_prefix(name)?.strip_prefix('{')?.rsplit_once("} ")?;
+        let line_labels = labels_part.split(',').collect::<Vec<_>>();
+        let is_match = line_labels.len() == labels.len()
+            && labels
+                .iter()
+                .all(|(key, value)| line_labels.contains(&format!("{key}=\"{value}\"").as_str()));
+        is_match.then_some(value)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::telemetry::PrometheusExporter;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+    use shine_test::test;
+
+    #[test]
+    fn connection_guard_metrics() {
+        let exporter = PrometheusExporter::new();
+        let provider = SdkMeterProvider::builder().with_reader(exporter.clone()).build();
+        let realtime = Arc::new(RealtimeService::with_meter(
+            &RealtimeConfig::default(),
+            &provider.meter("test"),
+        ));
+
+        let mut ws = realtime.connect(ConnectionKind::WebSocket);
+        let sse = realtime.connect(ConnectionKind::EventStream);
+        ws.count_received();
+        ws.count_sent();
+        sse.count_sent();
+
+        let text = exporter.collect().unwrap();
+        assert_eq!(
+            find_metric(&text, "realtime_connections", &[("kind", "ws")]),
+            Some("1"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(&text, "realtime_connections", &[("kind", "sse")]),
+            Some("1"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(
+                &text,
+                "realtime_messages_total",
+                &[("direction", "received"), ("kind", "ws")]
+            ),
+            Some("1"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(
+                &text,
+                "realtime_messages_total",
+                &[("direction", "sent"), ("kind", "sse")]
+            ),
+            Some("1"),
+            "{text}"
+        );
+
+        ws.set_reason(CloseReason::Unresponsive);
+        drop(ws);
+        drop(sse);
+
+        let text = exporter.collect().unwrap();
+        assert_eq!(
+            find_metric(&text, "realtime_connections", &[("kind", "ws")]),
+            Some("0"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(&text, "realtime_connections", &[("kind", "sse")]),
+            Some("0"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(
+                &text,
+                "realtime_closed_total",
+                &[("kind", "ws"), ("reason", "unresponsive")]
+            ),
+            Some("1"),
+            "{text}"
+        );
+        assert_eq!(
+            find_metric(&text, "realtime_closed_total", &[("kind", "sse"), ("reason", "closed")]),
+            Some("1"),
+            "{text}"
+        );
+    }
+}
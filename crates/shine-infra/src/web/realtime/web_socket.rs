@@ -0,0 +1,405 @@
+use crate::{
+    session::{CheckedCurrentUser, CurrentUser, CurrentUserService},
+    web::realtime::{
+        session_revoked, CloseReason, ConnectionGuard, ConnectionKind, JsonCodec, MessageCodec, RealtimeService,
+    },
+};
+use axum::{
+    body::Bytes,
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocketUpgrade},
+        FromRequestParts,
+    },
+    http::request::Parts,
+    response::{IntoResponse, Response},
+    Extension, RequestPartsExt,
+};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, future::Future, sync::Arc, time::Duration};
+use thiserror::Error as ThisError;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+#[derive(Debug, ThisError)]
+pub enum WsSendError {
+    #[error("Connection is closed")]
+    Closed,
+    #[error("Send buffer is full")]
+    Full,
+}
+
+/// Send typed messages to the client. The buffer of the messages is limited, [WsSender::send] waits
+/// for a free slot, while [WsSender::try_send] fails if the client cannot keep up with the messages.
+pub struct WsSender<Out>(mpsc::Sender<Out>);
+
+impl<Out> Clone for WsSender<Out> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<Out> WsSender<Out> {
+    pub async fn send(&self, message: Out) -> Result<(), WsSendError> {
+        self.0.send(message).await.map_err(|_| WsSendError::Closed)
+    }
+
+    pub fn try_send(&self, message: Out) -> Result<(), WsSendError> {
+        self.0.try_send(message).map_err(|err| match err {
+            TrySendError::Full(_) => WsSendError::Full,
+            TrySendError::Closed(_) => WsSendError::Closed,
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+/// Receive the typed messages of the client. Messages failing to decode are logged and skipped.
+pub struct WsReceiver<In>(mpsc::Receiver<In>);
+
+impl<In> WsReceiver<In> {
+    /// Receive the next message, `None` is returned once the connection is closed.
+    pub async fn recv(&mut self) -> Option<In> {
+        self.0.recv().await
+    }
+}
+
+/// An authenticated WebSocket connection with typed messages. The connection is closed when the
+/// handler returns (drops all the senders), the client disconnects or the session of the user is revoked.
+pub struct WsConnection<In, Out> {
+    user: CurrentUser,
+    sender: WsSender<Out>,
+    receiver: WsReceiver<In>,
+}
+
+impl<In, Out> WsConnection<In, Out> {
+    pub fn user(&self) -> &CurrentUser {
+        &self.user
+    }
+
+    pub async fn send(&self, message: Out) -> Result<(), WsSendError> {
+        self.sender.send(message).await
+    }
+
+    pub async fn recv(&mut self) -> Option<In> {
+        self.receiver.recv().await
+    }
+
+    pub fn into_parts(self) -> (CurrentUser, WsSender<Out>, WsReceiver<In>) {
+        (self.user, self.sender, self.receiver)
+    }
+}
+
+/// Extractor of a WebSocket upgrade of an authenticated user. The session of the user is revalidated
+/// periodically and the connection is closed on revocation, keep-alive pings are sent and the received
+/// messages are limited in size and buffering (see [RealtimeService]).
+pub struct AuthenticatedWebSocket {
+    upgrade: WebSocketUpgrade,
+    user: CurrentUser,
+    user_service: Arc<CurrentUserService>,
+    realtime: Arc<RealtimeService>,
+}
+
+impl AuthenticatedWebSocket {
+    pub fn user(&self) -> &CurrentUser {
+        &self.user
+    }
+
+    /// Complete the upgrade with json encoded messages.
+    pub fn on_upgrade<In, Out, F, Fut>(self, handler: F) -> Response
+    where
+        In: DeserializeOwned + Send + 'static,
+        Out: Serialize + Send + 'static,
+        F: FnOnce(WsConnection<In, Out>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let codec = JsonCodec::<In, Out>::default().with_max_message_size(self.realtime.max_message_size());
+        self.on_upgrade_with(codec, handler)
+    }
+
+    /// Complete the upgrade with a custom message codec.
+    pub fn on_upgrade_with<C, F, Fut>(self, codec: C, handler: F) -> Response
+    where
+        C: MessageCodec,
+        F: FnOnce(WsConnection<C::In, C::Out>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let Self {
+            upgrade,
+            user,
+            user_service,
+            realtime,
+        } = self;
+
+        upgrade
+            .max_message_size(realtime.max_message_size())
+            .on_upgrade(move |socket| async move {
+                let user_id = user.user_id;
+                let (outgoing_sender, outgoing) = mpsc::channel(realtime.buffer_size());
+                let (incoming, incoming_receiver) = mpsc::channel(realtime.buffer_size());
+                let revoked = session_revoked(user_service, user.clone(), realtime.revalidate_interval());
+                let connection = WsConnection {
+                    user,
+                    sender: WsSender(outgoing_sender),
+                    receiver: WsReceiver(incoming_receiver),
+                };
+
+                log::debug!("[{user_id}] WebSocket connected");
+                let (sink, stream) = socket.split();
+                let transport = Transport {
+                    codec,
+                    ping_interval: realtime.ping_interval(),
+                    guard: realtime.connect(ConnectionKind::WebSocket),
+                };
+                let (reason, _) = tokio::join!(
+                    transport.run(sink, stream, outgoing, incoming, revoked),
+                    handler(connection)
+                );
+                log::debug!("[{user_id}] WebSocket closed: {}", reason.as_str());
+            })
+    }
+}
+
+impl<S> FromRequestParts<S> for AuthenticatedWebSocket
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = CheckedCurrentUser::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?
+            .into_user();
+        let upgrade = WebSocketUpgrade::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(user_service) = parts
+            .extract::<Extension<Arc<CurrentUserService>>>()
+            .await
+            .expect("Missing CurrentUserService extension");
+        let Extension(realtime) = parts
+            .extract::<Extension<Arc<RealtimeService>>>()
+            .await
+            .expect("Missing RealtimeService extension");
+
+        Ok(Self {
+            upgrade,
+            user,
+            user_service,
+            realtime,
+        })
+    }
+}
+
+/// Pump the messages between the socket and the handler.
+struct Transport<C> {
+    codec: C,
+    ping_interval: Duration,
+    guard: ConnectionGuard,
+}
+
+impl<C> Transport<C>
+where
+    C: MessageCodec,
+{
+    async fn run<Si, St, E, R>(
+        mut self,
+        mut sink: Si,
+        mut stream: St,
+        mut outgoing: mpsc::Receiver<C::Out>,
+        incoming: mpsc::Sender<C::In>,
+        revoked: R,
+    ) -> CloseReason
+    where
+        Si: Sink<Message> + Unpin,
+        Si::Error: fmt::Display,
+        St: Stream<Item = Result<Message, E>> + Unpin,
+        E: fmt::Display,
+        R: Future<Output = ()>,
+    {
+        let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + self.ping_interval, self.ping_interval);
+        let mut awaiting_pong = false;
+        tokio::pin!(revoked);
+
+        let reason = loop {
+            tokio::select! {
+                message = stream.next() => match message {
+                    Some(Ok(Message::Pong(_))) => awaiting_pong = false,
+                    // pings are answered by the transport
+                    Some(Ok(Message::Ping(_))) => {}
+                    Some(Ok(Message::Close(_))) | None => break CloseReason::Closed,
+                    Some(Ok(message)) => {
+                        self.guard.count_received();
+                        match self.codec.decode(message) {
+                            Ok(message) => match incoming.try_send(message) {
+                                Ok(()) => {}
+                                Err(TrySendError::Full(_)) => break CloseReason::Overloaded,
+                                // the handler is not interested in the messages
+                                Err(TrySendError::Closed(_)) => {}
+                            },
+                            Err(err) => log::warn!("Received an invalid message: {err}"),
+                        }
+                    }
+                    Some(Err(err)) => {
+                        log::warn!("Failed to receive message: {err}");
+                        break CloseReason::Error;
+                    }
+                },
+                message = outgoing.recv() => match message {
+                    Some(message) => match self.codec.encode(&message) {
+                        Ok(message) => {
+                            if let Err(err) = sink.send(message).await {
+                                log::warn!("Failed to send message: {err}");
+                                break CloseReason::Error;
+                            }
+                            self.guard.count_sent();
+                        }
+                        Err(err) => log::error!("Failed to encode message: {err}"),
+                    },
+                    None => break CloseReason::Closed,
+                },
+                _ = ping.tick() => {
+                    if awaiting_pong {
+                        break CloseReason::Unresponsive;
+                    }
+                    awaiting_pong = true;
+                    if let Err(err) = sink.send(Message::Ping(Bytes::new())).await {
+                        log::warn!("Failed to send ping: {err}");
+                        break CloseReason::Error;
+                    }
+                },
+                _ = &mut revoked => break CloseReason::SessionRevoked,
+            }
+        };
+
+        let close = match reason {
+            CloseReason::Closed => Some((close_code::NORMAL, "closed")),
+            CloseReason::SessionRevoked => Some((close_code::POLICY, "session revoked")),
+            CloseReason::Overloaded => Some((close_code::SIZE, "too many messages")),
+            CloseReason::Unresponsive | CloseReason::Error => None,
+        };
+        if let Some((code, reason)) = close {
+            let frame = CloseFrame { code, reason: reason.into() };
+            let _ = sink.send(Message::Close(Some(frame))).await;
+        }
+
+        self.guard.set_reason(reason);
+        reason
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::web::RealtimeConfig;
+    use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+    use serde::Deserialize;
+    use shine_test::test;
+    use tokio::task::JoinHandle;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: u32,
+    }
+
+    struct Client {
+        to_server: UnboundedSender<Result<Message, axum::Error>>,
+        from_server: UnboundedReceiver<Message>,
+        outgoing: mpsc::Sender<Item>,
+        incoming: mpsc::Receiver<Item>,
+    }
+
+    impl Client {
+        fn send_text(&self, text: &str) {
+            self.to_server.unbounded_send(Ok(Message::Text(text.into()))).unwrap();
+        }
+
+        async fn close_code(&mut self) -> Option<u16> {
+            match self.from_server.next().await {
+                Some(Message::Close(Some(frame))) => Some(frame.code),
+                _ => None,
+            }
+        }
+    }
+
+    fn start<R>(buffer_size: usize, ping_interval: Duration, revoked: R) -> (Client, JoinHandle<CloseReason>)
+    where
+        R: Future<Output = ()> + Send + 'static,
+    {
+        let realtime = Arc::new(RealtimeService::new(&RealtimeConfig::default()));
+        let (to_server, stream) = unbounded();
+        let (sink, from_server) = unbounded();
+        let (outgoing, outgoing_receiver) = mpsc::channel(buffer_size);
+        let (incoming_sender, incoming) = mpsc::channel(buffer_size);
+
+        let transport = Transport {
+            codec: JsonCodec::<Item, Item>::default(),
+            ping_interval,
+            guard: realtime.connect(ConnectionKind::WebSocket),
+        };
+        let task = tokio::spawn(transport.run(sink, stream, outgoing_receiver, incoming_sender, revoked));
+        let client = Client {
+            to_server,
+            from_server,
+            outgoing,
+            incoming,
+        };
+        (client, task)
+    }
+
+    #[test]
+    async fn exchange_messages() {
+        let (mut client, task) = start(4, Duration::from_secs(60), futures::future::pending());
+
+        client.send_text("{\"id\":1}");
+        client.send_text("invalid");
+        client.send_text("{\"id\":2}");
+        assert_eq!(client.incoming.recv().await, Some(Item { id: 1 }));
+        assert_eq!(client.incoming.recv().await, Some(Item { id: 2 }));
+
+        client.outgoing.send(Item { id: 3 }).await.unwrap();
+        assert!(matches!(client.from_server.next().await, Some(Message::Text(text)) if text.as_str() == "{\"id\":3}"));
+
+        // the handler has returned
+        drop(client.outgoing);
+        assert_eq!(task.await.unwrap(), CloseReason::Closed);
+        assert_eq!(client.incoming.recv().await, None);
+        let close = client.from_server.next().await;
+        assert!(matches!(close, Some(Message::Close(Some(frame))) if frame.code == close_code::NORMAL));
+    }
+
+    #[test]
+    async fn close_on_revocation() {
+        let revoked = tokio::time::sleep(Duration::from_millis(10));
+        let (mut client, task) = start(4, Duration::from_secs(60), revoked);
+
+        assert_eq!(task.await.unwrap(), CloseReason::SessionRevoked);
+        assert_eq!(client.close_code().await, Some(close_code::POLICY));
+    }
+
+    #[test]
+    async fn close_on_overload() {
+        let (mut client, task) = start(2, Duration::from_secs(60), futures::future::pending());
+
+        for id in 0..3 {
+            client.send_text(&format!("{{\"id\":{id}}}"));
+        }
+        assert_eq!(task.await.unwrap(), CloseReason::Overloaded);
+        assert_eq!(client.close_code().await, Some(close_code::SIZE));
+    }
+
+    #[test]
+    async fn close_unresponsive() {
+        let (mut client, task) = start(2, Duration::from_millis(10), futures::future::pending());
+
+        assert!(matches!(client.from_server.next().await, Some(Message::Ping(_))));
+        client
+            .to_server
+            .unbounded_send(Ok(Message::Pong(Bytes::new())))
+            .unwrap();
+        assert!(matches!(client.from_server.next().await, Some(Message::Ping(_))));
+        assert_eq!(task.await.unwrap(), CloseReason::Unresponsive);
+    }
+}
@@ -2,3 +2,5 @@ mod problem_detail;
 pub use self::problem_detail::*;
 mod page;
 pub use self::page::*;
+mod streaming;
+pub use self::streaming::*;
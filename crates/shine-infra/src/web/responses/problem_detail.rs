@@ -1,12 +1,21 @@
-use crate::serde::serde_status_code;
+use crate::{
+    language::{Language, MessageCatalog},
+    serde::serde_status_code,
+    web::extracts::AcceptLanguage,
+};
 use axum::{
-    http::StatusCode,
+    http::{HeaderMap, Request, StatusCode, Uri},
     response::{IntoResponse, Response},
-    Extension, Json,
+    Json,
 };
 use serde::Serialize;
 use serde_json::Value as JsonValue;
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
 use url::Url;
 
 pub trait ProblemType {
@@ -93,6 +102,10 @@ impl Problem {
         Self::new(StatusCode::PRECONDITION_FAILED, ty)
     }
 
+    pub fn too_many_requests(ty: &'static str) -> Self {
+        Self::new(StatusCode::TOO_MANY_REQUESTS, ty)
+    }
+
     pub fn with_detail<S: ToString>(self, detail: S) -> Self {
         Self {
             detail: detail.to_string(),
@@ -150,22 +163,61 @@ impl Problem {
 #[derive(Clone)]
 pub struct ProblemConfig {
     include_internal: bool,
+    catalog: Arc<MessageCatalog>,
+    language: Language,
 }
 
 impl ProblemConfig {
     pub fn new(include_internal: bool) -> Self {
-        Self { include_internal }
+        Self {
+            include_internal,
+            catalog: Arc::new(MessageCatalog::core()),
+            language: Language::default(),
+        }
+    }
+
+    /// Set the messages used to localize the detail of the problems.
+    pub fn with_catalog(self, catalog: MessageCatalog) -> Self {
+        Self {
+            language: catalog.default_language().clone(),
+            catalog: Arc::new(catalog),
+            ..self
+        }
+    }
+
+    /// Set the language of the problem details, usually it is negotiated for each request by the layer.
+    pub fn with_language(self, language: Language) -> Self {
+        Self { language, ..self }
+    }
+
+    pub fn catalog(&self) -> &MessageCatalog {
+        &self.catalog
+    }
+
+    pub fn language(&self) -> &Language {
+        &self.language
+    }
+
+    /// Create a config for the language negotiated for the request.
+    pub fn for_request(&self, uri: &Uri, headers: &HeaderMap) -> Self {
+        let AcceptLanguage(language) = AcceptLanguage::negotiate(&self.catalog, uri, headers);
+        self.clone().with_language(language)
     }
 
-    pub fn into_layer(self) -> Extension<Self> {
-        Extension(self)
+    /// Create a layer that provides the [ProblemConfig] and the [AcceptLanguage] negotiated for each request
+    /// as request extensions.
+    pub fn into_layer(self) -> ProblemConfigLayer {
+        ProblemConfigLayer { config: self }
     }
 
     pub fn transform<P>(&self, problem: P) -> Problem
     where
         P: Into<Problem>,
     {
-        let problem = problem.into();
+        let mut problem = problem.into();
+        if let Some(detail) = self.catalog.message(&self.language, problem.ty) {
+            problem.detail = detail.to_string();
+        }
         if !self.include_internal {
             Problem {
                 sensitive: Default::default(),
@@ -177,6 +229,51 @@ impl ProblemConfig {
     }
 }
 
+#[derive(Clone)]
+pub struct ProblemConfigLayer {
+    config: ProblemConfig,
+}
+
+impl<S> Layer<S> for ProblemConfigLayer {
+    type Service = ProblemConfigMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProblemConfigMiddleware {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+#[must_use]
+pub struct ProblemConfigMiddleware<S> {
+    inner: S,
+    config: ProblemConfig,
+}
+
+impl<S, B> Service<Request<B>> for ProblemConfigMiddleware<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let config = self.config.for_request(request.uri(), request.headers());
+        request
+            .extensions_mut()
+            .insert(AcceptLanguage(config.language().clone()));
+        request.extensions_mut().insert(config);
+        self.inner.call(request)
+    }
+}
+
 pub trait IntoProblemResponse {
     fn into_response(self, config: &ProblemConfig) -> ProblemResponse;
 }
@@ -247,3 +344,40 @@ where
         ProblemResponse::new(&config, problem).into_response()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, http::header, routing::get, Extension, Router};
+    use shine_test::test;
+    use tower::ServiceExt;
+
+    async fn not_found(Extension(problem_config): Extension<ProblemConfig>) -> ProblemResponse {
+        Problem::not_found()
+            .with_detail("Item not found")
+            .into_response(&problem_config)
+    }
+
+    async fn get_detail(router: &Router, accept_language: &str) -> String {
+        let request = Request::get("/item")
+            .header(header::ACCEPT_LANGUAGE, accept_language)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem = serde_json::from_slice::<JsonValue>(&body).unwrap();
+        problem["detail"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    async fn localized_detail() {
+        let catalog = MessageCatalog::core().with_messages(Language::HU, [("not-found", "Nincs ilyen elem")]);
+        let router = Router::new()
+            .route("/item", get(not_found))
+            .layer(ProblemConfig::new(false).with_catalog(catalog).into_layer());
+
+        assert_eq!(get_detail(&router, "en-US").await, "Item not found");
+        assert_eq!(get_detail(&router, "de, hu;q=0.5").await, "Nincs ilyen elem");
+    }
+}
@@ -0,0 +1,133 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderValue},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    BoxError,
+};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::time::Duration;
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Stream the items as newline delimited json. An error of the stream aborts the response.
+pub struct NdJson<S>(pub S);
+
+impl<S, T, E> IntoResponse for NdJson<S>
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Serialize,
+    E: Into<BoxError>,
+{
+    fn into_response(self) -> Response {
+        let body = self.0.map(|item| {
+            let mut line = serde_json::to_vec(&item.map_err(Into::into)?)?;
+            line.push(b'\n');
+            Ok::<_, BoxError>(Bytes::from(line))
+        });
+
+        (
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static(NDJSON_CONTENT_TYPE)),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            Body::from_stream(body),
+        )
+            .into_response()
+    }
+}
+
+/// Stream the items as server-sent events with json data.
+pub struct JsonEvents<S> {
+    stream: S,
+    event: Option<&'static str>,
+    keep_alive: Duration,
+}
+
+impl<S, T> JsonEvents<S>
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: Serialize,
+{
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            event: None,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+        }
+    }
+
+    /// Set the name of the events. Default: unnamed, ie. `message` events.
+    pub fn with_event(self, event: &'static str) -> Self {
+        Self { event: Some(event), ..self }
+    }
+
+    /// Set the interval of the keep-alive comments. Default: 15s.
+    pub fn with_keep_alive(self, keep_alive: Duration) -> Self {
+        Self { keep_alive, ..self }
+    }
+}
+
+impl<S, T> IntoResponse for JsonEvents<S>
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: Serialize,
+{
+    fn into_response(self) -> Response {
+        let event = self.event;
+        let events = self.stream.map(move |item| match event {
+            Some(event) => Event::default().event(event).json_data(item),
+            None => Event::default().json_data(item),
+        });
+
+        Sse::new(events)
+            .keep_alive(KeepAlive::new().interval(self.keep_alive))
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{http::Request, routing::get, Router};
+    use shine_test::test;
+    use tower::ServiceExt;
+
+    async fn get_body(router: &Router, path: &str) -> (String, String) {
+        let request = Request::get(path).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    async fn stream_items() {
+        let items = || futures::stream::iter([serde_json::json!({"id": 1}), serde_json::json!({"id": 2})]);
+        let router = Router::new()
+            .route(
+                "/ndjson",
+                get(move || async move { NdJson(items().map(Ok::<_, std::io::Error>)) }),
+            )
+            .route(
+                "/sse",
+                get(move || async move { JsonEvents::new(items()).with_event("item") }),
+            );
+
+        assert_eq!(
+            get_body(&router, "/ndjson").await,
+            (NDJSON_CONTENT_TYPE.to_string(), "{\"id\":1}\n{\"id\":2}\n".to_string())
+        );
+        assert_eq!(
+            get_body(&router, "/sse").await,
+            (
+                "text/event-stream".to_string(),
+                "event: item\ndata: {\"id\":1}\n\nevent: item\ndata: {\"id\":2}\n\n".to_string()
+            )
+        );
+    }
+}
@@ -1,31 +1,474 @@
+use crate::{
+    crypto::JwtAlgorithm, email::EmailNormalizationConfig, session::PolicyConfig, web::extracts::ValidationErrorEx,
+};
+use ipnet::IpNet;
+use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use validator::{Validate, ValidationError};
 
 /// The application configuration
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct TlsConfig {
     pub cert: String,
     pub key: String,
 }
 
+/// A key used to sign or verify the tokens.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenKeyConfig {
+    /// The key id published in the JWKS and in the token header.
+    #[validate(length(min = 1))]
+    pub kid: String,
+    pub algorithm: JwtAlgorithm,
+    /// Base64 (url-safe) encoded PKCS#8 private key. Only the issuing service requires it.
+    pub private_key: Option<String>,
+    /// Base64 (url-safe) encoded raw public key. Ignored if the private key is present.
+    pub public_key: Option<String>,
+}
+
+/// Signed (JWT) token configuration
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_signing_key"))]
+pub struct TokenConfig {
+    /// The issuer of the tokens.
+    #[validate(length(min = 1))]
+    pub issuer: String,
+    /// The audience accepted by this service and the default audience of the issued tokens.
+    #[validate(length(min = 1))]
+    pub audience: String,
+    /// Time to live of the issued tokens in seconds.
+    #[validate(range(min = 1))]
+    pub ttl: u64,
+    /// Allowed clock skew in seconds during validation. Default: 30.
+    pub leeway: Option<u64>,
+    /// The id of the key used for signing. If not given, tokens are not issued, only verified.
+    pub signing_key: Option<String>,
+    /// The keys used for signing and verification. Keep the retired keys for a while to allow rotation.
+    #[validate(length(min = 1), nested)]
+    pub keys: Vec<TokenKeyConfig>,
+}
+
+fn validate_signing_key(config: &TokenConfig) -> Result<(), ValidationError> {
+    match &config.signing_key {
+        Some(kid) if !config.keys.iter().any(|key| &key.kid == kid) => Err(ValidationError::new("signing_key")
+            .with_message("The signing key is not in the list of keys".into())
+            .with_param("kid", kid)),
+        _ => Ok(()),
+    }
+}
+
+/// In-process cache of the user sessions
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCacheConfig {
+    /// Time to live of the cached sessions in seconds. It limits the staleness if an invalidation is lost.
+    #[validate(range(min = 1))]
+    pub ttl: u64,
+    /// The minimum time between two expiration extension of a session in Redis in seconds. Default: 60.
+    pub extend_interval: Option<u64>,
+    /// The maximum number of cached sessions. Default: 10000.
+    #[validate(range(min = 1))]
+    pub capacity: Option<usize>,
+}
+
+/// The property of the request used to identify the client for rate limiting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum RateLimitKey {
+    Ip,
+    /// The fingerprint of the client within its ip, it separates the clients behind a shared address (ex. NAT).
+    Fingerprint,
+    /// The user of the session, anonymous requests are limited by ip.
+    User,
+    /// The api key or bearer token, requests without it are limited by ip.
+    ApiKey,
+}
+
+/// Resolution of the client address and location
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientIpConfig {
+    /// Network ranges (CIDR) of the trusted reverse proxies. The Forwarded and X-Forwarded-For headers are
+    /// considered only if the peer is a trusted proxy, otherwise the client is identified by the address of the peer.
+    #[serde(default)]
+    #[validate(custom(function = "validate_networks"))]
+    pub trusted_proxies: Vec<String>,
+    /// Network ranges (CIDR) of the Cloudflare proxies. The CF-Connecting-IP and the geo location headers are
+    /// considered only if the request was received from one of these ranges, directly or as the last hop before
+    /// the trusted proxies.
+    #[serde(default)]
+    #[validate(custom(function = "validate_networks"))]
+    pub cloudflare_proxies: Vec<String>,
+    /// Path of an offline GeoIP database (MaxMind City format) used to locate the clients when the location
+    /// is not provided by a trusted proxy. Default: none.
+    pub geo_ip_database: Option<String>,
+}
+
+/// Weights of the client fingerprint components
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintWeightsConfig {
+    /// The browser family from the client hints or the User-Agent without versions. Default: 0.5
+    #[validate(range(min = 0.0))]
+    pub agent: Option<f32>,
+    /// The platform and mobile client hints. Default: 0.2
+    #[validate(range(min = 0.0))]
+    pub platform: Option<f32>,
+    /// The Accept-Language header. Default: 0.1
+    #[validate(range(min = 0.0))]
+    pub language: Option<f32>,
+    /// The device id provided by the client in the X-Device-Id header. Default: 0.5
+    #[validate(range(min = 0.0))]
+    pub device: Option<f32>,
+    /// The network prefix (/24 for IPv4, /48 for IPv6) of the client. Default: 0.15
+    #[validate(range(min = 0.0))]
+    pub network: Option<f32>,
+}
+
+/// Client fingerprinting to detect stolen sessions and tokens
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintConfig {
+    /// The minimum weighted similarity of the fingerprints to accept the client. With the default weights a client
+    /// matching only the user agent is rejected from an other network. Default: 0.8
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub min_similarity: Option<f32>,
+    #[serde(default)]
+    #[validate(nested)]
+    pub weights: FingerprintWeightsConfig,
+}
+
+/// Rate limit rule of a route group
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitRuleConfig {
+    pub key: RateLimitKey,
+    /// The number of requests allowed in a period.
+    #[validate(range(min = 1))]
+    pub limit: u32,
+    /// The length of the period in seconds.
+    #[validate(range(min = 1))]
+    pub period: u64,
+    /// The number of requests allowed in a burst. Default: limit.
+    pub burst: Option<u32>,
+}
+
+/// Rate limiting configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    /// Redis connection string of the shared counters. Default: the session redis.
+    pub redis_cns: Option<String>,
+    /// The rules of the route groups, groups without a rule are not limited.
+    #[serde(default)]
+    #[validate(nested)]
+    pub groups: HashMap<String, RateLimitRuleConfig>,
+}
+
+/// Idempotent replay of the mutating requests
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct IdempotencyConfig {
+    /// Redis connection string of the stored responses. Default: the session redis.
+    pub redis_cns: Option<String>,
+    /// The time in seconds a response is kept for replay. Default: 86400.
+    #[validate(range(min = 1))]
+    pub ttl: Option<u64>,
+    /// The time in seconds a key is reserved for a request in progress. The key is released earlier if the request
+    /// fails or it is dropped. Default: the request timeout.
+    #[validate(range(min = 1))]
+    pub lease: Option<u64>,
+    /// The maximum size of the request and of the stored response body in bytes. Default: 65536.
+    #[validate(range(min = 1))]
+    pub max_body_size: Option<usize>,
+}
+
+/// Outgoing http requests of the services
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpClientConfig {
+    /// The timeout of a request in seconds. Default: 30.
+    #[validate(range(min = 1))]
+    pub timeout: Option<u64>,
+    /// The timeout of establishing the connection in seconds. Default: 10.
+    #[validate(range(min = 1))]
+    pub connect_timeout: Option<u64>,
+    /// The number of retries of the idempotent requests on connection and server errors. Default: 2.
+    #[validate(range(max = 10))]
+    pub max_retries: Option<u32>,
+    /// Path of the additional (PEM) root certificates.
+    #[serde(default)]
+    pub ca_certificates: Vec<String>,
+    /// Disable the validation of the certificates, do NOT use in production. Default: false.
+    pub ignore_certificates: Option<bool>,
+}
+
+/// Long living WebSocket and server-sent event connections
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeConfig {
+    /// The interval in seconds to check if the session of the user is still valid. Default: 60.
+    #[validate(range(min = 1))]
+    pub revalidate_interval: Option<u64>,
+    /// The interval in seconds of the WebSocket pings, the connection is closed if no pong is received
+    /// until the next ping. Default: 30.
+    #[validate(range(min = 1))]
+    pub ping_interval: Option<u64>,
+    /// The maximum size of a received WebSocket message in bytes. Default: 65536.
+    #[validate(range(min = 1))]
+    pub max_message_size: Option<usize>,
+    /// The number of messages buffered in each direction. If the handler cannot keep up with the received
+    /// messages, the connection is closed. Default: 32.
+    #[validate(range(min = 1))]
+    pub buffer_size: Option<usize>,
+}
+
+/// Request body size limit of the routes matching a pattern
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyLimitRouteConfig {
+    /// Regular expression matched against the path of the request.
+    #[validate(custom(function = "validate_pattern"))]
+    pub path: String,
+    /// The maximum size of the request body in bytes.
+    #[validate(range(min = 1))]
+    pub max_body_size: usize,
+}
+
+/// Request size and time limits
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestLimitsConfig {
+    /// The maximum size of the request body in bytes. Default: 2097152.
+    #[validate(range(min = 1))]
+    pub max_body_size: Option<usize>,
+    /// Overrides of the body size limit, ex. for uploads. The first matching route is used.
+    #[serde(default)]
+    #[validate(nested)]
+    pub routes: Vec<BodyLimitRouteConfig>,
+    /// The time limit of producing the response (excluding the streaming of the body) in seconds. Default: 30.
+    #[validate(range(min = 1))]
+    pub timeout: Option<u64>,
+}
+
+/// The supported response compression algorithms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum CompressionAlgorithm {
+    Gzip,
+    Br,
+    Zstd,
+}
+
+/// Response compression
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionConfig {
+    /// Default: true.
+    pub enabled: Option<bool>,
+    /// The algorithms offered for the Accept-Encoding negotiation. Default: all.
+    pub algorithms: Option<Vec<CompressionAlgorithm>>,
+    /// The compressed content types, a content type is matched if it starts with an item of the list.
+    /// Default: text/html, text/css, text/plain, text/javascript, application/json, application/problem+json,
+    /// application/javascript, image/svg+xml.
+    pub content_types: Option<Vec<String>>,
+    /// The minimum size of the response body in bytes to compress. Default: 1024.
+    pub min_size: Option<u16>,
+}
+
+/// Configuration reload
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigReloadConfig {
+    /// The time in seconds between checking the file layers for changes. Default: 5.
+    #[validate(range(min = 1))]
+    pub watch_interval: Option<u64>,
+    /// The time in seconds between refreshing the remote (key vault) layers. Default: never.
+    pub refresh_interval: Option<u64>,
+}
+
+/// Health check configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthConfig {
+    /// The time limit of a single dependency probe in milliseconds, slower probes are reported as unhealthy. Default: 3000.
+    pub probe_timeout: Option<u64>,
+    /// The time in seconds between the background probes of the dependencies, the health endpoints report the
+    /// result of the last probe. Default: 10.
+    #[validate(range(min = 1))]
+    pub probe_interval: Option<u64>,
+    /// The time in seconds between reporting not ready and closing the listener on shutdown, it lets the load
+    /// balancers drain the traffic. Default: 0.
+    pub drain_delay: Option<u64>,
+}
+
+/// Strict-Transport-Security header
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_hsts_preload"))]
+pub struct HstsConfig {
+    /// The time in seconds the browser should access the site only over https.
+    pub max_age: u64,
+    #[serde(default)]
+    pub include_sub_domains: bool,
+    /// Request the inclusion in the preload list of the browsers. It requires includeSubDomains and a max age
+    /// of at least one year.
+    #[serde(default)]
+    pub preload: bool,
+}
+
+fn validate_hsts_preload(config: &HstsConfig) -> Result<(), ValidationError> {
+    if config.preload && (!config.include_sub_domains || config.max_age < 31536000) {
+        Err(ValidationError::new("preload")
+            .with_message("Preload requires includeSubDomains and a max age of at least one year".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Security headers of the responses. The unset fields are inherited from the default profile, an empty value
+/// removes the header.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityHeadersProfileConfig {
+    /// Content-Security-Policy, the `{nonce}` placeholder is replaced by the random nonce of the response.
+    pub content_security_policy: Option<String>,
+    /// Report the violations of the Content-Security-Policy without enforcing it.
+    pub content_security_policy_report_only: Option<bool>,
+    /// Default: one year including the subdomains.
+    #[validate(nested)]
+    pub hsts: Option<HstsConfig>,
+    pub permissions_policy: Option<String>,
+    /// Cross-Origin-Opener-Policy, `same-origin` is required (with COEP) for SharedArrayBuffer.
+    pub cross_origin_opener_policy: Option<String>,
+    /// Cross-Origin-Embedder-Policy, `require-corp` or `credentialless` is required (with COOP) for SharedArrayBuffer.
+    pub cross_origin_embedder_policy: Option<String>,
+    pub cross_origin_resource_policy: Option<String>,
+    /// X-Frame-Options. Default: DENY.
+    pub frame_options: Option<String>,
+    pub referrer_policy: Option<String>,
+}
+
+/// Security header configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityHeadersConfig {
+    /// The profile of the routes without an explicit profile.
+    #[serde(default)]
+    #[validate(nested)]
+    pub default: SecurityHeadersProfileConfig,
+    /// Named profiles overriding the default profile, the routes select them by name.
+    #[serde(default)]
+    #[validate(nested)]
+    pub profiles: HashMap<String, SecurityHeadersProfileConfig>,
+}
+
 /// The application configuration
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct ServiceConfig {
     pub tls: Option<TlsConfig>,
     pub port: u16,
     /// Regular expressions for the allowed origins.
+    #[validate(custom(function = "validate_patterns"))]
     pub allowed_origins: Vec<String>,
     /// Indicates if the full problem response should be returned. In production, it should be `false`.
     pub full_problem_response: bool,
     /// The secret for the used captcha solution.
+    #[validate(length(min = 1))]
     pub captcha_secret: String,
     /// The user session secret for the used cookie validation
+    #[validate(length(min = 1))]
     pub session_secret: String,
     /// The user session time to live - auto logout after this time of "inactivity"
+    #[validate(range(min = 1))]
     pub session_ttl: u64,
+    /// The absolute lifetime of a session in seconds since the login independent of the activity. Default: unlimited.
+    #[serde(default)]
+    pub session_max_lifetime: Option<u64>,
+    /// The maximum number of concurrent sessions of a user, the oldest sessions are closed on a new login. Default: unlimited.
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub session_max_count: Option<usize>,
+    /// Sensitive operations require a login not older than this many seconds. The logins with a remember-me token
+    /// keep the time of the original authentication. Default: disabled.
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub session_step_up_age: Option<u64>,
+    /// The domain of the session cookie issued by the identity service. If set, the cookie of a session with an
+    /// upgraded (legacy) fingerprint is re-issued. Default: the cookie is not re-issued.
+    #[serde(default)]
+    pub session_cookie_domain: Option<String>,
+    /// Cache the sessions in the process to reduce the load on Redis. Default: disabled.
+    #[serde(default)]
+    #[validate(nested)]
+    pub session_cache: Option<SessionCacheConfig>,
     /// The get up-to-date session information of the current user
+    #[validate(length(min = 1))]
     pub session_redis_cns: String,
+    /// Signed token configuration for clients without cookie support and for service-to-service calls.
+    #[serde(default)]
+    #[validate(nested)]
+    pub token: Option<TokenConfig>,
+    /// Trusted proxies and geo location of the clients.
+    #[serde(default)]
+    #[validate(nested)]
+    pub client_ip: ClientIpConfig,
+    /// Fingerprinting of the clients.
+    #[serde(default)]
+    #[validate(nested)]
+    pub fingerprint: FingerprintConfig,
+    /// Request rate limits of the route groups.
+    #[serde(default)]
+    #[validate(nested)]
+    pub rate_limit: RateLimitConfig,
+    /// Replay of the requests with an Idempotency-Key header.
+    #[serde(default)]
+    #[validate(nested)]
+    pub idempotency: IdempotencyConfig,
+    /// Session revalidation, keep-alive and buffering of the WebSocket and SSE connections.
+    #[serde(default)]
+    #[validate(nested)]
+    pub realtime: RealtimeConfig,
+    /// Timeouts, retries and certificates of the outgoing http requests.
+    #[serde(default)]
+    #[validate(nested)]
+    pub http_client: HttpClientConfig,
+    /// Email normalization rules and disposable email domains. Default: the builtin rules and domains.
+    #[serde(default)]
+    #[validate(nested)]
+    pub email_normalization: EmailNormalizationConfig,
+    /// Directory of the translation files (ex. `hu.json`), the messages override the built-in translations.
+    #[serde(default)]
+    pub translations: Option<String>,
+    /// Request body size limits and request timeout.
+    #[serde(default)]
+    #[validate(nested)]
+    pub limits: RequestLimitsConfig,
+    /// Compression of the responses.
+    #[serde(default)]
+    #[validate(nested)]
+    pub compression: CompressionConfig,
+    /// Security headers of the responses.
+    #[serde(default)]
+    #[validate(nested)]
+    pub security_headers: SecurityHeadersConfig,
+    /// Role to permission mapping, it extends the builtin policy of the service.
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    /// Reload the configuration on change. Only the allowed origins and the default log level are applied without
+    /// restart. Default: disabled.
+    #[serde(default)]
+    #[validate(nested)]
+    pub config_reload: Option<ConfigReloadConfig>,
+    /// Health check of the dependencies and readiness on shutdown.
+    #[serde(default)]
+    pub health: HealthConfig,
     /// Expose x-powered-by response header with service name and version. Default: false.
     #[serde(default)]
     pub expose_powered_by: bool,
@@ -33,3 +476,30 @@ pub struct ServiceConfig {
     #[serde(default)]
     pub expose_api_docs: bool,
 }
+
+/// Check if the item is a valid regular expression.
+pub fn validate_pattern(pattern: &str) -> Result<(), ValidationError> {
+    match Regex::new(pattern) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(ValidationError::new("regex")
+            .with_message(err.to_string().into())
+            .with_param("pattern", &pattern)),
+    }
+}
+
+/// Check if all the items are valid regular expressions.
+pub fn validate_patterns(patterns: &[String]) -> Result<(), ValidationError> {
+    patterns.iter().try_for_each(|pattern| validate_pattern(pattern))
+}
+
+/// Check if all the items are valid network ranges (CIDR).
+pub fn validate_networks(networks: &[String]) -> Result<(), ValidationError> {
+    for network in networks {
+        if network.parse::<IpNet>().is_err() {
+            return Err(ValidationError::new("cidr")
+                .with_message("Invalid network range".into())
+                .with_param("network", network));
+        }
+    }
+    Ok(())
+}
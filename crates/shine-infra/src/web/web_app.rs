@@ -1,28 +1,50 @@
 use crate::{
-    health::HealthService,
-    session::CurrentUserService,
+    crypto::JwtService,
+    email::EmailNormalizer,
+    health::{HealthService, Readiness},
+    language::MessageCatalog,
+    session::{CurrentUserService, PolicyConfig, PolicyService},
     telemetry::TelemetryService,
     web::{
-        middlewares::{PoweredBy, SecurityHeaders},
+        extracts::{ClientIpResolver, WeightedFingerprint},
+        middlewares::{
+            create_compression_layer, BodyLimit, IdempotencyStore, PoweredBy, RateLimiter, ReissueSessionCookie,
+            RequestTimeout, SecurityHeaders, DEFAULT_REQUEST_TIMEOUT, IDEMPOTENCY_KEY_HEADER,
+            IDEMPOTENT_REPLAYED_HEADER,
+        },
+        realtime::RealtimeService,
         responses::ProblemConfig,
-        ApiUrl, FeatureConfig, WebAppConfig,
+        split_versioned_openapi, ApiUrl, ApiVersionSelector, ConfigWatcher, FeatureConfig, WebAppConfig,
+        API_VERSION_HEADER,
     },
 };
 use anyhow::{anyhow, Error as AnyError};
 use axum::{
-    http::{header, Method},
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, Method},
     routing::Router,
+    Extension,
 };
 use axum_server::Handle;
 use regex::bytes::Regex;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
-use std::{env, fmt::Debug, fs, future::Future, net::SocketAddr, time::Duration as StdDuration};
-use tokio::{net::TcpListener, runtime::Runtime, signal, time::sleep};
+use std::{
+    env,
+    fmt::Debug,
+    fs,
+    future::Future,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration as StdDuration,
+};
+use tokio::{net::TcpListener, runtime::Runtime, signal, sync::watch, time::sleep};
+use tower::Layer;
 use tower_http::{
     cors::{AllowOrigin, CorsLayer},
     trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
 };
-use tracing::{instrument, level_filters::LevelFilter, Level};
+use tracing::{dispatcher::DefaultGuard, instrument, level_filters::LevelFilter, Level};
 use tracing_log::LogTracer;
 use tracing_subscriber::EnvFilter;
 use utoipa::{
@@ -30,7 +52,8 @@ use utoipa::{
     OpenApi, ToResponse,
 };
 use utoipa_axum::router::OpenApiRouter;
-use utoipa_swagger_ui::{Config as SwaggerConfig, SwaggerUi};
+use utoipa_swagger_ui::{Config as SwaggerConfig, SwaggerUi, Url as SwaggerUrl};
+use validator::Validate;
 
 #[derive(OpenApi)]
 #[openapi(paths(), components(), tags())]
@@ -83,8 +106,18 @@ async fn shutdown_signal() {
     }
 }
 
-async fn graceful_shutdown(handle: Handle<SocketAddr>) {
+/// Wait for the shutdown signal, then report not ready and wait for the load balancers to drain the traffic.
+async fn drain_signal(readiness: Readiness, drain_delay: StdDuration) {
     shutdown_signal().await;
+    readiness.set_draining();
+    if !drain_delay.is_zero() {
+        log::info!("Draining traffic for {}s...", drain_delay.as_secs());
+        sleep(drain_delay).await;
+    }
+}
+
+async fn graceful_shutdown(handle: Handle<SocketAddr>, readiness: Readiness, drain_delay: StdDuration) {
+    drain_signal(readiness, drain_delay).await;
     handle.graceful_shutdown(Some(StdDuration::from_secs(10)));
 
     loop {
@@ -94,13 +127,24 @@ async fn graceful_shutdown(handle: Handle<SocketAddr>) {
 }
 
 pub trait WebApplication {
-    type AppConfig: FeatureConfig + DeserializeOwned + Debug + Send + Sync + 'static;
+    type AppConfig: FeatureConfig + DeserializeOwned + Validate + JsonSchema + Debug + Send + Sync + 'static;
     type AppState: Clone + Send + Sync + 'static;
 
     fn feature_name(&self) -> &'static str {
         Self::AppConfig::NAME
     }
 
+    /// The builtin role to permission mapping of the service. It is extended by the core policy and the configuration.
+    fn policy(&self) -> PolicyConfig {
+        PolicyConfig::default()
+    }
+
+    /// The localized messages of the service, ex. the translations of the problem details. It extends the core
+    /// catalog.
+    fn messages(&self) -> MessageCatalog {
+        MessageCatalog::default()
+    }
+
     fn create(
         &self,
         config: &WebAppConfig<Self::AppConfig>,
@@ -109,16 +153,38 @@ pub trait WebApplication {
     ) -> impl Future<Output = Result<Self::AppState, AnyError>> + Send;
 }
 
-fn create_cors_layer(allowed_origins: &[String]) -> Result<CorsLayer, AnyError> {
-    let allowed_origins = allowed_origins
+fn compile_allowed_origins(allowed_origins: &[String]) -> Result<Vec<Regex>, AnyError> {
+    allowed_origins
         .iter()
         .map(|r| Regex::new(r))
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|err| anyhow!("Cors config error: {err}"))?;
+        .map_err(|err| anyhow!("Cors config error: {err}"))
+}
+
+fn create_cors_layer(mut allowed_origins: watch::Receiver<Vec<String>>) -> Result<CorsLayer, AnyError> {
+    let patterns = compile_allowed_origins(&allowed_origins.borrow_and_update())?;
+    let patterns = Arc::new(RwLock::new(patterns));
+
+    {
+        let patterns = patterns.clone();
+        tokio::spawn(async move {
+            while allowed_origins.changed().await.is_ok() {
+                let origins = allowed_origins.borrow_and_update().clone();
+                match compile_allowed_origins(&origins) {
+                    Ok(new_patterns) => {
+                        *patterns.write().unwrap() = new_patterns;
+                        log::info!("Cors allowed origins changed");
+                    }
+                    Err(err) => log::error!("{err}"),
+                }
+            }
+        });
+    }
+
     let cors = CorsLayer::default()
         .allow_origin(AllowOrigin::predicate(move |origin, _| {
             let origin = origin.as_bytes();
-            allowed_origins.iter().any(|r| r.is_match(origin))
+            patterns.read().unwrap().iter().any(|r| r.is_match(origin))
         }))
         .allow_methods([
             Method::GET,
@@ -128,24 +194,70 @@ fn create_cors_layer(allowed_origins: &[String]) -> Result<CorsLayer, AnyError>
             Method::PATCH,
             Method::OPTIONS,
         ])
-        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::ACCEPT])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            API_VERSION_HEADER,
+            IDEMPOTENCY_KEY_HEADER,
+        ])
+        .expose_headers([
+            HeaderName::from_static("deprecation"),
+            HeaderName::from_static("sunset"),
+            IDEMPOTENT_REPLAYED_HEADER,
+        ])
         .allow_credentials(true);
     Ok(cors)
 }
 
-async fn load_app_config<A: WebApplication>() -> Result<WebAppConfig<A::AppConfig>, AnyError> {
-    let args: Vec<String> = env::args().collect();
-    let stage = args.get(1).ok_or(anyhow!("Missing config stage parameter"))?.clone();
-
-    // initialize a pre-init logger
-    let _pre_init_log_guard = {
-        LogTracer::init().expect("Failed to set log tracer");
-        let env_filter = EnvFilter::builder()
-            .with_default_directive(LevelFilter::INFO.into())
-            .from_env_lossy();
-        let pre_init_log = tracing_subscriber::fmt().with_env_filter(env_filter).compact().finish();
-        tracing::dispatcher::set_default(&pre_init_log.into())
-    };
+/// The command line of the service: `<stage> [--check-config]` or `--config-schema`.
+enum Command {
+    /// Start the service.
+    Run(String),
+    /// Load and validate the configuration of the stage, print the merged configuration without the secrets.
+    CheckConfig(String),
+    /// Print the JSON Schema of the configuration.
+    ConfigSchema,
+}
+
+impl Command {
+    fn from_args() -> Result<Self, AnyError> {
+        let args: Vec<String> = env::args().skip(1).collect();
+        if args.iter().any(|arg| arg == "--config-schema") {
+            return Ok(Command::ConfigSchema);
+        }
+
+        let stage = args
+            .iter()
+            .find(|arg| !arg.starts_with("--"))
+            .ok_or(anyhow!("Missing config stage parameter"))?
+            .clone();
+        if args.iter().any(|arg| arg == "--check-config") {
+            Ok(Command::CheckConfig(stage))
+        } else {
+            Ok(Command::Run(stage))
+        }
+    }
+}
+
+fn init_pre_init_log() -> DefaultGuard {
+    LogTracer::init().expect("Failed to set log tracer");
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+    let pre_init_log = tracing_subscriber::fmt().with_env_filter(env_filter).compact().finish();
+    tracing::dispatcher::set_default(&pre_init_log.into())
+}
+
+async fn check_app_config<A: WebApplication>(stage: &str) -> Result<(), AnyError> {
+    let _pre_init_log_guard = init_pre_init_log();
+    let config = WebAppConfig::<A::AppConfig>::check(stage, None).await?;
+    println!("{}", serde_json::to_string_pretty(&config)?);
+    Ok(())
+}
+
+async fn load_app_config<A: WebApplication>(stage: &str) -> Result<WebAppConfig<A::AppConfig>, AnyError> {
+    let _pre_init_log_guard = init_pre_init_log();
 
     log::trace!("init-trace - log: ok");
     log::debug!("init-debug - log: ok");
@@ -158,35 +270,82 @@ async fn load_app_config<A: WebApplication>() -> Result<WebAppConfig<A::AppConfi
     tracing::warn!("init-warn  - tracing: ok");
     tracing::error!("init-error - tracing: ok");
 
-    let config = WebAppConfig::<A::AppConfig>::load(&stage, None).await?;
+    let config = WebAppConfig::<A::AppConfig>::load(stage, None).await?;
     log::info!("pre-init completed");
 
     Ok(config)
 }
 
-#[instrument(skip(config, app))]
+#[instrument(skip(watcher, app))]
 async fn create_web_app<A: WebApplication>(
-    config: &WebAppConfig<A::AppConfig>,
+    watcher: &ConfigWatcher<A::AppConfig>,
     app: &A,
-) -> Result<Router<()>, AnyError> {
+) -> Result<(Router<()>, Readiness), AnyError> {
+    let config = &*watcher.current();
+
     log::trace!("Creating telemetry service...");
     let telemetry_service = TelemetryService::new(app.feature_name(), &config.telemetry).await?;
     log::trace!("Creating health service...");
     let mut health_service = HealthService::new(app.feature_name(), config)?;
     log::trace!("Creating problem service...");
-    let problem_service = ProblemConfig::new(config.service.full_problem_response);
+    let problem_service = {
+        let mut catalog = MessageCatalog::core();
+        catalog.merge(app.messages());
+        if let Some(translations) = &config.service.translations {
+            catalog.load_dir(translations)?;
+        }
+        ProblemConfig::new(config.service.full_problem_response).with_catalog(catalog)
+    };
     log::trace!("Creating in-flight service...");
     let in_flight_service = crate::health::InFlightService::new();
+    log::trace!("Creating email normalizer...");
+    EmailNormalizer::new(&config.service.email_normalization)?.install();
     log::trace!("Creating current user service...");
     let current_user_service = CurrentUserService::from_config(&config.service).await?;
 
+    log::trace!("Creating realtime service...");
+    let realtime_service = RealtimeService::new(&config.service.realtime);
+    log::trace!("Creating client ip resolver...");
+    let client_ip_resolver = ClientIpResolver::new(&config.service.client_ip)?;
+    log::trace!("Creating fingerprint strategy...");
+    let fingerprint_strategy = WeightedFingerprint::new(&config.service.fingerprint);
+    log::trace!("Creating rate limiter...");
+    let rate_limiter = RateLimiter::from_config(&config.service).await?;
+    log::trace!("Creating idempotency store...");
+    let idempotency_store = IdempotencyStore::from_config(&config.service).await?;
+
+    let policy_service = {
+        let mut policy = PolicyConfig::core();
+        policy.merge(app.policy());
+        policy.merge(config.service.policy.clone());
+        PolicyService::new(&policy)?
+    };
+
+    let jwt_service = match &config.service.token {
+        Some(token_config) => Some(Arc::new(JwtService::from_config(token_config)?)),
+        None => None,
+    };
+
     log::trace!("Creating layer...");
-    let cors_layer = create_cors_layer(&config.service.allowed_origins)?;
+    watcher.add_validator(|config| compile_allowed_origins(&config.service.allowed_origins).map(|_| ()));
+    let cors_layer = create_cors_layer(watcher.subscribe(|config| config.service.allowed_origins.clone()))?;
+    telemetry_service.watch_default_level(watcher.subscribe(|config| config.telemetry.default_level.clone()));
     let powered_by_layer = if config.service.expose_powered_by {
         Some(PoweredBy::from_service_info(app.feature_name(), &config.core.version)?)
     } else {
         None
     };
+    let security_headers_layer = SecurityHeaders::new(&config.service.security_headers)?;
+    let body_limit_layer = BodyLimit::new(&config.service.limits)?;
+    let timeout_layer = RequestTimeout::new(
+        config
+            .service
+            .limits
+            .timeout
+            .map(StdDuration::from_secs)
+            .unwrap_or(DEFAULT_REQUEST_TIMEOUT),
+    );
+    let compression_layer = create_compression_layer(&config.service.compression);
     let log_layer = TraceLayer::new_for_http()
         .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
         .on_response(DefaultOnResponse::new().level(Level::INFO));
@@ -199,18 +358,39 @@ async fn create_web_app<A: WebApplication>(
     let mut router = OpenApiRouter::new();
     router = router.nest(&format!("/{}", app.feature_name()), health_service.create_router());
     router = router.nest(&format!("/{}", app.feature_name()), telemetry_service.create_router());
+    router = router.nest(&format!("/{}", app.feature_name()), policy_service.create_router());
+    if let Some(jwt_service) = &jwt_service {
+        router = router.nest(&format!("/{}", app.feature_name()), jwt_service.create_router());
+    }
 
     log::trace!("Creating app state...");
     let app_state = app.create(config, &mut health_service, &mut router).await?;
+    log::trace!("Starting health probes...");
+    health_service.start_probing().await;
 
     log::trace!("Setting up open API...");
     let (router, open_api) = router.split_for_parts();
+    let prefix = format!("/{}", app.feature_name());
+    let version_selector = ApiVersionSelector::from_openapi(&prefix, &open_api, problem_service.clone());
     let router = if config.service.expose_api_docs {
+        let (open_api, version_docs) = split_versioned_openapi(&prefix, open_api);
         let mut doc = ApiDoc::with_default_components();
         doc.merge(open_api);
 
+        // one document per api version, the unversioned routes are in the default document
+        let mut urls = vec![(
+            SwaggerUrl::from(format!("/{}/doc/openapi.json", app.feature_name())),
+            doc,
+        )];
+        for (version, open_api) in version_docs {
+            let mut doc = ApiDoc::with_default_components();
+            doc.merge(open_api);
+            let url = format!("/{}/doc/v{version}/openapi.json", app.feature_name());
+            urls.push((SwaggerUrl::from(url), doc));
+        }
+
         let swagger = SwaggerUi::new(format!("/{}/doc/swagger-ui", app.feature_name()))
-            .url(format!("/{}/doc/openapi.json", app.feature_name()), doc)
+            .urls(urls)
             .config(
                 SwaggerConfig::default()
                     .with_credentials(true)
@@ -222,21 +402,64 @@ async fn create_web_app<A: WebApplication>(
     };
 
     log::trace!("Creating app routes...");
-    Ok(router
+    let router = router
         .layer(current_user_service.create_layer())
+        .layer(ReissueSessionCookie::new())
+        .layer(realtime_service.into_layer())
+        .layer(policy_service.into_layer())
+        .layer(rate_limiter.into_layer())
+        .layer(idempotency_store.into_layer())
+        .layer(tower::util::option_layer(jwt_service.map(Extension)))
+        .layer(client_ip_resolver.into_layer())
+        .layer(fingerprint_strategy.into_layer())
+        .layer(timeout_layer)
+        .layer(body_limit_layer)
+        .layer(DefaultBodyLimit::disable())
         .layer(problem_service.into_layer())
         .layer(in_flight_service.create_layer())
         .layer(tower::util::option_layer(powered_by_layer))
-        .layer(SecurityHeaders)
+        .layer(compression_layer)
+        .layer(security_headers_layer)
         .layer(cors_layer)
         .layer(telemetry_service.create_layer())
         .layer(log_layer)
-        .with_state(app_state))
+        .with_state(app_state);
+    // the version is selected before routing
+    let router = Router::new().fallback_service(version_selector.layer(router));
+
+    Ok((router, health_service.readiness()))
+}
+
+/// Create the router of the application from an in-code configuration without binding a port, ex. to test the
+/// application in-process. The configuration is validated, but it is not watched for changes.
+pub async fn create_web_app_router<A: WebApplication>(
+    config: WebAppConfig<A::AppConfig>,
+    app: &A,
+) -> Result<Router<()>, AnyError> {
+    config
+        .validate()
+        .map_err(|err| anyhow!("Invalid configuration:\n{err}"))?;
+    let watcher = ConfigWatcher::new(config);
+    let (router, _readiness) = create_web_app(&watcher, app).await?;
+    Ok(router)
 }
 
 async fn start_web_app<A: WebApplication>(app: A) -> Result<(), AnyError> {
-    let config = load_app_config::<A>().await?;
-    let router = create_web_app(&config, &app).await?;
+    let stage = match Command::from_args()? {
+        Command::Run(stage) => stage,
+        Command::CheckConfig(stage) => return check_app_config::<A>(&stage).await,
+        Command::ConfigSchema => {
+            let schema = WebAppConfig::<A::AppConfig>::json_schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(());
+        }
+    };
+
+    let watcher = Arc::new(ConfigWatcher::new(load_app_config::<A>(&stage).await?));
+    let (router, readiness) = create_web_app(&watcher, &app).await?;
+    let _watch_task = watcher.start();
+    let config = watcher.current();
+    let drain_delay = StdDuration::from_secs(config.service.health.drain_delay.unwrap_or(0));
     log::info!("Starting web app with config...");
 
     let addr = SocketAddr::from(([0, 0, 0, 0], config.service.port));
@@ -252,18 +475,18 @@ async fn start_web_app<A: WebApplication>(app: A) -> Result<(), AnyError> {
             .map_err(|e| anyhow!(e))?;
 
         let handle = Handle::new();
-        tokio::spawn(graceful_shutdown(handle.clone()));
+        tokio::spawn(graceful_shutdown(handle.clone(), readiness, drain_delay));
 
         axum_server::bind_rustls(addr, config)
             .handle(handle)
-            .serve(router.into_make_service())
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .map_err(|e| anyhow!(e))
     } else {
         log::info!("Starting service on http://{addr:?} ...");
         let listener = TcpListener::bind(&addr).await.unwrap();
-        axum::serve(listener, router)
-            .with_graceful_shutdown(shutdown_signal())
+        axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(drain_signal(readiness, drain_delay))
             .await
             .map_err(|e| anyhow!(e))
     }
@@ -2,6 +2,7 @@ use serde::Deserialize;
 use shine_infra::web::{Environment, FeatureConfig, WebAppConfig};
 use shine_test::test;
 use std::env;
+use validator::Validate;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,7 +12,7 @@ struct Data {
     override_value: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
 #[serde(rename_all = "camelCase")]
 struct Feature {
     some_data: Data,
@@ -1,4 +1,7 @@
 pub use shine_test_macros::test;
+
+#[cfg(all(feature = "web", not(target_arch = "wasm32")))]
+pub mod web;
 use std::sync::Once;
 
 static INIT: Once = Once::new();
@@ -0,0 +1,12 @@
+mod test_server;
+
+mod test_redis;
+pub use self::test_redis::*;
+mod test_smtp;
+pub use self::test_smtp::*;
+mod test_user;
+pub use self::test_user::*;
+mod test_client;
+pub use self::test_client::*;
+mod test_app;
+pub use self::test_app::*;
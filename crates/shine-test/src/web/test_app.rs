@@ -0,0 +1,149 @@
+use crate::web::{test_client::TestSessions, TestClient, TestRedis};
+use anyhow::Error as AnyError;
+use axum::Router;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::{json, Value};
+use shine_infra::{
+    session::CurrentUserService,
+    web::{
+        create_web_app_router,
+        extracts::{ClientIpResolver, WeightedFingerprint},
+        FeatureConfig, WebAppConfig, WebApplication,
+    },
+};
+use std::sync::Arc;
+
+/// Merge the patch into the value, the objects are merged recursively, the other values are replaced.
+fn merge_json(value: &mut Value, patch: Value) {
+    match (value, patch) {
+        (Value::Object(value), Value::Object(patch)) => {
+            for (key, patch) in patch {
+                match value.get_mut(&key) {
+                    Some(value) => merge_json(value, patch),
+                    None => {
+                        value.insert(key, patch);
+                    }
+                }
+            }
+        }
+        (value, patch) => *value = patch,
+    }
+}
+
+/// Builder of a [TestApp].
+pub struct TestAppBuilder<A: WebApplication> {
+    app: A,
+    config: Value,
+    redis: Option<TestRedis>,
+}
+
+impl<A: WebApplication> TestAppBuilder<A> {
+    /// Patch the configuration, ex. `json!({ "service": { "sessionTtl": 60 }, "identity": { ... } })`.
+    /// The objects are merged recursively into the default test configuration.
+    pub fn with_config(mut self, patch: Value) -> Self {
+        merge_json(&mut self.config, patch);
+        self
+    }
+
+    /// Use a shared redis server, by default each application has its own server.
+    pub fn with_redis(self, redis: TestRedis) -> Self {
+        Self { redis: Some(redis), ..self }
+    }
+
+    pub async fn start(self) -> Result<TestApp, AnyError> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let redis = match self.redis {
+            Some(redis) => redis,
+            None => TestRedis::start()?,
+        };
+
+        let session_secret = {
+            let mut key = [0; 64];
+            SystemRandom::new()
+                .fill(&mut key)
+                .map_err(|_| anyhow::anyhow!("Failed to generate the session secret"))?;
+            B64.encode(key)
+        };
+
+        let mut config = json!({
+            "stage": "test",
+            "version": "test",
+            "rootFile": "test",
+            "beforeLayers": [],
+            "afterLayers": [],
+            "service": {
+                "port": 0,
+                "allowedOrigins": [],
+                "fullProblemResponse": true,
+                "captchaSecret": "1x0000000000000000000000000000000AA",
+                "sessionSecret": session_secret,
+                "sessionTtl": 3600,
+                "sessionRedisCns": redis.cns(),
+            },
+            "telemetry": {
+                "enableConsoleLog": false,
+                "allowReconfigure": false,
+                "metrics": { "type": "none" },
+                "tracing": { "type": "none" },
+            },
+        });
+        config[A::AppConfig::NAME] = json!({});
+        merge_json(&mut config, self.config);
+
+        let web_config = serde_json::from_value::<WebAppConfig<A::AppConfig>>(config.clone())?;
+        let service = web_config.service.clone();
+        let router = create_web_app_router(web_config, &self.app).await?;
+
+        let sessions = TestSessions {
+            user_service: CurrentUserService::from_config(&service).await?,
+            fingerprint: Arc::new(WeightedFingerprint::new(&service.fingerprint)),
+            client_ip: ClientIpResolver::new(&service.client_ip)?,
+        };
+
+        Ok(TestApp {
+            router,
+            redis,
+            config,
+            sessions: Arc::new(sessions),
+        })
+    }
+}
+
+/// A web application running in-process with the given configuration. The requests are sent directly to the
+/// router by the [TestClient], no port is bound. Redis is substituted by a [TestRedis] server.
+pub struct TestApp {
+    router: Router,
+    redis: TestRedis,
+    config: Value,
+    sessions: Arc<TestSessions>,
+}
+
+impl TestApp {
+    pub fn builder<A: WebApplication>(app: A) -> TestAppBuilder<A> {
+        TestAppBuilder {
+            app,
+            config: json!({}),
+            redis: None,
+        }
+    }
+
+    pub fn redis(&self) -> &TestRedis {
+        &self.redis
+    }
+
+    /// The effective configuration of the application.
+    pub fn config(&self) -> &Value {
+        &self.config
+    }
+
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    /// Create a new client without any cookies.
+    pub fn client(&self) -> TestClient {
+        TestClient::new(self.router.clone(), self.sessions.clone())
+    }
+}
@@ -0,0 +1,301 @@
+use crate::web::TestUser;
+use anyhow::Error as AnyError;
+use axum::{
+    body::{Body, Bytes},
+    extract::ConnectInfo,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode},
+    response::IntoResponse,
+    Router,
+};
+use axum_extra::extract::{cookie::Cookie, SignedCookieJar};
+use chrono::Utc;
+use http_body_util::BodyExt;
+use ring::rand::SystemRandom;
+use serde::{de::DeserializeOwned, Serialize};
+use shine_infra::{
+    session::{CurrentUser, CurrentUserService, SessionCookie, SessionKey},
+    web::extracts::{ClientFingerprint, ClientIpResolver, FingerprintStrategy, GeoLocation, SiteInfo},
+};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tower::ServiceExt;
+
+/// The services to start sessions the same way as the identity service does.
+pub(crate) struct TestSessions {
+    pub user_service: CurrentUserService,
+    pub fingerprint: Arc<dyn FingerprintStrategy>,
+    pub client_ip: ClientIpResolver,
+}
+
+/// A http client sending the requests directly to the router of a [TestApp](crate::web::TestApp). The cookies
+/// of the responses are stored and sent with the subsequent requests, the clones share the cookies.
+#[derive(Clone)]
+pub struct TestClient {
+    router: Router,
+    sessions: Arc<TestSessions>,
+    headers: HeaderMap,
+    peer: SocketAddr,
+    cookies: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl TestClient {
+    pub(crate) fn new(router: Router, sessions: Arc<TestSessions>) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, HeaderValue::from_static("shine-test"));
+
+        Self {
+            router,
+            sessions,
+            headers,
+            peer: SocketAddr::from(([127, 0, 0, 1], 50000)),
+            cookies: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Add a header sent with all the requests, ex. to change the fingerprint of the client.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        let name = HeaderName::try_from(name).expect("Invalid header name");
+        let value = HeaderValue::try_from(value).expect("Invalid header value");
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Set the address of the connecting peer.
+    pub fn with_peer(self, peer: SocketAddr) -> Self {
+        Self { peer, ..self }
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.cookies.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn set_cookie(&self, name: &str, value: &str) {
+        self.cookies.lock().unwrap().insert(name.to_string(), value.to_string());
+    }
+
+    pub fn clear_cookies(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    fn update_cookies(&self, headers: &HeaderMap) {
+        let mut cookies = self.cookies.lock().unwrap();
+        for value in headers.get_all(header::SET_COOKIE) {
+            let Some(cookie) = value.to_str().ok().and_then(|value| Cookie::parse(value).ok()) else {
+                continue;
+            };
+            let is_removed = cookie.value().is_empty()
+                || cookie.max_age().is_some_and(|max_age| max_age.is_zero())
+                || cookie
+                    .expires_datetime()
+                    .is_some_and(|expires| expires.unix_timestamp() <= Utc::now().timestamp());
+            if is_removed {
+                cookies.remove(cookie.name());
+            } else {
+                cookies.insert(cookie.name().to_string(), cookie.value().to_string());
+            }
+        }
+    }
+
+    /// Start a session of the user and store the session cookie as the identity service would do on login.
+    pub async fn login(&self, user: &TestUser) -> Result<CurrentUser, AnyError> {
+        let sessions = &self.sessions;
+        let user_service = &sessions.user_service;
+
+        let key = SessionKey::new_random(&SystemRandom::new())?;
+        let client_ip = sessions.client_ip.resolve(Some(self.peer.ip()), &self.headers);
+        let fingerprint = match &user.fingerprint {
+            Some(fingerprint) => fingerprint.clone(),
+            None => ClientFingerprint::new(sessions.fingerprint.clone(), &self.headers, &client_ip).into_string(),
+        };
+        let now = Utc::now();
+        let session_start = now - user.session_age;
+        let ttl = user_service
+            .lifetime()
+            .ttl(session_start, now)
+            .ok_or_else(|| anyhow::anyhow!("The session age exceeds the lifetime of the sessions"))?;
+
+        let current_user = CurrentUser {
+            user_id: user.user_id,
+            key,
+            session_start,
+            session_end: now + chrono::Duration::seconds(ttl),
+            authenticated_at: session_start,
+            name: user.name.clone(),
+            is_email_confirmed: user.is_email_confirmed,
+            is_linked: user.is_linked,
+            roles: user.roles.clone(),
+            fingerprint: fingerprint.clone(),
+        };
+        let header_value = |name: &str| {
+            self.headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let location = if client_ip.cloudflare {
+            GeoLocation {
+                country: header_value("cf-ipcountry"),
+                region: header_value("cf-region"),
+                city: header_value("cf-ipcity"),
+            }
+        } else {
+            client_ip
+                .ip
+                .and_then(|ip| sessions.client_ip.locate(ip))
+                .unwrap_or_default()
+        };
+        let site_info = SiteInfo {
+            agent: header_value(header::USER_AGENT.as_str()).unwrap_or_default(),
+            country: location.country,
+            region: location.region,
+            city: location.city,
+        };
+        user_service.store_session(&current_user, &site_info).await?;
+
+        let cookie = SessionCookie {
+            user_id: user.user_id,
+            key,
+            fingerprint,
+        };
+        let jar = SignedCookieJar::new(user_service.cookie_secret().clone()).add(Cookie::new(
+            user_service.cookie_name().to_string(),
+            serde_json::to_string(&cookie)?,
+        ));
+        self.update_cookies(jar.into_response().headers());
+
+        Ok(current_user)
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> TestRequest {
+        TestRequest {
+            client: self.clone(),
+            method,
+            path: path.to_string(),
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> TestRequest {
+        self.request(Method::GET, path)
+    }
+
+    pub fn post(&self, path: &str) -> TestRequest {
+        self.request(Method::POST, path)
+    }
+
+    pub fn put(&self, path: &str) -> TestRequest {
+        self.request(Method::PUT, path)
+    }
+
+    pub fn patch(&self, path: &str) -> TestRequest {
+        self.request(Method::PATCH, path)
+    }
+
+    pub fn delete(&self, path: &str) -> TestRequest {
+        self.request(Method::DELETE, path)
+    }
+}
+
+pub struct TestRequest {
+    client: TestClient,
+    method: Method,
+    path: String,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TestRequest {
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let name = HeaderName::try_from(name).expect("Invalid header name");
+        let value = HeaderValue::try_from(value).expect("Invalid header value");
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn body<B: Into<Bytes>>(mut self, content_type: &str, body: B) -> Self {
+        self.body = body.into();
+        self.header(header::CONTENT_TYPE.as_str(), content_type)
+    }
+
+    pub fn json<T: Serialize>(self, body: &T) -> Self {
+        let body = serde_json::to_vec(body).expect("Failed to serialize the body");
+        self.body("application/json", body)
+    }
+
+    pub async fn send(self) -> TestResponse {
+        let client = self.client;
+
+        let mut request = Request::builder()
+            .method(self.method)
+            .uri(&self.path)
+            .body(Body::from(self.body))
+            .expect("Invalid request");
+        let headers = request.headers_mut();
+        for (name, value) in &client.headers {
+            headers.insert(name, value.clone());
+        }
+        headers.extend(self.headers);
+        let cookies = client
+            .cookies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+        if !cookies.is_empty() && !headers.contains_key(header::COOKIE) {
+            let cookies = HeaderValue::try_from(cookies.join("; ")).expect("Invalid cookie");
+            headers.insert(header::COOKIE, cookies);
+        }
+        request.extensions_mut().insert(ConnectInfo(client.peer));
+
+        let response = client.router.clone().oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        client.update_cookies(&parts.headers);
+        let body = body.collect().await.expect("Failed to read the body").to_bytes();
+
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TestResponse {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    pub fn bytes(&self) -> &Bytes {
+        &self.body
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Parse the json body, it panics with the body in the message if it is not the expected json.
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|err| panic!("Unexpected body ({}): {err}\n{}", self.status, self.text()))
+    }
+}
@@ -0,0 +1,706 @@
+use crate::web::test_server::{read_line, TestServer};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{tcp::OwnedReadHalf, TcpStream},
+    sync::mpsc,
+};
+
+enum Reply {
+    Status(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn ok() -> Self {
+        Reply::Status("OK")
+    }
+
+    fn bulk<T: Into<Vec<u8>>>(value: T) -> Self {
+        Reply::Bulk(Some(value.into()))
+    }
+
+    fn nil() -> Self {
+        Reply::Bulk(None)
+    }
+
+    fn syntax_error() -> Self {
+        Reply::Error("ERR syntax error".into())
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Reply::Status(status) => out.extend_from_slice(format!("+{status}\r\n").as_bytes()),
+            Reply::Error(err) => out.extend_from_slice(format!("-{err}\r\n").as_bytes()),
+            Reply::Integer(value) => out.extend_from_slice(format!(":{value}\r\n").as_bytes()),
+            Reply::Bulk(None) => out.extend_from_slice(b"$-1\r\n"),
+            Reply::Bulk(Some(value)) => {
+                out.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+                out.extend_from_slice(value);
+                out.extend_from_slice(b"\r\n");
+            }
+            Reply::Array(items) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                items.iter().for_each(|item| item.encode(out));
+            }
+        }
+    }
+}
+
+/// Match a key against a glob-style pattern of the KEYS, SCAN and PSUBSCRIBE commands.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') => glob_match(&pattern[1..], text) || (!text.is_empty() && glob_match(pattern, &text[1..])),
+        Some(b'?') => !text.is_empty() && glob_match(&pattern[1..], &text[1..]),
+        Some(b'[') => {
+            let Some((&c, text)) = text.split_first() else {
+                return false;
+            };
+            let negate = pattern.get(1) == Some(&b'^');
+            let mut i = if negate { 2 } else { 1 };
+            let mut matched = false;
+            while i < pattern.len() && pattern[i] != b']' {
+                if pattern[i] == b'\\' && i + 1 < pattern.len() {
+                    matched |= pattern[i + 1] == c;
+                    i += 2;
+                } else if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+                    let (low, high) = (pattern[i].min(pattern[i + 2]), pattern[i].max(pattern[i + 2]));
+                    matched |= (low..=high).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= pattern[i] == c;
+                    i += 1;
+                }
+            }
+            let pattern = pattern.get(i + 1..).unwrap_or_default();
+            matched != negate && glob_match(pattern, text)
+        }
+        Some(b'\\') if pattern.len() > 1 => text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..]),
+        Some(c) => text.first() == Some(c) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+fn parse_int(arg: &[u8]) -> Result<i64, Reply> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| Reply::Error("ERR value is not an integer or out of range".into()))
+}
+
+/// Convert a unix timestamp in milliseconds to an instant.
+fn unix_millis_to_instant(timestamp: i64) -> Instant {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    let delta = timestamp - now;
+    if delta > 0 {
+        Instant::now() + Duration::from_millis(delta as u64)
+    } else {
+        Instant::now()
+    }
+}
+
+struct Entry {
+    value: Vec<u8>,
+    expire_at: Option<Instant>,
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expire_at.is_some_and(|expire_at| expire_at <= now)
+    }
+}
+
+struct Subscriber {
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+    channels: HashSet<Vec<u8>>,
+    patterns: HashSet<Vec<u8>>,
+}
+
+impl Subscriber {
+    fn subscription_count(&self) -> i64 {
+        (self.channels.len() + self.patterns.len()) as i64
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    entries: HashMap<Vec<u8>, Entry>,
+    subscribers: HashMap<u64, Subscriber>,
+    next_client_id: u64,
+}
+
+impl Store {
+    fn entry(&mut self, key: &[u8]) -> Option<&mut Entry> {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(Instant::now()))
+        {
+            self.entries.remove(key);
+        }
+        self.entries.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Entry> {
+        self.entries
+            .remove(key)
+            .filter(|entry| !entry.is_expired(Instant::now()))
+    }
+
+    fn keys(&mut self, pattern: &[u8]) -> Vec<Vec<u8>> {
+        let now = Instant::now();
+        self.entries.retain(|_, entry| !entry.is_expired(now));
+        let mut keys = self
+            .entries
+            .keys()
+            .filter(|key| glob_match(pattern, key))
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    fn publish(&mut self, channel: &[u8], message: &[u8]) -> i64 {
+        let mut count = 0;
+        for subscriber in self.subscribers.values() {
+            let mut frames = Vec::new();
+            if subscriber.channels.contains(channel) {
+                frames.push(Reply::Array(vec![
+                    Reply::bulk("message"),
+                    Reply::bulk(channel),
+                    Reply::bulk(message),
+                ]));
+            }
+            for pattern in subscriber
+                .patterns
+                .iter()
+                .filter(|pattern| glob_match(pattern, channel))
+            {
+                frames.push(Reply::Array(vec![
+                    Reply::bulk("pmessage"),
+                    Reply::bulk(pattern.clone()),
+                    Reply::bulk(channel),
+                    Reply::bulk(message),
+                ]));
+            }
+            for frame in frames {
+                let mut out = Vec::new();
+                frame.encode(&mut out);
+                if subscriber.sender.send(out).is_ok() {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+/// The state of a client connection.
+struct Client {
+    id: u64,
+    store: Arc<Mutex<Store>>,
+    transaction: Option<Vec<Vec<Vec<u8>>>>,
+    quit: bool,
+}
+
+impl Client {
+    fn handle(&mut self, args: Vec<Vec<u8>>) -> Vec<Reply> {
+        let name = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+
+        if let Some(queued) = &mut self.transaction {
+            match name.as_str() {
+                "EXEC" | "DISCARD" => {}
+                "MULTI" => return vec![Reply::Error("ERR MULTI calls can not be nested".into())],
+                _ => {
+                    queued.push(args);
+                    return vec![Reply::Status("QUEUED")];
+                }
+            }
+        }
+
+        let store = self.store.clone();
+        let mut store = store.lock().unwrap();
+        match name.as_str() {
+            "MULTI" => {
+                self.transaction = Some(Vec::new());
+                vec![Reply::ok()]
+            }
+            "EXEC" => match self.transaction.take() {
+                Some(queued) => {
+                    let replies = queued
+                        .into_iter()
+                        .flat_map(|args| {
+                            let name = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+                            self.execute(&mut store, &name, &args[1..])
+                        })
+                        .collect();
+                    vec![Reply::Array(replies)]
+                }
+                None => vec![Reply::Error("ERR EXEC without MULTI".into())],
+            },
+            "DISCARD" => match self.transaction.take() {
+                Some(_) => vec![Reply::ok()],
+                None => vec![Reply::Error("ERR DISCARD without MULTI".into())],
+            },
+            _ => self.execute(&mut store, &name, &args[1..]),
+        }
+    }
+
+    fn execute(&mut self, store: &mut Store, name: &str, args: &[Vec<u8>]) -> Vec<Reply> {
+        match name {
+            "SUBSCRIBE" | "PSUBSCRIBE" | "UNSUBSCRIBE" | "PUNSUBSCRIBE" => self.subscription(store, name, args),
+            _ => vec![self.command(store, name, args).unwrap_or_else(|err| err)],
+        }
+    }
+
+    fn subscription(&mut self, store: &mut Store, name: &str, args: &[Vec<u8>]) -> Vec<Reply> {
+        let Some(subscriber) = store.subscribers.get_mut(&self.id) else {
+            return vec![Reply::Error("ERR unknown client".into())];
+        };
+
+        let kind = name.to_ascii_lowercase();
+        let is_pattern = name.starts_with('P');
+        let mut replies = Vec::new();
+        if name.contains("UNSUBSCRIBE") {
+            let targets = if is_pattern {
+                &mut subscriber.patterns
+            } else {
+                &mut subscriber.channels
+            };
+            let names = if args.is_empty() {
+                targets.drain().collect::<Vec<_>>()
+            } else {
+                args.iter().filter(|name| targets.remove(*name)).cloned().collect()
+            };
+            for channel in names {
+                replies.push((kind.clone(), Some(channel)));
+            }
+            if replies.is_empty() {
+                replies.push((kind.clone(), None));
+            }
+        } else {
+            for channel in args {
+                if is_pattern {
+                    subscriber.patterns.insert(channel.clone());
+                } else {
+                    subscriber.channels.insert(channel.clone());
+                }
+                replies.push((kind.clone(), Some(channel.clone())));
+            }
+        }
+
+        let count = subscriber.subscription_count();
+        replies
+            .into_iter()
+            .map(|(kind, channel)| Reply::Array(vec![Reply::bulk(kind), Reply::Bulk(channel), Reply::Integer(count)]))
+            .collect()
+    }
+
+    fn command(&mut self, store: &mut Store, name: &str, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+        let arity = |min: usize| {
+            if args.len() < min {
+                Err(Reply::Error(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    name.to_ascii_lowercase()
+                )))
+            } else {
+                Ok(())
+            }
+        };
+
+        let reply = match name {
+            "PING" => match args.first() {
+                Some(message) => Reply::bulk(message.clone()),
+                None => Reply::Status("PONG"),
+            },
+            "ECHO" => {
+                arity(1)?;
+                Reply::bulk(args[0].clone())
+            }
+            "QUIT" => {
+                self.quit = true;
+                Reply::ok()
+            }
+            "SELECT" | "AUTH" | "CLIENT" | "READONLY" | "WATCH" | "UNWATCH" => Reply::ok(),
+            "INFO" => Reply::bulk("# Server\r\nredis_version:7.0.0\r\n"),
+            "DBSIZE" => Reply::Integer(store.keys(b"*").len() as i64),
+            "FLUSHDB" | "FLUSHALL" => {
+                store.entries.clear();
+                Reply::ok()
+            }
+
+            "GET" => {
+                arity(1)?;
+                Reply::Bulk(store.entry(&args[0]).map(|entry| entry.value.clone()))
+            }
+            "GETDEL" => {
+                arity(1)?;
+                Reply::Bulk(store.remove(&args[0]).map(|entry| entry.value))
+            }
+            "MGET" => {
+                arity(1)?;
+                Reply::Array(
+                    args.iter()
+                        .map(|key| Reply::Bulk(store.entry(key).map(|entry| entry.value.clone())))
+                        .collect(),
+                )
+            }
+            "SET" => {
+                arity(2)?;
+                return self.set(store, args);
+            }
+            "SETNX" => {
+                arity(2)?;
+                if store.entry(&args[0]).is_some() {
+                    Reply::Integer(0)
+                } else {
+                    store.entries.insert(
+                        args[0].clone(),
+                        Entry {
+                            value: args[1].clone(),
+                            expire_at: None,
+                        },
+                    );
+                    Reply::Integer(1)
+                }
+            }
+            "SETEX" | "PSETEX" => {
+                arity(3)?;
+                let ttl = parse_int(&args[1])?;
+                if ttl <= 0 {
+                    return Err(Reply::Error("ERR invalid expire time".into()));
+                }
+                let ttl = if name == "SETEX" {
+                    Duration::from_secs(ttl as u64)
+                } else {
+                    Duration::from_millis(ttl as u64)
+                };
+                store.entries.insert(
+                    args[0].clone(),
+                    Entry {
+                        value: args[2].clone(),
+                        expire_at: Some(Instant::now() + ttl),
+                    },
+                );
+                Reply::ok()
+            }
+            "DEL" | "UNLINK" => {
+                arity(1)?;
+                Reply::Integer(args.iter().filter(|key| store.remove(key).is_some()).count() as i64)
+            }
+            "EXISTS" => {
+                arity(1)?;
+                Reply::Integer(args.iter().filter(|key| store.entry(key).is_some()).count() as i64)
+            }
+            "TYPE" => {
+                arity(1)?;
+                Reply::Status(if store.entry(&args[0]).is_some() {
+                    "string"
+                } else {
+                    "none"
+                })
+            }
+            "INCR" | "DECR" | "INCRBY" | "DECRBY" => {
+                arity(if name.ends_with("BY") { 2 } else { 1 })?;
+                let delta = match name {
+                    "INCR" => 1,
+                    "DECR" => -1,
+                    "INCRBY" => parse_int(&args[1])?,
+                    _ => -parse_int(&args[1])?,
+                };
+                let (current, expire_at) = match store.entry(&args[0]) {
+                    Some(entry) => (parse_int(&entry.value)?, entry.expire_at),
+                    None => (0, None),
+                };
+                let value = current
+                    .checked_add(delta)
+                    .ok_or_else(|| Reply::Error("ERR increment or decrement would overflow".into()))?;
+                store.entries.insert(
+                    args[0].clone(),
+                    Entry {
+                        value: value.to_string().into_bytes(),
+                        expire_at,
+                    },
+                );
+                Reply::Integer(value)
+            }
+
+            "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT" => {
+                arity(2)?;
+                let time = parse_int(&args[1])?;
+                let expire_at = match name {
+                    "EXPIRE" => Instant::now() + Duration::from_secs(time.max(0) as u64),
+                    "PEXPIRE" => Instant::now() + Duration::from_millis(time.max(0) as u64),
+                    "EXPIREAT" => unix_millis_to_instant(time.saturating_mul(1000)),
+                    _ => unix_millis_to_instant(time),
+                };
+                match store.entry(&args[0]) {
+                    Some(entry) => {
+                        entry.expire_at = Some(expire_at);
+                        Reply::Integer(1)
+                    }
+                    None => Reply::Integer(0),
+                }
+            }
+            "PERSIST" => {
+                arity(1)?;
+                match store.entry(&args[0]) {
+                    Some(entry) if entry.expire_at.is_some() => {
+                        entry.expire_at = None;
+                        Reply::Integer(1)
+                    }
+                    _ => Reply::Integer(0),
+                }
+            }
+            "TTL" | "PTTL" => {
+                arity(1)?;
+                match store.entry(&args[0]) {
+                    None => Reply::Integer(-2),
+                    Some(Entry { expire_at: None, .. }) => Reply::Integer(-1),
+                    Some(Entry { expire_at: Some(expire_at), .. }) => {
+                        let remaining = expire_at.saturating_duration_since(Instant::now()).as_millis() as i64;
+                        if name == "TTL" {
+                            Reply::Integer((remaining + 500) / 1000)
+                        } else {
+                            Reply::Integer(remaining)
+                        }
+                    }
+                }
+            }
+
+            "KEYS" => {
+                arity(1)?;
+                Reply::Array(store.keys(&args[0]).into_iter().map(Reply::bulk).collect())
+            }
+            "SCAN" => {
+                arity(1)?;
+                // all the keys are returned in a single iteration
+                let mut pattern = b"*".to_vec();
+                let mut options = args[1..].chunks(2);
+                for option in &mut options {
+                    match option {
+                        [option, value] if option.eq_ignore_ascii_case(b"MATCH") => pattern = value.clone(),
+                        [option, _]
+                            if option.eq_ignore_ascii_case(b"COUNT") || option.eq_ignore_ascii_case(b"TYPE") => {}
+                        _ => return Err(Reply::syntax_error()),
+                    }
+                }
+                let keys = store.keys(&pattern).into_iter().map(Reply::bulk).collect();
+                Reply::Array(vec![Reply::bulk("0"), Reply::Array(keys)])
+            }
+
+            "PUBLISH" => {
+                arity(2)?;
+                Reply::Integer(store.publish(&args[0], &args[1]))
+            }
+
+            "EVAL" | "EVALSHA" | "EVAL_RO" | "EVALSHA_RO" | "SCRIPT" | "FCALL" | "FUNCTION" => {
+                Reply::Error("ERR scripting is not supported by the test server".into())
+            }
+            _ => Reply::Error(format!("ERR unknown command '{}'", name.to_ascii_lowercase())),
+        };
+        Ok(reply)
+    }
+
+    fn set(&mut self, store: &mut Store, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+        let (mut only_new, mut only_existing, mut get, mut keep_ttl) = (false, false, false, false);
+        let mut expire_at = None;
+
+        let mut options = args[2..].iter();
+        while let Some(option) = options.next() {
+            let option = String::from_utf8_lossy(option).to_ascii_uppercase();
+            match option.as_str() {
+                "NX" => only_new = true,
+                "XX" => only_existing = true,
+                "GET" => get = true,
+                "KEEPTTL" => keep_ttl = true,
+                "EX" | "PX" | "EXAT" | "PXAT" => {
+                    let time = parse_int(options.next().ok_or_else(Reply::syntax_error)?)?;
+                    expire_at = Some(match option.as_str() {
+                        "EX" => Instant::now() + Duration::from_secs(time.max(0) as u64),
+                        "PX" => Instant::now() + Duration::from_millis(time.max(0) as u64),
+                        "EXAT" => unix_millis_to_instant(time.saturating_mul(1000)),
+                        _ => unix_millis_to_instant(time),
+                    });
+                }
+                _ => return Err(Reply::syntax_error()),
+            }
+        }
+
+        let current = store.entry(&args[0]);
+        let old_value = current.as_ref().map(|entry| entry.value.clone());
+        let old_expire_at = current.and_then(|entry| entry.expire_at);
+        if (only_new && old_value.is_some()) || (only_existing && old_value.is_none()) {
+            return Ok(if get { Reply::Bulk(old_value) } else { Reply::nil() });
+        }
+
+        store.entries.insert(
+            args[0].clone(),
+            Entry {
+                value: args[1].clone(),
+                expire_at: if keep_ttl { old_expire_at } else { expire_at },
+            },
+        );
+        Ok(if get { Reply::Bulk(old_value) } else { Reply::ok() })
+    }
+}
+
+/// Read a command either in the RESP array or in the inline format.
+async fn read_command(reader: &mut BufReader<OwnedReadHalf>) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let Some(line) = read_line(reader).await? else {
+        return Ok(None);
+    };
+
+    if line.first() != Some(&b'*') {
+        let args = line
+            .split(|c| c.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| arg.to_vec())
+            .collect();
+        return Ok(Some(args));
+    }
+
+    let count = parse_int(&line[1..]).map_err(|_| invalid("Invalid array length"))?;
+    let mut args = Vec::with_capacity(count.max(0) as usize);
+    for _ in 0..count {
+        let header = read_line(reader)
+            .await?
+            .ok_or_else(|| invalid("Unexpected end of stream"))?;
+        if header.first() != Some(&b'$') {
+            return Err(invalid("Expected a bulk string"));
+        }
+        let len = parse_int(&header[1..]).map_err(|_| invalid("Invalid bulk length"))?;
+        let mut data = vec![0; len.max(0) as usize + 2];
+        reader.read_exact(&mut data).await?;
+        data.truncate(len.max(0) as usize);
+        args.push(data);
+    }
+    Ok(Some(args))
+}
+
+async fn serve_client(store: Arc<Mutex<Store>>, stream: TcpStream) {
+    let (reader, mut writer) = stream.into_split();
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Vec<u8>>();
+    let write_task = tokio::spawn(async move {
+        while let Some(frame) = receiver.recv().await {
+            if writer.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let id = {
+        let mut store = store.lock().unwrap();
+        store.next_client_id += 1;
+        let id = store.next_client_id;
+        store.subscribers.insert(
+            id,
+            Subscriber {
+                sender: sender.clone(),
+                channels: HashSet::new(),
+                patterns: HashSet::new(),
+            },
+        );
+        id
+    };
+
+    let mut client = Client {
+        id,
+        store: store.clone(),
+        transaction: None,
+        quit: false,
+    };
+    let mut reader = BufReader::new(reader);
+    loop {
+        let args = match read_command(&mut reader).await {
+            Ok(Some(args)) if args.is_empty() => continue,
+            Ok(Some(args)) => args,
+            Ok(None) => break,
+            Err(err) => {
+                log::warn!("Invalid redis command: {err}");
+                break;
+            }
+        };
+
+        let mut out = Vec::new();
+        client.handle(args).iter().for_each(|reply| reply.encode(&mut out));
+        if sender.send(out).is_err() || client.quit {
+            break;
+        }
+    }
+
+    store.lock().unwrap().subscribers.remove(&id);
+    drop(sender);
+    let _ = write_task.await;
+}
+
+/// An in-process substitute of Redis for the tests. It implements the string, key expiration, scan, transaction
+/// and publish/subscribe commands of the Redis protocol, the scripting commands are not supported.
+/// The server is stopped when the last clone is dropped.
+#[derive(Clone)]
+pub struct TestRedis {
+    server: Arc<TestServer>,
+    store: Arc<Mutex<Store>>,
+}
+
+impl TestRedis {
+    pub fn start() -> io::Result<Self> {
+        let store = Arc::new(Mutex::new(Store::default()));
+        let server = {
+            let store = store.clone();
+            TestServer::start("test-redis", move |stream| serve_client(store.clone(), stream))?
+        };
+
+        Ok(Self {
+            server: Arc::new(server),
+            store,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.server.address()
+    }
+
+    /// The connection string of the server, ex. for the `sessionRedisCns` configuration.
+    pub fn cns(&self) -> String {
+        format!("redis://{}", self.address())
+    }
+
+    /// The keys matching the glob-style pattern in sorted order.
+    pub fn keys(&self, pattern: &str) -> Vec<String> {
+        self.store
+            .lock()
+            .unwrap()
+            .keys(pattern.as_bytes())
+            .into_iter()
+            .map(|key| String::from_utf8_lossy(&key).into_owned())
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.store
+            .lock()
+            .unwrap()
+            .entry(key.as_bytes())
+            .map(|entry| entry.value.clone())
+    }
+
+    /// Remove all the keys.
+    pub fn flush(&self) {
+        self.store.lock().unwrap().entries.clear();
+    }
+}
@@ -0,0 +1,89 @@
+use std::{future::Future, io, net::SocketAddr, thread};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::{tcp::OwnedReadHalf, TcpListener, TcpStream},
+    sync::oneshot,
+    task::JoinSet,
+};
+
+/// A local tcp server running on a dedicated thread. The server is independent of the runtime of the test, thus
+/// it also serves the blocking clients. The server and the open connections are closed on drop.
+pub(crate) struct TestServer {
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl TestServer {
+    pub fn start<F, H>(name: &str, handler: H) -> io::Result<Self>
+    where
+        F: Future<Output = ()> + Send + 'static,
+        H: Fn(TcpStream) -> F + Send + 'static,
+    {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        let name = name.to_string();
+        thread::Builder::new().name(name.clone()).spawn(move || {
+            runtime.block_on(async move {
+                let listener = match TcpListener::from_std(listener) {
+                    Ok(listener) => listener,
+                    Err(err) => {
+                        log::error!("Failed to start {name}: {err}");
+                        return;
+                    }
+                };
+
+                let mut connections = JoinSet::new();
+                let accept = async {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => {
+                                while connections.try_join_next().is_some() {}
+                                connections.spawn(handler(stream));
+                            }
+                            Err(err) => log::warn!("Failed to accept connection of {name}: {err}"),
+                        }
+                    }
+                };
+
+                tokio::select! {
+                    _ = shutdown_signal => {},
+                    _ = accept => {},
+                }
+                connections.abort_all();
+            })
+        })?;
+
+        Ok(Self {
+            address,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Read a line without the line ending, `None` at the end of the stream.
+pub(crate) async fn read_line(reader: &mut BufReader<OwnedReadHalf>) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+    while line.last().is_some_and(|c| *c == b'\n' || *c == b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
@@ -0,0 +1,330 @@
+use crate::web::test_server::{read_line, TestServer};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::Notify,
+    time::Instant,
+};
+
+/// A mail captured by the [TestSmtp] server.
+#[derive(Clone, Debug)]
+pub struct TestMail {
+    pub from: String,
+    pub to: Vec<String>,
+    /// The raw message with the headers and the encoded body.
+    pub data: String,
+}
+
+impl TestMail {
+    fn headers(&self) -> &str {
+        let end = self
+            .data
+            .find("\r\n\r\n")
+            .or_else(|| self.data.find("\n\n"))
+            .unwrap_or(self.data.len());
+        &self.data[..end]
+    }
+
+    /// The unfolded value of the first header with the given name.
+    pub fn header(&self, name: &str) -> Option<String> {
+        let mut value: Option<String> = None;
+        for line in self.headers().lines() {
+            if line.starts_with([' ', '\t']) {
+                if let Some(value) = &mut value {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+            if value.is_some() {
+                break;
+            }
+            if let Some((key, rest)) = line.split_once(':') {
+                if key.trim().eq_ignore_ascii_case(name) {
+                    value = Some(rest.trim().to_string());
+                }
+            }
+        }
+        value
+    }
+
+    /// The subject with the encoded words (RFC 2047) decoded.
+    pub fn subject(&self) -> Option<String> {
+        self.header("Subject").map(|subject| decode_encoded_words(&subject))
+    }
+
+    /// The body decoded by the Content-Transfer-Encoding. The multipart bodies are returned as is.
+    pub fn body(&self) -> String {
+        let start = self
+            .data
+            .find("\r\n\r\n")
+            .map(|pos| pos + 4)
+            .or_else(|| self.data.find("\n\n").map(|pos| pos + 2))
+            .unwrap_or(self.data.len());
+        let body = &self.data[start..];
+
+        let encoding = self.header("Content-Transfer-Encoding").unwrap_or_default();
+        let decoded = if encoding.eq_ignore_ascii_case("quoted-printable") {
+            decode_quoted_printable(body, false)
+        } else if encoding.eq_ignore_ascii_case("base64") {
+            let body = body.chars().filter(|c| !c.is_ascii_whitespace()).collect::<String>();
+            B64.decode(body).unwrap_or_default()
+        } else {
+            return body.to_string();
+        };
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
+}
+
+fn decode_quoted_printable(text: &str, underscore_is_space: bool) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' if bytes[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if bytes[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(c) => {
+                        out.push(c);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if underscore_is_space => {
+                out.push(b' ');
+                i += 1;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Decode the `=?charset?B|Q?text?=` encoded words, only the utf-8 and ascii charsets are supported.
+fn decode_encoded_words(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    let mut last_was_word = false;
+    while let Some(start) = rest.find("=?") {
+        let decoded = rest[start + 2..].split_once('?').and_then(|(_, after)| {
+            let (encoding, after) = after.split_once('?')?;
+            let end = after.find("?=")?;
+            let word = &after[..end];
+            let bytes = match encoding {
+                "B" | "b" => B64.decode(word).ok()?,
+                "Q" | "q" => decode_quoted_printable(word, true),
+                _ => return None,
+            };
+            let consumed = text.len() - after.len() + end + 2;
+            Some((String::from_utf8_lossy(&bytes).into_owned(), consumed))
+        });
+
+        match decoded {
+            Some((word, consumed)) => {
+                // the whitespace between the adjacent encoded words is ignored
+                let between = &rest[..start];
+                if !(last_was_word && between.trim().is_empty()) {
+                    out.push_str(between);
+                }
+                out.push_str(&word);
+                rest = &text[consumed..];
+                last_was_word = true;
+            }
+            None => {
+                out.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                last_was_word = false;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Default)]
+struct Mailbox {
+    mails: Mutex<Vec<TestMail>>,
+    received: Notify,
+}
+
+async fn serve_client(mailbox: Arc<Mailbox>, stream: TcpStream) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    writer.write_all(b"220 localhost ESMTP shine-test\r\n").await?;
+
+    let mut from = None;
+    let mut to = Vec::new();
+    while let Some(line) = read_line(&mut reader).await? {
+        let line = String::from_utf8_lossy(&line).into_owned();
+        let (command, args) = line.split_once(' ').unwrap_or((&line, ""));
+        let response = match command.to_ascii_uppercase().as_str() {
+            "EHLO" => "250-localhost\r\n250-8BITMIME\r\n250-AUTH PLAIN LOGIN\r\n250 OK\r\n".to_string(),
+            "HELO" => "250 localhost\r\n".to_string(),
+            "AUTH" => {
+                let mut parts = args.split_whitespace();
+                let mechanism = parts.next().unwrap_or_default().to_ascii_uppercase();
+                // the credentials are not checked, only the exchange is completed
+                match (mechanism.as_str(), parts.next()) {
+                    ("PLAIN", Some(_)) => {}
+                    ("PLAIN", None) => {
+                        writer.write_all(b"334 \r\n").await?;
+                        read_line(&mut reader).await?;
+                    }
+                    ("LOGIN", initial) => {
+                        if initial.is_none() {
+                            writer.write_all(b"334 VXNlcm5hbWU6\r\n").await?;
+                            read_line(&mut reader).await?;
+                        }
+                        writer.write_all(b"334 UGFzc3dvcmQ6\r\n").await?;
+                        read_line(&mut reader).await?;
+                    }
+                    _ => {
+                        writer.write_all(b"504 Unrecognized authentication type\r\n").await?;
+                        continue;
+                    }
+                }
+                "235 Authentication successful\r\n".to_string()
+            }
+            "MAIL" => {
+                from = Some(parse_address(args));
+                to.clear();
+                "250 OK\r\n".to_string()
+            }
+            "RCPT" => {
+                to.push(parse_address(args));
+                "250 OK\r\n".to_string()
+            }
+            "DATA" => {
+                if from.is_none() || to.is_empty() {
+                    "503 Bad sequence of commands\r\n".to_string()
+                } else {
+                    writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await?;
+                    let mut data = String::new();
+                    loop {
+                        let Some(line) = read_line(&mut reader).await? else {
+                            return Ok(());
+                        };
+                        if line == b"." {
+                            break;
+                        }
+                        // the leading dots are doubled by the clients
+                        let line = line.strip_prefix(b".").unwrap_or(&line);
+                        data.push_str(&String::from_utf8_lossy(line));
+                        data.push_str("\r\n");
+                    }
+
+                    let mail = TestMail {
+                        from: from.take().unwrap_or_default(),
+                        to: std::mem::take(&mut to),
+                        data,
+                    };
+                    mailbox.mails.lock().unwrap().push(mail);
+                    mailbox.received.notify_one();
+                    "250 OK: queued\r\n".to_string()
+                }
+            }
+            "RSET" => {
+                from = None;
+                to.clear();
+                "250 OK\r\n".to_string()
+            }
+            "NOOP" => "250 OK\r\n".to_string(),
+            "VRFY" => "252 Cannot verify the user\r\n".to_string(),
+            "QUIT" => {
+                writer.write_all(b"221 Bye\r\n").await?;
+                return Ok(());
+            }
+            _ => "502 Command not implemented\r\n".to_string(),
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+/// Parse the address of the `FROM:<address>` and `TO:<address>` arguments.
+fn parse_address(args: &str) -> String {
+    let address = args.split_once(':').map(|(_, address)| address).unwrap_or(args).trim();
+    let address = address.split_whitespace().next().unwrap_or_default();
+    address.trim_start_matches('<').trim_end_matches('>').to_string()
+}
+
+/// An in-process SMTP server capturing the sent mails for the tests. Any credentials are accepted and the
+/// mails are not delivered. The server is stopped when the last clone is dropped.
+#[derive(Clone)]
+pub struct TestSmtp {
+    server: Arc<TestServer>,
+    mailbox: Arc<Mailbox>,
+}
+
+impl TestSmtp {
+    pub fn start() -> io::Result<Self> {
+        let mailbox = Arc::new(Mailbox::default());
+        let server = {
+            let mailbox = mailbox.clone();
+            TestServer::start("test-smtp", move |stream| {
+                let mailbox = mailbox.clone();
+                async move {
+                    if let Err(err) = serve_client(mailbox, stream).await {
+                        log::warn!("SMTP connection failed: {err}");
+                    }
+                }
+            })?
+        };
+
+        Ok(Self {
+            server: Arc::new(server),
+            mailbox,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.server.address()
+    }
+
+    /// The captured mails in the order of arrival.
+    pub fn mails(&self) -> Vec<TestMail> {
+        self.mailbox.mails.lock().unwrap().clone()
+    }
+
+    /// Remove and return the captured mails.
+    pub fn take_mails(&self) -> Vec<TestMail> {
+        std::mem::take(&mut *self.mailbox.mails.lock().unwrap())
+    }
+
+    /// Wait for the next mail and remove it from the captured mails, `None` on timeout.
+    pub async fn next_mail(&self, timeout: Duration) -> Option<TestMail> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            {
+                let mut mails = self.mailbox.mails.lock().unwrap();
+                if !mails.is_empty() {
+                    return Some(mails.remove(0));
+                }
+            }
+            tokio::time::timeout_at(deadline, self.mailbox.received.notified())
+                .await
+                .ok()?;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::Duration;
+use uuid::Uuid;
+
+/// The user of a session created by the [TestClient](crate::web::TestClient) without the identity service.
+#[derive(Clone, Debug)]
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub name: String,
+    pub is_email_confirmed: bool,
+    pub is_linked: bool,
+    pub roles: Vec<String>,
+    /// The time elapsed since the login and the authentication, ex. to test the step-up authentication.
+    pub session_age: Duration,
+    /// The fingerprint the session is bound to instead of the fingerprint of the client, ex. a legacy one.
+    pub fingerprint: Option<String>,
+}
+
+impl TestUser {
+    pub fn new(name: &str) -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            name: name.to_string(),
+            is_email_confirmed: true,
+            is_linked: false,
+            roles: Vec::new(),
+            session_age: Duration::zero(),
+            fingerprint: None,
+        }
+    }
+
+    pub fn with_id(self, user_id: Uuid) -> Self {
+        Self { user_id, ..self }
+    }
+
+    pub fn with_roles<I, S>(self, roles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        Self {
+            roles: roles.into_iter().map(|role| role.to_string()).collect(),
+            ..self
+        }
+    }
+
+    pub fn with_email_confirmed(self, is_email_confirmed: bool) -> Self {
+        Self { is_email_confirmed, ..self }
+    }
+
+    pub fn with_linked(self, is_linked: bool) -> Self {
+        Self { is_linked, ..self }
+    }
+
+    pub fn with_session_age(self, session_age: Duration) -> Self {
+        Self { session_age, ..self }
+    }
+
+    pub fn with_fingerprint(self, fingerprint: &str) -> Self {
+        Self {
+            fingerprint: Some(fingerprint.to_string()),
+            ..self
+        }
+    }
+}
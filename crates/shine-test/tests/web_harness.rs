@@ -0,0 +1,450 @@
+use anyhow::Error as AnyError;
+use axum::{extract::State, http::StatusCode, routing::get, Extension, Json};
+use axum_extra::extract::cookie::Cookie;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
+use chrono::Duration;
+use futures::StreamExt;
+use lettre::{message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport, Transport};
+use redis::AsyncCommands;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shine_infra::{
+    crypto::{JwtAlgorithm, JwtService, JwtSigningKey},
+    health::HealthService,
+    session::{CheckedCurrentUser, CurrentUserService, StepUpCurrentUser, TokenCurrentUser},
+    web::{extracts::ClientFingerprint, FeatureConfig, WebAppConfig, WebApplication},
+};
+use shine_test::{
+    test,
+    web::{TestApp, TestMail, TestRedis, TestSmtp, TestUser},
+};
+use std::{sync::Arc, time::Duration as StdDuration};
+use utoipa_axum::router::OpenApiRouter;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+struct SampleConfig {
+    #[serde(default)]
+    greeting: String,
+}
+
+impl FeatureConfig for SampleConfig {
+    const NAME: &'static str = "sample";
+}
+
+#[derive(Clone)]
+struct SampleState {
+    greeting: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Me {
+    name: String,
+    roles: Vec<String>,
+}
+
+async fn hello(State(state): State<SampleState>) -> String {
+    state.greeting.clone()
+}
+
+async fn me(user: CheckedCurrentUser) -> Json<Me> {
+    Json(Me {
+        name: user.name.clone(),
+        roles: user.roles.clone(),
+    })
+}
+
+async fn step_up(user: StepUpCurrentUser) -> Json<Me> {
+    Json(Me {
+        name: user.name.clone(),
+        roles: user.roles.clone(),
+    })
+}
+
+async fn issue_token(
+    Extension(user_service): Extension<Arc<CurrentUserService>>,
+    Extension(jwt_service): Extension<Arc<JwtService>>,
+    user: CheckedCurrentUser,
+) -> String {
+    jwt_service.issue_session_token(&user_service, &user).unwrap().token
+}
+
+async fn token_me(user: TokenCurrentUser) -> Json<Me> {
+    Json(Me {
+        name: user.name.clone(),
+        roles: user.roles.clone(),
+    })
+}
+
+struct SampleApp;
+
+impl WebApplication for SampleApp {
+    type AppConfig = SampleConfig;
+    type AppState = SampleState;
+
+    async fn create(
+        &self,
+        config: &WebAppConfig<Self::AppConfig>,
+        _health_service: &mut HealthService,
+        router: &mut OpenApiRouter<Self::AppState>,
+    ) -> Result<Self::AppState, AnyError> {
+        let app_router = OpenApiRouter::new()
+            .route("/hello", get(hello))
+            .route("/me", get(me))
+            .route("/step-up", get(step_up))
+            .route("/token", get(issue_token))
+            .route("/token/me", get(token_me));
+        *router = router.clone().nest(&format!("/{}", SampleConfig::NAME), app_router);
+
+        Ok(SampleState {
+            greeting: config.feature.greeting.clone(),
+        })
+    }
+}
+
+#[test]
+async fn redis_commands() {
+    let server = TestRedis::start().unwrap();
+    let client = redis::Client::open(server.cns()).unwrap();
+    let mut con = client.get_multiplexed_async_connection().await.unwrap();
+
+    let _: () = con.set_ex("test:a", "1", 60).await.unwrap();
+    let _: () = con.set("test:b", "2").await.unwrap();
+    let created: bool = con.set_nx("test:b", "3").await.unwrap();
+    assert!(!created);
+    assert_eq!(
+        con.get::<_, Option<String>>("test:b").await.unwrap().as_deref(),
+        Some("2")
+    );
+    assert_eq!(con.ttl::<_, i64>("test:a").await.unwrap(), 60);
+    assert_eq!(con.ttl::<_, i64>("test:b").await.unwrap(), -1);
+    assert_eq!(con.incr::<_, _, i64>("test:counter", 5).await.unwrap(), 5);
+
+    let (ttl, a): (i64, String) = redis::pipe()
+        .expire("test:b", 10)
+        .ignore()
+        .ttl("test:b")
+        .get("test:a")
+        .query_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!((ttl, a.as_str()), (10, "1"));
+
+    let mut keys = con
+        .scan_match::<_, String>("test:[ab]")
+        .await
+        .unwrap()
+        .map(|key| key.unwrap())
+        .collect::<Vec<_>>()
+        .await;
+    keys.sort();
+    assert_eq!(keys, vec!["test:a", "test:b"]);
+    assert_eq!(server.keys("test:*"), vec!["test:a", "test:b", "test:counter"]);
+
+    let _: () = con.pset_ex("test:expiring", "x", 1).await.unwrap();
+    tokio::time::sleep(StdDuration::from_millis(10)).await;
+    assert!(!con.exists::<_, bool>("test:expiring").await.unwrap());
+
+    let removed: i64 = con.del(&["test:a", "test:b", "test:missing"]).await.unwrap();
+    assert_eq!(removed, 2);
+    assert_eq!(server.get("test:counter").as_deref(), Some(&b"5"[..]));
+    server.flush();
+    assert!(server.keys("*").is_empty());
+
+    let result: Result<i64, _> = redis::Script::new("return 1").invoke_async(&mut con).await;
+    assert!(result.is_err());
+}
+
+#[test]
+async fn redis_pubsub() {
+    let server = TestRedis::start().unwrap();
+    let client = redis::Client::open(server.cns()).unwrap();
+    let mut con = client.get_multiplexed_async_connection().await.unwrap();
+
+    let mut pubsub = client.get_async_pubsub().await.unwrap();
+    pubsub.subscribe("events").await.unwrap();
+    pubsub.psubscribe("topic:*").await.unwrap();
+
+    let receivers: i64 = con.publish("events", "hello").await.unwrap();
+    assert_eq!(receivers, 1);
+    let _: i64 = con.publish("topic:a", "world").await.unwrap();
+    let _: i64 = con.publish("other", "ignored").await.unwrap();
+
+    let mut messages = pubsub.on_message();
+    let message = messages.next().await.unwrap();
+    assert_eq!(message.get_channel_name(), "events");
+    assert_eq!(message.get_payload::<String>().unwrap(), "hello");
+    let message = messages.next().await.unwrap();
+    assert_eq!(message.get_channel_name(), "topic:a");
+    assert_eq!(message.get_pattern::<String>().unwrap(), "topic:*");
+    assert_eq!(message.get_payload::<String>().unwrap(), "world");
+}
+
+#[test]
+async fn smtp_capture() {
+    let server = TestSmtp::start().unwrap();
+    let address = server.address();
+
+    let message = Message::builder()
+        .from("Shine <no-reply@example.com>".parse::<Mailbox>().unwrap())
+        .to("user@example.com".parse::<Mailbox>().unwrap())
+        .subject("Helló világ")
+        .body("Árvíztűrő tükörfúrógép\n.leading dot".to_string())
+        .unwrap();
+    tokio::task::spawn_blocking(move || {
+        let mailer = SmtpTransport::builder_dangerous(address.ip().to_string())
+            .port(address.port())
+            .credentials(Credentials::new("user".into(), "password".into()))
+            .build();
+        mailer.send(&message).unwrap();
+    })
+    .await
+    .unwrap();
+
+    let mail = server.next_mail(StdDuration::from_secs(5)).await.unwrap();
+    assert_eq!(mail.from, "no-reply@example.com");
+    assert_eq!(mail.to, vec!["user@example.com"]);
+    assert_eq!(mail.subject().as_deref(), Some("Helló világ"));
+    assert_eq!(
+        mail.body().replace("\r\n", "\n").trim_end(),
+        "Árvíztűrő tükörfúrógép\n.leading dot"
+    );
+    assert!(server.mails().is_empty());
+    assert!(server.next_mail(StdDuration::from_millis(50)).await.is_none());
+}
+
+#[test]
+fn mail_decoding() {
+    let mail = TestMail {
+        from: "from@example.com".into(),
+        to: vec!["to@example.com".into()],
+        data: [
+            "Subject: =?utf-8?B?SGVsbMOz?=",
+            " =?utf-8?Q?_vil=C3=A1g?=",
+            "Content-Transfer-Encoding: quoted-printable",
+            "",
+            "Hell=C3=B3 a very long =",
+            "line",
+            "",
+        ]
+        .join("\r\n"),
+    };
+
+    assert_eq!(
+        mail.header("subject").as_deref(),
+        Some("=?utf-8?B?SGVsbMOz?= =?utf-8?Q?_vil=C3=A1g?=")
+    );
+    assert_eq!(mail.subject().as_deref(), Some("Helló világ"));
+    assert_eq!(mail.body(), "Helló a very long line\r\n");
+    assert_eq!(mail.header("missing"), None);
+}
+
+#[test]
+async fn app_with_session() {
+    let app = TestApp::builder(SampleApp)
+        .with_config(json!({ "sample": { "greeting": "Hello test" } }))
+        .start()
+        .await
+        .unwrap();
+    assert_eq!(app.config()["service"]["sessionTtl"], json!(3600));
+
+    let client = app.client();
+    let response = client.get("/sample/hello").send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "Hello test");
+
+    let response = client.get("/sample/me").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let problem: Value = response.json();
+    assert_eq!(problem["type"], "unauthorized");
+
+    let user = client
+        .login(&TestUser::new("Alice").with_roles(["admin"]))
+        .await
+        .unwrap();
+    assert!(client.cookie("sid").is_some());
+    assert_eq!(
+        app.redis()
+            .keys(&format!("session:{}:*", user.user_id.as_simple()))
+            .len(),
+        2
+    );
+
+    // the sentinel has the format of the identity service
+    let sentinel_key = app
+        .redis()
+        .keys(&format!("session:{}:*:sentinel", user.user_id.as_simple()));
+    let sentinel: Value = serde_json::from_slice(&app.redis().get(&sentinel_key[0]).unwrap()).unwrap();
+    assert_eq!(sentinel["agent"], "shine-test");
+    assert_eq!(sentinel["createdAt"], sentinel["authenticatedAt"]);
+    assert!(sentinel["fingerprint"].is_string());
+    assert!(sentinel.get("country").is_some_and(Value::is_null));
+
+    let response = client.get("/sample/me").send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Me = response.json();
+    assert_eq!(me.name, "Alice");
+    assert_eq!(me.roles, vec!["admin"]);
+
+    let response = client.get("/sample/step-up").send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // the cookies are not shared with the new clients
+    let response = app.client().get("/sample/me").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    client.clear_cookies();
+    let response = client.get("/sample/me").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+async fn app_with_old_session() {
+    // step-up is disabled by default
+    let app = TestApp::builder(SampleApp).start().await.unwrap();
+    let client = app.client();
+    client
+        .login(&TestUser::new("Bob").with_session_age(Duration::minutes(30)))
+        .await
+        .unwrap();
+    let response = client.get("/sample/step-up").send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let app = TestApp::builder(SampleApp)
+        .with_config(json!({ "service": { "sessionStepUpAge": 300 } }))
+        .start()
+        .await
+        .unwrap();
+
+    let client = app.client();
+    client
+        .login(&TestUser::new("Bob").with_session_age(Duration::minutes(30)))
+        .await
+        .unwrap();
+
+    let response = client.get("/sample/me").send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client.get("/sample/step-up").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let problem: Value = response.json();
+    assert_eq!(problem["type"], "step-up-required");
+}
+
+#[test]
+async fn app_with_legacy_session() {
+    let app = TestApp::builder(SampleApp)
+        .with_config(json!({ "service": { "sessionCookieDomain": "localhost" } }))
+        .start()
+        .await
+        .unwrap();
+    let legacy = ClientFingerprint::from_agent("shine-test".to_string()).unwrap();
+
+    let client = app.client();
+    let user = client
+        .login(&TestUser::new("Carol").with_fingerprint(legacy.as_str()))
+        .await
+        .unwrap();
+
+    let response = client.get("/sample/me").send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let sentinel_key = app
+        .redis()
+        .keys(&format!("session:{}:*:sentinel", user.user_id.as_simple()));
+    let sentinel: Value = serde_json::from_slice(&app.redis().get(&sentinel_key[0]).unwrap()).unwrap();
+    let fingerprint = sentinel["fingerprint"].as_str().unwrap();
+    assert!(fingerprint.starts_with("fp2."));
+
+    // the cookie is re-issued with the upgraded fingerprint
+    let cookie = Cookie::parse_encoded(format!("sid={}", client.cookie("sid").unwrap())).unwrap();
+    assert!(
+        cookie.value().ends_with(&format!("\"fp\":\"{fingerprint}\"}}")),
+        "{cookie}"
+    );
+
+    // and it is accepted from the same user agent
+    let response = client.get("/sample/me").send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("set-cookie").is_none());
+
+    // but not from another one
+    let response = client
+        .get("/sample/me")
+        .header("user-agent", "other-agent")
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let problem: Value = response.json();
+    assert_eq!(problem["type"], "unauthorized");
+}
+
+#[test]
+async fn app_with_session_token() {
+    let private_key = JwtSigningKey::generate_pkcs8(JwtAlgorithm::ES256).unwrap();
+    let app = TestApp::builder(SampleApp)
+        .with_config(json!({
+            "service": {
+                "token": {
+                    "issuer": "test",
+                    "audience": "sample",
+                    "ttl": 600,
+                    "signingKey": "k1",
+                    "keys": [{ "kid": "k1", "algorithm": "ES256", "privateKey": B64.encode(&private_key) }]
+                }
+            }
+        }))
+        .start()
+        .await
+        .unwrap();
+
+    let client = app.client();
+    let user = client.login(&TestUser::new("Dave")).await.unwrap();
+    let token = client.get("/sample/token").send().await.text();
+
+    // the session key is not exposed in the token
+    let claims = token.split('.').nth(1).unwrap();
+    let claims = String::from_utf8(B64.decode(claims).unwrap()).unwrap();
+    assert!(!claims.contains(&user.key.to_hex()));
+
+    let bearer = format!("Bearer {token}");
+    let token_client = app.client();
+    let response = token_client
+        .get("/sample/token/me")
+        .header("authorization", &bearer)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Me = response.json();
+    assert_eq!(me.name, "Dave");
+
+    // the token is bound to the fingerprint of the session
+    let response = app
+        .client()
+        .with_peer(([10, 1, 2, 3], 50000).into())
+        .with_header("user-agent", "other-agent")
+        .get("/sample/token/me")
+        .header("authorization", &bearer)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = token_client.get("/sample/token/me").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = token_client
+        .get("/sample/token/me")
+        .header("authorization", "Bearer invalid")
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // revoking the session revokes the token
+    app.redis().flush();
+    let response = token_client
+        .get("/sample/token/me")
+        .header("authorization", &bearer)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
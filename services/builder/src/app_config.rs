@@ -1,11 +1,14 @@
 use crate::repositories::DBConfig;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use shine_infra::web::FeatureConfig;
+use validator::Validate;
 
 /// The application configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
+    #[validate(nested)]
     pub db: DBConfig,
 }
 
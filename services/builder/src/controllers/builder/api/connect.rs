@@ -2,22 +2,12 @@ use crate::{
     app_state::AppState,
     services::{Message, MessageSource, Session},
 };
-use axum::{
-    extract::{
-        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
-        State,
-    },
-    response::IntoResponse,
-    Extension,
-};
-use futures::{SinkExt, StreamExt};
+use axum::{extract::State, response::Response, Extension};
 use serde::Deserialize;
-use shine_infra::{
-    session::{CheckedCurrentUser, CurrentUser},
-    web::{
-        extracts::ValidatedPath,
-        responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
-    },
+use shine_infra::web::{
+    extracts::ValidatedPath,
+    realtime::{AuthenticatedWebSocket, WsConnection},
+    responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
 };
 use std::sync::Arc;
 use utoipa::IntoParams;
@@ -48,93 +38,52 @@ pub async fn connect(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
     ValidatedPath(path): ValidatedPath<PathParams>,
-    user: CheckedCurrentUser,
-    ws: WebSocketUpgrade,
-) -> Result<impl IntoResponse, ProblemResponse> {
-    let user = user.into_user();
+    ws: AuthenticatedWebSocket,
+) -> Result<Response, ProblemResponse> {
+    let user_id = ws.user().user_id;
     log::info!(
         "User {} requesting a connection to the session {}...",
-        path.session_id,
-        user.user_id
+        user_id,
+        path.session_id
     );
 
     let session = state
         .sessions()
-        .acquire_session(&path.session_id, &user.user_id)
+        .acquire_session(&path.session_id, &user_id)
         .await
         .map_err(|err| err.into_response(&problem_config))?;
-    Ok(ws.on_upgrade(move |socket| handle_socket(socket, user, session)))
+    Ok(ws.on_upgrade(move |connection| handle_socket(connection, session)))
 }
 
-async fn handle_socket(socket: WebSocket, user: CurrentUser, session: Arc<Session>) {
-    let (mut ws_sender, mut ws_receiver) = socket.split();
+async fn handle_socket(connection: WsConnection<RequestMessage, ResponseMessage>, session: Arc<Session>) {
+    let (user, ws_sender, mut ws_receiver) = connection.into_parts();
     let current_user_id = user.user_id;
     let session_id = session.id();
 
     log::info!("[{current_user_id}] Connected to the session {session_id}");
     let message_sender = session.message_sender(MessageSource::User(current_user_id));
     let mut message_receiver = session.subscribe_messages();
+    message_sender.send(Message::Chat(current_user_id, "${tr: Connected}".to_string()));
 
-    let mut recv_task = {
-        let message_sender = message_sender.clone();
-        tokio::spawn(async move {
-            message_sender.send(Message::Chat(current_user_id, "${tr: Connected}".to_string()));
-
-            while let Some(Ok(message)) = ws_receiver.next().await {
-                log::info!("[{current_user_id}] WsMessage received");
-                match message {
-                    WsMessage::Text(text) => {
-                        let msg = match serde_json::from_str::<RequestMessage>(&text) {
-                            Ok(msg) => match msg {
-                                RequestMessage::Chat { text } => Some(Message::Chat(current_user_id, text)),
-                            },
-                            Err(_) => {
-                                log::error!("[{current_user_id}] Received invalid message: {text}");
-                                None
-                            }
-                        };
-
-                        if let Some(msg) = msg {
-                            message_sender.send(msg);
-                        }
+    loop {
+        tokio::select! {
+            request = ws_receiver.recv() => match request {
+                Some(RequestMessage::Chat { text }) => message_sender.send(Message::Chat(current_user_id, text)),
+                None => break,
+            },
+            message = message_receiver.recv() => match message {
+                Ok(Message::Chat(user_id, text)) => {
+                    let response = ResponseMessage::Chat { from: user_id, text };
+                    if let Err(err) = ws_sender.send(response).await {
+                        log::info!("[{current_user_id}] Failed to send message to the user: {err}");
+                        break;
                     }
-                    _ => {}
                 }
-            }
-        })
-    };
-
-    let mut send_task = tokio::spawn(async move {
-        while let Ok(message) = message_receiver.recv().await {
-            log::info!("[{current_user_id}] Message received");
-            let msg = match message {
-                Message::Chat(user_id, text) => Some(ResponseMessage::Chat { from: user_id, text }),
-            };
-
-            if let Some(msg) = msg {
-                let data = match serde_json::to_string(&msg) {
-                    Ok(data) => data,
-                    Err(err) => {
-                        log::error!("[{current_user_id}] Failed to serialize message {msg:#?} with error {err:#?}");
-                        continue;
-                    }
-                };
-                if let Err(err) = ws_sender.send(WsMessage::Text(data.into())).await {
-                    log::error!("[{current_user_id}] Failed to send message to the user: {err:#?}");
+                Err(err) => {
+                    log::warn!("[{current_user_id}] Message channel closed: {err}");
+                    break;
                 }
-            }
-        }
-    });
-
-    // If any one of the tasks exit, abort the other.
-    tokio::select! {
-        rv_a = (&mut send_task) => {
-            log::info!("Send task exited: {rv_a:?}");
-            recv_task.abort();
-        },
-        rv_b = (&mut recv_task) => {
-            log::info!("Receive task exited: {rv_b:?}");
-            send_task.abort();
+            },
         }
     }
 
@@ -0,0 +1,2 @@
+-- The preferred language (BCP 47 tag) of the user for the emails and the messages
+ALTER TABLE identities ADD COLUMN language VARCHAR(35);
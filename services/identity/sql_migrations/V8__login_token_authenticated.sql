@@ -0,0 +1,3 @@
+-- The time of the authentication the token was issued for, it is kept when a token is rotated to
+-- preserve the time of the original login. NULL for the earlier tokens, their creation time is used.
+ALTER TABLE login_tokens ADD COLUMN authenticated TIMESTAMPTZ NULL;
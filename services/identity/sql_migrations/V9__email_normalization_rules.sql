@@ -0,0 +1,7 @@
+-- The email normalization rules of each version. The rows normalized by a previous version are found by the hash
+-- of their rules until they are migrated to the current version.
+CREATE TABLE email_normalization_rules (
+    version VARCHAR(64) NOT NULL PRIMARY KEY,
+    rules TEXT NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
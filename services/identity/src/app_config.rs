@@ -1,49 +1,63 @@
 use crate::repositories::DBConfig;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use shine_infra::web::FeatureConfig;
+use shine_infra::web::{validate_patterns, FeatureConfig};
 use std::collections::{HashMap, HashSet};
 use url::Url;
+use validator::Validate;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthSessionConfig {
+    #[validate(length(min = 1))]
     pub external_login_cookie_secret: String,
+    #[validate(length(min = 1))]
     pub token_cookie_secret: String,
 
     /// The maximum time to live for an access (remember me) token in seconds
+    #[validate(range(min = 1))]
     pub ttl_access_token: usize,
     /// The maximum time to live for a single access (one-time-use) token in seconds
+    #[validate(range(min = 1))]
     pub ttl_single_access: usize,
     /// The maximum time to live for an api-key in seconds
+    #[validate(range(min = 1))]
     pub ttl_api_key: usize,
     /// The maximum time to live for an email login in seconds
+    #[validate(range(min = 1))]
     pub ttl_email_token: usize,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum ExternalUserInfoExtensions {
     GithubEmail,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct OAuth2Config {
+    #[validate(url)]
     pub authorization_url: String,
+    #[validate(url)]
     pub token_url: String,
+    #[validate(url)]
     pub user_info_url: String,
     pub user_info_mapping: HashMap<String, String>,
     pub extensions: HashSet<ExternalUserInfoExtensions>,
+    #[validate(length(min = 1))]
     pub client_id: String,
     pub client_secret: String,
     pub scopes: Vec<String>,
     pub ignore_certificates: Option<bool>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct OIDCConfig {
+    #[validate(url)]
     pub discovery_url: String,
+    #[validate(length(min = 1))]
     pub client_id: String,
     pub client_secret: Option<String>,
     pub scopes: Vec<String>,
@@ -52,7 +66,7 @@ pub struct OIDCConfig {
     pub ttl_client: Option<usize>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthConfig {
     /// The name of the application
@@ -69,14 +83,20 @@ pub struct AuthConfig {
     pub auth_base_url: Url,
 
     /// A list of regex patterns for allowed redirect URLs.
+    #[validate(custom(function = "validate_patterns"))]
     pub allowed_redirect_urls: Vec<String>,
 
     /// Hash of the master key to manage user roles. In production once a user is created it's highly
     /// recommended to disable this feature.
     pub super_user_api_key_hash: Option<String>,
 
+    /// Reject the email addresses of the disposable email domains at email login and email change. Default: false.
+    #[serde(default)]
+    pub reject_disposable_email: bool,
+
     /// Authentication related cookie configuration.
     #[serde(flatten)]
+    #[validate(nested)]
     pub auth_session: AuthSessionConfig,
 
     /// The time interval before redirecting the user from embedded pages. If not provided, no redirection occurs,
@@ -86,8 +106,10 @@ pub struct AuthConfig {
     /// Initiates OIDC discovery at startup to identify and rectify any potential misconfigurations.
     pub openid_startup_discovery: bool,
     /// A list of external providers utilizing the (interactive) OpenID Connect login flow.
+    #[validate(nested)]
     pub openid: HashMap<String, OIDCConfig>,
     /// List of external providers utilizing the (interactive) OAuth2 login flow
+    #[validate(nested)]
     pub oauth2: HashMap<String, OAuth2Config>,
 }
 
@@ -100,7 +122,7 @@ impl AuthConfig {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "idEncoder")]
 pub enum IdEncoderConfig {
@@ -109,17 +131,35 @@ pub enum IdEncoderConfig {
 
     #[serde(rename_all = "camelCase")]
     Harsh { salt: String },
+
+    #[serde(rename_all = "camelCase")]
+    Feistel {
+        alphabet: Option<String>,
+        length: usize,
+        key: String,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Sqids {
+        alphabet: Option<String>,
+        min_length: Option<u8>,
+        #[serde(default)]
+        blocklist: Vec<String>,
+    },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct AutoNameConfig {
     pub base_name: String,
     #[serde(flatten)]
     pub id_encoder: IdEncoderConfig,
+    /// Append a check character to the generated names to reject mistyped names early
+    #[serde(default)]
+    pub checksum: bool,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type")]
 pub enum MailerConfig {
@@ -134,11 +174,13 @@ pub enum MailerConfig {
 }
 
 /// The application configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
+    #[validate(nested)]
     pub db: DBConfig,
     pub name: AutoNameConfig,
+    #[validate(nested)]
     pub auth: AuthConfig,
     pub mailer: MailerConfig,
 }
@@ -1,5 +1,5 @@
 use crate::{
-    app_config::{AppConfig, IdEncoderConfig, MailerConfig},
+    app_config::{AppConfig, AutoNameConfig, IdEncoderConfig, MailerConfig},
     repositories::{
         identity::pg::PgIdentityDb,
         mailer::{smtp::SmtpEmailSender, EmailSender},
@@ -7,17 +7,22 @@ use crate::{
         CaptchaValidator, DBPool,
     },
     services::{
-        IdentityTopic, LinkService, MailerService, RoleService, SessionService, SettingsService, TokenService,
-        TokenSettings, UserService,
+        identity_messages, IdentityTopic, LinkService, MailerService, RoleService, SessionService, SettingsService,
+        TokenService, TokenSettings, UserService,
     },
 };
 use anyhow::{anyhow, Error as AnyError};
 use chrono::Duration;
 use ring::rand::SystemRandom;
 use shine_infra::{
-    crypto::{HarshIdEncoder, IdEncoder, OptimusIdEncoder, PrefixedIdEncoder},
+    crypto::{
+        ChecksumIdEncoder, FeistelIdEncoder, HarshIdEncoder, IdEncoder, IdEncoderError, OptimusIdEncoder,
+        PrefixedIdEncoder, SqidsIdEncoder,
+    },
+    language::MessageCatalog,
+    session::SessionLifetime,
     sync::TopicBus,
-    web::{responses::ProblemConfig, WebAppConfig},
+    web::{responses::ProblemConfig, HttpClientFactory, WebAppConfig},
 };
 use std::sync::Arc;
 use tera::Tera;
@@ -28,6 +33,7 @@ struct Inner {
     random: SystemRandom,
     tera: Tera,
     db: DBPool,
+    http_client_factory: HttpClientFactory,
     captcha_validator: CaptchaValidator,
     session_service: SessionService<RedisSessionDb>,
     email_sender: SmtpEmailSender,
@@ -73,24 +79,34 @@ impl AppState {
                 external_providers: config_auth.collect_providers(),
                 page_redirect_time: config_auth.page_redirect_time,
                 super_user_api_key_hash: config_auth.super_user_api_key_hash.clone(),
+                reject_disposable_email: config_auth.reject_disposable_email,
             }
         };
 
         let problem_config = ProblemConfig::new(config.service.full_problem_response);
 
         let tera = {
+            let mut catalog = MessageCatalog::core();
+            catalog.merge(identity_messages());
+            if let Some(translations) = &config.service.translations {
+                catalog.load_dir(translations)?;
+            }
+
             let mut tera = Tera::new("tera_templates/**/*").map_err(|e| anyhow!(e))?;
             tera.autoescape_on(vec![".html"]);
+            tera.register_function("t", Arc::new(catalog).tera_function());
             tera
         };
 
         let db_pool = DBPool::new(config_db).await?;
-        let captcha_validator = CaptchaValidator::new(&config.service.captcha_secret);
+        let http_client_factory = HttpClientFactory::new(&config.service.http_client)?;
+        let captcha_validator =
+            CaptchaValidator::new(&config.service.captcha_secret, http_client_factory.create("captcha")?);
 
         let session_service = {
-            let ttl_session = Duration::seconds(i64::try_from(config.service.session_ttl)?);
-            let session_db = RedisSessionDb::new(&db_pool.redis, "".to_string(), ttl_session).await?;
-            SessionService::new(session_db)
+            let lifetime = SessionLifetime::from_config(&config.service)?;
+            let session_db = RedisSessionDb::new(&db_pool.redis, "".to_string(), lifetime).await?;
+            SessionService::new(session_db, config.service.session_max_count)
         };
 
         let email_sender = {
@@ -117,16 +133,7 @@ impl AppState {
 
         let user_service = {
             let identity_db = PgIdentityDb::new(&db_pool.postgres, &config_db.email_protection).await?;
-            let user_name_generator: Box<dyn IdEncoder> = match &config_user_name.id_encoder {
-                IdEncoderConfig::Optimus { prime, random } => Box::new(PrefixedIdEncoder::new(
-                    &config_user_name.base_name,
-                    OptimusIdEncoder::new(*prime, *random),
-                )),
-                IdEncoderConfig::Harsh { salt } => Box::new(PrefixedIdEncoder::new(
-                    &config_user_name.base_name,
-                    HarshIdEncoder::new(salt)?,
-                )),
-            };
+            let user_name_generator = create_name_generator(config_user_name)?;
             UserService::new(identity_db, user_name_generator, Arc::clone(&events))
         };
 
@@ -151,6 +158,7 @@ impl AppState {
             random: SystemRandom::new(),
             tera,
             db: db_pool,
+            http_client_factory,
             captcha_validator,
             session_service,
             email_sender,
@@ -178,6 +186,11 @@ impl AppState {
         &self.0.db
     }
 
+    /// The factory of the http clients of the external services, ex. the login providers.
+    pub fn http_client_factory(&self) -> &HttpClientFactory {
+        &self.0.http_client_factory
+    }
+
     pub fn captcha_validator(&self) -> &CaptchaValidator {
         &self.0.captcha_validator
     }
@@ -190,6 +203,10 @@ impl AppState {
         &self.0.random
     }
 
+    pub fn email_sender(&self) -> &SmtpEmailSender {
+        &self.0.email_sender
+    }
+
     pub fn mailer_service(&self) -> MailerService<'_, impl EmailSender> {
         MailerService::new(&self.0.settings, &self.0.email_sender, &self.0.tera)
     }
@@ -215,3 +232,45 @@ impl AppState {
         &self.0.link_service
     }
 }
+
+fn create_name_generator(config: &AutoNameConfig) -> Result<Box<dyn IdEncoder>, IdEncoderError> {
+    fn with_checksum<E: IdEncoder>(
+        config: &AutoNameConfig,
+        alphabet: &str,
+        encoder: E,
+    ) -> Result<Box<dyn IdEncoder>, IdEncoderError> {
+        if config.checksum {
+            Ok(Box::new(PrefixedIdEncoder::new(
+                &config.base_name,
+                ChecksumIdEncoder::new(alphabet, encoder)?,
+            )))
+        } else {
+            Ok(Box::new(PrefixedIdEncoder::new(&config.base_name, encoder)))
+        }
+    }
+
+    match &config.id_encoder {
+        IdEncoderConfig::Optimus { prime, random } => with_checksum(
+            config,
+            OptimusIdEncoder::ALPHABET,
+            OptimusIdEncoder::new(*prime, *random),
+        ),
+        IdEncoderConfig::Harsh { salt } => with_checksum(config, HarshIdEncoder::ALPHABET, HarshIdEncoder::new(salt)?),
+        IdEncoderConfig::Feistel { alphabet, length, key } => {
+            let alphabet = alphabet.as_deref().unwrap_or(FeistelIdEncoder::DEFAULT_ALPHABET);
+            with_checksum(config, alphabet, FeistelIdEncoder::new(alphabet, *length, key)?)
+        }
+        IdEncoderConfig::Sqids {
+            alphabet,
+            min_length,
+            blocklist,
+        } => {
+            let alphabet = alphabet.as_deref().unwrap_or(SqidsIdEncoder::DEFAULT_ALPHABET);
+            with_checksum(
+                config,
+                alphabet,
+                SqidsIdEncoder::new(alphabet, min_length.unwrap_or(6), blocklist)?,
+            )
+        }
+    }
+}
@@ -7,11 +7,15 @@ use crate::{
 };
 use axum_extra::headers::{authorization::Bearer, Authorization};
 use axum_extra::typed_header::TypedHeader;
+use chrono::{DateTime, Utc};
 use shine_infra::web::extracts::ClientFingerprint;
 
 /// Result of a successful authentication attempt
 pub struct AuthenticationSuccess {
     pub identity: Identity,
+    /// The time of the original authentication when a remember-me token or a session was used for the login,
+    /// None for a new authentication.
+    pub authenticated_at: Option<DateTime<Utc>>,
     pub create_access_token: bool,
     pub auth_session: AuthSession,
     pub rotated_token: Option<String>,
@@ -108,6 +112,7 @@ where
 
         Ok(AuthenticationSuccess {
             identity,
+            authenticated_at: None,
             create_access_token: remember_me,
             auth_session,
             rotated_token: None,
@@ -187,6 +192,7 @@ where
             match token_info.kind {
                 TokenKind::SingleAccess => Ok(AuthenticationSuccess {
                     identity,
+                    authenticated_at: None,
                     create_access_token: remember_me,
                     auth_session: response_session,
                     rotated_token: None,
@@ -300,8 +306,19 @@ where
                 auth_session: response_session,
             })
         } else {
+            if token_info.needs_fingerprint_upgrade(fingerprint) {
+                log::debug!("Upgrading the legacy fingerprint of the token ...");
+                if let Err(err) = self
+                    .token_service
+                    .update_fingerprint(&token_info.token_hash, fingerprint)
+                    .await
+                {
+                    log::error!("Failed to upgrade the fingerprint of the token: {err}");
+                }
+            }
             Ok(AuthenticationSuccess {
                 identity,
+                authenticated_at: Some(token_info.authenticated_at),
                 create_access_token: remember_me,
                 auth_session: response_session,
                 rotated_token: None,
@@ -425,6 +442,7 @@ where
         } else {
             Ok(AuthenticationSuccess {
                 identity,
+                authenticated_at: Some(token_info.authenticated_at),
                 create_access_token: true,
                 auth_session: response_session,
                 rotated_token: Some(token),
@@ -446,7 +464,10 @@ where
         assert!(auth_session.access().is_none());
         assert!(auth_session.user_session().is_some());
 
-        let user_id = auth_session.user_session().as_ref().unwrap().user_id;
+        let (user_id, authenticated_at) = auth_session
+            .user_session()
+            .map(|user| (user.user_id, user.authenticated_at))
+            .unwrap();
         let identity = match self.user_service.find_by_id(user_id).await {
             Ok(Some(info)) => info,
             Ok(None) => {
@@ -474,6 +495,7 @@ where
 
         Ok(AuthenticationSuccess {
             identity,
+            authenticated_at: Some(authenticated_at),
             create_access_token: remember_me,
             auth_session: response_session,
             rotated_token: None,
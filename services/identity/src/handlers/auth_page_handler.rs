@@ -4,7 +4,10 @@ use crate::{
     services::SettingsService,
 };
 use axum::response::{Html, IntoResponse, Response};
-use shine_infra::web::responses::{Problem, ProblemConfig};
+use shine_infra::web::{
+    middlewares::CspNonce,
+    responses::{Problem, ProblemConfig},
+};
 use tera::Tera;
 use url::Url;
 
@@ -42,6 +45,12 @@ impl<'a> AuthPageHandler<'a> {
         }
     }
 
+    fn bind_csp_nonce(&self, context: &mut tera::Context) {
+        if let Some(nonce) = CspNonce::current() {
+            context.insert("cspNonce", nonce.as_str());
+        }
+    }
+
     pub fn error<E>(&self, auth_session: AuthSession, error: E, error_url: Option<&Url>) -> AuthPage
     where
         E: Into<AuthError>,
@@ -62,10 +71,18 @@ impl<'a> AuthPageHandler<'a> {
     }
 
     pub fn redirect(&self, auth_session: AuthSession, target_url: Option<&Url>, problem: Option<&Problem>) -> AuthPage {
+        AuthPage {
+            auth_session: Some(auth_session),
+            html: self.render_redirect(target_url, problem),
+        }
+    }
+
+    fn render_redirect(&self, target_url: Option<&Url>, problem: Option<&Problem>) -> String {
         let mut context = tera::Context::new();
 
         self.bind_timeout(&mut context);
         self.bind_app_nme(&mut context);
+        self.bind_csp_nonce(&mut context);
 
         context.insert("targetUrl", target_url.unwrap_or(&self.settings.home_url).as_str());
 
@@ -74,15 +91,9 @@ impl<'a> AuthPageHandler<'a> {
             .unwrap_or_default();
         context.insert("problem", &problem_json);
 
-        let html = self
-            .tera
+        self.tera
             .render("redirect.html", &context)
-            .expect("Failed to generate redirect.html template");
-
-        AuthPage {
-            auth_session: Some(auth_session),
-            html,
-        }
+            .expect("Failed to generate redirect.html template")
     }
 }
 
@@ -91,3 +102,90 @@ impl AppState {
         AuthPageHandler::new(self.settings(), self.problem_config(), self.tera())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::services::TokenSettings;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use chrono::Duration;
+    use shine_infra::web::{
+        middlewares::{SecurityHeaders, SecurityHeadersProfile},
+        SecurityHeadersConfig, SecurityHeadersProfileConfig,
+    };
+    use shine_test::test;
+    use std::{collections::HashMap, sync::Arc};
+    use tower::ServiceExt;
+
+    fn settings() -> SettingsService {
+        let url = Url::parse("https://example.com").unwrap();
+        SettingsService {
+            app_name: "Shine".to_string(),
+            home_url: url.clone(),
+            auth_base_url: url.clone(),
+            link_url: url.clone(),
+            error_url: url,
+            token: TokenSettings {
+                ttl_access_token: Duration::days(1),
+                ttl_single_access: Duration::minutes(1),
+                ttl_api_key: Duration::days(1),
+                ttl_email_login_token: Duration::minutes(1),
+            },
+            allowed_redirect_urls: Vec::new(),
+            external_providers: Vec::new(),
+            page_redirect_time: None,
+            super_user_api_key_hash: None,
+            reject_disposable_email: false,
+        }
+    }
+
+    #[test]
+    async fn page_nonce_matches_header() {
+        let settings = Arc::new(settings());
+        let problem_config = Arc::new(ProblemConfig::new(false));
+        let tera = Arc::new({
+            let mut tera = Tera::new("tera_templates/**/*").unwrap();
+            tera.autoescape_on(vec![".html"]);
+            tera
+        });
+        let page = move || {
+            let handler = AuthPageHandler::new(&settings, &problem_config, &tera);
+            let html = handler.render_redirect(None, None);
+            async move { Html(html) }
+        };
+
+        let config = SecurityHeadersConfig {
+            profiles: HashMap::from([(
+                "page".to_string(),
+                SecurityHeadersProfileConfig {
+                    content_security_policy: Some("script-src 'nonce-{nonce}'".to_string()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let router = Router::new()
+            .route("/page", get(page))
+            .layer(SecurityHeadersProfile::new(&config, "page").unwrap())
+            .layer(SecurityHeaders::new(&config).unwrap());
+
+        let response = router
+            .oneshot(Request::get("/page").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let csp = response.headers()["content-security-policy"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let nonce = csp
+            .strip_prefix("script-src 'nonce-")
+            .and_then(|csp| csp.strip_suffix('\''))
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            html.contains(&format!(r#"<meta name="csp-nonce" content="{nonce}" />"#)),
+            "{html}"
+        );
+    }
+}
@@ -7,9 +7,14 @@ use crate::{
         session::{redis::RedisSessionDb, SessionDb},
     },
     routes::auth::{AuthSession, TokenCookie},
-    services::{SettingsService, TokenService},
+    services::{IdentityMetrics, SettingsService, TokenService},
+};
+use chrono::{DateTime, Utc};
+use opentelemetry::KeyValue;
+use shine_infra::{
+    telemetry::metrics,
+    web::extracts::{ClientFingerprint, SiteInfo},
 };
-use shine_infra::web::extracts::{ClientFingerprint, SiteInfo};
 use url::Url;
 
 /// Describes how to issue the access token when completing a login.
@@ -55,9 +60,15 @@ where
         }
     }
 
+    /// Establish the credentials, the provider is the authentication method used in the metrics.
+    /// The time of the authentication is the current time if not given, ex. a login with a remember-me token
+    /// keeps the time of the original login.
+    #[allow(clippy::too_many_arguments)]
     pub async fn establish(
         &self,
+        provider: &str,
         identity: Identity,
+        authenticated_at: Option<DateTime<Utc>>,
         issuance: TokenIssuance,
         auth_session: AuthSession,
         fingerprint: &ClientFingerprint,
@@ -89,6 +100,7 @@ where
                         Some(fingerprint),
                         None,
                         site_info,
+                        authenticated_at,
                     )
                     .await
                 {
@@ -109,7 +121,7 @@ where
         let auth_session = {
             let user_session = match self
                 .user_session_handler
-                .create_user_session(&identity, fingerprint, site_info)
+                .create_user_session(&identity, authenticated_at, fingerprint, site_info)
                 .await
             {
                 Ok(Some(session)) => session,
@@ -127,6 +139,9 @@ where
         };
 
         log::info!("Credentials established for: {}", identity.id);
+        metrics::<IdentityMetrics>()
+            .logins
+            .add(1, &[KeyValue::new("provider", provider.to_string())]);
         self.page_handler.redirect(auth_session, redirect_url, None)
     }
 }
@@ -20,6 +20,7 @@ const TOKEN_EXPIRED: &str = "email-token-expired";
 const INVALID_TOKEN: &str = "email-invalid-token";
 const MISSING_EMAIL: &str = "email-missing-email";
 const EMAIL_CONFLICT: &str = "email-conflict";
+const DISPOSABLE_EMAIL: &str = "email-disposable";
 
 #[derive(Debug, ThisError)]
 pub enum EmailAuthError {
@@ -33,6 +34,8 @@ pub enum EmailAuthError {
     MissingEmail,
     #[error("Email already in use")]
     EmailConflict,
+    #[error("Disposable email addresses are not allowed")]
+    DisposableEmail,
     #[error(transparent)]
     IdentityError(#[from] IdentityError),
     #[error(transparent)]
@@ -51,6 +54,7 @@ impl From<EmailAuthError> for Problem {
             EmailAuthError::TokenWrongUser => Problem::bad_request(TOKEN_EXPIRED).with_sensitive("wrongUser"),
             EmailAuthError::MissingEmail => Problem::precondition_failed(MISSING_EMAIL),
             EmailAuthError::EmailConflict => Problem::precondition_failed(EMAIL_CONFLICT),
+            EmailAuthError::DisposableEmail => Problem::bad_request(DISPOSABLE_EMAIL),
             EmailAuthError::IdentityError(IdentityError::UserDeleted) => {
                 Problem::unauthorized_ty(TOKEN_EXPIRED).with_sensitive("userDeleted")
             }
@@ -96,16 +100,27 @@ where
         }
     }
 
+    fn check_disposable(&self, email: &Email) -> Result<(), EmailAuthError> {
+        if self.settings.reject_disposable_email && email.is_disposable() {
+            log::info!("Disposable email address rejected");
+            return Err(EmailAuthError::DisposableEmail);
+        }
+        Ok(())
+    }
+
     /// Send login/registration email
-    /// Creates user if doesn't exist, then sends email with login token
+    /// Creates user if doesn't exist, then sends email with login token.
+    /// The preferred language of an existing user takes precedence over the requested language.
     pub async fn send_login_email(
         &self,
         email: &Email,
         remember_me: Option<bool>,
         redirect_url: Option<&Url>,
         site_info: &SiteInfo,
-        lang: Option<Language>,
+        lang: Language,
     ) -> Result<Identity, EmailAuthError> {
+        self.check_disposable(email)?;
+
         let (is_registration, identity) = {
             match self.user_service.create_with_retry(None, Some(email)).await {
                 Ok(identity) => {
@@ -142,6 +157,7 @@ where
                 None,
                 Some(email.raw()),
                 site_info,
+                None,
             )
             .await?;
 
@@ -165,6 +181,7 @@ where
             }
         }
 
+        let lang = identity.language.clone().unwrap_or(lang);
         if is_registration {
             self.mailer_service
                 .send_email_register(email.raw(), link_url, &identity.name, lang)
@@ -178,12 +195,12 @@ where
         Ok(identity)
     }
 
-    /// Send email confirmation link
+    /// Send email confirmation link in the preferred language of the user if set
     pub async fn start_email_confirm_flow(
         &self,
         user_id: Uuid,
         site_info: &SiteInfo,
-        lang: Option<Language>,
+        lang: Language,
     ) -> Result<(), EmailAuthError> {
         let user = self
             .user_service
@@ -204,9 +221,11 @@ where
                 None,              // No fingerprint binding
                 Some(email.raw()), // Bind to email being confirmed
                 site_info,
+                None,
             )
             .await?;
 
+        let lang = user.language.clone().unwrap_or(lang);
         self.mailer_service
             .send_email_confirmation(email.raw(), &token, &user.name, lang)
             .await?;
@@ -214,14 +233,16 @@ where
         Ok(())
     }
 
-    /// Send email change confirmation link
+    /// Send email change confirmation link in the preferred language of the user if set
     pub async fn start_email_change_flow(
         &self,
         user_id: Uuid,
         new_email: &Email,
         site_info: &SiteInfo,
-        lang: Option<Language>,
+        lang: Language,
     ) -> Result<(), EmailAuthError> {
+        self.check_disposable(new_email)?;
+
         let user = self
             .user_service
             .find_by_id(user_id)
@@ -240,9 +261,11 @@ where
                 None,                  // No fingerprint binding
                 Some(new_email.raw()), // Bind to new email
                 site_info,
+                None,
             )
             .await?;
 
+        let lang = user.language.clone().unwrap_or(lang);
         self.mailer_service
             .send_email_change(new_email.raw(), &token, lang, &user.name)
             .await?;
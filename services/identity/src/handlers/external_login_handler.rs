@@ -134,7 +134,9 @@ where
         };
         self.credential_handler
             .establish(
+                &external_user.provider,
                 identity,
+                None,
                 issuance,
                 auth_session.with_external_login(None),
                 &fingerprint,
@@ -63,7 +63,9 @@ where
 
         self.credential_handler
             .establish(
+                "guest",
                 identity,
+                None,
                 TokenIssuance::Create,
                 auth_session,
                 &fingerprint,
@@ -7,6 +7,7 @@ use crate::{
     },
     services::{LinkService, RoleService, SessionService, UserService},
 };
+use chrono::{DateTime, Utc};
 use shine_infra::{
     session::CurrentUser,
     web::{
@@ -65,9 +66,11 @@ where
         Ok(Some(UserInfo { identity, roles, is_linked }))
     }
 
+    /// Create a session of the user. The time of the authentication is the current time if not given.
     pub async fn create_user_session(
         &self,
         identity: &Identity,
+        authenticated_at: Option<DateTime<Utc>>,
         fingerprint: &ClientFingerprint,
         site_info: &SiteInfo,
     ) -> Result<Option<CurrentUser>, Problem> {
@@ -81,7 +84,7 @@ where
         log::debug!("Creating session for identity: {identity:#?}");
         let (user_session, user_session_key) = self
             .session_service
-            .create(identity, roles, is_linked, fingerprint, site_info)
+            .create(identity, authenticated_at, roles, is_linked, fingerprint, site_info)
             .await?;
 
         Ok(Some(CurrentUser {
@@ -89,6 +92,7 @@ where
             key: user_session_key,
             session_start: user_session.info.created_at,
             session_end: user_session.expire_at,
+            authenticated_at: user_session.info.authenticated_at,
             name: user_session.user.name,
             roles: user_session.user.roles,
             is_email_confirmed: user_session.user.is_email_confirmed,
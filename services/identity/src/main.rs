@@ -15,7 +15,9 @@ use anyhow::Error as AnyError;
 use shine_infra::{
     db::{PostgresPoolStatus, RedisPoolStatus},
     health::HealthService,
-    web::{WebAppConfig, WebApplication},
+    language::MessageCatalog,
+    session::PolicyConfig,
+    web::{middlewares::Idempotent, VersionedApi, WebAppConfig, WebApplication},
 };
 use utoipa_axum::router::OpenApiRouter;
 
@@ -25,6 +27,14 @@ impl WebApplication for Application {
     type AppConfig = AppConfig;
     type AppState = AppState;
 
+    fn policy(&self) -> PolicyConfig {
+        services::identity_policy()
+    }
+
+    fn messages(&self) -> MessageCatalog {
+        services::identity_messages()
+    }
+
     async fn create(
         &self,
         config: &WebAppConfig<Self::AppConfig>,
@@ -86,14 +96,31 @@ impl WebApplication for Application {
                 .await;
         }
 
+        // Migrate the emails of the previous normalization rules, until it is completed the stale rows are also
+        // looked up by the hash of the previous rules
+        {
+            let state = state.clone();
+            tokio::spawn(async move { state.user_service().normalize_all_emails().await });
+        }
+
+        let auth_router = auth::AuthRouter::new(config, state.http_client_factory()).await?;
+
         // Register status providers
         health_service.add_provider(PostgresPoolStatus::new(state.db().postgres.clone()));
         health_service.add_provider(RedisPoolStatus::new(state.db().redis.clone()));
+        health_service.add_provider(state.email_sender().status_provider());
+        for provider in auth_router.status_providers() {
+            health_service.add_provider(provider);
+        }
 
         // Register routes
-        let identity_controller = identity::IdentityRouter::new().into_router();
-        let auth_controller = auth::AuthRouter::new(config).await?.into_router();
-        let app_router = OpenApiRouter::new().merge(identity_controller).merge(auth_controller);
+        // the unversioned api routes are served by the default (latest) version
+        let api_v1 = OpenApiRouter::new()
+            .merge(identity::IdentityRouter::new().into_router())
+            .merge(auth::AuthRouter::api_router())
+            .layer(Idempotent::new());
+        let api_router = VersionedApi::new().version(1, api_v1).into_router();
+        let app_router = OpenApiRouter::new().merge(auth_router.into_router()).merge(api_router);
         *router = router.clone().nest(&format!("/{}", self.feature_name()), app_router);
 
         Ok(state)
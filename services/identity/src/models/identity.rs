@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use shine_infra::email::Email;
+use shine_infra::{email::Email, language::Language};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -18,5 +18,7 @@ pub struct Identity {
     pub name: String,
     pub email: Option<Email>,
     pub is_email_confirmed: bool,
+    /// The preferred language of the user for the emails and the messages.
+    pub language: Option<Language>,
     pub created: DateTime<Utc>,
 }
@@ -22,3 +22,5 @@ mod session_error;
 pub use self::session_error::*;
 mod purge_guests;
 pub use self::purge_guests::*;
+mod normalize_emails;
+pub use self::normalize_emails::*;
@@ -0,0 +1,13 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizeEmailsResult {
+    pub updated: usize,
+    /// Users whose normalized email is already used by another user, they are not updated.
+    pub conflicts: Vec<Uuid>,
+    /// The cursor of the next batch, it is not present when all the users were processed.
+    pub next: Option<Uuid>,
+}
@@ -6,6 +6,8 @@ use uuid::Uuid;
 #[derive(Debug)]
 pub struct SessionInfo {
     pub created_at: DateTime<Utc>,
+    /// The time of the authentication, the sessions started by a remember-me token keep the time of the original login.
+    pub authenticated_at: DateTime<Utc>,
     pub user_id: Uuid,
     pub key_hash: String,
     pub fingerprint: String,
@@ -11,6 +11,8 @@ pub enum SessionError {
     KeyConflict,
     #[error("Error in the stored key")]
     InvalidKey,
+    #[error("Session lifetime has elapsed")]
+    LifetimeElapsed,
 
     #[error(transparent)]
     SessionKeyError(#[from] SessionKeyError),
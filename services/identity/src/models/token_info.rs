@@ -10,6 +10,8 @@ pub struct TokenInfo {
     pub kind: TokenKind,
     pub token_hash: String,
     pub created_at: DateTime<Utc>,
+    /// The time of the authentication the token was issued for, a rotated token keeps the time of the original login.
+    pub authenticated_at: DateTime<Utc>,
     pub expire_at: DateTime<Utc>,
     pub is_expired: bool,
     pub bound_fingerprint: Option<String>,
@@ -22,6 +24,15 @@ pub struct TokenInfo {
 
 impl TokenInfo {
     pub fn check_fingerprint(&self, fingerprint: &ClientFingerprint) -> bool {
-        self.bound_fingerprint.is_none() || Some(fingerprint.as_str()) == self.bound_fingerprint.as_deref()
+        self.bound_fingerprint
+            .as_deref()
+            .is_none_or(|bound| fingerprint.matches(bound))
+    }
+
+    /// Check if the token is bound to a legacy fingerprint that should be replaced by the current one.
+    pub fn needs_fingerprint_upgrade(&self, fingerprint: &ClientFingerprint) -> bool {
+        self.bound_fingerprint
+            .as_deref()
+            .is_some_and(|bound| fingerprint.is_upgrade_of(bound))
     }
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
-use shine_infra::web::responses::Problem;
-use std::sync::Arc;
+use shine_infra::web::{responses::Problem, HttpClient};
+use std::{net::IpAddr, sync::Arc};
 use thiserror::Error as ThisError;
 use uuid::Uuid;
 
@@ -67,14 +67,18 @@ pub struct TurnstileValidationResponse {
 
 struct Inner {
     secret: String,
+    http_client: HttpClient,
 }
 
 #[derive(Clone)]
 pub struct CaptchaValidator(Arc<Inner>);
 
 impl CaptchaValidator {
-    pub fn new<S: ToString>(secret: S) -> Self {
-        Self(Arc::new(Inner { secret: secret.to_string() }))
+    pub fn new<S: ToString>(secret: S, http_client: HttpClient) -> Self {
+        Self(Arc::new(Inner {
+            secret: secret.to_string(),
+            http_client,
+        }))
     }
 
     pub async fn validate_request(
@@ -113,11 +117,9 @@ impl CaptchaValidator {
             idempotency_key: Some(&idempotency_key),
         };
 
-        let client = reqwest::Client::new();
+        let client = &self.0.http_client;
         let response = client
-            .post(CAPTCHA_URL)
-            .form(&request)
-            .send()
+            .send(client.post(CAPTCHA_URL).form(&request))
             .await
             .map_err(|err| CaptchaError::Request(format!("{err:?}")))?
             .json::<TurnstileValidationResponse>()
@@ -126,9 +128,10 @@ impl CaptchaValidator {
         Ok(response)
     }
 
-    pub async fn validate(&self, token: Option<&str>) -> Result<(), CaptchaError> {
+    pub async fn validate(&self, token: Option<&str>, remote_ip: Option<IpAddr>) -> Result<(), CaptchaError> {
         if let Some(token) = token {
-            match self.validate_request(token, None).await {
+            let remote_ip = remote_ip.map(|ip| ip.to_string());
+            match self.validate_request(token, remote_ip.as_deref()).await {
                 Ok(result) => {
                     if !result.success {
                         Err(CaptchaError::FailedValidation(result.error_codes.join(", ")))
@@ -147,11 +150,19 @@ impl CaptchaValidator {
 #[cfg(test)]
 mod test {
     use super::*;
+    use shine_infra::web::{HttpClientConfig, HttpClientFactory};
     use shine_test::test;
 
+    fn test_http_client() -> HttpClient {
+        HttpClientFactory::new(&HttpClientConfig::default())
+            .unwrap()
+            .create("captcha")
+            .unwrap()
+    }
+
     #[test]
     async fn test_captcha_validator_test_token_pass() {
-        let validator = CaptchaValidator::new("1x0000000000000000000000000000000AA");
+        let validator = CaptchaValidator::new("1x0000000000000000000000000000000AA", test_http_client());
         let token = "1x00000000000000000000AA";
         let response = validator
             .validate_request(token, None)
@@ -163,7 +174,7 @@ mod test {
 
     #[test]
     async fn test_captcha_validator_test_token_invalid() {
-        let validator = CaptchaValidator::new("2x0000000000000000000000000000000AA");
+        let validator = CaptchaValidator::new("2x0000000000000000000000000000000AA", test_http_client());
         let token = "token";
         let response = validator
             .validate_request(token, None)
@@ -176,7 +187,7 @@ mod test {
 
     #[test]
     async fn test_captcha_validator_test_token_expired() {
-        let validator = CaptchaValidator::new("3x0000000000000000000000000000000AA");
+        let validator = CaptchaValidator::new("3x0000000000000000000000000000000AA", test_http_client());
         let token = "token";
         let response = validator
             .validate_request(token, None)
@@ -190,7 +201,7 @@ mod test {
     #[test]
     async fn test_captcha_validator_real_token_invalid() {
         if let Ok(secret) = std::env::var("CF_CAPTCHA_SECRET") {
-            let validator = CaptchaValidator::new(secret);
+            let validator = CaptchaValidator::new(secret, test_http_client());
 
             let token = "invalid";
             let response = validator
@@ -208,7 +219,7 @@ mod test {
     #[test]
     async fn test_captcha_validator_real_token_expired() {
         if let Ok(secret) = std::env::var("CF_CAPTCHA_SECRET") {
-            let validator = CaptchaValidator::new(secret);
+            let validator = CaptchaValidator::new(secret, test_http_client());
 
             let token = "0.xVJwSGxsgonZ5dcUUCehmsoDATadFvQcJHYJ2T77vggEXA0EfzqtYQKk8dRdGgdZQieN1Cdh9TR1BCd3jU80Tkq_wBt5jdhvvMeGQNDtNRbkyj4W_Tp2_kEFRfQRWmnNA56MC2jpaNbi74OD3Ixz52koRwBkbaKWukRnHyxtQ80gkm2Uv_rnJsxFbsQurrs1JBy2azoc5zdW7esOi9gZEhwBhhXbnyj7u3Pu0Ui2ywe7ehfuU1-1dtzEMM9Gt2jSm8qYSD2AvYr2-CIUj8kIXbi5K9Z8tibclvQgePsdWo7mgMkQkpDzUKZwLpUUkqBSgP-wvcsdRS_El487aHUBjrIhVCqtaca_mCi7vIQNDSXFjzhn7_ffhzxcGZUeCj13vDjkCOcHZdtx9pJWd_G6Ir9pul0XXo60QEJJkzgxKUY3cYPaxsAhpPLvq3yfRvP7.tJWm1L0wA8I5zg2c1vPVTg.3125c6192bcc80a18596acdb789c53362fd48b71cde2ceaa0206b1e44c22f2e8";
             let response = validator
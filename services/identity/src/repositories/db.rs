@@ -1,18 +1,25 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use shine_infra::db::{self, DBError, PGConnectionPool, RedisConnectionPool};
+use validator::Validate;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct EmailProtectionConfig {
+    #[validate(length(min = 1))]
     pub encryption_key: String,
+    #[validate(length(min = 1))]
     pub hash_key: String,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct DBConfig {
+    #[validate(length(min = 1))]
     pub sql_cns: String,
+    #[validate(length(min = 1))]
     pub redis_cns: String,
+    #[validate(nested)]
     pub email_protection: EmailProtectionConfig,
 }
 
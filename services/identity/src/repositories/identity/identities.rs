@@ -1,6 +1,6 @@
 use crate::models::{Identity, IdentityError};
 use chrono::{DateTime, Utc};
-use shine_infra::email::Email;
+use shine_infra::{email::Email, language::Language};
 use std::future::Future;
 use uuid::Uuid;
 
@@ -27,6 +27,13 @@ pub trait Identities {
         email: Option<(&Email, bool)>,
     ) -> impl Future<Output = Result<Option<Identity>, IdentityError>> + Send;
 
+    /// Set or clear the preferred language of the user.
+    fn update_language(
+        &mut self,
+        id: Uuid,
+        language: Option<&Language>,
+    ) -> impl Future<Output = Result<Option<Identity>, IdentityError>> + Send;
+
     fn cascaded_delete(&mut self, id: Uuid) -> impl Future<Output = Result<(), IdentityError>> + Send;
 
     /// Delete guest users (no confirmed email, no external links) created before `cutoff`.
@@ -36,4 +43,19 @@ pub trait Identities {
         cutoff: DateTime<Utc>,
         limit: i64,
     ) -> impl Future<Output = Result<Vec<Uuid>, IdentityError>> + Send;
+
+    /// List the users whose email was normalized by a different version of the normalization rules, ordered by the
+    /// user id. The returned emails are normalized by the current rules. At most `limit` users are listed after `after`.
+    fn list_stale_emails(
+        &mut self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<(Uuid, Email)>, IdentityError>> + Send;
+
+    /// Store the normalized form and hash of the (unchanged) email of a user.
+    fn update_normalized_email(
+        &mut self,
+        id: Uuid,
+        email: &Email,
+    ) -> impl Future<Output = Result<(), IdentityError>> + Send;
 }
@@ -1,7 +1,7 @@
 use crate::{
     models::{ExternalLink, ExternalUserInfo, Identity, IdentityError, IdentityKind},
     repositories::identity::{
-        pg::{PgIdentityBuildError, PgIdentityDbContext},
+        pg::{decrypt_email, PgIdentityBuildError, PgIdentityDbContext},
         ExternalLinks,
     },
 };
@@ -9,7 +9,7 @@ use chrono::{DateTime, Utc};
 use postgres_from_row::FromRow;
 use shine_infra::{
     db::{DBError, PGClient, PGErrorChecks},
-    email::{Email, NORM_EMAIL_VERSION},
+    language::Language,
     pg_query,
 };
 use tracing::instrument;
@@ -33,6 +33,7 @@ struct FindByProviderIdRow {
     encrypted_email: Option<String>,
     encrypted_normalized_email: Option<String>,
     email_confirmed: bool,
+    language: Option<String>,
     created: DateTime<Utc>,
 }
 
@@ -40,7 +41,7 @@ pg_query!( FindByProviderId =>
     in = provider: &str, provider_id: &str;
     out = FindByProviderIdRow;
     sql = r#"
-        SELECT i.user_id, i.kind, i.name, i.encrypted_email, i.encrypted_normalized_email, i.email_confirmed, i.created
+        SELECT i.user_id, i.kind, i.name, i.encrypted_email, i.encrypted_normalized_email, i.email_confirmed, i.language, i.created
             FROM external_logins e, identities i
             WHERE e.user_id = i.user_id
                 AND e.provider = $1
@@ -199,20 +200,18 @@ impl ExternalLinks for PgIdentityDbContext<'_> {
             .map_err(DBError::from)?;
 
         if let Some(row) = row {
-            let email = match (row.encrypted_email, row.encrypted_normalized_email) {
-                (Some(enc_raw), Some(enc_norm)) => {
-                    let raw = self.email_protection.decrypt(&enc_raw)?;
-                    let normalized = self.email_protection.decrypt_versioned(NORM_EMAIL_VERSION, &enc_norm)?;
-                    Some(Email::from_parts(raw, normalized))
-                }
-                _ => None,
-            };
+            let email = decrypt_email(
+                self.email_protection,
+                row.encrypted_email,
+                row.encrypted_normalized_email,
+            )?;
             Ok(Some(Identity {
                 id: row.user_id,
                 kind: row.kind,
                 name: row.name,
                 email,
                 is_email_confirmed: row.email_confirmed,
+                language: row.language.as_deref().and_then(Language::from_tag),
                 created: row.created,
             }))
         } else {
@@ -6,11 +6,13 @@ use bytes::BytesMut;
 use chrono::{DateTime, Utc};
 use postgres_from_row::FromRow;
 use shine_infra::{
-    crypto::DataProtectionUtils,
+    crypto::{DataProtectionError, DataProtectionUtils},
     db::{DBError, PGClient, PGConvertError, PGErrorChecks, PGValueTypeINT2, ToPGType},
-    email::{Email, NORM_EMAIL_VERSION},
+    email::{email_normalizer, Email, EmailNormalizer, EmailProviderRule},
+    language::Language,
     pg_query,
 };
+use std::sync::atomic::Ordering;
 use tokio_postgres::types::{accepts, to_sql_checked, FromSql, IsNull, ToSql, Type};
 use tracing::instrument;
 use uuid::Uuid;
@@ -65,6 +67,28 @@ pg_query!( CascadedDelete =>
     "#
 );
 
+/// Decrypt the stored email. The normalized email of a stale normalization version is normalized again from the raw
+/// email until the row is migrated.
+pub(in crate::repositories::identity::pg) fn decrypt_email(
+    email_protection: &DataProtectionUtils,
+    encrypted_email: Option<String>,
+    encrypted_normalized_email: Option<String>,
+) -> Result<Option<Email>, IdentityError> {
+    match (encrypted_email, encrypted_normalized_email) {
+        (Some(enc_raw), Some(enc_norm)) => {
+            let raw = email_protection.decrypt(&enc_raw)?;
+            let normalizer = email_normalizer();
+            let normalized = match email_protection.decrypt_versioned(normalizer.version(), &enc_norm) {
+                Ok(normalized) => normalized,
+                Err(DataProtectionError::VersionMismatch) => normalizer.normalize(&raw).1,
+                Err(err) => return Err(err.into()),
+            };
+            Ok(Some(Email::from_parts(raw, normalized)))
+        }
+        _ => Ok(None),
+    }
+}
+
 #[derive(FromRow)]
 pub(in crate::repositories::identity::pg) struct IdentityRow {
     user_id: Uuid,
@@ -73,6 +97,7 @@ pub(in crate::repositories::identity::pg) struct IdentityRow {
     encrypted_email: Option<String>,
     encrypted_normalized_email: Option<String>,
     email_confirmed: bool,
+    language: Option<String>,
     created: DateTime<Utc>,
 }
 
@@ -81,20 +106,14 @@ impl IdentityRow {
         self,
         email_protection: &DataProtectionUtils,
     ) -> Result<Identity, IdentityError> {
-        let email = match (self.encrypted_email, self.encrypted_normalized_email) {
-            (Some(enc_raw), Some(enc_norm)) => {
-                let raw = email_protection.decrypt(&enc_raw)?;
-                let normalized = email_protection.decrypt_versioned(NORM_EMAIL_VERSION, &enc_norm)?;
-                Some(Email::from_parts(raw, normalized))
-            }
-            _ => None,
-        };
+        let email = decrypt_email(email_protection, self.encrypted_email, self.encrypted_normalized_email)?;
         Ok(Identity {
             id: self.user_id,
             kind: self.kind,
             name: self.name,
             email,
             is_email_confirmed: self.email_confirmed,
+            language: self.language.as_deref().and_then(Language::from_tag),
             created: self.created,
         })
     }
@@ -104,7 +123,7 @@ pg_query!( FindById =>
     in = user_id: Uuid;
     out = IdentityRow;
     sql = r#"
-        SELECT user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, created
+        SELECT user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, language, created
             FROM identities
             WHERE user_id = $1
     "#
@@ -114,7 +133,7 @@ pg_query!( FindByEmailHash =>
     in = email_hash: &str;
     out = IdentityRow;
     sql = r#"
-        SELECT user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, created
+        SELECT user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, language, created
             FROM identities
             WHERE email_hash = $1
     "#
@@ -131,7 +150,18 @@ pg_query!( UpdateIdentity =>
                 email_hash = COALESCE($5, email_hash),
                 email_confirmed = COALESCE($6, email_confirmed)
             WHERE user_id = $1
-        RETURNING user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, created
+        RETURNING user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, language, created
+    "#
+);
+
+pg_query!( UpdateLanguage =>
+    in = user_id: Uuid, language: Option<&str>;
+    out = IdentityRow;
+    sql = r#"
+        UPDATE identities
+            SET language = $2
+            WHERE user_id = $1
+        RETURNING user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, language, created
     "#
 );
 
@@ -158,14 +188,84 @@ pg_query!( DeleteGuests =>
     "#
 );
 
+pg_query!( FindByStaleEmailHash =>
+    in = email_hash: &str, version_prefix: &str;
+    out = IdentityRow;
+    sql = r#"
+        SELECT user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, language, created
+            FROM identities
+            WHERE email_hash = $1
+              AND NOT starts_with(encrypted_normalized_email, $2)
+    "#
+);
+
+#[derive(FromRow)]
+struct StaleEmailRow {
+    user_id: Uuid,
+    encrypted_email: String,
+}
+
+pg_query!( ListStaleEmails =>
+    in = version_prefix: &str, after: Option<Uuid>, limit: i64;
+    out = StaleEmailRow;
+    sql = r#"
+        SELECT user_id, encrypted_email FROM identities
+            WHERE encrypted_email IS NOT NULL
+              AND encrypted_normalized_email IS NOT NULL
+              AND NOT starts_with(encrypted_normalized_email, $1)
+              AND ($2::uuid IS NULL OR user_id > $2)
+            ORDER BY user_id
+            LIMIT $3
+    "#
+);
+
+pg_query!( UpdateNormalizedEmail =>
+    in = user_id: Uuid, encrypted_normalized_email: &str, email_hash: &str;
+    sql = r#"
+        UPDATE identities
+            SET encrypted_normalized_email = $2,
+                email_hash = $3
+            WHERE user_id = $1
+    "#
+);
+
+pg_query!( InsertEmailNormalizationRules =>
+    in = version: &str, rules: &str;
+    sql = r#"
+        INSERT INTO email_normalization_rules (version, rules)
+            VALUES ($1, $2)
+        ON CONFLICT (version) DO NOTHING
+    "#
+);
+
+#[derive(FromRow)]
+struct EmailNormalizationRulesRow {
+    version: String,
+    rules: String,
+}
+
+pg_query!( ListEmailNormalizationRules =>
+    in = ;
+    out = EmailNormalizationRulesRow;
+    sql = r#"
+        SELECT version, rules FROM email_normalization_rules
+    "#
+);
+
 #[derive(Clone)]
 pub struct PgIdentitiesStatements {
     insert_identity: InsertIdentity,
     cascaded_delete: CascadedDelete,
     find_by_id: FindById,
     find_by_email_hash: FindByEmailHash,
+    find_by_stale_email_hash: FindByStaleEmailHash,
     update: UpdateIdentity,
+    update_language: UpdateLanguage,
     delete_guests: DeleteGuests,
+    list_stale_emails: ListStaleEmails,
+    update_normalized_email: UpdateNormalizedEmail,
+    insert_email_normalization_rules: InsertEmailNormalizationRules,
+    list_email_normalization_rules: ListEmailNormalizationRules,
 }
 
 impl PgIdentitiesStatements {
@@ -175,10 +275,89 @@ impl PgIdentitiesStatements {
             cascaded_delete: CascadedDelete::new(client).await.map_err(DBError::from)?,
             find_by_id: FindById::new(client).await.map_err(DBError::from)?,
             find_by_email_hash: FindByEmailHash::new(client).await.map_err(DBError::from)?,
+            find_by_stale_email_hash: FindByStaleEmailHash::new(client).await.map_err(DBError::from)?,
             update: UpdateIdentity::new(client).await.map_err(DBError::from)?,
+            update_language: UpdateLanguage::new(client).await.map_err(DBError::from)?,
             delete_guests: DeleteGuests::new(client).await.map_err(DBError::from)?,
+            list_stale_emails: ListStaleEmails::new(client).await.map_err(DBError::from)?,
+            update_normalized_email: UpdateNormalizedEmail::new(client).await.map_err(DBError::from)?,
+            insert_email_normalization_rules: InsertEmailNormalizationRules::new(client)
+                .await
+                .map_err(DBError::from)?,
+            list_email_normalization_rules: ListEmailNormalizationRules::new(client).await.map_err(DBError::from)?,
         })
     }
+
+    /// Store the rules of the installed email normalizer and load the rules of the other versions, including the
+    /// previous builtin rules. The rows normalized by these versions can be found only by the hash of their rules
+    /// until they are migrated.
+    pub async fn stale_email_normalizers(
+        &self,
+        client: &PGClient,
+    ) -> Result<Vec<EmailNormalizer>, PgIdentityBuildError> {
+        let normalizer = email_normalizer();
+        let rules = serde_json::to_string(normalizer.rules()).expect("Rules shall be serializable");
+        self.insert_email_normalization_rules
+            .execute(client, &normalizer.version(), &rules.as_str())
+            .await
+            .map_err(DBError::from)?;
+
+        let rows = self
+            .list_email_normalization_rules
+            .query(client)
+            .await
+            .map_err(DBError::from)?;
+        let mut normalizers = Vec::new();
+        for row in rows {
+            if row.version == normalizer.version() {
+                continue;
+            }
+            match serde_json::from_str::<Vec<EmailProviderRule>>(&row.rules) {
+                Ok(rules) => normalizers.push(EmailNormalizer::with_rules(&row.version, rules)),
+                Err(err) => log::warn!("Invalid email normalization rules of version {}: {err}", row.version),
+            }
+        }
+
+        let previous = EmailNormalizer::previous_builtin();
+        if previous.version() != normalizer.version() && normalizers.iter().all(|n| n.version() != previous.version()) {
+            normalizers.push(previous);
+        }
+
+        Ok(normalizers)
+    }
+}
+
+impl PgIdentityDbContext<'_> {
+    /// Find the row of an email stored with the hash of a previous version of the normalization rules (builtin
+    /// or configured) until it is migrated.
+    async fn find_stale_email(&self, email: &Email) -> Result<Option<IdentityRow>, IdentityError> {
+        if !self.has_stale_emails.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        let mut stale_hashes = Vec::new();
+        for normalizer in self.stale_email_normalizers {
+            if let Some(hash) = email.hash_with(normalizer) {
+                if !stale_hashes.contains(&hash) {
+                    stale_hashes.push(hash);
+                }
+            }
+        }
+
+        let version_prefix = format!("{}.", email_normalizer().version());
+        for stale_hash in stale_hashes {
+            let row = self
+                .stmts_identities
+                .find_by_stale_email_hash
+                .query_opt(&self.client, &stale_hash.as_str(), &version_prefix.as_str())
+                .await
+                .map_err(DBError::from)?;
+            if row.is_some() {
+                return Ok(row);
+            }
+        }
+        Ok(None)
+    }
 }
 
 impl Identities for PgIdentityDbContext<'_> {
@@ -193,11 +372,18 @@ impl Identities for PgIdentityDbContext<'_> {
             return Err(IdentityError::NameTooLong);
         }
 
+        if let Some((email, _)) = email {
+            if self.find_stale_email(email).await?.is_some() {
+                log::info!("Conflicting (stale) email: {user_id}, rolling back user creation");
+                return Err(IdentityError::EmailConflict);
+            }
+        }
+
         let (encrypted_email, encrypted_normalized_email, email_hash) = if let Some((email, _)) = email {
             let encrypted_email = self.email_protection.encrypt(email.raw())?;
             let encrypted_normalized_email = self
                 .email_protection
-                .encrypt_versioned(NORM_EMAIL_VERSION, email.normalized())?;
+                .encrypt_versioned(email_normalizer().version(), email.normalized())?;
             let email_hash = email.hash();
             (
                 Some(encrypted_email),
@@ -245,6 +431,7 @@ impl Identities for PgIdentityDbContext<'_> {
             name: user_name.to_owned(),
             email: email.map(|(e, _)| e.clone()),
             is_email_confirmed: email.map(|(_, confirmed)| confirmed).unwrap_or(false),
+            language: None,
             kind: IdentityKind::User,
             created,
         })
@@ -271,6 +458,10 @@ impl Identities for PgIdentityDbContext<'_> {
             .query_opt(&self.client, &email_hash.as_str())
             .await
             .map_err(DBError::from)?;
+        let row = match row {
+            Some(row) => Some(row),
+            None => self.find_stale_email(email).await?,
+        };
 
         row.map(|r| r.into_identity(self.email_protection)).transpose()
     }
@@ -282,11 +473,18 @@ impl Identities for PgIdentityDbContext<'_> {
         name: Option<&str>,
         email: Option<(&Email, bool)>,
     ) -> Result<Option<Identity>, IdentityError> {
+        if let Some((email, _)) = email {
+            if self.find_stale_email(email).await?.is_some_and(|row| row.user_id != id) {
+                log::info!("Conflicting (stale) email: {email:?}, rolling back user update");
+                return Err(IdentityError::EmailConflict);
+            }
+        }
+
         let (encrypted_email, encrypted_normalized_email, email_hash) = if let Some((email, _)) = email {
             let encrypted_email = self.email_protection.encrypt(email.raw())?;
             let encrypted_normalized_email = self
                 .email_protection
-                .encrypt_versioned(NORM_EMAIL_VERSION, email.normalized())?;
+                .encrypt_versioned(email_normalizer().version(), email.normalized())?;
             let email_hash = email.hash();
             (
                 Some(encrypted_email),
@@ -327,6 +525,22 @@ impl Identities for PgIdentityDbContext<'_> {
         Ok(Some(identity_row.into_identity(self.email_protection)?))
     }
 
+    #[instrument(skip(self))]
+    async fn update_language(
+        &mut self,
+        id: Uuid,
+        language: Option<&Language>,
+    ) -> Result<Option<Identity>, IdentityError> {
+        let row = self
+            .stmts_identities
+            .update_language
+            .query_opt(&self.client, &id, &language.map(Language::as_str))
+            .await
+            .map_err(DBError::from)?;
+
+        row.map(|r| r.into_identity(self.email_protection)).transpose()
+    }
+
     #[instrument(skip(self))]
     async fn cascaded_delete(&mut self, id: Uuid) -> Result<(), IdentityError> {
         self.stmts_identities
@@ -347,4 +561,221 @@ impl Identities for PgIdentityDbContext<'_> {
             .map_err(DBError::from)?;
         Ok(rows.into_iter().map(|r| r.user_id).collect())
     }
+
+    #[instrument(skip(self))]
+    async fn list_stale_emails(
+        &mut self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<(Uuid, Email)>, IdentityError> {
+        let normalizer = email_normalizer();
+        let version_prefix = format!("{}.", normalizer.version());
+        let rows = self
+            .stmts_identities
+            .list_stale_emails
+            .query(&self.client, &version_prefix.as_str(), &after, &limit)
+            .await
+            .map_err(DBError::from)?;
+        if after.is_none() && rows.is_empty() {
+            self.has_stale_emails.store(false, Ordering::Relaxed);
+        }
+
+        rows.into_iter()
+            .map(|row| {
+                let raw = self.email_protection.decrypt(&row.encrypted_email)?;
+                let (raw, normalized) = normalizer.normalize(&raw);
+                Ok((row.user_id, Email::from_parts(raw, normalized)))
+            })
+            .collect()
+    }
+
+    #[instrument(skip(self))]
+    async fn update_normalized_email(&mut self, id: Uuid, email: &Email) -> Result<(), IdentityError> {
+        let encrypted_normalized_email = self
+            .email_protection
+            .encrypt_versioned(email_normalizer().version(), email.normalized())?;
+        let email_hash = email.hash();
+
+        match self
+            .stmts_identities
+            .update_normalized_email
+            .execute(
+                &self.client,
+                &id,
+                &encrypted_normalized_email.as_str(),
+                &email_hash.as_str(),
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) if err.is_constraint("identities", "idx_email_hash") => {
+                log::info!("Conflicting normalized email for user {id}");
+                Err(IdentityError::EmailConflict)
+            }
+            Err(err) => Err(DBError::from(err).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        models::IdentityError,
+        repositories::{
+            identity::{pg::PgIdentityDb, Identities, IdentityDb},
+            DBConfig, DBPool, EmailProtectionConfig,
+        },
+    };
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
+    use shine_infra::{
+        crypto::DataProtectionUtils,
+        email::{previous_email_normalizer, Email, EmailNormalizer, EmailProviderRule},
+    };
+    use shine_test::{test, web::TestRedis};
+    use std::env;
+    use uuid::Uuid;
+
+    async fn test_pool() -> Option<(DBPool, EmailProtectionConfig, TestRedis)> {
+        let sql_cns = match env::var("SHINE_TEST_PG_CNS") {
+            Ok(cns) => cns,
+            Err(_) => {
+                log::warn!("SHINE_TEST_PG_CNS not set, skipping test");
+                return None;
+            }
+        };
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let redis = TestRedis::start().unwrap();
+        let email_protection = EmailProtectionConfig {
+            encryption_key: B64.encode([1u8; 32]),
+            hash_key: B64.encode([2u8; 32]),
+        };
+        let pool = DBPool::new(&DBConfig {
+            sql_cns,
+            redis_cns: redis.cns(),
+            email_protection: email_protection.clone(),
+        })
+        .await
+        .unwrap();
+        Some((pool, email_protection, redis))
+    }
+
+    /// Store the email of the user as it was normalized by the given rules.
+    async fn store_stale_email(pool: &DBPool, user_id: Uuid, email: &Email, normalizer: &EmailNormalizer) {
+        let stale_hash = email
+            .hash_with(normalizer)
+            .expect("Rules should have changed for the domain");
+        let (_, stale_normalized) = normalizer.normalize(email.raw());
+        let encrypted = DataProtectionUtils::new(&[1u8; 32], &[2u8; 32])
+            .unwrap()
+            .encrypt_versioned(normalizer.version(), &stale_normalized)
+            .unwrap();
+        pool.postgres
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                "UPDATE identities SET email_hash = $2, encrypted_normalized_email = $3 WHERE user_id = $1",
+                &[&user_id, &stale_hash.as_str(), &encrypted.as_str()],
+            )
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    async fn find_stale_email() {
+        let Some((pool, email_protection, _redis)) = test_pool().await else {
+            return;
+        };
+        let db = PgIdentityDb::new(&pool.postgres, &email_protection).await.unwrap();
+        let mut ctx = db.create_context().await.unwrap();
+
+        // The plus tags of the unknown domains were kept by the previous rules
+        let raw_email = format!("{}+tag@me.com", Uuid::new_v4().simple());
+        let email = Email::new(&raw_email).unwrap();
+        let user_id = Uuid::new_v4();
+        ctx.create_user(user_id, &user_id.simple().to_string()[..20], Some((&email, true)))
+            .await
+            .unwrap();
+
+        // store the row as it was created by the previous rules
+        store_stale_email(&pool, user_id, &email, previous_email_normalizer()).await;
+
+        let found = ctx
+            .find_by_email(&email)
+            .await
+            .unwrap()
+            .expect("Stale email should be found");
+        assert_eq!(found.id, user_id);
+
+        let other_id = Uuid::new_v4();
+        let err = ctx
+            .create_user(other_id, &other_id.simple().to_string()[..20], Some((&email, false)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IdentityError::EmailConflict), "{err:?}");
+
+        ctx.update_normalized_email(user_id, &email).await.unwrap();
+        let found = ctx
+            .find_by_email(&email)
+            .await
+            .unwrap()
+            .expect("Migrated email should be found");
+        assert_eq!(found.id, user_id);
+
+        ctx.cascaded_delete(user_id).await.unwrap();
+    }
+
+    #[test]
+    async fn find_stale_email_of_configured_rules() {
+        let Some((pool, email_protection, _redis)) = test_pool().await else {
+            return;
+        };
+
+        // the rules of a previous configuration, stored by the instance that used them
+        let configured = EmailNormalizer::with_rules(
+            "2-test",
+            vec![EmailProviderRule {
+                domains: vec!["example.test".into()],
+                strip_plus_tag: true,
+                ..Default::default()
+            }],
+        );
+        let rules = serde_json::to_string(configured.rules()).unwrap();
+        pool.postgres
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                "INSERT INTO email_normalization_rules (version, rules) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                &[&configured.version(), &rules.as_str()],
+            )
+            .await
+            .unwrap();
+
+        let db = PgIdentityDb::new(&pool.postgres, &email_protection).await.unwrap();
+        let mut ctx = db.create_context().await.unwrap();
+
+        let email = Email::new(format!("{}+tag@example.test", Uuid::new_v4().simple())).unwrap();
+        let user_id = Uuid::new_v4();
+        ctx.create_user(user_id, &user_id.simple().to_string()[..20], Some((&email, true)))
+            .await
+            .unwrap();
+        store_stale_email(&pool, user_id, &email, &configured).await;
+
+        let found = ctx
+            .find_by_email(&email)
+            .await
+            .unwrap()
+            .expect("Email of the configured rules should be found");
+        assert_eq!(found.id, user_id);
+
+        let other_id = Uuid::new_v4();
+        let err = ctx
+            .create_user(other_id, &other_id.simple().to_string()[..20], Some((&email, false)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IdentityError::EmailConflict), "{err:?}");
+
+        ctx.cascaded_delete(user_id).await.unwrap();
+    }
 }
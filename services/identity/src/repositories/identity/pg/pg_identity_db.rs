@@ -9,7 +9,9 @@ use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
 use shine_infra::{
     crypto::DataProtectionUtils,
     db::{DBError, PGConnectionPool, PGPooledConnection},
+    email::EmailNormalizer,
 };
+use std::sync::atomic::AtomicBool;
 
 use super::{
     PgExternalLinksStatements, PgIdSequencesStatements, PgIdentitiesStatements, PgRolesStatements, PgTokensStatements,
@@ -18,6 +20,10 @@ use super::{
 pub struct PgIdentityDbContext<'c> {
     pub(in crate::repositories::identity::pg) client: PGPooledConnection<'c>,
     pub(in crate::repositories::identity::pg) email_protection: &'c DataProtectionUtils,
+    /// If there may be rows with an email normalized by a previous version of the rules.
+    pub(in crate::repositories::identity::pg) has_stale_emails: &'c AtomicBool,
+    /// The normalizers of the previous versions of the rules to find the rows that are not migrated yet.
+    pub(in crate::repositories::identity::pg) stale_email_normalizers: &'c [EmailNormalizer],
     pub(in crate::repositories::identity::pg) stmts_identities: PgIdentitiesStatements,
     pub(in crate::repositories::identity::pg) stmts_external_links: PgExternalLinksStatements,
     pub(in crate::repositories::identity::pg) stmts_tokens: PgTokensStatements,
@@ -30,6 +36,8 @@ impl<'c> IdentityDbContext<'c> for PgIdentityDbContext<'c> {}
 pub struct PgIdentityDb {
     client: PGConnectionPool,
     email_protection: DataProtectionUtils,
+    has_stale_emails: AtomicBool,
+    stale_email_normalizers: Vec<EmailNormalizer>,
     stmts_identities: PgIdentitiesStatements,
     stmts_external_links: PgExternalLinksStatements,
     stmts_tokens: PgTokensStatements,
@@ -48,10 +56,15 @@ impl PgIdentityDb {
         let hash_key = B64.decode(config.hash_key.as_bytes())?;
         let email_protection = DataProtectionUtils::new(&encryption_key, &hash_key)?;
 
+        let stmts_identities = PgIdentitiesStatements::new(&client).await?;
+        let stale_email_normalizers = stmts_identities.stale_email_normalizers(&client).await?;
+
         Ok(Self {
             client: postgres.clone(),
             email_protection,
-            stmts_identities: PgIdentitiesStatements::new(&client).await?,
+            has_stale_emails: AtomicBool::new(true),
+            stale_email_normalizers,
+            stmts_identities,
             stmts_external_links: PgExternalLinksStatements::new(&client).await?,
             stmts_tokens: PgTokensStatements::new(&client).await?,
             stmts_roles: PgRolesStatements::new(&client).await?,
@@ -66,6 +79,8 @@ impl IdentityDb for PgIdentityDb {
         Ok(PgIdentityDbContext {
             client,
             email_protection: &self.email_protection,
+            has_stale_emails: &self.has_stale_emails,
+            stale_email_normalizers: &self.stale_email_normalizers,
             stmts_identities: self.stmts_identities.clone(),
             stmts_external_links: self.stmts_external_links.clone(),
             stmts_tokens: self.stmts_tokens.clone(),
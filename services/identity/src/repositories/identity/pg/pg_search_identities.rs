@@ -15,7 +15,7 @@ impl IdentitySearch for PgIdentityDbContext<'_> {
     #[instrument(skip(self))]
     async fn search_identity(&mut self, search: SearchIdentity<'_>) -> Result<Vec<Identity>, IdentityError> {
         let mut builder = QueryBuilder::new(
-            "SELECT user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, created FROM identities",
+            "SELECT user_id, kind, name, encrypted_email, encrypted_normalized_email, email_confirmed, language, created FROM identities",
         );
 
         let name_patterns: Vec<String> = search
@@ -1,7 +1,7 @@
 use crate::{
     models::{Identity, IdentityError, IdentityKind, TokenInfo, TokenKind},
     repositories::identity::{
-        pg::{PgIdentityBuildError, PgIdentityDbContext},
+        pg::{decrypt_email, PgIdentityBuildError, PgIdentityDbContext},
         Tokens,
     },
 };
@@ -10,7 +10,8 @@ use chrono::{DateTime, Duration, Utc};
 use postgres_from_row::FromRow;
 use shine_infra::{
     db::{DBError, PGClient, PGConvertError, PGErrorChecks, PGValueTypeINT2, ToPGType},
-    email::{Email, NORM_EMAIL_VERSION},
+    email::Email,
+    language::Language,
     pg_query,
     web::extracts::{ClientFingerprint, SiteInfo},
 };
@@ -55,6 +56,7 @@ impl ToPGType for TokenKind {
 #[derive(FromRow)]
 struct InsertTokenRow {
     created: DateTime<Utc>,
+    authenticated: DateTime<Utc>,
     expire: DateTime<Utc>,
 }
 
@@ -62,20 +64,21 @@ pg_query!( InsertToken =>
     in = user_id: Uuid, token: &str,
         kind: TokenKind, fingerprint: Option<&str>, encrypted_email: Option<&str>,
         expire_s: i32,
-        agent: &str, country: Option<&str>, region: Option<&str>, city: Option<&str>;
+        agent: &str, country: Option<&str>, region: Option<&str>, city: Option<&str>,
+        authenticated: Option<DateTime<Utc>>;
     out = InsertTokenRow;
     sql =  r#"
         INSERT INTO login_tokens (
-                user_id, token, created,
+                user_id, token, created, authenticated,
                 kind, fingerprint, encrypted_email,
                 expire,
                 agent, country, region, city)
             VALUES (
-                $1, $2, now(),
+                $1, $2, now(), COALESCE($11, now()),
                 $3, $4, $5,
                 now() + $6 * interval '1 seconds',
                 $7, $8, $9, $10)
-        RETURNING created, expire
+        RETURNING created, authenticated, expire
     "#
 );
 
@@ -84,6 +87,7 @@ struct TokenRow {
     user_id: Uuid,
     token: String,
     created: DateTime<Utc>,
+    authenticated: DateTime<Utc>,
     expire: DateTime<Utc>,
     fingerprint: Option<String>,
     encrypted_email: Option<String>,
@@ -99,7 +103,7 @@ pg_query!( FindByHashToken =>
     in = token: &str;
     out = TokenRow;
     sql = r#"
-        SELECT t.user_id, t.token, t.created, t.expire, t.fingerprint, t.encrypted_email, t.kind, t.expire < now() is_expired,
+        SELECT t.user_id, t.token, t.created, COALESCE(t.authenticated, t.created) authenticated, t.expire, t.fingerprint, t.encrypted_email, t.kind, t.expire < now() is_expired,
                 t.agent, t.country, t.region, t.city
             FROM login_tokens t
             WHERE t.token = $1
@@ -110,7 +114,7 @@ pg_query!( ListByUser =>
     in = user_id: Uuid;
     out = TokenRow;
     sql = r#"
-        SELECT t.user_id, t.token, t.created, t.expire, t.fingerprint, t.encrypted_email, t.kind, t.expire < now() is_expired,
+        SELECT t.user_id, t.token, t.created, COALESCE(t.authenticated, t.created) authenticated, t.expire, t.fingerprint, t.encrypted_email, t.kind, t.expire < now() is_expired,
                 t.agent, t.country, t.region, t.city
             FROM login_tokens t
             WHERE t.user_id = $1
@@ -124,6 +128,13 @@ pg_query!( DeleteToken =>
     "#
 );
 
+pg_query!( UpdateFingerprint =>
+    in = token: &str, fingerprint: &str;
+    sql = r#"
+        UPDATE login_tokens SET fingerprint = $2 WHERE token = $1 AND fingerprint IS NOT NULL
+    "#
+);
+
 pg_query!( DeleteByUser =>
     in = user_id: Uuid, token: &str;
     sql = r#"
@@ -147,9 +158,11 @@ struct IdentityTokenRow {
     encrypted_email: Option<String>,
     encrypted_normalized_email: Option<String>,
     email_confirmed: bool,
+    language: Option<String>,
     created: DateTime<Utc>,
     token_hash: String,
     token_created: DateTime<Utc>,
+    token_authenticated: DateTime<Utc>,
     token_expire: DateTime<Utc>,
     token_fingerprint: Option<String>,
     token_encrypted_email: Option<String>,
@@ -166,9 +179,10 @@ pg_query!( TestToken =>
     in = token: &str, allowed_kind: &[TokenKind];
     out = IdentityTokenRow;
     sql = r#"
-        SELECT i.user_id, i.kind, i.name, i.encrypted_email, i.encrypted_normalized_email, i.email_confirmed, i.created,
+        SELECT i.user_id, i.kind, i.name, i.encrypted_email, i.encrypted_normalized_email, i.email_confirmed, i.language, i.created,
                 t.token token_hash,
                 t.created token_created,
+                COALESCE(t.authenticated, t.created) token_authenticated,
                 t.expire token_expire,
                 t.fingerprint token_fingerprint,
                 t.encrypted_email token_encrypted_email,
@@ -195,9 +209,10 @@ pg_query!( TakeToken =>
         WHERE lt.token = $1 AND lt.kind = any($2)
         RETURNING *        
     )
-    SELECT i.user_id, i.kind, i.name, i.encrypted_email, i.encrypted_normalized_email, i.email_confirmed, i.created,
+    SELECT i.user_id, i.kind, i.name, i.encrypted_email, i.encrypted_normalized_email, i.email_confirmed, i.language, i.created,
         t.token token_hash,
         t.created token_created,
+        COALESCE(t.authenticated, t.created) token_authenticated,
         t.expire token_expire,
         t.fingerprint token_fingerprint,
         t.encrypted_email token_encrypted_email,
@@ -218,6 +233,7 @@ pub struct PgTokensStatements {
     find_by_hash: FindByHashToken,
     list_by_user: ListByUser,
     delete: DeleteToken,
+    update_fingerprint: UpdateFingerprint,
     delete_by_user: DeleteByUser,
     delete_all_by_user: DeleteAllByUser,
     test: TestToken,
@@ -231,6 +247,7 @@ impl PgTokensStatements {
             find_by_hash: FindByHashToken::new(client).await.map_err(DBError::from)?,
             list_by_user: ListByUser::new(client).await.map_err(DBError::from)?,
             delete: DeleteToken::new(client).await.map_err(DBError::from)?,
+            update_fingerprint: UpdateFingerprint::new(client).await.map_err(DBError::from)?,
             delete_by_user: DeleteByUser::new(client).await.map_err(DBError::from)?,
             delete_all_by_user: DeleteAllByUser::new(client).await.map_err(DBError::from)?,
             test: TestToken::new(client).await.map_err(DBError::from)?,
@@ -250,6 +267,7 @@ impl Tokens for PgIdentityDbContext<'_> {
         fingerprint_to_bind_to: Option<&ClientFingerprint>,
         email_to_bind_to: Option<&str>,
         site_info: &SiteInfo,
+        authenticated_at: Option<DateTime<Utc>>,
     ) -> Result<TokenInfo, IdentityError> {
         let time_to_live = time_to_live.num_seconds() as i32;
         assert!(time_to_live > 2);
@@ -273,6 +291,7 @@ impl Tokens for PgIdentityDbContext<'_> {
                 &site_info.country.as_deref(),
                 &site_info.region.as_deref(),
                 &site_info.city.as_deref(),
+                &authenticated_at,
             )
             .await
         {
@@ -299,6 +318,7 @@ impl Tokens for PgIdentityDbContext<'_> {
             kind,
             token_hash: token_hash.to_owned(),
             created_at: row.created,
+            authenticated_at: row.authenticated,
             expire_at: row.expire,
             is_expired: false,
             bound_fingerprint: fingerprint_to_bind_to.map(|f| f.to_string()),
@@ -336,6 +356,7 @@ impl Tokens for PgIdentityDbContext<'_> {
                     kind: row.kind,
                     token_hash: row.token,
                     created_at: row.created,
+                    authenticated_at: row.authenticated,
                     expire_at: row.expire,
                     is_expired: row.is_expired,
                     bound_fingerprint: row.fingerprint,
@@ -376,6 +397,7 @@ impl Tokens for PgIdentityDbContext<'_> {
                     kind: row.kind,
                     token_hash: row.token,
                     created_at: row.created,
+                    authenticated_at: row.authenticated,
                     expire_at: row.expire,
                     is_expired: row.is_expired,
                     bound_fingerprint: row.fingerprint,
@@ -404,6 +426,25 @@ impl Tokens for PgIdentityDbContext<'_> {
         }
     }
 
+    #[instrument(skip(self))]
+    async fn update_token_fingerprint(
+        &mut self,
+        token_hash: &str,
+        fingerprint: &ClientFingerprint,
+    ) -> Result<Option<()>, IdentityError> {
+        let count = self
+            .stmts_tokens
+            .update_fingerprint
+            .execute(&self.client, &token_hash, &fingerprint.as_str())
+            .await
+            .map_err(DBError::from)?;
+        if count == 1 {
+            Ok(Some(()))
+        } else {
+            Ok(None)
+        }
+    }
+
     #[instrument(skip(self))]
     async fn delete_token_by_user(&mut self, user_id: Uuid, token_hash: &str) -> Result<Option<()>, IdentityError> {
         let count = self
@@ -460,6 +501,7 @@ impl Tokens for PgIdentityDbContext<'_> {
                 kind: row.token_kind,
                 token_hash: row.token_hash,
                 created_at: row.token_created,
+                authenticated_at: row.token_authenticated,
                 expire_at: row.token_expire,
                 is_expired: row.token_is_expired,
                 bound_fingerprint: row.token_fingerprint,
@@ -470,20 +512,18 @@ impl Tokens for PgIdentityDbContext<'_> {
                 city: row.token_city,
             };
 
-            let identity_email = match (row.encrypted_email, row.encrypted_normalized_email) {
-                (Some(enc_raw), Some(enc_norm)) => {
-                    let raw = self.email_protection.decrypt(&enc_raw)?;
-                    let normalized = self.email_protection.decrypt_versioned(NORM_EMAIL_VERSION, &enc_norm)?;
-                    Some(Email::from_parts(raw, normalized))
-                }
-                _ => None,
-            };
+            let identity_email = decrypt_email(
+                self.email_protection,
+                row.encrypted_email,
+                row.encrypted_normalized_email,
+            )?;
             let identity = Identity {
                 id: row.user_id,
                 kind: row.kind,
                 name: row.name,
                 email: identity_email,
                 is_email_confirmed: row.email_confirmed,
+                language: row.language.as_deref().and_then(Language::from_tag),
                 created: row.created,
             };
 
@@ -522,6 +562,7 @@ impl Tokens for PgIdentityDbContext<'_> {
                 kind: row.token_kind,
                 token_hash: row.token_hash,
                 created_at: row.token_created,
+                authenticated_at: row.token_authenticated,
                 expire_at: row.token_expire,
                 is_expired: row.token_is_expired,
                 bound_fingerprint: row.token_fingerprint,
@@ -532,20 +573,18 @@ impl Tokens for PgIdentityDbContext<'_> {
                 city: row.token_city,
             };
 
-            let identity_email = match (row.encrypted_email, row.encrypted_normalized_email) {
-                (Some(enc_raw), Some(enc_norm)) => {
-                    let raw = self.email_protection.decrypt(&enc_raw)?;
-                    let normalized = self.email_protection.decrypt_versioned(NORM_EMAIL_VERSION, &enc_norm)?;
-                    Some(Email::from_parts(raw, normalized))
-                }
-                _ => None,
-            };
+            let identity_email = decrypt_email(
+                self.email_protection,
+                row.encrypted_email,
+                row.encrypted_normalized_email,
+            )?;
             let identity = Identity {
                 id: row.user_id,
                 kind: row.kind,
                 name: row.name,
                 email: identity_email,
                 is_email_confirmed: row.email_confirmed,
+                language: row.language.as_deref().and_then(Language::from_tag),
                 created: row.created,
             };
 
@@ -1,11 +1,13 @@
 use crate::models::{Identity, IdentityError, TokenInfo, TokenKind};
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use shine_infra::web::extracts::{ClientFingerprint, SiteInfo};
 use std::future::Future;
 use uuid::Uuid;
 
 /// Handle tokens
 pub trait Tokens {
+    /// Store a new token. The time of the authentication is the current time if not given.
+    #[allow(clippy::too_many_arguments)]
     fn store_token(
         &mut self,
         user_id: Uuid,
@@ -15,6 +17,7 @@ pub trait Tokens {
         fingerprint: Option<&ClientFingerprint>,
         email: Option<&str>,
         site_info: &SiteInfo,
+        authenticated_at: Option<DateTime<Utc>>,
     ) -> impl Future<Output = Result<TokenInfo, IdentityError>> + Send;
 
     fn find_by_hash(
@@ -30,6 +33,13 @@ pub trait Tokens {
         token_hash: &str,
     ) -> impl Future<Output = Result<Option<()>, IdentityError>> + Send;
 
+    /// Replace the fingerprint the token is bound to, ex. to upgrade a legacy fingerprint.
+    fn update_token_fingerprint(
+        &mut self,
+        token_hash: &str,
+        fingerprint: &ClientFingerprint,
+    ) -> impl Future<Output = Result<Option<()>, IdentityError>> + Send;
+
     fn delete_token_by_user(
         &mut self,
         user_id: Uuid,
@@ -1,4 +1,5 @@
 use crate::repositories::mailer::{Email, EmailContent, EmailSender, EmailSenderError};
+use async_trait::async_trait;
 use lettre::{
     address::AddressError,
     error::Error as EmailError,
@@ -6,6 +7,7 @@ use lettre::{
     transport::smtp::{self, authentication::Credentials, client::Tls, SUBMISSIONS_PORT},
     Message, SmtpTransport, Transport,
 };
+use shine_infra::health::{HealthState, HealthStatus, StatusProvider};
 use thiserror::Error as ThisError;
 
 #[derive(ThisError, Debug)]
@@ -84,6 +86,45 @@ impl SmtpEmailSender {
     }
 }
 
+impl SmtpEmailSender {
+    pub fn status_provider(&self) -> SmtpStatus {
+        SmtpStatus { mailer: self.mailer.clone() }
+    }
+}
+
+/// Check the connection to the SMTP relay. Emails cannot be sent without it, but the service is still operational.
+pub struct SmtpStatus {
+    mailer: SmtpTransport,
+}
+
+#[async_trait]
+impl StatusProvider for SmtpStatus {
+    fn name(&self) -> &str {
+        "smtp"
+    }
+
+    async fn status(&self) -> HealthStatus {
+        let mailer = self.mailer.clone();
+        let probe = tokio::task::spawn_blocking(move || mailer.test_connection()).await;
+        match probe {
+            Ok(Ok(true)) => HealthStatus::healthy(serde_json::Value::Null),
+            Ok(Ok(false)) => HealthStatus::degraded(serde_json::json!({ "error": "connection refused" })),
+            Ok(Err(err)) => {
+                log::warn!("SMTP health probe failed: {err}");
+                HealthStatus::degraded(serde_json::json!({ "error": err.to_string() }))
+            }
+            Err(err) => {
+                log::warn!("SMTP health probe panicked: {err}");
+                HealthStatus::degraded(serde_json::json!({ "error": "probe failed" }))
+            }
+        }
+    }
+
+    fn timeout_state(&self) -> HealthState {
+        HealthState::Degraded
+    }
+}
+
 impl EmailSender for SmtpEmailSender {
     async fn send(&self, from_name: &str, to: &str, content: Email) -> Result<(), EmailSenderError> {
         log::info!(
@@ -108,3 +149,32 @@ impl EmailSender for SmtpEmailSender {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::{test, web::TestSmtp};
+    use std::time::Duration;
+
+    #[test]
+    async fn send_email() {
+        let server = TestSmtp::start().unwrap();
+        let sender =
+            SmtpEmailSender::new("example.com", &server.address().to_string(), false, "user", "password").unwrap();
+        assert_eq!(sender.status_provider().status().await.state, HealthState::Healthy);
+        assert_eq!(sender.status_provider().timeout_state(), HealthState::Degraded);
+
+        let email = Email {
+            subject: "Bejelentkezés".to_string(),
+            body: EmailContent::Html("<p>Hello</p>".to_string()),
+        };
+        sender.send("no-reply", "user@test.com", email).await.unwrap();
+
+        let mail = server.next_mail(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(mail.from, "no-reply@example.com");
+        assert_eq!(mail.to, vec!["user@test.com"]);
+        assert_eq!(mail.subject().as_deref(), Some("Bejelentkezés"));
+        assert!(mail.header("Content-Type").unwrap().starts_with("text/html"));
+        assert_eq!(mail.body().trim_end(), "<p>Hello</p>");
+    }
+}
@@ -2,13 +2,15 @@ use crate::{
     models::SessionError,
     repositories::session::{redis::RedisSessionBuildError, SessionDb, SessionDbContext},
 };
-use chrono::Duration;
-use shine_infra::db::{DBError, RedisConnectionPool, RedisPooledConnection};
+use shine_infra::{
+    db::{DBError, RedisConnectionPool, RedisPooledConnection},
+    session::SessionLifetime,
+};
 
 pub struct RedisSessionDbContext<'c> {
     pub(in crate::repositories::session::redis) client: RedisPooledConnection<'c>,
     pub(in crate::repositories::session::redis) key_prefix: &'c str,
-    pub(in crate::repositories::session::redis) ttl_session: i64,
+    pub(in crate::repositories::session::redis) lifetime: SessionLifetime,
 }
 
 impl<'c> SessionDbContext<'c> for RedisSessionDbContext<'c> {}
@@ -17,20 +19,20 @@ impl<'c> SessionDbContext<'c> for RedisSessionDbContext<'c> {}
 pub struct RedisSessionDb {
     client: RedisConnectionPool,
     key_prefix: String,
-    ttl_session: i64,
+    lifetime: SessionLifetime,
 }
 
 impl RedisSessionDb {
     pub async fn new(
         redis: &RedisConnectionPool,
         key_prefix: String,
-        ttl_session: Duration,
+        lifetime: SessionLifetime,
     ) -> Result<Self, RedisSessionBuildError> {
         let _client = redis.get().await.map_err(DBError::RedisPoolError)?;
         Ok(Self {
             client: redis.clone(),
             key_prefix,
-            ttl_session: ttl_session.num_seconds(),
+            lifetime,
         })
     }
 }
@@ -42,7 +44,7 @@ impl SessionDb for RedisSessionDb {
         Ok(RedisSessionDbContext {
             client,
             key_prefix: &self.key_prefix,
-            ttl_session: self.ttl_session,
+            lifetime: self.lifetime,
         })
     }
 }
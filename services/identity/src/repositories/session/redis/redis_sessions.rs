@@ -7,6 +7,7 @@ use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use shine_infra::{
     db::{DBError, RedisJsonValue},
+    session::SessionInvalidation,
     web::extracts::SiteInfo,
 };
 use uuid::Uuid;
@@ -15,6 +16,9 @@ use uuid::Uuid;
 #[serde(rename_all = "camelCase")]
 pub struct RedisSessionSentinel {
     pub created_at: DateTime<Utc>,
+    /// The time of the authentication, the sessions created before it was tracked use the creation time.
+    #[serde(default)]
+    pub authenticated_at: Option<DateTime<Utc>>,
     pub fingerprint: String,
     pub agent: String,
     pub country: Option<String>,
@@ -34,6 +38,7 @@ struct RedisSessionUser {
 fn create_session_info(user_id: Uuid, key_hash: String, sentinel: RedisSessionSentinel) -> SessionInfo {
     SessionInfo {
         created_at: sentinel.created_at,
+        authenticated_at: sentinel.authenticated_at.unwrap_or(sentinel.created_at),
         user_id,
         key_hash,
         fingerprint: sentinel.fingerprint,
@@ -100,6 +105,17 @@ impl RedisSessionDbContext<'_> {
         Ok((user, key, role))
     }
 
+    /// Notify the services caching the sessions about the change.
+    async fn publish_invalidation(&mut self, invalidation: SessionInvalidation) -> Result<(), SessionError> {
+        let channel = SessionInvalidation::channel(self.key_prefix);
+        let _: i64 = self
+            .client
+            .publish(channel, invalidation.to_message())
+            .await
+            .map_err(DBError::RedisError)?;
+        Ok(())
+    }
+
     async fn find_redis_keys(&mut self, user_id: Uuid) -> Result<Vec<String>, SessionError> {
         let pattern = format!("{}session:{}:*", self.key_prefix, user_id.as_simple());
         //log::debug!("pattern: {pattern}");
@@ -122,6 +138,7 @@ impl Sessions for RedisSessionDbContext<'_> {
     async fn store_session(
         &mut self,
         created_at: DateTime<Utc>,
+        authenticated_at: DateTime<Utc>,
         session_key_hash: String,
         fingerprint: String,
         site_info: &SiteInfo,
@@ -151,6 +168,7 @@ impl Sessions for RedisSessionDbContext<'_> {
 
         let sentinel = RedisSessionSentinel {
             created_at,
+            authenticated_at: Some(authenticated_at),
             fingerprint: fingerprint.to_string(),
             agent: site_info.agent.clone(),
             country: site_info.country.clone(),
@@ -165,6 +183,10 @@ impl Sessions for RedisSessionDbContext<'_> {
             .await
             .map_err(DBError::RedisError)?;
         if created {
+            let ttl = self
+                .lifetime
+                .ttl(created_at, Utc::now())
+                .ok_or(SessionError::LifetimeElapsed)?;
             let data = RedisSessionUser {
                 name: identity.name.clone(),
                 is_email_confirmed: identity.is_email_confirmed,
@@ -173,9 +195,9 @@ impl Sessions for RedisSessionDbContext<'_> {
             };
             log::debug!("data:{sentinel:#?}");
             redis::pipe()
-                .expire(&sentinel_key, self.ttl_session)
+                .expire(&sentinel_key, ttl)
                 .set(&key, &data)
-                .expire(&key, self.ttl_session)
+                .expire(&key, ttl)
                 .query_async::<()>(&mut *self.client)
                 .await
                 .map_err(DBError::RedisError)?;
@@ -183,6 +205,7 @@ impl Sessions for RedisSessionDbContext<'_> {
             Ok(Session {
                 info: SessionInfo {
                     created_at,
+                    authenticated_at,
                     user_id: identity.id,
                     key_hash: session_key_hash,
                     fingerprint: sentinel.fingerprint,
@@ -194,7 +217,7 @@ impl Sessions for RedisSessionDbContext<'_> {
                     is_linked: data.is_linked,
                     roles: data.roles,
                 },
-                expire_at: Utc::now() + Duration::seconds(self.ttl_session),
+                expire_at: Utc::now() + Duration::seconds(ttl),
             })
         } else {
             log::debug!("key conflict");
@@ -294,9 +317,11 @@ impl Sessions for RedisSessionDbContext<'_> {
             identity.id
         );
 
-        let is_open = self.client.exists(sentinel_key).await.map_err(DBError::RedisError)?;
-        if is_open {
-            // an update on the session extends the expiration time
+        let sentinel: Option<RedisSessionSentinel> =
+            self.client.get(sentinel_key).await.map_err(DBError::RedisError)?;
+        let ttl = sentinel.and_then(|sentinel| self.lifetime.ttl(sentinel.created_at, Utc::now()));
+        if let Some(ttl) = ttl {
+            // an update on the session extends the expiration time, but never beyond the absolute lifetime
             let data = RedisSessionUser {
                 name: identity.name.clone(),
                 is_email_confirmed: identity.is_email_confirmed,
@@ -304,16 +329,18 @@ impl Sessions for RedisSessionDbContext<'_> {
                 roles: roles.to_vec(),
             };
             redis::pipe()
-                .expire(&key, self.ttl_session)
+                .expire(&key, ttl)
                 .set(&key, &data)
-                .expire(&key, self.ttl_session)
+                .expire(&key, ttl)
                 .ignore()
                 .query_async::<()>(&mut *self.client)
                 .await
                 .map_err(DBError::RedisError)?;
+            self.publish_invalidation(SessionInvalidation::Session(identity.id, session_key_hash.to_owned()))
+                .await?;
             self.find_session_by_hash(identity.id, session_key_hash).await
         } else {
-            // sentinel is gone, session is closed.
+            // sentinel is gone or too old, session is closed.
             Ok(None)
         }
     }
@@ -328,6 +355,8 @@ impl Sessions for RedisSessionDbContext<'_> {
             .del(&[sentinel_key, key])
             .await
             .map_err(DBError::RedisError)?;
+        self.publish_invalidation(SessionInvalidation::Session(user_id, session_key_hash.to_owned()))
+            .await?;
         Ok(())
     }
 
@@ -339,6 +368,7 @@ impl Sessions for RedisSessionDbContext<'_> {
             // todo: https://github.com/redis-rs/redis-rs/issues/1228, https://github.com/redis-rs/redis-rs/issues/1322
             () = self.client.del(keys).await.map_err(DBError::RedisError)?;
         }
+        self.publish_invalidation(SessionInvalidation::User(user_id)).await?;
 
         Ok(())
     }
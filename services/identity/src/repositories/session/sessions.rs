@@ -5,9 +5,11 @@ use std::future::Future;
 use uuid::Uuid;
 
 pub trait Sessions {
+    #[allow(clippy::too_many_arguments)]
     fn store_session(
         &mut self,
         created_at: DateTime<Utc>,
+        authenticated_at: DateTime<Utc>,
         session_key_hash: String,
         fingerprint: String,
         site_info: &SiteInfo,
@@ -3,7 +3,7 @@ use axum::{extract::State, Extension, Json};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use shine_infra::{
-    session::CheckedCurrentUser,
+    session::{CheckedCurrentUser, StepUpCurrentUser},
     web::{
         extracts::ValidatedPath,
         responses::{IntoProblemResponse, Problem, ProblemConfig, ProblemResponse},
@@ -89,7 +89,7 @@ pub struct ProviderSelectPathParam {
 pub async fn delete_external_link(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
-    user: CheckedCurrentUser,
+    user: StepUpCurrentUser,
     ValidatedPath(params): ValidatedPath<ProviderSelectPathParam>,
 ) -> Result<(), ProblemResponse> {
     let link = state
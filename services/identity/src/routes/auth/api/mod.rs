@@ -10,3 +10,5 @@ mod tokens;
 pub use self::tokens::*;
 mod user_email_confirm;
 pub use self::user_email_confirm::*;
+mod user_language;
+pub use self::user_language::*;
@@ -1,11 +1,17 @@
 use crate::app_state::AppState;
-use axum::{extract::State, Extension, Json};
+use axum::{
+    extract::State,
+    http::{header, HeaderName},
+    Extension, Json,
+};
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 use shine_infra::{
-    session::CheckedCurrentUser,
-    web::responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
+    crypto::JwtService,
+    session::{CheckedCurrentUser, CurrentUserService},
+    web::responses::{IntoProblemResponse, Problem, ProblemConfig, ProblemResponse},
 };
+use std::sync::Arc;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -62,3 +68,47 @@ pub async fn list_sessions(
         .collect();
     Ok(Json(ActiveSessions { sessions }))
 }
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionToken {
+    token: String,
+    token_type: String,
+    expire_at: DateTime<Utc>,
+}
+
+/// Issue a short lived signed token bound to the current session. The token can be used as a bearer token
+/// and it is invalidated together with the session.
+#[utoipa::path(
+    post,
+    path = "/api/auth/user/sessions/token",
+    tag = "auth",
+    responses(
+        (status = OK, body = SessionToken)
+    )
+)]
+pub async fn create_session_token(
+    Extension(problem_config): Extension<ProblemConfig>,
+    Extension(user_service): Extension<Arc<CurrentUserService>>,
+    jwt_service: Option<Extension<Arc<JwtService>>>,
+    user: CheckedCurrentUser,
+) -> Result<([(HeaderName, &'static str); 1], Json<SessionToken>), ProblemResponse> {
+    let Some(Extension(jwt_service)) = jwt_service else {
+        return Err(Problem::not_found()
+            .with_detail("Token service is not configured")
+            .into_response(&problem_config));
+    };
+
+    let token = jwt_service
+        .issue_session_token(&user_service, &user)
+        .map_err(|err| err.into_response(&problem_config))?;
+    // the token is not stored for idempotent replay
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(SessionToken {
+            token: token.token,
+            token_type: "Bearer".into(),
+            expire_at: token.expire_at,
+        }),
+    ))
+}
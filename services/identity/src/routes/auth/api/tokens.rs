@@ -2,11 +2,15 @@ use crate::{
     app_state::AppState,
     models::{TokenInfo, TokenKind},
 };
-use axum::{extract::State, Extension, Json};
+use axum::{
+    extract::State,
+    http::{header, HeaderName},
+    Extension, Json,
+};
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use shine_infra::{
-    session::CheckedCurrentUser,
+    session::{CheckedCurrentUser, StepUpCurrentUser},
     web::{
         extracts::{ClientFingerprint, SiteInfo, ValidatedJson, ValidatedPath, ValidationErrorEx},
         responses::{IntoProblemResponse, Problem, ProblemConfig, ProblemResponse},
@@ -115,11 +119,11 @@ impl From<TokenInfo> for ActiveToken {
 pub async fn create_token(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
-    user: CheckedCurrentUser,
+    user: StepUpCurrentUser,
     fingerprint: ClientFingerprint,
     site_info: SiteInfo,
     ValidatedJson(params): ValidatedJson<CreateTokenRequest>,
-) -> Result<Json<CreatedToken>, ProblemResponse> {
+) -> Result<([(HeaderName, &'static str); 1], Json<CreatedToken>), ProblemResponse> {
     let time_to_live = Duration::seconds(params.time_to_live as i64);
 
     // validate time_to_live against server config
@@ -146,6 +150,7 @@ pub async fn create_token(
             site_fingerprint,
             None,
             &site_info,
+            Some(user.authenticated_at),
         )
         .await
         .map_err(|err| match err {
@@ -156,13 +161,17 @@ pub async fn create_token(
                 .into_response(&problem_config),
         })?;
 
-    Ok(Json(CreatedToken {
-        kind: params.kind.into(),
-        token,
-        token_hash: info.token_hash,
-        token_type: "Bearer".into(),
-        expire_at: info.expire_at,
-    }))
+    // the token is not stored for idempotent replay
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(CreatedToken {
+            kind: params.kind.into(),
+            token,
+            token_hash: info.token_hash,
+            token_type: "Bearer".into(),
+            expire_at: info.expire_at,
+        }),
+    ))
 }
 
 #[utoipa::path(
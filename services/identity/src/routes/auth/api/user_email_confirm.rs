@@ -4,28 +4,22 @@ use serde::Deserialize;
 use shine_infra::{
     email::Email,
     language::Language,
-    session::CheckedCurrentUser,
+    session::{CheckedCurrentUser, StepUpCurrentUser},
     web::{
-        extracts::{SiteInfo, ValidatedJson, ValidatedQuery},
+        extracts::{AcceptLanguage, SiteInfo, ValidatedJson, ValidatedQuery},
         responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
     },
 };
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
-#[derive(Deserialize, Validate, IntoParams)]
-#[serde(rename_all = "camelCase")]
-pub struct ConfirmQueryParams {
-    lang: Option<Language>,
-}
-
 /// Start email address confirmation flow.
 #[utoipa::path(
     post,
     path = "/api/auth/user/email/confirm",
     tag = "auth",
     params(
-        ConfirmQueryParams
+        ("lang" = Option<Language>, Query, description = "Language of the email, it overrides the Accept-Language header")
     ),
     responses(
         (status = OK, description="Start email confirmation")
@@ -34,25 +28,19 @@ pub struct ConfirmQueryParams {
 pub async fn start_user_email_validation(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
-    ValidatedQuery(query): ValidatedQuery<ConfirmQueryParams>,
+    AcceptLanguage(lang): AcceptLanguage,
     user: CheckedCurrentUser,
     site_info: SiteInfo,
 ) -> Result<(), ProblemResponse> {
     state
         .email_auth_handler()
-        .start_email_confirm_flow(user.user_id, &site_info, query.lang)
+        .start_email_confirm_flow(user.user_id, &site_info, lang)
         .await
         .map_err(|err| err.into_response(&problem_config))?;
 
     Ok(())
 }
 
-#[derive(Deserialize, Validate, IntoParams)]
-#[serde(rename_all = "camelCase")]
-pub struct ChangeQueryParams {
-    lang: Option<Language>,
-}
-
 #[derive(Deserialize, Validate, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ChangeEmailRequest {
@@ -65,7 +53,7 @@ pub struct ChangeEmailRequest {
     path = "/api/auth/user/email/change",
     tag = "auth",
     params(
-        ChangeQueryParams
+        ("lang" = Option<Language>, Query, description = "Language of the email, it overrides the Accept-Language header")
     ),
     request_body = ChangeEmailRequest,
     responses(
@@ -75,15 +63,15 @@ pub struct ChangeEmailRequest {
 pub async fn start_user_email_change(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
-    ValidatedQuery(query): ValidatedQuery<ChangeQueryParams>,
-    user: CheckedCurrentUser,
+    AcceptLanguage(lang): AcceptLanguage,
+    user: StepUpCurrentUser,
     site_info: SiteInfo,
     ValidatedJson(body): ValidatedJson<ChangeEmailRequest>,
 ) -> Result<(), ProblemResponse> {
     // Email is already validated and normalized by the Email type
     state
         .email_auth_handler()
-        .start_email_change_flow(user.user_id, &body.email, &site_info, query.lang)
+        .start_email_change_flow(user.user_id, &body.email, &site_info, lang)
         .await
         .map_err(|err| err.into_response(&problem_config))?;
 
@@ -3,6 +3,7 @@ use axum::{extract::State, Extension, Json};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use shine_infra::{
+    language::Language,
     session::CheckedCurrentUser,
     web::{
         extracts::ValidatedQuery,
@@ -34,6 +35,7 @@ pub struct CurrentUserInfoDetails {
     kind: IdentityKind,
     created_at: DateTime<Utc>,
     email: Option<String>,
+    language: Option<Language>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -47,6 +49,9 @@ pub struct CurrentUserInfo {
     roles: Vec<String>,
     session_length: u64,
     remaining_session_time: u64,
+    /// The time in seconds since the last authentication, the logins with a remember-me token keep the time of the
+    /// original authentication.
+    authentication_age: u64,
     details: Option<CurrentUserInfoDetails>,
 }
 
@@ -73,6 +78,7 @@ pub async fn get_user_info(
 
     let session_length = (Utc::now() - user.session_start).num_seconds().max(0) as u64;
     let remaining_session_time = (user.session_end - Utc::now()).num_seconds().max(0) as u64;
+    let authentication_age = (Utc::now() - user.authenticated_at).num_seconds().max(0) as u64;
 
     let info = match method {
         // read the user info from the session
@@ -87,6 +93,7 @@ pub async fn get_user_info(
                 roles: user.roles,
                 session_length,
                 remaining_session_time,
+                authentication_age,
                 details: None,
             }
         }
@@ -120,11 +127,13 @@ pub async fn get_user_info(
                 is_guest: !user_info.identity.is_email_confirmed && !user_info.is_linked,
                 session_length,
                 remaining_session_time,
+                authentication_age,
                 roles: user_info.roles,
                 details: Some(CurrentUserInfoDetails {
                     kind: user_info.identity.kind,
                     created_at: user_info.identity.created,
                     email: user_info.identity.email.map(|e| e.to_raw()),
+                    language: user_info.identity.language,
                 }),
             }
         }
@@ -0,0 +1,51 @@
+use crate::app_state::AppState;
+use axum::{extract::State, Extension};
+use serde::Deserialize;
+use shine_infra::{
+    language::Language,
+    session::CheckedCurrentUser,
+    web::{
+        extracts::ValidatedJson,
+        responses::{IntoProblemResponse, Problem, ProblemConfig, ProblemResponse},
+    },
+};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUserLanguageRequest {
+    /// The preferred language, `null` to use the language of the requests.
+    language: Option<Language>,
+}
+
+/// Set the preferred language of the current user. The emails are sent in this language
+/// independent of the language of the request.
+#[utoipa::path(
+    put,
+    path = "/api/auth/user/language",
+    tag = "auth",
+    request_body = UpdateUserLanguageRequest,
+    responses(
+        (status = OK, description="Preferred language updated")
+    )
+)]
+pub async fn update_user_language(
+    State(state): State<AppState>,
+    Extension(problem_config): Extension<ProblemConfig>,
+    user: CheckedCurrentUser,
+    ValidatedJson(body): ValidatedJson<UpdateUserLanguageRequest>,
+) -> Result<(), ProblemResponse> {
+    state
+        .user_service()
+        .update_language(user.user_id, body.language.as_ref())
+        .await
+        .map_err(|err| err.into_response(&problem_config))?
+        .ok_or_else(|| {
+            Problem::not_found()
+                .with_instance_str(format!("{{identity_api}}/identities/{}", user.user_id))
+                .into_response(&problem_config)
+        })?;
+
+    Ok(())
+}
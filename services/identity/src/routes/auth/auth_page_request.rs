@@ -6,6 +6,7 @@ use shine_infra::web::{
     extracts::{InputError, ValidatedQuery},
     responses::ErrorResponse,
 };
+use std::net::IpAddr;
 use url::Url;
 
 /// Helper for common auth page request handling patterns
@@ -96,8 +97,13 @@ impl<'a> AuthPageRequest<'a> {
 
     /// Validate captcha
     /// Returns None on success, Some(AuthPage) for early return on error
-    pub async fn validate_captcha(&self, captcha: Option<&str>, error_url: Option<&Url>) -> Option<AuthPage> {
-        if let Err(err) = self.state.captcha_validator().validate(captcha).await {
+    pub async fn validate_captcha(
+        &self,
+        captcha: Option<&str>,
+        remote_ip: Option<IpAddr>,
+        error_url: Option<&Url>,
+    ) -> Option<AuthPage> {
+        if let Err(err) = self.state.captcha_validator().validate(captcha, remote_ip).await {
             return Some(
                 self.state
                     .auth_page_handler()
@@ -16,7 +16,10 @@ mod pages;
 use crate::{app_config::AppConfig, app_state::AppState};
 use anyhow::Error as AnyError;
 use axum::Extension;
-use shine_infra::web::WebAppConfig;
+use shine_infra::web::{
+    middlewares::{RateLimit, SecurityHeadersProfile},
+    HttpClientFactory, WebAppConfig,
+};
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 mod auth_error;
@@ -24,19 +27,30 @@ use std::sync::Arc;
 
 pub struct AuthRouter {
     auth_session_meta: AuthSessionMeta,
+    page_security_profile: SecurityHeadersProfile,
     oauth2_clients: Vec<OAuth2Client>,
-    openid_clients: Vec<OIDCClient>,
+    openid_clients: Vec<Arc<OIDCClient>>,
 }
 
 impl AuthRouter {
-    pub async fn new(config: &WebAppConfig<AppConfig>) -> Result<Self, AnyError> {
+    pub async fn new(
+        config: &WebAppConfig<AppConfig>,
+        http_client_factory: &HttpClientFactory,
+    ) -> Result<Self, AnyError> {
         let config_auth = &config.feature.auth;
 
         let auth_session_meta = AuthSessionMeta::new(config)?;
+        let page_security_profile = SecurityHeadersProfile::new(&config.service.security_headers, "page")?;
 
         let mut oauth2_clients = Vec::new();
         for (provider, provider_config) in &config_auth.oauth2 {
-            let connect = OAuth2Client::new(provider, &config_auth.auth_base_url, provider_config).await?;
+            let connect = OAuth2Client::new(
+                provider,
+                &config_auth.auth_base_url,
+                provider_config,
+                http_client_factory,
+            )
+            .await?;
             oauth2_clients.push(connect);
         }
 
@@ -48,10 +62,11 @@ impl AuthRouter {
                 &config_auth.auth_base_url,
                 openid_startup_discovery,
                 provider_config,
+                http_client_factory,
             )
             .await?
             {
-                openid_clients.push(connect);
+                openid_clients.push(Arc::new(connect));
             } else {
                 log::error!("Skipping {provider} provider");
             }
@@ -59,16 +74,53 @@ impl AuthRouter {
 
         Ok(Self {
             auth_session_meta,
+            page_security_profile,
             oauth2_clients,
             openid_clients,
         })
     }
 
+    /// Health probes of the external providers.
+    pub fn status_providers(&self) -> Vec<OIDCDiscoveryStatus> {
+        self.openid_clients
+            .iter()
+            .map(|client| OIDCDiscoveryStatus::new(client.clone()))
+            .collect()
+    }
+
+    /// The (versioned) api routes.
+    pub fn api_router() -> OpenApiRouter<AppState> {
+        let token_routes = OpenApiRouter::new()
+            .routes(routes!(api::create_token))
+            .routes(routes!(api::create_session_token))
+            .layer(RateLimit::new("token"));
+
+        OpenApiRouter::new()
+            .merge(token_routes)
+            .routes(routes!(api::get_user_info))
+            .routes(routes!(api::start_user_email_validation))
+            .routes(routes!(api::start_user_email_change))
+            .routes(routes!(api::complete_user_email_operation))
+            .routes(routes!(api::update_user_language))
+            .routes(routes!(api::get_token))
+            .routes(routes!(api::list_tokens))
+            .routes(routes!(api::delete_token))
+            .routes(routes!(api::list_external_providers))
+            .routes(routes!(api::list_external_links))
+            .routes(routes!(api::delete_external_link))
+            .routes(routes!(api::list_sessions))
+    }
+
+    /// The (interactive) authentication page routes.
     pub fn into_router(self) -> OpenApiRouter<AppState> {
-        let mut auth_routes = OpenApiRouter::new()
+        let login_routes = OpenApiRouter::new()
             .routes(routes!(pages::guest_login))
             .routes(routes!(pages::token_login))
             .routes(routes!(pages::email_login))
+            .layer(RateLimit::new("login"));
+
+        let mut auth_routes = OpenApiRouter::new()
+            .merge(login_routes)
             .routes(routes!(pages::validate))
             .routes(routes!(pages::logout))
             .routes(routes!(pages::delete_user));
@@ -110,27 +162,13 @@ impl AuthRouter {
                     &format!("/auth/{}", client.provider),
                     OpenApiRouter::new().routes(routes!(pages::oidc_auth)),
                 )
-                .layer(Extension(Arc::new(client)));
+                .layer(Extension(client));
 
             auth_routes = auth_routes.merge(provider_route);
         }
 
-        auth_routes = auth_routes.layer(self.auth_session_meta.into_layer());
-
-        let api_routes = OpenApiRouter::new()
-            .routes(routes!(api::get_user_info))
-            .routes(routes!(api::start_user_email_validation))
-            .routes(routes!(api::start_user_email_change))
-            .routes(routes!(api::complete_user_email_operation))
-            .routes(routes!(api::create_token))
-            .routes(routes!(api::get_token))
-            .routes(routes!(api::list_tokens))
-            .routes(routes!(api::delete_token))
-            .routes(routes!(api::list_external_providers))
-            .routes(routes!(api::list_external_links))
-            .routes(routes!(api::delete_external_link))
-            .routes(routes!(api::list_sessions));
-
-        auth_routes.merge(api_routes)
+        auth_routes
+            .layer(self.auth_session_meta.into_layer())
+            .layer(self.page_security_profile)
     }
 }
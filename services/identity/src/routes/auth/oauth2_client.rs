@@ -7,10 +7,13 @@ use oauth2::{
     basic::BasicClient, AuthUrl, ClientId, ClientSecret, EndpointNotSet, EndpointSet, RedirectUrl, Scope, TokenUrl,
 };
 use openidconnect::UserInfoUrl;
-use reqwest::{header, Client as HttpClient};
+use reqwest::header;
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
-use shine_infra::email::Email;
+use shine_infra::{
+    email::Email,
+    web::{HttpClient, HttpClientFactory},
+};
 use std::collections::HashMap;
 use thiserror::Error as ThisError;
 use url::Url;
@@ -46,7 +49,12 @@ pub struct OAuth2Client {
 }
 
 impl OAuth2Client {
-    pub async fn new(provider: &str, auth_base_url: &Url, config: &OAuth2Config) -> Result<Self, AnyError> {
+    pub async fn new(
+        provider: &str,
+        auth_base_url: &Url,
+        config: &OAuth2Config,
+        http_client_factory: &HttpClientFactory,
+    ) -> Result<Self, AnyError> {
         let client_id = ClientId::new(config.client_id.clone());
         let client_secret = ClientSecret::new(config.client_secret.clone());
         let redirect_url = auth_base_url.join(&format!("{provider}/auth"))?;
@@ -66,10 +74,14 @@ impl OAuth2Client {
                 "Certificate validation is disabled for OAuth2 provider '{provider}' — do NOT use in production"
             );
         }
-        let http_client = HttpClient::builder()
-            .redirect(reqwest::redirect::Policy::none())
-            .danger_accept_invalid_certs(ignore_certificates)
-            .build()?;
+        let http_client = http_client_factory.create_with(provider, |builder| {
+            let builder = builder.redirect(reqwest::redirect::Policy::none());
+            if ignore_certificates {
+                builder.danger_accept_invalid_certs(true)
+            } else {
+                builder
+            }
+        })?;
 
         Ok(Self {
             provider: provider.to_string(),
@@ -94,10 +106,7 @@ impl OAuth2Client {
         let client = &self.http_client;
 
         let response = client
-            .get(url)
-            .bearer_auth(token)
-            .header(header::USER_AGENT, app_name)
-            .send()
+            .send(client.get(url).bearer_auth(token).header(header::USER_AGENT, app_name))
             .await
             .map_err(|err| OAuth2Error::RequestError(format!("{err}")))?;
 
@@ -169,10 +178,7 @@ impl OAuth2Client {
 
             let url = Url::parse("https://api.github.com/user/emails").unwrap();
             let response = client
-                .get(url)
-                .bearer_auth(token)
-                .header(header::USER_AGENT, app_name)
-                .send()
+                .send(client.get(url).bearer_auth(token).header(header::USER_AGENT, app_name))
                 .await
                 .map_err(|err| OAuth2Error::RequestError(format!("{err}")))?;
 
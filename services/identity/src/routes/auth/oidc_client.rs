@@ -6,6 +6,7 @@ use std::{
 
 use crate::{app_config::OIDCConfig, models::ExternalUserInfo, routes::auth::ExternalLoginError};
 use anyhow::Error as AnyError;
+use async_trait::async_trait;
 use oauth2::{
     basic::BasicTokenType, ClientId, ClientSecret, EmptyExtraTokenFields, EndpointMaybeSet, EndpointNotSet,
     EndpointSet, RedirectUrl, Scope, StandardTokenResponse,
@@ -15,11 +16,14 @@ use openidconnect::{
         CoreClient as OIDCCoreClient, CoreGenderClaim, CoreJweContentEncryptionAlgorithm, CoreJwsSigningAlgorithm,
         CoreProviderMetadata,
     },
-    EmptyAdditionalClaims, IdTokenFields, IssuerUrl, Nonce, TokenResponse,
+    EmptyAdditionalClaims, HttpRequest, IdTokenFields, IssuerUrl, Nonce, TokenResponse,
 };
-use reqwest::Client as HttpClient;
 use serde::Serialize;
-use shine_infra::email::Email;
+use shine_infra::{
+    email::Email,
+    health::{HealthState, HealthStatus, StatusProvider},
+    web::{HttpClient, HttpClientFactory},
+};
 use thiserror::Error as ThisError;
 use tokio::sync::Mutex;
 use url::Url;
@@ -127,6 +131,7 @@ impl OIDCClient {
         auth_base_url: &Url,
         startup_discovery: bool,
         config: &OIDCConfig,
+        http_client_factory: &HttpClientFactory,
     ) -> Result<Option<Self>, AnyError> {
         let client_id = ClientId::new(config.client_id.clone());
         let client_secret = config.client_secret.clone().map(ClientSecret::new);
@@ -144,10 +149,14 @@ impl OIDCClient {
         if ignore_certificates {
             log::warn!("Certificate validation is disabled for OIDC provider '{provider}' — do NOT use in production");
         }
-        let http_client = HttpClient::builder()
-            .redirect(reqwest::redirect::Policy::none())
-            .danger_accept_invalid_certs(ignore_certificates)
-            .build()?;
+        let http_client = http_client_factory.create_with(provider, |builder| {
+            let builder = builder.redirect(reqwest::redirect::Policy::none());
+            if ignore_certificates {
+                builder.danger_accept_invalid_certs(true)
+            } else {
+                builder
+            }
+        })?;
 
         let client = Self {
             provider: provider.to_string(),
@@ -186,26 +195,24 @@ impl OIDCClient {
         }
 
         // get client configuration from discovery
-        let client =
-            {
-                let provider_metadata =
-                    match CoreProviderMetadata::discover_async(client_info.discovery_url.clone(), &self.http_client)
-                        .await
-                    {
-                        Ok(meta) => meta,
-                        Err(err) => {
-                            log::warn!("Discovery failed for {}: {:#?}", self.provider, err);
-                            return Err(OIDCDiscoveryError(format!("{err:#?}")));
-                        }
-                    };
-
-                CoreClient::from_provider_metadata(
-                    provider_metadata,
-                    client_info.client_id.clone(),
-                    client_info.client_secret.clone(),
-                )
-                .set_redirect_uri(client_info.redirect_url.clone())
-            };
+        let http_client = |request: HttpRequest| self.http_client.execute_http(request);
+        let client = {
+            let provider_metadata =
+                match CoreProviderMetadata::discover_async(client_info.discovery_url.clone(), &http_client).await {
+                    Ok(meta) => meta,
+                    Err(err) => {
+                        log::warn!("Discovery failed for {}: {:#?}", self.provider, err);
+                        return Err(OIDCDiscoveryError(format!("{err:#?}")));
+                    }
+                };
+
+            CoreClient::from_provider_metadata(
+                provider_metadata,
+                client_info.client_id.clone(),
+                client_info.client_secret.clone(),
+            )
+            .set_redirect_uri(client_info.redirect_url.clone())
+        };
 
         // cache the new client (last writer wins)
         {
@@ -219,3 +226,42 @@ impl OIDCClient {
         Ok(client)
     }
 }
+
+/// Check the discovery endpoint of an OpenId Connect provider. The cached client configuration is not used
+/// to detect the outage of the provider. Login with the provider fails without it, but the service is still operational.
+pub struct OIDCDiscoveryStatus {
+    name: String,
+    client: Arc<OIDCClient>,
+}
+
+impl OIDCDiscoveryStatus {
+    pub fn new(client: Arc<OIDCClient>) -> Self {
+        Self {
+            name: format!("oidc-{}", client.provider),
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl StatusProvider for OIDCDiscoveryStatus {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn status(&self) -> HealthStatus {
+        let client_info = &self.client.client_info;
+        let http_client = |request: HttpRequest| self.client.http_client.execute_http(request);
+        match CoreProviderMetadata::discover_async(client_info.discovery_url.clone(), &http_client).await {
+            Ok(_) => HealthStatus::healthy(serde_json::Value::Null),
+            Err(err) => {
+                log::warn!("Discovery probe failed for {}: {err}", self.client.provider);
+                HealthStatus::degraded(serde_json::json!({ "error": err.to_string() }))
+            }
+        }
+    }
+
+    fn timeout_state(&self) -> HealthState {
+        HealthState::Degraded
+    }
+}
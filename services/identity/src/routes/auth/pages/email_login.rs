@@ -8,7 +8,7 @@ use shine_infra::{
     email::Email,
     language::Language,
     web::{
-        extracts::{InputError, SiteInfo, ValidatedQuery},
+        extracts::{AcceptLanguage, ClientIp, InputError, SiteInfo, ValidatedQuery},
         responses::ErrorResponse,
     },
 };
@@ -24,7 +24,6 @@ pub struct QueryParams {
     error_url: Option<Url>,
     remember_me: Option<bool>,
     captcha: Option<String>,
-    lang: Option<Language>,
 }
 
 /// Login with token using query, auth and cookie as sources.
@@ -33,7 +32,8 @@ pub struct QueryParams {
     path = "/auth/email/login",
     tag = "page",
     params(
-        QueryParams
+        QueryParams,
+        ("lang" = Option<Language>, Query, description = "Language of the email, it overrides the Accept-Language header")
     ),
     responses(
         (status = OK, description="Start an email login flow")
@@ -44,6 +44,8 @@ pub async fn email_login(
     query: Result<ValidatedQuery<QueryParams>, ErrorResponse<InputError>>,
     auth_session: AuthSession,
     site_info: SiteInfo,
+    AcceptLanguage(lang): AcceptLanguage,
+    client_ip: ClientIp,
 ) -> AuthPage {
     // 1. Create request helper
     let req = AuthPageRequest::new(&state, auth_session);
@@ -63,7 +65,7 @@ pub async fn email_login(
 
     // 4. Validate captcha
     if let Some(page) = req
-        .validate_captcha(query.captcha.as_deref(), query.error_url.as_ref())
+        .validate_captcha(query.captcha.as_deref(), client_ip.ip, query.error_url.as_ref())
         .await
     {
         return page;
@@ -81,7 +83,7 @@ pub async fn email_login(
             query.remember_me,
             query.redirect_url.as_ref(),
             &site_info,
-            query.lang,
+            lang,
         )
         .await
     {
@@ -5,7 +5,7 @@ use crate::{
 use axum::extract::State;
 use serde::Deserialize;
 use shine_infra::web::{
-    extracts::{ClientFingerprint, InputError, SiteInfo, ValidatedQuery},
+    extracts::{ClientFingerprint, ClientIp, InputError, SiteInfo, ValidatedQuery},
     responses::ErrorResponse,
 };
 use url::Url;
@@ -38,6 +38,7 @@ pub async fn guest_login(
     auth_session: AuthSession,
     fingerprint: ClientFingerprint,
     site_info: SiteInfo,
+    client_ip: ClientIp,
 ) -> AuthPage {
     // 1. Create request helper
     let req = AuthPageRequest::new(&state, auth_session);
@@ -57,7 +58,7 @@ pub async fn guest_login(
 
     // 4. Validate captcha
     if let Some(page) = req
-        .validate_captcha(query.captcha.as_deref(), query.error_url.as_ref())
+        .validate_captcha(query.captcha.as_deref(), client_ip.ip, query.error_url.as_ref())
         .await
     {
         return page;
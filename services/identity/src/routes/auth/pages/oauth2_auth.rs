@@ -90,7 +90,7 @@ pub async fn oauth2_auth(
         .client
         .exchange_code(auth_code)
         .set_pkce_verifier(PkceCodeVerifier::new(pkce_code_verifier))
-        .request_async(&client.http_client)
+        .request_async(&|request| client.http_client.execute_http(request))
         .await
     {
         Ok(token) => token,
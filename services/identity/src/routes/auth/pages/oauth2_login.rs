@@ -8,7 +8,7 @@ use serde::Deserialize;
 use shine_infra::{
     crypto::random,
     web::{
-        extracts::{InputError, ValidatedQuery},
+        extracts::{ClientIp, InputError, ValidatedQuery},
         responses::ErrorResponse,
     },
 };
@@ -43,6 +43,7 @@ pub async fn oauth2_login(
     Extension(client): Extension<Arc<OAuth2Client>>,
     auth_session: AuthSession,
     query: Result<ValidatedQuery<QueryParams>, ErrorResponse<InputError>>,
+    client_ip: ClientIp,
 ) -> AuthPage {
     // 1. Create request helper
     let req = AuthPageRequest::new(&state, auth_session);
@@ -62,7 +63,7 @@ pub async fn oauth2_login(
 
     // 4. Validate captcha
     if let Some(page) = req
-        .validate_captcha(query.captcha.as_deref(), query.error_url.as_ref())
+        .validate_captcha(query.captcha.as_deref(), client_ip.ip, query.error_url.as_ref())
         .await
     {
         return page;
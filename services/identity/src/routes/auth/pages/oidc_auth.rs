@@ -111,7 +111,7 @@ pub async fn oidc_auth(
     };
     let token = match exchange_request
         .set_pkce_verifier(PkceCodeVerifier::new(pkce_code_verifier))
-        .request_async(&client.http_client)
+        .request_async(&|request| client.http_client.execute_http(request))
         .await
     {
         Ok(token) => token,
@@ -13,7 +13,7 @@ use serde::Deserialize;
 use shine_infra::{
     crypto::random,
     web::{
-        extracts::{InputError, ValidatedQuery},
+        extracts::{ClientIp, InputError, ValidatedQuery},
         responses::ErrorResponse,
     },
 };
@@ -48,6 +48,7 @@ pub async fn oidc_login(
     Extension(client): Extension<Arc<OIDCClient>>,
     auth_session: AuthSession,
     query: Result<ValidatedQuery<QueryParams>, ErrorResponse<InputError>>,
+    client_ip: ClientIp,
 ) -> AuthPage {
     // 1. Create request helper
     let req = AuthPageRequest::new(&state, auth_session);
@@ -67,7 +68,7 @@ pub async fn oidc_login(
 
     // 4. Validate captcha
     if let Some(page) = req
-        .validate_captcha(query.captcha.as_deref(), query.error_url.as_ref())
+        .validate_captcha(query.captcha.as_deref(), client_ip.ip, query.error_url.as_ref())
         .await
     {
         return page;
@@ -65,6 +65,7 @@ pub async fn token_login(
 
     let AuthenticationSuccess {
         identity,
+        authenticated_at,
         create_access_token,
         auth_session,
         rotated_token,
@@ -98,7 +99,9 @@ pub async fn token_login(
     state
         .credential_handler()
         .establish(
+            "token",
             identity,
+            authenticated_at,
             issuance,
             auth_session,
             &fingerprint,
@@ -4,5 +4,7 @@ mod generate_user_name;
 pub use self::generate_user_name::*;
 mod purge_guests;
 pub use self::purge_guests::*;
+mod normalize_emails;
+pub use self::normalize_emails::*;
 mod user_roles;
 pub use self::user_roles::*;
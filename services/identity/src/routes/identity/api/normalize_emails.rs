@@ -0,0 +1,53 @@
+use crate::{app_state::AppState, models::NormalizeEmailsResult, services::permissions};
+use axum::{extract::State, Extension, Json};
+use serde::Deserialize;
+use shine_infra::{
+    session::RequirePermission,
+    web::{
+        extracts::ValidatedQuery,
+        responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
+    },
+};
+use utoipa::IntoParams;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Deserialize, Validate, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryParams {
+    /// Continue after this user, the `next` field of the previous response.
+    after: Option<Uuid>,
+    /// Maximum number of users to process per call. Range: 1–1000. Defaults to 500.
+    #[validate(range(min = 1, max = 1000))]
+    limit: Option<u32>,
+}
+
+/// Normalize again the stale emails, that were normalized by a different version of the normalization rules.
+///
+/// Requires SuperAdmin role. Processes at most `limit` users per call.
+/// Call repeatedly with `after` set to the returned `next` until it is not present to fully migrate.
+#[utoipa::path(
+    post,
+    path = "/api/identities/emails/normalize",
+    tag = "identity",
+    params(QueryParams),
+    responses(
+        (status = OK, body = NormalizeEmailsResult)
+    )
+)]
+pub async fn normalize_emails(
+    State(state): State<AppState>,
+    Extension(problem_config): Extension<ProblemConfig>,
+    ValidatedQuery(query): ValidatedQuery<QueryParams>,
+    _user: RequirePermission<permissions::NormalizeEmails>,
+) -> Result<Json<NormalizeEmailsResult>, ProblemResponse> {
+    let limit = query.limit.unwrap_or(500) as usize;
+
+    let result = state
+        .user_service()
+        .normalize_emails(query.after, limit)
+        .await
+        .map_err(|err| err.into_response(&problem_config))?;
+
+    Ok(Json(result))
+}
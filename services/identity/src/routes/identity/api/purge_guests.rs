@@ -1,14 +1,10 @@
-use crate::{
-    app_state::AppState,
-    models::PurgeGuestsResult,
-    services::{permissions, IdentityPermissions},
-};
+use crate::{app_state::AppState, models::PurgeGuestsResult, services::permissions};
 use axum::{extract::State, Extension, Json};
 use chrono::Utc;
 use iso8601_duration::Duration as IsoDuration;
 use serde::Deserialize;
 use shine_infra::{
-    session::CheckedCurrentUser,
+    session::RequirePermission,
     web::{
         extracts::ValidatedQuery,
         responses::{IntoProblemResponse, Problem, ProblemConfig, ProblemResponse},
@@ -45,12 +41,8 @@ pub async fn purge_guests(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
     ValidatedQuery(query): ValidatedQuery<QueryParams>,
-    user: CheckedCurrentUser,
+    _user: RequirePermission<permissions::PurgeGuestUsers>,
 ) -> Result<Json<PurgeGuestsResult>, ProblemResponse> {
-    user.identity_permissions()
-        .check(permissions::PURGE_GUEST_USERS)
-        .map_err(|err| err.into_response(&problem_config))?;
-
     let iso: IsoDuration = query.older_than.parse().map_err(|_| {
         Problem::bad_request("invalid_duration")
             .with_detail(format!("Invalid ISO 8601 duration: '{}'", query.older_than))
@@ -1,13 +1,13 @@
 use crate::{
     app_state::AppState,
     models::{IdentityKind, SearchIdentity, MAX_SEARCH_RESULT_COUNT},
-    services::{permissions, IdentityPermissions},
+    services::permissions,
 };
 use axum::{extract::State, Extension, Json};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use shine_infra::{
-    session::CheckedCurrentUser,
+    session::RequirePermission,
     web::{
         extracts::ValidatedQuery,
         responses::{IntoProblemResponse, ProblemConfig, ProblemResponse},
@@ -68,12 +68,8 @@ pub async fn search_identity(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
     ValidatedQuery(query): ValidatedQuery<QueryParams>,
-    user: CheckedCurrentUser,
+    _user: RequirePermission<permissions::ReadAnyIdentity>,
 ) -> Result<Json<IdentitySearchPage>, ProblemResponse> {
-    user.identity_permissions()
-        .check(permissions::READ_ANY_IDENTITY)
-        .map_err(|err| err.into_response(&problem_config))?;
-
     let count = query
         .count
         .unwrap_or(MAX_SEARCH_RESULT_COUNT)
@@ -1,7 +1,4 @@
-use crate::{
-    app_state::AppState,
-    services::{permissions, IdentityPermissions},
-};
+use crate::{app_state::AppState, services::permissions};
 use axum::{extract::State, Extension, Json};
 use axum_extra::{
     headers::{authorization::Bearer, Authorization},
@@ -9,12 +6,13 @@ use axum_extra::{
 };
 use serde::{Deserialize, Serialize};
 use shine_infra::{
-    session::{CheckedCurrentUser, PermissionError},
+    session::{CheckedCurrentUser, PermissionError, PolicyService},
     web::{
         extracts::{ValidatedJson, ValidatedPath},
         responses::{IntoProblemResponse, Problem, ProblemConfig, ProblemResponse},
     },
 };
+use std::sync::Arc;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
@@ -70,6 +68,7 @@ pub struct AddUserRole {
 pub async fn add_user_role(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
+    Extension(policy): Extension<Arc<PolicyService>>,
     user: CheckedCurrentUser,
     auth_key: Option<TypedHeader<Authorization<Bearer>>>,
     ValidatedPath(path): ValidatedPath<PathParams>,
@@ -87,7 +86,8 @@ pub async fn add_user_role(
         }
     } else {
         log::trace!("Using cookie");
-        user.identity_permissions()
+        policy
+            .permissions(&user)
             .check(permissions::UPDATE_ANY_USER_ROLE)
             .map_err(|err| err.into_response(&problem_config))?;
     }
@@ -106,6 +106,8 @@ pub async fn add_user_role(
     Ok(Json(UserRoles { roles }))
 }
 
+/// Get the roles of a user. Granting the read permission as an owner permission allows the users to read
+/// only their own roles.
 #[utoipa::path(
     get,
     path = "/api/identities/{id}/roles",
@@ -121,6 +123,7 @@ pub async fn add_user_role(
 pub async fn get_user_roles(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
+    Extension(policy): Extension<Arc<PolicyService>>,
     user: CheckedCurrentUser,
     auth_key: Option<TypedHeader<Authorization<Bearer>>>,
     ValidatedPath(path): ValidatedPath<PathParams>,
@@ -137,8 +140,9 @@ pub async fn get_user_roles(
         }
     } else {
         log::trace!("Using cookie");
-        user.identity_permissions()
-            .check(permissions::READ_ANY_USER_ROLE)
+        policy
+            .permissions(&user)
+            .check_for_owner(permissions::READ_ANY_USER_ROLE, path.user_id)
             .map_err(|err| err.into_response(&problem_config))?;
     }
 
@@ -182,6 +186,7 @@ pub struct DeleteUserRole {
 pub async fn delete_user_role(
     State(state): State<AppState>,
     Extension(problem_config): Extension<ProblemConfig>,
+    Extension(policy): Extension<Arc<PolicyService>>,
     user: CheckedCurrentUser,
     auth_key: Option<TypedHeader<Authorization<Bearer>>>,
     ValidatedPath(path): ValidatedPath<PathParams>,
@@ -199,7 +204,8 @@ pub async fn delete_user_role(
         }
     } else {
         log::trace!("Using cookie");
-        user.identity_permissions()
+        policy
+            .permissions(&user)
             .check(permissions::UPDATE_ANY_USER_ROLE)
             .map_err(|err| err.into_response(&problem_config))?;
     }
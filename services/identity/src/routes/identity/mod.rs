@@ -18,5 +18,6 @@ impl IdentityRouter {
             .routes(routes!(api::get_user_roles))
             .routes(routes!(api::delete_user_role))
             .routes(routes!(api::purge_guests))
+            .routes(routes!(api::normalize_emails))
     }
 }
@@ -0,0 +1,45 @@
+use shine_infra::language::{Language, MessageCatalog};
+
+/// The translations of the problem details and the mails of the identity service.
+pub fn identity_messages() -> MessageCatalog {
+    let mut catalog = MessageCatalog::new(Language::EN);
+    catalog
+        .add_json(Language::EN, include_str!("../../locales/en.json"))
+        .expect("Invalid en translations");
+    catalog
+        .add_json(Language::HU, include_str!("../../locales/hu.json"))
+        .expect("Invalid hu translations");
+    catalog
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shine_test::test;
+    use std::sync::Arc;
+    use tera::Tera;
+
+    #[test]
+    fn mail_templates_are_translated() {
+        let catalog = Arc::new(identity_messages());
+        let mut tera = Tera::new("tera_templates/**/*").unwrap();
+        tera.autoescape_on(vec![".html"]);
+        tera.register_function("t", catalog.tera_function());
+
+        for template in ["login", "register", "confirm", "change"] {
+            for lang in ["en", "hu", "hu-HU"] {
+                let mut context = tera::Context::new();
+                context.insert("user", "Bob");
+                context.insert("link", "https://example.com");
+                context.insert("app", "Shine");
+                context.insert("lang", lang);
+                let html = tera.render(&format!("mail/{template}.html"), &context).unwrap();
+                assert!(
+                    !html.contains("mail-"),
+                    "Missing translation in {template} ({lang}): {html}"
+                );
+                assert!(html.contains("Bob"), "{template} ({lang})");
+            }
+        }
+    }
+}
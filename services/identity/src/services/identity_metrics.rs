@@ -0,0 +1,25 @@
+use opentelemetry::metrics::{Counter, Meter};
+use shine_infra::telemetry::MetricSet;
+
+/// Business metrics of the identity service.
+pub struct IdentityMetrics {
+    /// Number of the established logins by the authentication provider.
+    pub logins: Counter<u64>,
+    /// Number of the sent emails by the template.
+    pub emails_sent: Counter<u64>,
+}
+
+impl MetricSet for IdentityMetrics {
+    fn new(meter: &Meter) -> Self {
+        Self {
+            logins: meter
+                .u64_counter("identity_logins")
+                .with_description("Number of the established logins")
+                .build(),
+            emails_sent: meter
+                .u64_counter("identity_emails_sent")
+                .with_description("Number of the sent emails")
+                .build(),
+        }
+    }
+}
@@ -1,40 +1,58 @@
-use shine_infra::session::{roles, CorePermissions, CurrentUser, PermissionSet};
+use shine_infra::session::{roles, Permission, PolicyConfig, RolePolicyConfig};
 
 pub mod permissions {
+    use super::Permission;
+
     /// Allow to query the general information of an identity
     pub const READ_ANY_IDENTITY: &str = "ReadAnyIdentity";
-    /// Allow to get the roles of any user
+    /// Allow to get the roles of any user, as an owner permission only the own roles
     pub const READ_ANY_USER_ROLE: &str = "ReadAnyUserRole";
     /// Allow to update the roles of any user
     pub const UPDATE_ANY_USER_ROLE: &str = "UpdateAnyUserRole";
     /// Allow purging old guest users
     pub const PURGE_GUEST_USERS: &str = "PurgeGuestUsers";
-}
-
-pub trait IdentityPermissions {
-    fn identity_permissions(&self) -> PermissionSet;
-}
+    /// Allow migrating the emails to the current normalization rules
+    pub const NORMALIZE_EMAILS: &str = "NormalizeEmails";
 
-impl IdentityPermissions for CurrentUser {
-    fn identity_permissions(&self) -> PermissionSet {
-        let mut permission = self.core_permissions();
+    pub struct ReadAnyIdentity;
+    impl Permission for ReadAnyIdentity {
+        const NAME: &'static str = READ_ANY_IDENTITY;
+    }
 
-        for role in &self.roles {
-            match role.as_str() {
-                roles::SUPER_ADMIN => {
-                    permission.add(permissions::READ_ANY_IDENTITY);
-                    permission.add(permissions::READ_ANY_USER_ROLE);
-                    permission.add(permissions::UPDATE_ANY_USER_ROLE);
-                    permission.add(permissions::PURGE_GUEST_USERS);
-                }
-                roles::USER_ADMIN => {
-                    permission.add(permissions::READ_ANY_IDENTITY);
-                    permission.add(permissions::READ_ANY_USER_ROLE);
-                }
-                _ => {}
-            };
-        }
+    pub struct PurgeGuestUsers;
+    impl Permission for PurgeGuestUsers {
+        const NAME: &'static str = PURGE_GUEST_USERS;
+    }
 
-        permission
+    pub struct NormalizeEmails;
+    impl Permission for NormalizeEmails {
+        const NAME: &'static str = NORMALIZE_EMAILS;
     }
 }
+
+/// The builtin role to permission mapping of the identity service.
+pub fn identity_policy() -> PolicyConfig {
+    PolicyConfig::default()
+        .with_role(
+            roles::USER_ADMIN,
+            RolePolicyConfig {
+                permissions: vec![
+                    permissions::READ_ANY_IDENTITY.into(),
+                    permissions::READ_ANY_USER_ROLE.into(),
+                ],
+                ..Default::default()
+            },
+        )
+        .with_role(
+            roles::SUPER_ADMIN,
+            RolePolicyConfig {
+                inherits: vec![roles::USER_ADMIN.into()],
+                permissions: vec![
+                    permissions::UPDATE_ANY_USER_ROLE.into(),
+                    permissions::PURGE_GUEST_USERS.into(),
+                    permissions::NORMALIZE_EMAILS.into(),
+                ],
+                ..Default::default()
+            },
+        )
+}
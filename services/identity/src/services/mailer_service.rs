@@ -1,8 +1,9 @@
 use crate::{
     repositories::mailer::{Email, EmailContent, EmailSender, EmailSenderError},
-    services::SettingsService,
+    services::{IdentityMetrics, SettingsService},
 };
-use shine_infra::language::Language;
+use opentelemetry::KeyValue;
+use shine_infra::{language::Language, telemetry::metrics};
 use tera::Tera;
 use url::Url;
 
@@ -36,7 +37,7 @@ impl<'a, E: EmailSender> MailerService<'a, E> {
         to: &str,
         link: Url,
         user_name: &str,
-        lang: Option<Language>,
+        lang: Language,
         template: &str,
     ) -> Result<(), EmailSenderError> {
         let mut context = tera::Context::new();
@@ -44,11 +45,11 @@ impl<'a, E: EmailSender> MailerService<'a, E> {
         context.insert("user", user_name);
         context.insert("link", link.as_str());
         context.insert("app", self.settings.app_name.as_str());
+        context.insert("lang", lang.as_str());
 
-        let lang = lang.unwrap_or(Language::En);
         let html = self
             .tera
-            .render(&format!("mail/{lang}/{template}"), &context)
+            .render(&format!("mail/{template}"), &context)
             .expect("Failed to generate email html");
 
         self.mailer
@@ -61,6 +62,9 @@ impl<'a, E: EmailSender> MailerService<'a, E> {
                 },
             )
             .await?;
+        metrics::<IdentityMetrics>()
+            .emails_sent
+            .add(1, &[KeyValue::new("template", template.to_string())]);
         Ok(())
     }
 
@@ -69,7 +73,7 @@ impl<'a, E: EmailSender> MailerService<'a, E> {
         to: &str,
         token: &str,
         user_name: &str,
-        lang: Option<Language>,
+        lang: Language,
     ) -> Result<(), EmailSenderError> {
         let mut redirect_url = self
             .settings
@@ -85,7 +89,7 @@ impl<'a, E: EmailSender> MailerService<'a, E> {
         &self,
         to: &str,
         token: &str,
-        lang: Option<Language>,
+        lang: Language,
         user_name: &str,
     ) -> Result<(), EmailSenderError> {
         let mut redirect_url = self
@@ -103,7 +107,7 @@ impl<'a, E: EmailSender> MailerService<'a, E> {
         to: &str,
         redirect_url: Url,
         user_name: &str,
-        lang: Option<Language>,
+        lang: Language,
     ) -> Result<(), EmailSenderError> {
         self.send_email(to, redirect_url, user_name, lang, "login.html").await
     }
@@ -113,7 +117,7 @@ impl<'a, E: EmailSender> MailerService<'a, E> {
         to: &str,
         redirect_url: Url,
         user_name: &str,
-        lang: Option<Language>,
+        lang: Language,
     ) -> Result<(), EmailSenderError> {
         self.send_email(to, redirect_url, user_name, lang, "register.html")
             .await
@@ -1,10 +1,14 @@
 mod identity_permissions;
 pub use self::identity_permissions::*;
+mod identity_messages;
+pub use self::identity_messages::*;
 
 mod settings_service;
 pub use self::settings_service::*;
 mod identity_events;
 pub use self::identity_events::*;
+mod identity_metrics;
+pub use self::identity_metrics::*;
 mod session_service;
 pub use self::session_service::*;
 mod mailer_service;
@@ -2,7 +2,7 @@ use crate::{
     models::{Identity, Session, SessionError},
     repositories::session::{SessionDb, Sessions},
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use ring::{digest, rand::SystemRandom};
 use shine_infra::{
     session::SessionKey,
@@ -12,6 +12,7 @@ use uuid::Uuid;
 
 pub struct SessionService<DB: SessionDb> {
     db: DB,
+    max_session_count: Option<usize>,
     random: SystemRandom,
 }
 
@@ -19,18 +20,22 @@ impl<DB> SessionService<DB>
 where
     DB: SessionDb,
 {
-    pub fn new(db: DB) -> Self {
+    pub fn new(db: DB, max_session_count: Option<usize>) -> Self {
         Self {
             db,
+            max_session_count,
             random: SystemRandom::new(),
         }
     }
 
     /// Creates a new session for the given user. This is the only place where the generated raw session key is accessible in the server,
     /// all the other raw keys are provided by the client and are not stored.
+    /// The time of the authentication is the current time if not given, ex. a login with a remember-me token
+    /// should keep the time of the original login.
     pub async fn create(
         &self,
         identity: &Identity,
+        authenticated_at: Option<DateTime<Utc>>,
         roles: Vec<String>,
         is_linked: bool,
         fingerprint: &ClientFingerprint,
@@ -45,6 +50,7 @@ where
         let session = db
             .store_session(
                 created_at,
+                authenticated_at.unwrap_or(created_at),
                 session_key_hash,
                 fingerprint,
                 site_info,
@@ -53,6 +59,28 @@ where
                 is_linked,
             )
             .await?;
+
+        if let Some(max_session_count) = self.max_session_count {
+            // close the oldest sessions above the limit, the new session is always kept
+            let mut sessions = db.find_all_sessions_by_user(identity.id).await?;
+            if sessions.len() > max_session_count {
+                sessions.sort_by_key(|s| s.info.created_at);
+                let evict_count = sessions.len() - max_session_count;
+                for old in sessions
+                    .iter()
+                    .filter(|s| s.info.key_hash != session.info.key_hash)
+                    .take(evict_count)
+                {
+                    log::info!(
+                        "Closing session of user {} above the limit: {}",
+                        identity.id,
+                        old.info.key_hash
+                    );
+                    db.delete_session_by_hash(identity.id, &old.info.key_hash).await?;
+                }
+            }
+        }
+
         Ok((session, session_key))
     }
 
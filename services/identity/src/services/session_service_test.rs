@@ -7,7 +7,7 @@ use chrono::{Duration, Utc};
 use ring::rand::SystemRandom;
 use shine_infra::{
     db,
-    session::SessionKey,
+    session::{SessionKey, SessionLifetime},
     web::extracts::{ClientFingerprint, SiteInfo},
 };
 use shine_test::test;
@@ -18,7 +18,8 @@ async fn create_db(scope: &str) -> Option<impl SessionDb + Clone> {
     match env::var("SHINE_TEST_REDIS_CNS") {
         Ok(cns) => {
             let redis = db::create_redis_pool(cns.as_str()).await.unwrap();
-            let db = RedisSessionDb::new(&redis, format!("{scope}_"), Duration::seconds(1000))
+            let lifetime = SessionLifetime::new(Duration::seconds(1000), None);
+            let db = RedisSessionDb::new(&redis, format!("{scope}_"), lifetime)
                 .await
                 .unwrap();
             Some(db)
@@ -35,7 +36,7 @@ async fn create_get_remove() {
     let scope = &Uuid::new_v4().to_string()[..5];
     log::debug!("test scope: {scope}");
     let session_manager = match create_db(scope).await {
-        Some(db) => SessionService::new(db),
+        Some(db) => SessionService::new(db, None),
         None => return,
     };
 
@@ -45,6 +46,7 @@ async fn create_get_remove() {
         name: "user".into(),
         email: None,
         is_email_confirmed: false,
+        language: None,
         created: Utc::now(),
     };
     let roles = vec!["R1".into(), "R2".into()];
@@ -59,7 +61,7 @@ async fn create_get_remove() {
 
     log::info!("Creating a new session...");
     let (session, session_key) = session_manager
-        .create(&identity, roles.clone(), is_linked, &fingerprint, &site_info)
+        .create(&identity, None, roles.clone(), is_linked, &fingerprint, &site_info)
         .await
         .unwrap();
     log::debug!("session: {session:#?}");
@@ -68,6 +70,7 @@ async fn create_get_remove() {
     assert_eq!(identity.name, session.user.name);
     assert_eq!(roles, session.user.roles);
     assert_eq!(is_linked, session.user.is_linked);
+    assert_eq!(session.info.created_at, session.info.authenticated_at);
 
     log::info!("Finding the session...");
     let found_session = session_manager
@@ -102,7 +105,7 @@ async fn update_invalid_key() {
     let scope = &Uuid::new_v4().to_string()[..5];
     log::debug!("test scope: {scope}");
     let session_manager = match create_db(scope).await {
-        Some(db) => SessionService::new(db),
+        Some(db) => SessionService::new(db, None),
         None => return,
     };
 
@@ -114,6 +117,7 @@ async fn update_invalid_key() {
         name: "user".into(),
         email: None,
         is_email_confirmed: false,
+        language: None,
         created: Utc::now(),
     };
     let roles = vec!["R1".into(), "R2".into()];
@@ -130,7 +134,7 @@ async fn create_update() {
     let scope = &Uuid::new_v4().to_string()[..5];
     log::debug!("test scope: {scope}");
     let session_manager = match create_db(scope).await {
-        Some(db) => SessionService::new(db),
+        Some(db) => SessionService::new(db, None),
         None => return,
     };
 
@@ -140,6 +144,7 @@ async fn create_update() {
         name: "user".into(),
         email: None,
         is_email_confirmed: false,
+        language: None,
         created: Utc::now(),
     };
     let roles1 = vec!["R1".into(), "Rfix".into()];
@@ -154,7 +159,7 @@ async fn create_update() {
 
     log::info!("Creating a new session...");
     let (session, session_key) = session_manager
-        .create(&identity1, roles1.clone(), is_linked1, &fingerprint, &site_info)
+        .create(&identity1, None, roles1.clone(), is_linked1, &fingerprint, &site_info)
         .await
         .unwrap();
 
@@ -196,7 +201,7 @@ async fn create_many_remove_all() {
     let scope = &Uuid::new_v4().to_string()[..5];
     log::debug!("test scope: {scope}");
     let session_manager = match create_db(scope).await {
-        Some(db) => SessionService::new(db),
+        Some(db) => SessionService::new(db, None),
         None => return,
     };
 
@@ -206,6 +211,7 @@ async fn create_many_remove_all() {
         name: "user".into(),
         email: None,
         is_email_confirmed: false,
+        language: None,
         created: Utc::now(),
     };
     let roles = vec!["R1".into(), "R2".into()];
@@ -221,7 +227,7 @@ async fn create_many_remove_all() {
     let mut keys = vec![];
     for _ in 0..10 {
         let (_, session_key) = session_manager
-            .create(&identity, roles.clone(), false, &fingerprint, &site_info)
+            .create(&identity, None, roles.clone(), false, &fingerprint, &site_info)
             .await
             .unwrap();
         keys.push(session_key);
@@ -231,7 +237,7 @@ async fn create_many_remove_all() {
     let mut identity2 = identity.clone();
     identity2.id = Uuid::new_v4();
     let (session2, session2_key) = session_manager
-        .create(&identity2, roles.clone(), false, &fingerprint, &site_info)
+        .create(&identity2, None, roles.clone(), false, &fingerprint, &site_info)
         .await
         .unwrap();
 
@@ -252,3 +258,99 @@ async fn create_many_remove_all() {
     assert_eq!(session2.info.key_hash, found_session.info.key_hash);
     assert_eq!(identity2.id, found_session.info.user_id);
 }
+
+#[test]
+async fn create_above_limit() {
+    let scope = &Uuid::new_v4().to_string()[..5];
+    log::debug!("test scope: {scope}");
+    let session_manager = match create_db(scope).await {
+        Some(db) => SessionService::new(db, Some(3)),
+        None => return,
+    };
+
+    let identity = Identity {
+        id: Uuid::new_v4(),
+        kind: IdentityKind::User,
+        name: "user".into(),
+        email: None,
+        is_email_confirmed: false,
+        language: None,
+        created: Utc::now(),
+    };
+    let roles = vec!["R1".into()];
+    let fingerprint = ClientFingerprint::from_agent("test".into()).unwrap();
+    let site_info = SiteInfo {
+        agent: "test".into(),
+        country: None,
+        region: None,
+        city: None,
+    };
+
+    let mut keys = vec![];
+    for _ in 0..5 {
+        let (_, session_key) = session_manager
+            .create(&identity, None, roles.clone(), false, &fingerprint, &site_info)
+            .await
+            .unwrap();
+        keys.push(session_key);
+    }
+
+    // only the newest sessions are kept
+    assert_eq!(session_manager.find_all(identity.id).await.unwrap().len(), 3);
+    for (i, key) in keys.iter().enumerate() {
+        let session = session_manager.find(identity.id, key).await.unwrap();
+        assert_eq!(session.is_some(), i >= 2, "session {i}");
+    }
+}
+
+#[test]
+async fn keep_authentication_time() {
+    let scope = &Uuid::new_v4().to_string()[..5];
+    log::debug!("test scope: {scope}");
+    let session_manager = match create_db(scope).await {
+        Some(db) => SessionService::new(db, None),
+        None => return,
+    };
+
+    let identity = Identity {
+        id: Uuid::new_v4(),
+        kind: IdentityKind::User,
+        name: "user".into(),
+        email: None,
+        is_email_confirmed: false,
+        language: None,
+        created: Utc::now(),
+    };
+    let fingerprint = ClientFingerprint::from_agent("test".into()).unwrap();
+    let site_info = SiteInfo {
+        agent: "test".into(),
+        country: None,
+        region: None,
+        city: None,
+    };
+
+    // ex. login with a remember-me token
+    let authenticated_at = Utc::now() - Duration::days(3);
+    let (session, session_key) = session_manager
+        .create(
+            &identity,
+            Some(authenticated_at),
+            vec![],
+            false,
+            &fingerprint,
+            &site_info,
+        )
+        .await
+        .unwrap();
+    assert_eq!(session.info.authenticated_at, authenticated_at);
+    assert!(session.info.created_at > authenticated_at);
+
+    let found_session = session_manager
+        .find(identity.id, &session_key)
+        .await
+        .unwrap()
+        .expect("Session should have been found");
+    assert_eq!(found_session.info.authenticated_at, authenticated_at);
+
+    session_manager.remove_all(identity.id).await.unwrap();
+}
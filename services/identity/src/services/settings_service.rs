@@ -20,4 +20,5 @@ pub struct SettingsService {
     pub external_providers: Vec<String>,
     pub page_redirect_time: Option<u32>,
     pub super_user_api_key_hash: Option<String>,
+    pub reject_disposable_email: bool,
 }
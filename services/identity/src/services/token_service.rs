@@ -2,7 +2,7 @@ use crate::{
     models::{Identity, IdentityError, TokenInfo, TokenKind},
     repositories::identity::{IdentityDb, Tokens},
 };
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use ring::digest;
 use shine_infra::web::extracts::{ClientFingerprint, SiteInfo};
 use thiserror::Error as ThisError;
@@ -61,6 +61,15 @@ impl<DB: IdentityDb> TokenService<DB> {
         ctx.delete_token_by_hash(kind, &token_hash).await
     }
 
+    pub async fn update_fingerprint(
+        &self,
+        token_hash: &str,
+        fingerprint: &ClientFingerprint,
+    ) -> Result<Option<()>, IdentityError> {
+        let mut ctx = self.db.create_context().await?;
+        ctx.update_token_fingerprint(token_hash, fingerprint).await
+    }
+
     pub async fn delete_by_user(&self, user_id: Uuid, token_hash: &str) -> Result<Option<()>, IdentityError> {
         let mut ctx = self.db.create_context().await?;
         ctx.delete_token_by_user(user_id, token_hash).await
@@ -71,6 +80,9 @@ impl<DB: IdentityDb> TokenService<DB> {
         ctx.delete_all_token_by_user(user_id, kinds).await
     }
 
+    /// Create a new token. The time of the authentication is the current time if not given, ex. a rotated token
+    /// should keep the time of the original login.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_with_retry(
         &self,
         user_id: Uuid,
@@ -79,6 +91,7 @@ impl<DB: IdentityDb> TokenService<DB> {
         fingerprint: Option<&ClientFingerprint>,
         email: Option<&str>,
         site_info: &SiteInfo,
+        authenticated_at: Option<DateTime<Utc>>,
     ) -> Result<(String, TokenInfo), TokenError> {
         const MAX_RETRY_COUNT: usize = 10;
 
@@ -100,7 +113,16 @@ impl<DB: IdentityDb> TokenService<DB> {
 
             let mut ctx = self.db.create_context().await?;
             match ctx
-                .store_token(user_id, kind, &token_hash, ttl, fingerprint, email, site_info)
+                .store_token(
+                    user_id,
+                    kind,
+                    &token_hash,
+                    ttl,
+                    fingerprint,
+                    email,
+                    site_info,
+                    authenticated_at,
+                )
                 .await
             {
                 Ok(token_info) => return Ok((token, token_info)),
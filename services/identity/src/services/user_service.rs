@@ -1,10 +1,10 @@
 use crate::{
-    models::{ExternalUserInfo, Identity, IdentityError, SearchIdentity},
+    models::{ExternalUserInfo, Identity, IdentityError, NormalizeEmailsResult, SearchIdentity},
     repositories::identity::{ExternalLinks, IdSequences, Identities, IdentityDb, IdentitySearch},
     services::{IdentityTopic, UserEvent, UserLinkEvent},
 };
 use chrono::{DateTime, Utc};
-use shine_infra::{crypto::IdEncoder, email::Email, sync::TopicBus, web::responses::Problem};
+use shine_infra::{crypto::IdEncoder, email::Email, language::Language, sync::TopicBus, web::responses::Problem};
 use std::sync::Arc;
 use thiserror::Error as ThisError;
 use uuid::Uuid;
@@ -78,11 +78,92 @@ impl<DB: IdentityDb> UserService<DB> {
         }
     }
 
+    /// Set or clear the preferred language of the user.
+    pub async fn update_language(
+        &self,
+        id: Uuid,
+        language: Option<&Language>,
+    ) -> Result<Option<Identity>, IdentityError> {
+        let mut ctx = self.db.create_context().await?;
+        match ctx.update_language(id, language).await? {
+            Some(identity) => {
+                self.events.publish(&UserEvent::Updated(id)).await;
+                Ok(Some(identity))
+            }
+            None => Ok(None),
+        }
+    }
+
     pub async fn delete_guests(&self, cutoff: DateTime<Utc>, limit: usize) -> Result<Vec<Uuid>, IdentityError> {
         let mut ctx = self.db.create_context().await?;
         ctx.delete_guests(cutoff, limit as i64).await
     }
 
+    /// Migrate a batch of the stale normalized emails to the current normalization rules.
+    pub async fn normalize_emails(
+        &self,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<NormalizeEmailsResult, IdentityError> {
+        let mut ctx = self.db.create_context().await?;
+        let stale = ctx.list_stale_emails(after, limit as i64).await?;
+
+        let mut result = NormalizeEmailsResult {
+            updated: 0,
+            conflicts: Vec::new(),
+            next: if stale.len() == limit {
+                stale.last().map(|(id, _)| *id)
+            } else {
+                None
+            },
+        };
+        for (id, email) in &stale {
+            match ctx.update_normalized_email(*id, email).await {
+                Ok(()) => result.updated += 1,
+                Err(IdentityError::EmailConflict) => {
+                    log::warn!("Normalized email of user {id} is already used by another user");
+                    result.conflicts.push(*id);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Migrate all the stale normalized emails, it is started in the background on startup. The passes are repeated
+    /// until no more rows are updated, the last (empty) pass marks the migration as completed.
+    pub async fn normalize_all_emails(&self) {
+        let mut total_updated = 0;
+        loop {
+            let (mut after, mut updated, mut conflicts) = (None, 0, 0);
+            loop {
+                match self.normalize_emails(after, 500).await {
+                    Ok(result) => {
+                        updated += result.updated;
+                        conflicts += result.conflicts.len();
+                        after = result.next;
+                    }
+                    Err(err) => {
+                        log::error!("Failed to normalize the stale emails: {err}");
+                        return;
+                    }
+                }
+                if after.is_none() {
+                    break;
+                }
+            }
+
+            total_updated += updated;
+            if updated == 0 {
+                if total_updated > 0 || conflicts > 0 {
+                    log::info!("Stale emails normalized, updated: {total_updated}, conflicts: {conflicts}");
+                }
+                return;
+            }
+        }
+    }
+
     pub async fn delete(&self, id: Uuid) -> Result<(), IdentityError> {
         let mut ctx = self.db.create_context().await?;
         ctx.cascaded_delete(id).await?;
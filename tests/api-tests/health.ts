@@ -8,10 +8,27 @@ test.describe('Sanity check', () => {
         expect(response).toHaveStatus(404);
     });
 
-    test('Health check shall pass', async ({ identityUrl, api }) => {
+    test('Liveness check shall pass', async ({ identityUrl, api }) => {
+        const url = joinURL(identityUrl, '/info/live');
+        const response = await api.client.get(url);
+        expect(response).toHaveStatus(200);
+    });
+
+    test('Readiness check shall pass', async ({ identityUrl, api }) => {
         const url = joinURL(identityUrl, '/info/ready');
         const response = await api.client.get(url);
         expect(response).toHaveStatus(200);
+
+        const readiness = await response.json();
+        expect(readiness).toEqual(
+            expect.objectContaining({
+                ready: true,
+                providers: expect.objectContaining({
+                    postgres: 'healthy',
+                    redis: 'healthy'
+                })
+            })
+        );
     });
 });
 
@@ -39,20 +56,35 @@ test.describe('Service status', () => {
 
         expect(status).toEqual(
             expect.objectContaining({
-                uptime: expect.objectContaining({
-                    startTime: expect.any(String),
-                    uptimeSeconds: expect.any(Number)
-                }),
-                http: expect.objectContaining({
-                    inFlightRequests: expect.any(Number)
-                }),
-                postgres: expect.objectContaining({
-                    connections: expect.any(Number),
-                    idleConnections: expect.any(Number)
-                }),
-                redis: expect.objectContaining({
-                    connections: expect.any(Number),
-                    idleConnections: expect.any(Number)
+                state: expect.any(String),
+                providers: expect.objectContaining({
+                    uptime: {
+                        state: 'healthy',
+                        details: expect.objectContaining({
+                            startTime: expect.any(String),
+                            uptimeSeconds: expect.any(Number)
+                        })
+                    },
+                    http: {
+                        state: 'healthy',
+                        details: expect.objectContaining({
+                            inFlightRequests: expect.any(Number)
+                        })
+                    },
+                    postgres: {
+                        state: 'healthy',
+                        details: expect.objectContaining({
+                            connections: expect.any(Number),
+                            idleConnections: expect.any(Number)
+                        })
+                    },
+                    redis: {
+                        state: 'healthy',
+                        details: expect.objectContaining({
+                            connections: expect.any(Number),
+                            idleConnections: expect.any(Number)
+                        })
+                    }
                 })
             })
         );
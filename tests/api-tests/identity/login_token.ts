@@ -2,18 +2,18 @@ import { expect, test } from '$fixtures/setup';
 import { TestUser } from '$lib/api/test_user';
 import { UserInfo } from '$lib/api/user_api';
 import { getPageProblem, getPageRedirectUrl } from '$lib/api/utils';
-import { createUrl } from '$lib/utils';
+import { createUrl, delay } from '$lib/utils';
 
 test.describe('Login with access cookie', () => {
     let testUser: TestUser = undefined!;
-    let userInfo: Omit<UserInfo, 'sessionLength' | 'remainingSessionTime'> = undefined!;
+    let userInfo: Omit<UserInfo, 'sessionLength' | 'remainingSessionTime' | 'authenticationAge'> = undefined!;
 
     test.beforeEach(async ({ api }) => {
         testUser = await api.testUsers.createGuest();
         expect(testUser.sid).toBeDefined();
         expect(testUser.tid).toBeDefined();
 
-        const { sessionLength, remainingSessionTime, ...partialUserInfo } = testUser.userInfo!;
+        const { sessionLength, remainingSessionTime, authenticationAge, ...partialUserInfo } = testUser.userInfo!;
         userInfo = partialUserInfo;
     });
 
@@ -191,6 +191,37 @@ test.describe('Login with access cookie', () => {
         expect(await api.user.getUserInfoRequest(testUser.sid, 'full')).toHaveStatus(401);
         expect(await api.user.getUserInfo(newCookies.sid.value, 'full')).toEqual(expect.objectContaining(userInfo));
     });
+
+    test('Login with (token: VALID, session: NULL) shall keep the time of the original authentication', async ({
+        api
+    }) => {
+        await delay(2000);
+        const response = await api.auth.loginWithTokenRequest(testUser.tid!, null, null, null, true, undefined);
+        expect(response).toHaveStatus(200);
+
+        const newCookies = response.cookies();
+        expect(newCookies.tid).toBeValidTID();
+        expect(newCookies.sid).toBeValidSID();
+
+        const newUserInfo = await api.user.getUserInfo(newCookies.sid.value, 'fast');
+        expect(newUserInfo.sessionLength).toBeLessThan(2);
+        expect(newUserInfo.authenticationAge).toBeGreaterThanOrEqual(2);
+
+        // the rotated token keeps the time as well
+        const rotatedResponse = await api.auth.loginWithTokenRequest(
+            newCookies.tid.value,
+            null,
+            null,
+            null,
+            true,
+            undefined
+        );
+        expect(rotatedResponse).toHaveStatus(200);
+        const rotatedCookies = rotatedResponse.cookies();
+        expect(rotatedCookies.sid).toBeValidSID();
+        const rotatedUserInfo = await api.user.getUserInfo(rotatedCookies.sid.value, 'fast');
+        expect(rotatedUserInfo.authenticationAge).toBeGreaterThanOrEqual(2);
+    });
 });
 
 test.describe('Login edge cases', () => {
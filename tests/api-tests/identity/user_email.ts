@@ -119,7 +119,7 @@ test.describe('Email confirmation', () => {
     for (const lang of ['en', 'hu', undefined]) {
         test(`Confirming email with lang ${lang} shall work`, async ({ api }) => {
             const user = await api.testUsers.createLinked(mockAuth);
-            const { sessionLength, remainingSessionTime, ...userInfo } = user.userInfo!;
+            const { sessionLength, remainingSessionTime, authenticationAge, ...userInfo } = user.userInfo!;
 
             const smtp = await startMockEmail();
             const mailPromise = smtp.waitMail();
@@ -336,7 +336,7 @@ test.describe('Email change', () => {
         // Note: Language support tested in confirmation tests above
         const email = randomUUID() + '@example.com';
         const user = await api.testUsers.createLinked(mockAuth, { email });
-        const { sessionLength, remainingSessionTime, ...userInfo } = user.userInfo!;
+        const { sessionLength, remainingSessionTime, authenticationAge, ...userInfo } = user.userInfo!;
 
         const smtp = await startMockEmail();
         const mailPromise = smtp.waitMail();
@@ -375,7 +375,7 @@ test.describe('Email change', () => {
 
     test('Changing email without email shall succeed', async ({ api }) => {
         const user = await api.testUsers.createGuest();
-        const { sessionLength, remainingSessionTime, ...userInfo } = user.userInfo!;
+        const { sessionLength, remainingSessionTime, authenticationAge, ...userInfo } = user.userInfo!;
         expect(user.email).not.toBeNull();
 
         const smtp = await startMockEmail();
@@ -412,7 +412,7 @@ test.describe('Email change', () => {
         const email = randomUUID() + '@example.com';
         const user = await api.testUsers.createLinked(mockAuth, { email });
         await user.confirmEmail(smtp);
-        const { sessionLength, remainingSessionTime, ...userInfo } = user.userInfo!;
+        const { sessionLength, remainingSessionTime, authenticationAge, ...userInfo } = user.userInfo!;
 
         const mailPromise = smtp.waitMail();
         const newEmail = `updated-${randomUUID()}@example.com`;
@@ -463,7 +463,7 @@ test.describe('Email change', () => {
 
         const newEmail2 = `updated-${randomUUID()}@example.com`;
         await user.changeEmail(smtp, newEmail2);
-        const { sessionLength, remainingSessionTime, ...userInfo } = user.userInfo!;
+        const { sessionLength, remainingSessionTime, authenticationAge, ...userInfo } = user.userInfo!;
 
         // Old token is automatically deleted when second email change creates new token (unique constraint)
         const response = await api.user.completeConfirmEmailRequest(user.sid, token!);
@@ -502,7 +502,7 @@ test.describe('Email change', () => {
             }
 
             const user = await api.testUsers.createLinked(mockAuth);
-            const { sessionLength, remainingSessionTime, ...userInfo } = user.userInfo!;
+            const { sessionLength, remainingSessionTime, authenticationAge, ...userInfo } = user.userInfo!;
 
             const mailPromise = smtp.waitMail();
             expect(user.email).not.toEqual(email);
@@ -543,7 +543,7 @@ test.describe('Email change', () => {
 
         const user = await api.testUsers.createLinked(mockAuth, { email: baseEmail });
         await user.confirmEmail(smtp);
-        const { sessionLength, remainingSessionTime, ...userInfo } = user.userInfo!;
+        const { sessionLength, remainingSessionTime, authenticationAge, ...userInfo } = user.userInfo!;
 
         await user.changeEmail(smtp, taggedEmail);
 
@@ -0,0 +1,53 @@
+import { expect, test } from '$fixtures/setup';
+
+test.describe('Preferred language', () => {
+    test('Setting the language without session shall fail', async ({ api }) => {
+        const response = await api.user.updateLanguageRequest(null, 'hu');
+        expect(response).toHaveStatus(401);
+        expect(await response.parseProblem()).toEqual(
+            expect.objectContaining({
+                type: 'unauthorized',
+                status: 401,
+                sensitive: 'unauthenticated'
+            })
+        );
+    });
+
+    test('Setting the language shall store the canonical tag', async ({ api }) => {
+        const user = await api.testUsers.createGuest();
+        expect((await api.user.getUserInfo(user.sid, 'full')).details?.language).toBeUndefined();
+
+        await api.user.updateLanguage(user.sid, 'hu_hu');
+        expect((await api.user.getUserInfo(user.sid, 'full')).details?.language).toEqual('hu-HU');
+
+        await api.user.updateLanguage(user.sid, 'sr-latn-rs-u-co-phonebk');
+        expect((await api.user.getUserInfo(user.sid, 'full')).details?.language).toEqual('sr-Latn-RS');
+    });
+
+    test('Clearing the language shall succeed', async ({ api }) => {
+        const user = await api.testUsers.createGuest();
+
+        await api.user.updateLanguage(user.sid, 'hu');
+        expect((await api.user.getUserInfo(user.sid, 'full')).details?.language).toEqual('hu');
+
+        await api.user.updateLanguage(user.sid, null);
+        expect((await api.user.getUserInfo(user.sid, 'full')).details?.language).toBeUndefined();
+    });
+
+    for (const language of ['', 'hungarian', 'hu-HU-', 'sl-Latn-IT-rozaj-biske-1994-alalc97-fonipa']) {
+        test(`Setting an invalid language shall fail (${language || '<empty>'})`, async ({ api }) => {
+            const user = await api.testUsers.createGuest();
+            await api.user.updateLanguage(user.sid, 'hu');
+
+            const response = await api.user.updateLanguageRequest(user.sid, language);
+            expect(response).toHaveStatus(400);
+            expect(await response.parseProblem()).toEqual(
+                expect.objectContaining({
+                    type: 'input-body-format',
+                    status: 400
+                })
+            );
+            expect((await api.user.getUserInfo(user.sid, 'full')).details?.language).toEqual('hu');
+        });
+    }
+});
@@ -67,6 +67,7 @@ export const expect = baseExpect.extend({
         expect(received.name).toStartWith('Freshman_');
         expect(received.sessionLength).toBeGreaterThanOrEqual(0);
         expect(received.remainingSessionTime).toBeGreaterThanOrEqual(0);
+        expect(received.authenticationAge).toBeGreaterThanOrEqual(0);
         expect(received.roles).toEqual([]);
         expect(received.isEmailConfirmed).toBeFalsy();
         expect(received.isLinked).toBeFalsy();
@@ -8,6 +8,7 @@ export type GetUserInfoMethod = 'fast' | 'full' | 'fillWithRefresh';
 export const UserInfoDetailSchema = z.object({
     kind: z.string(),
     email: OptionalSchema(z.string()),
+    language: OptionalSchema(z.string()),
     createdAt: DateStringSchema
 });
 export type UserInfoDetail = z.infer<typeof UserInfoDetailSchema>;
@@ -21,6 +22,7 @@ export const UserInfoSchema = z.object({
     roles: z.array(z.string()),
     sessionLength: z.number(),
     remainingSessionTime: z.number(),
+    authenticationAge: z.number(),
     details: UserInfoDetailSchema.nullable()
 });
 export type UserInfo = z.infer<typeof UserInfoSchema>;
@@ -52,6 +54,12 @@ const EmailChangeSchema = z.object({
 });
 export type EmailChange = z.infer<typeof EmailChangeSchema>;
 
+// eslint-disable-next-line @typescript-eslint/no-unused-vars
+const LanguageUpdateSchema = z.object({
+    language: z.string().nullable()
+});
+export type LanguageUpdate = z.infer<typeof LanguageUpdateSchema>;
+
 export const IdentityInfoSchema = z.object({
     id: z.string(),
     kind: z.string(),
@@ -253,6 +261,20 @@ export class UserAPI {
         expect(response).toHaveStatus(200);
     }
 
+    updateLanguageRequest(sid: string | null, language: string | null): ApiRequest<LanguageUpdate> {
+        const cs = sid && { sid };
+
+        return this.client
+            .put<LanguageUpdate>(this.urlFor('/api/auth/user/language'))
+            .withCookies({ ...cs })
+            .withBody({ language });
+    }
+
+    async updateLanguage(sid: string, language: string | null): Promise<void> {
+        const response = await this.updateLanguageRequest(sid, language);
+        expect(response).toHaveStatus(200);
+    }
+
     purgeGuestsRequest(sid: string | null, olderThan: string, limit?: number): ApiRequest {
         const cs = sid && { sid };
         const params: Record<string, string | number> = { olderThan };